@@ -0,0 +1,39 @@
+use menubar::headless::HeadlessBackend;
+use menubar::menu;
+
+fn main() {
+    let menu = menu! {
+        "File" {
+            "New" [id = "new", accel = "CmdOrCtrl+N"] => || println!("new"),
+            "Open..." [id = "open", accel = "CmdOrCtrl+O"] => || println!("open"),
+            "Open Recent" {
+                "a.txt" => || println!("open a.txt"),
+                "b.txt" => || println!("open b.txt"),
+            },
+            ---,
+            "Close" [accel = "CmdOrCtrl+W", enabled = false],
+        },
+        "View" {
+            "Word Wrap" [id = "word-wrap", checked = true],
+            "Submenus galore" {
+                "Item 1" {
+                    "Item 1 : 1",
+                    "Item 1 : 2" {
+                        "Item 1 : 2 : 1" [checked = true],
+                        "Item 1 : 2 : 2" [accel = "CmdOrCtrl+K"],
+                    },
+                },
+            },
+        },
+    };
+
+    let backend = HeadlessBackend::new();
+    let _attached = backend.attach(menu).unwrap();
+    for op in backend.take_ops() {
+        println!("{:?}", op);
+    }
+
+    backend.click_path(&["File", "New"]);
+    backend.click_path(&["File", "Open Recent", "b.txt"]);
+    backend.send_key("CmdOrCtrl+O".parse().unwrap());
+}
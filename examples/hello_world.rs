@@ -1,10 +1,11 @@
 #![windows_subsystem = "windows"]
 #![allow(unused_imports)] // While testing
 
-use env_logger;
+#[cfg(target_os = "macos")]
 use icrate::Foundation::MainThreadMarker;
 #[cfg(target_os = "macos")]
 use menubar::appkit::{InitializedApplication, MenuBar, MenuItemState, NSMenu, NSMenuItem};
+#[cfg(target_os = "macos")]
 use menubar::appkit::{MenuItemWrapper, MenuWrapper};
 #[cfg(target_os = "macos")]
 use objc2::{class, msg_send};
@@ -23,6 +24,7 @@ use winit::{
     window::{Window, WindowBuilder},
 };
 
+#[cfg(target_os = "macos")]
 fn main() -> Result<(), Box<dyn Error>> {
     #[cfg(target_os = "windows")]
     unsafe {
@@ -38,15 +40,19 @@ fn main() -> Result<(), Box<dyn Error>> {
         let mut services_menu = None;
 
         let mut menubar = MenuBar::new(mtm, |menu| {
-            menu.add(MenuItemWrapper::new("item 1", "a", None));
+            menu.add(MenuItemWrapper::new(
+                "item 1",
+                Some("Cmd+A".parse().unwrap()),
+                None,
+            ));
             menu.add(MenuItemWrapper::new_separator());
             menu.add({
-                let item = MenuItemWrapper::new("Services", "", None);
+                let item = MenuItemWrapper::new("Services", None, None);
                 services_menu = item.set_submenu({
                     let submenu = MenuWrapper::new(mtm);
                     submenu.add(MenuItemWrapper::new(
                         "will get removed or disappear?",
-                        "",
+                        None,
                         None,
                     ));
                     Some(submenu)
@@ -68,22 +74,31 @@ fn main() -> Result<(), Box<dyn Error>> {
             // item.set_hidden(true);
             // item.set_submenu({
             //     let submenu = MenuWrapper::new(mtm);
-            //     submenu.add(MenuItemWrapper::new("submenu item", "d", None));
+            //     submenu.add(MenuItemWrapper::new("submenu item", Some("Cmd+D".parse().unwrap()), None));
             //     Some(submenu)
             // });
             // menu.add(item);
-            // let item = MenuItemWrapper::new("item 2", "b", None);
+            // let item = MenuItemWrapper::new("item 2", Some("Cmd+B".parse().unwrap()), None);
             // unsafe {
             //     let _: () = msg_send![item.as_raw(), setEnabled: 1];
             // }
             // menu.add(item);
             menu.add({
                 // Unsure how key equivalents affect submenuitems???
-                let item = MenuItemWrapper::new("item w. submenu", "c", None);
+                let item =
+                    MenuItemWrapper::new("item w. submenu", Some("Cmd+C".parse().unwrap()), None);
                 item.set_submenu({
                     let submenu = MenuWrapper::new(mtm);
-                    submenu.add(MenuItemWrapper::new("submenu item 1 🤖", "d", None));
-                    submenu.add(MenuItemWrapper::new("submenu item 2", "e", None));
+                    submenu.add(MenuItemWrapper::new(
+                        "submenu item 1 🤖",
+                        Some("Cmd+D".parse().unwrap()),
+                        None,
+                    ));
+                    submenu.add(MenuItemWrapper::new(
+                        "submenu item 2",
+                        Some("Cmd+E".parse().unwrap()),
+                        None,
+                    ));
                     Some(submenu)
                 });
                 assert_eq!(item.state(), MenuItemState::Off);
@@ -95,7 +110,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                 assert_eq!(item.state(), MenuItemState::Off);
                 item
             });
-            let item = MenuItemWrapper::new("item x", "f", None);
+            let item = MenuItemWrapper::new("item x", Some("Cmd+F".parse().unwrap()), None);
             assert_eq!(item.title(), "item x");
             item.set_title("item 4");
             assert_eq!(item.title(), "item 4");
@@ -103,17 +118,17 @@ fn main() -> Result<(), Box<dyn Error>> {
         });
 
         menubar.add("menu hidden", |menu| {
-            let item = MenuItemWrapper::new("item 1", "g", None);
+            let item = MenuItemWrapper::new("item 1", Some("Cmd+G".parse().unwrap()), None);
             assert!(!item.hidden());
             menu.add(item);
-            let item = MenuItemWrapper::new("item 2", "h", None);
+            let item = MenuItemWrapper::new("item 2", Some("Cmd+H".parse().unwrap()), None);
             assert!(!item.hidden());
             item.set_hidden(true);
             assert!(item.hidden());
             item.set_hidden(false);
             assert!(!item.hidden());
             menu.add(item);
-            let item = MenuItemWrapper::new("item 3", "i", None);
+            let item = MenuItemWrapper::new("item 3", Some("Cmd+I".parse().unwrap()), None);
             item.set_hidden(true);
             menu.add(item);
         });
@@ -121,46 +136,55 @@ fn main() -> Result<(), Box<dyn Error>> {
         let window_menu = menubar.add("Window menu", |menu| {
             menu.add(MenuItemWrapper::new(
                 "Will be above the window data",
-                "",
+                None,
                 None,
             ));
         });
 
         menubar.add("Duplicate key equvalent", |menu| {
-            menu.add(MenuItemWrapper::new("item 1", "j", None));
-            menu.add(MenuItemWrapper::new("item 2", "j", None));
+            menu.add(MenuItemWrapper::new(
+                "item 1",
+                Some("Cmd+J".parse().unwrap()),
+                None,
+            ));
+            menu.add(MenuItemWrapper::new(
+                "item 2",
+                Some("Cmd+J".parse().unwrap()),
+                None,
+            ));
         });
 
         menubar.add("Submenus gallore", |menu| {
             menu.add({
-                let item = MenuItemWrapper::new("Item 1", "", None);
+                let item = MenuItemWrapper::new("Item 1", None, None);
                 item.set_submenu({
                     let submenu = MenuWrapper::new(mtm);
-                    submenu.add(MenuItemWrapper::new("Item 1 : 1", "", None));
-                    submenu.add(MenuItemWrapper::new("Item 1 : 2", "", None));
+                    submenu.add(MenuItemWrapper::new("Item 1 : 1", None, None));
+                    submenu.add(MenuItemWrapper::new("Item 1 : 2", None, None));
                     submenu.add({
-                        let submenuitem = MenuItemWrapper::new("Item 1 : 3", "", None);
+                        let submenuitem = MenuItemWrapper::new("Item 1 : 3", None, None);
                         submenuitem.set_submenu({
                             let submenu2 = MenuWrapper::new(mtm);
-                            submenu2.add(MenuItemWrapper::new("Item 1 : 3 : 1", "", None));
+                            submenu2.add(MenuItemWrapper::new("Item 1 : 3 : 1", None, None));
                             submenu2.add({
-                                let submenuitem2 = MenuItemWrapper::new("Item 1 : 3 : 2", "", None);
+                                let submenuitem2 =
+                                    MenuItemWrapper::new("Item 1 : 3 : 2", None, None);
                                 submenuitem2.set_submenu({
                                     let submenu3 = MenuWrapper::new(mtm);
                                     let submenuitem3 =
-                                        MenuItemWrapper::new("Item 1 : 3 : 2 : 1", "", None);
+                                        MenuItemWrapper::new("Item 1 : 3 : 2 : 1", None, None);
                                     submenuitem3.set_state(MenuItemState::On);
                                     submenu3.add(submenuitem3);
                                     submenu3.add(MenuItemWrapper::new(
                                         "Item 1 : 3 : 2 : 2",
-                                        "k",
+                                        Some("Cmd+K".parse().unwrap()),
                                         None,
                                     ));
                                     Some(submenu3)
                                 });
                                 submenuitem2
                             });
-                            submenu2.add(MenuItemWrapper::new("Item 1 : 3 : 3", "", None));
+                            submenu2.add(MenuItemWrapper::new("Item 1 : 3 : 3", None, None));
                             Some(submenu2)
                         });
                         submenuitem.set_state(MenuItemState::Mixed);
@@ -172,11 +196,11 @@ fn main() -> Result<(), Box<dyn Error>> {
                 item
             });
             menu.add({
-                let item = MenuItemWrapper::new("Item 2", "", None);
+                let item = MenuItemWrapper::new("Item 2", None, None);
                 item.set_submenu({
                     let submenu = MenuWrapper::new(mtm);
-                    submenu.add(MenuItemWrapper::new("Item 2 : 1", "", None));
-                    submenu.add(MenuItemWrapper::new("Item 2 : 2", "", None));
+                    submenu.add(MenuItemWrapper::new("Item 2 : 1", None, None));
+                    submenu.add(MenuItemWrapper::new("Item 2 : 2", None, None));
                     Some(submenu)
                 });
                 item
@@ -186,18 +210,18 @@ fn main() -> Result<(), Box<dyn Error>> {
         menubar.add("Empty menu", |_| {});
 
         menubar.add("Menu with a really loooooooooooong name!", |menu| {
-            menu.add(MenuItemWrapper::new("Item with a really loooooooooooong name!", "", None));
-            menu.add(MenuItemWrapper::new("Item with an even looooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooonger name!", "", None));
-            menu.add(MenuItemWrapper::new("Item with the looooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooongest name!", "", None));
+            menu.add(MenuItemWrapper::new("Item with a really loooooooooooong name!", None, None));
+            menu.add(MenuItemWrapper::new("Item with an even looooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooonger name!", None, None));
+            menu.add(MenuItemWrapper::new("Item with the looooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooongest name!", None, None));
         });
 
         menubar.add("This menu text is truncated on smaller screens since there's too many long menus already!", |menu| {
-            menu.add(MenuItemWrapper::new("item", "", None));
+            menu.add(MenuItemWrapper::new("item", None, None));
         });
 
         menubar.add("Length tests", |menu| {
             assert_eq!(menu.len(), 0);
-            menu.add(MenuItemWrapper::new("item", "", None));
+            menu.add(MenuItemWrapper::new("item", None, None));
             assert_eq!(menu.len(), 1);
             menu.remove_all();
             assert_eq!(menu.len(), 0);
@@ -206,17 +230,17 @@ fn main() -> Result<(), Box<dyn Error>> {
         let help_menu = menubar.add("Help menu", |menu| {
             menu.add(MenuItemWrapper::new(
                 "Will be below the help search box",
-                "",
+                None,
                 None,
             ));
         });
 
         menubar.add("Insert tests", |menu| {
-            menu.add(MenuItemWrapper::new("item 4", "", None));
-            menu.insert(MenuItemWrapper::new("item 3", "", None), 0);
-            menu.insert(MenuItemWrapper::new("item 1", "", None), 0);
-            menu.insert(MenuItemWrapper::new("item 2", "", None), 1);
-            menu.insert(MenuItemWrapper::new("item 5", "", None), 4);
+            menu.add(MenuItemWrapper::new("item 4", None, None));
+            menu.insert(MenuItemWrapper::new("item 3", None, None), 0);
+            menu.insert(MenuItemWrapper::new("item 1", None, None), 0);
+            menu.insert(MenuItemWrapper::new("item 2", None, None), 1);
+            menu.insert(MenuItemWrapper::new("item 5", None, None), 4);
         });
 
         // Debug print before we add a bunch of items
@@ -225,13 +249,13 @@ fn main() -> Result<(), Box<dyn Error>> {
         menubar.add("A lot of items", |menu| {
             const COUNT: usize = 65535;
             for i in 1..=COUNT {
-                menu.add(MenuItemWrapper::new(&format!("item {}", i), "", None));
+                menu.add(MenuItemWrapper::new(&format!("item {}", i), None, None));
             }
             assert_eq!(menu.len(), COUNT);
         });
 
         let apple_menu = menubar.add("apple menu", |menu| {
-            menu.add(MenuItemWrapper::new("FOO", "", None));
+            menu.add(MenuItemWrapper::new("FOO", None, None));
         });
 
         (
@@ -487,3 +511,8 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[cfg(not(target_os = "macos"))]
+fn main() {
+    eprintln!("This example only works on macOS.");
+}
@@ -1,6 +1,8 @@
 #![allow(unused_imports)] // While testing
+#[cfg(target_os = "macos")]
 use menubar::appkit::{MainThreadMarker, MenuBar, MenuItemWrapper, NSMenu, NSMenuItem};
 
+#[cfg(target_os = "macos")]
 fn main() {
     // Dynamic content
     let _recent_projects = vec!["a", "a", "a"];
@@ -9,8 +11,16 @@ fn main() {
     let mtm = MainThreadMarker::new().unwrap();
     let mut menu_bar = MenuBar::new(mtm, |_menu| {});
     menu_bar.add("File", |menu| {
-        menu.add(MenuItemWrapper::new("New File", "CMD+N", None));
-        menu.add(MenuItemWrapper::new("Open...", "CMD+O", None));
+        menu.add(MenuItemWrapper::new(
+            "New File",
+            Some("Cmd+N".parse().unwrap()),
+            None,
+        ));
+        menu.add(MenuItemWrapper::new(
+            "Open...",
+            Some("Cmd+O".parse().unwrap()),
+            None,
+        ));
         // // Has dynamic content
         // let open_recent_menu = NSMenu::new();
         // open_recent_menu.on_open(|menu| {
@@ -32,3 +42,8 @@ fn main() {
     // main_menu.set_visible(false);
     // unsafe { menu_bar.attach_to_application() };
 }
+
+#[cfg(not(target_os = "macos"))]
+fn main() {
+    eprintln!("This example only works on macOS.");
+}
@@ -1,13 +1,44 @@
 #[cfg(target_os = "macos")]
 use menubar::appkit::{MainThreadMarker, MenuItemWrapper, MenuWrapper};
+#[cfg(not(target_os = "macos"))]
+use menubar::{headless::HeadlessBackend, Menu, MenuItem, Separator, Submenu};
 
+#[cfg(target_os = "macos")]
 fn main() {
     let mtm = MainThreadMarker::new().unwrap();
     let menu = MenuWrapper::new(mtm);
-    menu.add(MenuItemWrapper::new("item 1", "a", None));
+    menu.add(MenuItemWrapper::new(
+        "item 1",
+        Some("Cmd+A".parse().unwrap()),
+        None,
+    ));
     menu.add(MenuItemWrapper::new_separator());
-    menu.add(MenuItemWrapper::new("item 2", "a", None));
+    menu.add(MenuItemWrapper::new(
+        "item 2",
+        Some("Cmd+A".parse().unwrap()),
+        None,
+    ));
 
     let _ = MenuItemWrapper::new_separator();
     loop {}
 }
+
+#[cfg(not(target_os = "macos"))]
+fn main() {
+    let menu = Menu::new().with(Submenu::new(
+        "menu",
+        Menu::new()
+            .with(MenuItem::new("item 1").with_handler(|| println!("item 1 activated")))
+            .with(Separator)
+            .with(MenuItem::new("item 2").with_handler(|| println!("item 2 activated"))),
+    ));
+
+    let backend = HeadlessBackend::new();
+    let _attached = backend.attach(menu).unwrap();
+    for op in backend.take_ops() {
+        println!("{:?}", op);
+    }
+
+    backend.click_path(&["menu", "item 1"]);
+    backend.click_path(&["menu", "item 2"]);
+}
@@ -21,7 +21,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         match event {
             Event::NewEvents(StartCause::Init) => {
                 for _ in 0..2 {
-                    let window = Window::new(&event_loop).unwrap();
+                    let window = Window::new(event_loop).unwrap();
                     window.set_title("Inside StartCause::Init");
                     windows.insert(window.id(), window);
                 }
@@ -39,13 +39,13 @@ fn main() -> Result<(), Box<dyn Error>> {
                     //     unsafe { Id::retain(NonNull::from(menubar)) };
                     // let mut menubar = unsafe { MenuBar::from_raw(menubar) };
                     let mut menubar = MenuBar::new(mtm, |menu| {
-                        menu.add(MenuItemWrapper::new("Some item", "", None));
+                        menu.add(MenuItemWrapper::new("Some item", None, None));
                     });
 
                     let window_menu = menubar.add("Window menu", |menu| {
                         menu.add(MenuItemWrapper::new(
                             "Will be above the window data",
-                            "",
+                            None,
                             None,
                         ));
                     });
@@ -9,7 +9,7 @@ mod win32 {
 
     pub(super) fn main2() {
         // Create an event loop and a winit window.
-        let event_loop = EventLoop::new();
+        let event_loop = EventLoop::new().unwrap();
         let window = Window::new(&event_loop).unwrap();
 
         // Create a menu.
@@ -59,18 +59,20 @@ mod win32 {
         menu.apply(&window).unwrap();
 
         // Begin running the event loop.
-        event_loop.run(move |event, _, flow| {
-            flow.set_wait();
+        event_loop
+            .run(move |event, _, flow| {
+                flow.set_wait();
 
-            match event {
-                Event::WindowEvent {
-                    event: WindowEvent::CloseRequested,
-                    window_id,
-                } if window.id() == window_id => flow.set_exit(),
-                Event::UserEvent(()) => flow.set_exit(),
-                _ => {}
-            }
-        })
+                match event {
+                    Event::WindowEvent {
+                        event: WindowEvent::CloseRequested,
+                        window_id,
+                    } if window.id() == window_id => flow.set_exit(),
+                    Event::UserEvent(()) => flow.set_exit(),
+                    _ => {}
+                }
+            })
+            .unwrap();
     }
 }
 
@@ -0,0 +1,371 @@
+//! Parsing accelerators, to check them at compile time.
+//!
+//! This is a copy of the parser in `menubar::accelerator`, without the
+//! formatting and platform conversions. It accepts exactly the same strings,
+//! with the same errors, which the tests check against the main crate.
+
+use core::fmt;
+use core::ops::Range;
+use core::str::FromStr;
+
+/// The platforms that accelerators can be resolved for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum Platform {
+    MacOs,
+    Windows,
+    Linux,
+}
+
+/// A set of modifier keys.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub(crate) struct Modifiers(u8);
+
+impl Modifiers {
+    const SHIFT: Self = Self(1 << 0);
+    const CONTROL: Self = Self(1 << 1);
+    const ALT: Self = Self(1 << 2);
+    const SUPER: Self = Self(1 << 3);
+    const CMD_OR_CTRL: Self = Self(1 << 4);
+
+    /// Accepted names, compared case-insensitively.
+    const ALIASES: [(Self, &'static str); 13] = [
+        (Self::CMD_OR_CTRL, "CmdOrCtrl"),
+        (Self::CMD_OR_CTRL, "CommandOrControl"),
+        (Self::SUPER, "Super"),
+        (Self::SUPER, "Cmd"),
+        (Self::SUPER, "Command"),
+        (Self::SUPER, "Meta"),
+        (Self::SUPER, "Win"),
+        (Self::CONTROL, "Ctrl"),
+        (Self::CONTROL, "Control"),
+        (Self::ALT, "Alt"),
+        (Self::ALT, "Option"),
+        (Self::ALT, "Opt"),
+        (Self::SHIFT, "Shift"),
+    ];
+
+    fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Replace `CMD_OR_CTRL` with the modifier it means on the given
+    /// platform.
+    pub(crate) fn resolve_for(self, platform: Platform) -> Self {
+        if !self.contains(Self::CMD_OR_CTRL) {
+            return self;
+        }
+        let primary = match platform {
+            Platform::MacOs => Self::SUPER,
+            Platform::Windows | Platform::Linux => Self::CONTROL,
+        };
+        Self(self.0 & !Self::CMD_OR_CTRL.0 | primary.0)
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALIASES
+            .iter()
+            .find(|(_, alias)| alias.eq_ignore_ascii_case(name))
+            .map(|(modifier, _)| *modifier)
+    }
+}
+
+/// A key that is not a modifier.
+///
+/// Named keys are identified by their canonical name, and letters by their
+/// uppercase form.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum Key {
+    Char(char),
+    F(u8),
+    Numpad(u8),
+    Named(&'static str),
+}
+
+impl Key {
+    /// The canonical names of named keys, other than `Plus`.
+    const NAMES: [&'static str; 28] = [
+        "Up",
+        "Down",
+        "Left",
+        "Right",
+        "Home",
+        "End",
+        "PageUp",
+        "PageDown",
+        "Insert",
+        "Delete",
+        "Backspace",
+        "Tab",
+        "Enter",
+        "Escape",
+        "Space",
+        "PrintScreen",
+        "NumAdd",
+        "NumSub",
+        "NumMult",
+        "NumDiv",
+        "NumDec",
+        "VolumeUp",
+        "VolumeDown",
+        "VolumeMute",
+        "MediaPlayPause",
+        "MediaStop",
+        "MediaNextTrack",
+        "MediaPreviousTrack",
+    ];
+
+    /// Accepted alternative names, with the canonical names they stand for.
+    const ALIASES: [(&'static str, &'static str); 13] = [
+        ("ArrowUp", "Up"),
+        ("ArrowDown", "Down"),
+        ("ArrowLeft", "Left"),
+        ("ArrowRight", "Right"),
+        ("PgUp", "PageUp"),
+        ("PgDn", "PageDown"),
+        ("Ins", "Insert"),
+        ("Del", "Delete"),
+        ("Return", "Enter"),
+        ("Esc", "Escape"),
+        ("PrtSc", "PrintScreen"),
+        ("Mute", "VolumeMute"),
+        ("PlayPause", "MediaPlayPause"),
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        let mut chars = name.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            if c == '+' || c.is_whitespace() || c.is_control() {
+                return None;
+            }
+            let mut upper = c.to_uppercase();
+            return match (upper.next(), upper.next()) {
+                (Some(upper), None) => Some(Self::Char(upper)),
+                _ => Some(Self::Char(c)),
+            };
+        }
+
+        if name.eq_ignore_ascii_case("Plus") {
+            return Some(Self::Char('+'));
+        }
+        if let Some(canonical) = Self::NAMES.iter().find(|n| n.eq_ignore_ascii_case(name)) {
+            return Some(Self::Named(canonical));
+        }
+        if let Some((_, canonical)) = Self::ALIASES
+            .iter()
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+        {
+            return Some(Self::Named(canonical));
+        }
+
+        let number = |prefix: &str| {
+            let head = name.get(..prefix.len())?;
+            let digits = &name[prefix.len()..];
+            if !head.eq_ignore_ascii_case(prefix) || digits.starts_with('0') && digits != "0" {
+                return None;
+            }
+            digits.parse::<u8>().ok()
+        };
+        number("Num")
+            .filter(|n| *n <= 9)
+            .map(Self::Numpad)
+            .or_else(|| number("F").filter(|n| (1..=24).contains(n)).map(Self::F))
+    }
+}
+
+/// A keyboard shortcut.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct Accelerator {
+    modifiers: Modifiers,
+    key: Key,
+}
+
+impl Accelerator {
+    pub(crate) fn new(modifiers: Modifiers, key: Key) -> Self {
+        Self { modifiers, key }
+    }
+
+    pub(crate) fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    pub(crate) fn key(&self) -> Key {
+        self.key
+    }
+}
+
+impl FromStr for Accelerator {
+    type Err = ParseAcceleratorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err(ParseAcceleratorError::new(ErrorKind::Empty, 0..s.len()));
+        }
+
+        // Split into parts, keeping track of where each part is.
+        let mut parts = Vec::new();
+        let mut start = 0;
+        for part in s.split('+') {
+            let trimmed_start = start + (part.len() - part.trim_start().len());
+            let name = part.trim();
+            parts.push((name, trimmed_start..trimmed_start + name.len()));
+            start += part.len() + 1;
+        }
+
+        let (key, modifiers) = parts.split_last().unwrap();
+        let mut result = Modifiers::default();
+        for (name, span) in modifiers {
+            if name.is_empty() {
+                return Err(ParseAcceleratorError::new(
+                    ErrorKind::EmptyPart,
+                    span.clone(),
+                ));
+            }
+            let modifier = match Modifiers::from_name(name) {
+                Some(modifier) => modifier,
+                None if Key::from_name(name).is_some() => {
+                    let kind = ErrorKind::KeyNotLast(name.to_string());
+                    return Err(ParseAcceleratorError::new(kind, span.clone()));
+                }
+                None => {
+                    let kind = ErrorKind::UnknownModifier(name.to_string());
+                    return Err(ParseAcceleratorError::new(kind, span.clone()));
+                }
+            };
+            if result.contains(modifier) {
+                let kind = ErrorKind::DuplicateModifier(name.to_string());
+                return Err(ParseAcceleratorError::new(kind, span.clone()));
+            }
+            result = Modifiers(result.0 | modifier.0);
+        }
+
+        let (name, span) = key;
+        if name.is_empty() {
+            return Err(ParseAcceleratorError::new(
+                ErrorKind::EmptyPart,
+                span.clone(),
+            ));
+        }
+        if Modifiers::from_name(name).is_some() {
+            let kind = ErrorKind::MissingKey(name.to_string());
+            return Err(ParseAcceleratorError::new(kind, span.clone()));
+        }
+        match Key::from_name(name) {
+            Some(key) => Ok(Self::new(result, key)),
+            None => {
+                let kind = ErrorKind::UnknownKey(name.to_string());
+                Err(ParseAcceleratorError::new(kind, span.clone()))
+            }
+        }
+    }
+}
+
+/// Error returned when parsing an [`Accelerator`] fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ParseAcceleratorError {
+    kind: ErrorKind,
+    span: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ErrorKind {
+    Empty,
+    EmptyPart,
+    UnknownModifier(String),
+    UnknownKey(String),
+    DuplicateModifier(String),
+    KeyNotLast(String),
+    MissingKey(String),
+}
+
+impl ParseAcceleratorError {
+    fn new(kind: ErrorKind, span: Range<usize>) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl fmt::Display for ParseAcceleratorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ErrorKind::Empty => f.write_str("empty accelerator")?,
+            ErrorKind::EmptyPart => f.write_str("expected a modifier or key")?,
+            ErrorKind::UnknownModifier(name) => write!(f, "unknown modifier `{}`", name)?,
+            ErrorKind::UnknownKey(name) => write!(f, "unknown key `{}`", name)?,
+            ErrorKind::DuplicateModifier(name) => write!(f, "duplicate modifier `{}`", name)?,
+            ErrorKind::KeyNotLast(name) => write!(f, "key `{}` must come last", name)?,
+            ErrorKind::MissingKey(name) => write!(f, "missing key after `{}`", name)?,
+        }
+        write!(f, " at position {}", self.span.start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Accelerators to compare with the main crate, valid or not.
+    fn inputs() -> Vec<String> {
+        let mut inputs: Vec<String> = [
+            "",
+            "  ",
+            "CmdOrCtrl+Shift+N",
+            "ctrl + shift + n",
+            "Cmd+Option+Esc",
+            "CommandOrControl+Meta+Win+S",
+            "Control+Opt+Super+A",
+            "Ctrl+Plus",
+            "Ctrl++",
+            "Ctrl+ +N",
+            "+N",
+            "Hyper+N",
+            "Ctrl+Foo",
+            "Ctrl+F0",
+            "Ctrl+F25",
+            "Ctrl+F01",
+            "Num10",
+            "Num01",
+            "Ctrl+Control+N",
+            "N+Ctrl",
+            "Ctrl+ Shift",
+            "Ctrl+/",
+            "Ctrl+ß",
+            "Alt+é",
+            "Ctrl+\t",
+            "Ctrl+\u{7}",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        let names = Key::NAMES.iter().chain(Key::ALIASES.iter().map(|(n, _)| n));
+        inputs.extend(names.map(|name| format!("Shift+{}", name)));
+        inputs.extend((0..=25).map(|n| format!("F{}", n)));
+        inputs.extend((0..=10).map(|n| format!("num{}", n)));
+        inputs
+    }
+
+    #[test]
+    fn test_same_as_menubar() {
+        let inputs = inputs();
+        let parsed: Vec<_> = inputs
+            .iter()
+            .map(|s| (s.parse::<Accelerator>(), s.parse::<menubar::Accelerator>()))
+            .collect();
+        for (s, (ours, theirs)) in inputs.iter().zip(&parsed) {
+            match (ours, theirs) {
+                (Ok(_), Ok(_)) => {}
+                (Err(ours), Err(theirs)) => assert_eq!(ours.to_string(), theirs.to_string()),
+                _ => panic!("{:?} parsed differently: {:?} and {:?}", s, ours, theirs),
+            }
+        }
+
+        // Accelerators are equal when they are equal in the main crate.
+        let valid: Vec<_> = parsed
+            .iter()
+            .filter_map(|(ours, theirs)| Some((ours.clone().ok()?, theirs.clone().ok()?)))
+            .collect();
+        for (a, theirs_a) in &valid {
+            for (b, theirs_b) in &valid {
+                assert_eq!(a == b, theirs_a == theirs_b, "{:?} and {:?}", a, b);
+            }
+        }
+    }
+}
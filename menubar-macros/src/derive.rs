@@ -0,0 +1,389 @@
+//! `#[derive(Menu)]`.
+
+use std::collections::HashMap;
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, Ident, LitStr};
+
+use crate::accelerator::{Accelerator, Platform};
+use crate::role;
+
+/// A variant with a `#[menu(...)]` attribute.
+struct Command {
+    variant: Ident,
+    path: LitStr,
+    id: LitStr,
+    accel: Option<LitStr>,
+    /// The variant of `menubar::Role`.
+    role: Option<&'static str>,
+}
+
+/// The menu is built as a tree before it's expanded, so that commands in the
+/// same submenu don't have to be declared next to each other.
+enum Node {
+    /// The index of the command.
+    Item(usize),
+    Submenu {
+        title: String,
+        children: Vec<Node>,
+    },
+}
+
+/// Expand the derive to an implementation of `menubar::MenuCommand`.
+pub(crate) fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let variants = match &input.data {
+        Data::Enum(data) => &data.variants,
+        _ => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "`#[derive(Menu)]` is only supported on enums",
+            ))
+        }
+    };
+
+    let mut deriver = Deriver::default();
+    for variant in variants {
+        for attr in &variant.attrs {
+            if !attr.path().is_ident("menu") {
+                continue;
+            }
+            if !matches!(variant.fields, Fields::Unit) {
+                deriver.error(syn::Error::new_spanned(
+                    &variant.fields,
+                    "`#[menu]` is only supported on unit variants",
+                ));
+            } else if let Some(command) = deriver.command(&variant.ident, attr) {
+                deriver.commands.push(command);
+            }
+        }
+    }
+
+    let mut roots = Vec::new();
+    for index in 0..deriver.commands.len() {
+        if let Err(err) = insert(&mut roots, &deriver.commands, index) {
+            deriver.error(err);
+        }
+    }
+    deriver.check_conflicts();
+    if let Some(err) = deriver.errors {
+        return Err(err);
+    }
+
+    let menu = expand_menu(&roots, &deriver.commands);
+    let ids = deriver.commands.iter().map(|command| &command.id);
+    let variants = deriver.commands.iter().map(|command| &command.variant);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::menubar::MenuCommand for #name #ty_generics #where_clause {
+            fn menu() -> ::menubar::Menu<Self> {
+                #menu
+            }
+
+            fn from_item_id(id: &str) -> ::core::option::Option<Self> {
+                match id {
+                    #(#ids => ::core::option::Option::Some(Self::#variants),)*
+                    _ => ::core::option::Option::None,
+                }
+            }
+        }
+    })
+}
+
+#[derive(Default)]
+struct Deriver {
+    commands: Vec<Command>,
+    errors: Option<syn::Error>,
+}
+
+impl Deriver {
+    fn error(&mut self, err: syn::Error) {
+        match &mut self.errors {
+            Some(errors) => errors.combine(err),
+            None => self.errors = Some(err),
+        }
+    }
+
+    fn command(&mut self, variant: &Ident, attr: &syn::Attribute) -> Option<Command> {
+        let mut path: Option<LitStr> = None;
+        let mut id: Option<LitStr> = None;
+        let mut accel: Option<LitStr> = None;
+        let mut role: Option<&'static str> = None;
+        let res = attr.parse_nested_meta(|meta| {
+            let key = meta
+                .path
+                .get_ident()
+                .map(Ident::to_string)
+                .unwrap_or_default();
+            let slot = match &*key {
+                "path" => &mut path,
+                "id" => &mut id,
+                "accel" => &mut accel,
+                "role" => {
+                    if role.is_some() {
+                        return Err(meta.error("duplicate attribute `role`"));
+                    }
+                    let value: LitStr = meta.value()?.parse()?;
+                    match role::parse(&value.value()) {
+                        Ok(parsed) => role = Some(parsed),
+                        Err(err) => return Err(syn::Error::new(value.span(), err)),
+                    }
+                    return Ok(());
+                }
+                _ => {
+                    return Err(meta.error(format!(
+                        "unknown attribute `{}`, expected one of: `path`, `id`, `accel`, `role`",
+                        key,
+                    )))
+                }
+            };
+            if slot.is_some() {
+                return Err(meta.error(format!("duplicate attribute `{}`", key)));
+            }
+            *slot = Some(meta.value()?.parse()?);
+            Ok(())
+        });
+        if let Err(err) = res {
+            self.error(err);
+            return None;
+        }
+
+        let path = match path {
+            Some(path) => path,
+            None => {
+                self.error(syn::Error::new_spanned(attr, "missing `path`"));
+                return None;
+            }
+        };
+        if path.value().split('/').any(str::is_empty) {
+            self.error(syn::Error::new(
+                path.span(),
+                "paths must be titles separated by `/`, like `\"File/Save\"`",
+            ));
+            return None;
+        }
+        if let Some(accel) = &accel {
+            if let Err(err) = accel.value().parse::<Accelerator>() {
+                self.error(syn::Error::new(
+                    accel.span(),
+                    format!("invalid accelerator: {}", err),
+                ));
+                return None;
+            }
+        }
+
+        Some(Command {
+            variant: variant.clone(),
+            // The path is unique, so it makes a good default id.
+            id: id.unwrap_or_else(|| path.clone()),
+            path,
+            accel,
+            role,
+        })
+    }
+
+    /// Check that no two commands share an id, or an accelerator on any
+    /// platform.
+    fn check_conflicts(&mut self) {
+        let mut ids: HashMap<String, &Command> = HashMap::new();
+        let mut accels: HashMap<(Platform, Accelerator), &LitStr> = HashMap::new();
+        let mut errors = Vec::new();
+        for command in &self.commands {
+            match ids.get(&command.id.value()) {
+                // Duplicate paths have already been reported.
+                Some(first) if first.path.value() != command.path.value() => {
+                    errors.push(duplicate("id", &command.id, &first.id));
+                }
+                Some(_) => {}
+                None => {
+                    ids.insert(command.id.value(), command);
+                }
+            }
+            if let Some(accel) = &command.accel {
+                let parsed: Accelerator = accel.value().parse().expect("accelerator was validated");
+                // `CmdOrCtrl+S` and `Ctrl+S` only collide on some platforms.
+                let mut conflict: Option<(&LitStr, Vec<Platform>)> = None;
+                for platform in PLATFORMS {
+                    let modifiers = parsed.modifiers().resolve_for(platform);
+                    let resolved = Accelerator::new(modifiers, parsed.key());
+                    match accels.get(&(platform, resolved)) {
+                        Some(first) => match &mut conflict {
+                            Some((other, platforms)) if other.value() == first.value() => {
+                                platforms.push(platform)
+                            }
+                            Some(_) => {}
+                            None => conflict = Some((first, vec![platform])),
+                        },
+                        None => {
+                            accels.insert((platform, resolved), accel);
+                        }
+                    }
+                }
+                match conflict {
+                    Some((first, _)) if first.value() == accel.value() => {
+                        errors.push(duplicate("accelerator", accel, first));
+                    }
+                    Some((first, platforms)) => {
+                        errors.push(accelerator_conflict(accel, first, &platforms));
+                    }
+                    None => {}
+                }
+            }
+        }
+        for err in errors {
+            self.error(err);
+        }
+    }
+}
+
+fn duplicate(what: &str, lit: &LitStr, first: &LitStr) -> syn::Error {
+    let mut err = syn::Error::new(lit.span(), format!("duplicate {} `{}`", what, lit.value()));
+    err.combine(syn::Error::new(
+        first.span(),
+        format!("{} `{}` is first used here", what, first.value()),
+    ));
+    err
+}
+
+/// The platforms to check accelerators on.
+const PLATFORMS: [Platform; 3] = [Platform::MacOs, Platform::Windows, Platform::Linux];
+
+fn accelerator_conflict(lit: &LitStr, first: &LitStr, platforms: &[Platform]) -> syn::Error {
+    let names: Vec<&str> = platforms
+        .iter()
+        .map(|platform| match platform {
+            Platform::MacOs => "macOS",
+            Platform::Windows => "Windows",
+            Platform::Linux => "Linux",
+        })
+        .collect();
+    let mut err = syn::Error::new(
+        lit.span(),
+        format!(
+            "accelerator `{}` is the same as `{}` on {}",
+            lit.value(),
+            first.value(),
+            names.join(" and "),
+        ),
+    );
+    err.combine(syn::Error::new(
+        first.span(),
+        format!("accelerator `{}` is first used here", first.value()),
+    ));
+    err
+}
+
+/// Insert the command into the tree, creating submenus as needed.
+fn insert(mut nodes: &mut Vec<Node>, commands: &[Command], index: usize) -> syn::Result<()> {
+    let command = &commands[index];
+    let path = command.path.value();
+    let segments: Vec<&str> = path.split('/').collect();
+    let (title, parents) = segments.split_last().expect("paths are not empty");
+
+    for (depth, parent) in parents.iter().enumerate() {
+        let position = nodes.iter().position(|node| match node {
+            Node::Item(other) => last_segment(&commands[*other]) == *parent,
+            Node::Submenu { title: other, .. } => other == parent,
+        });
+        let position = match position {
+            Some(position) => position,
+            None => {
+                nodes.push(Node::Submenu {
+                    title: parent.to_string(),
+                    children: Vec::new(),
+                });
+                nodes.len() - 1
+            }
+        };
+        nodes = match &mut nodes[position] {
+            Node::Submenu { children, .. } => children,
+            Node::Item(other) => {
+                let parent = segments[..=depth].join("/");
+                return Err(conflict(command, &commands[*other], &parent));
+            }
+        };
+    }
+
+    for node in nodes.iter() {
+        match node {
+            Node::Item(other) if last_segment(&commands[*other]) == *title => {
+                let mut err =
+                    syn::Error::new(command.path.span(), format!("duplicate path `{}`", path));
+                err.combine(syn::Error::new(
+                    commands[*other].path.span(),
+                    format!("path `{}` is first used here", path),
+                ));
+                return Err(err);
+            }
+            Node::Submenu { title: other, .. } if other == title => {
+                let other = commands
+                    .iter()
+                    .find(|other| other.path.value().starts_with(&format!("{}/", path)))
+                    .expect("submenus are created by commands");
+                return Err(conflict(command, other, &path));
+            }
+            _ => {}
+        }
+    }
+    nodes.push(Node::Item(index));
+    Ok(())
+}
+
+/// Error for a path that is used both by an item and as a submenu.
+fn conflict(command: &Command, other: &Command, path: &str) -> syn::Error {
+    let mut err = syn::Error::new(
+        command.path.span(),
+        format!("`{}` is both an item and a submenu", path),
+    );
+    err.combine(syn::Error::new(
+        other.path.span(),
+        format!("`{}` is also used here", path),
+    ));
+    err
+}
+
+fn last_segment(command: &Command) -> String {
+    let path = command.path.value();
+    path.rsplit('/').next().unwrap_or_default().to_string()
+}
+
+fn expand_menu(nodes: &[Node], commands: &[Command]) -> TokenStream {
+    let entries = nodes.iter().map(|node| match node {
+        Node::Item(index) => expand_item(&commands[*index]),
+        Node::Submenu { title, children } => {
+            let menu = expand_menu(children, commands);
+            quote!(::menubar::Submenu::new(#title, #menu))
+        }
+    });
+    quote! {
+        <::menubar::Menu<Self> as ::core::default::Default>::default()
+            #(.with(#entries))*
+    }
+}
+
+fn expand_item(command: &Command) -> TokenStream {
+    let title = last_segment(command);
+    let Command {
+        variant, id, accel, ..
+    } = command;
+    let mut tokens = quote! {
+        ::menubar::MenuItem::new(#title)
+            .with_id(#id)
+            .with_command(Self::#variant)
+    };
+    if let Some(accel) = accel {
+        tokens.extend(quote! {
+            .with_accelerator(
+                #accel
+                    .parse::<::menubar::Accelerator>()
+                    .expect("accelerator was validated by `#[derive(Menu)]`")
+            )
+        });
+    }
+    if let Some(role) = command.role {
+        let role = Ident::new(role, Span::call_site());
+        tokens.extend(quote!(.with_role(::menubar::Role::#role)));
+    }
+    tokens
+}
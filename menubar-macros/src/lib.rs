@@ -0,0 +1,121 @@
+//! Procedural macros for [`menubar`](https://docs.rs/menubar).
+//!
+//! Use these through the re-exports in `menubar`, with the `macros` feature
+//! enabled.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+mod accelerator;
+mod derive;
+mod menu;
+mod role;
+
+/// Declare a menu.
+///
+/// The macro takes a comma-separated list of entries, and evaluates to a
+/// `menubar::Menu`. Each entry is one of:
+///
+/// - `---`, a separator.
+/// - `"Title" { ... }`, a submenu containing the entries in the braces.
+/// - `"Title"`, an item, optionally followed by `=> handler`, where `handler`
+///   is a closure that is called when the item is activated.
+///
+/// Instead of a string literal, the title can be any expression in
+/// parentheses, like `(format!("Item {}", i))`.
+///
+/// The title can be followed by attributes in brackets:
+///
+/// - `id = "..."`: the id of the item or submenu. Ids must be unique.
+/// - `accel = "..."`: the accelerator of the item, like `"CmdOrCtrl+N"`.
+/// - `command = ...`: the command that the item delivers when activated.
+/// - `checked = ...`: makes the item a checkbox, with the given state.
+/// - `group = ...`: puts a checkable item in the radio group with the given
+///   name.
+/// - `enabled = ...`: whether the item or submenu is enabled.
+/// - `hidden = ...`: whether the item or submenu is hidden.
+///
+/// Accelerators and ids are checked at compile time.
+///
+/// If no item has a command, the menu is a `Menu<()>`. Otherwise, the type of
+/// the commands is inferred.
+///
+/// ```
+/// use menubar::menu;
+///
+/// let menu = menu! {
+///     "File" {
+///         "New" [id = "new", accel = "CmdOrCtrl+N"] => || println!("new"),
+///         "Open Recent" {
+///             "a.txt",
+///         },
+///         ---,
+///         "Word Wrap" [checked = true],
+///         "Close" [enabled = false],
+///     },
+/// };
+///
+/// assert_eq!(menu.find_id("new").unwrap().title(), Some("New"));
+/// ```
+#[proc_macro]
+pub fn menu(input: TokenStream) -> TokenStream {
+    let entries = parse_macro_input!(input as menu::Entries);
+    match menu::expand(&entries) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => {
+            // Several errors expand to several `compile_error!` invocations,
+            // which is only valid in statement position.
+            let errors = err.to_compile_error();
+            quote::quote!({ #errors ::menubar::Menu::new() }).into()
+        }
+    }
+}
+
+/// Derive `menubar::MenuCommand` for a command enum.
+///
+/// Each unit variant with a `#[menu(...)]` attribute becomes an item that
+/// delivers the variant as its command. Variants without the attribute are
+/// left out of the menu. The attribute takes:
+///
+/// - `path = "..."`: the titles of the submenus leading to the item, and the
+///   title of the item itself, separated by `/`. Submenus are created in the
+///   order they're first used.
+/// - `id = "..."`: the id of the item. Defaults to the path.
+/// - `accel = "..."`: the accelerator of the item, like `"CmdOrCtrl+S"`.
+/// - `role = "..."`: the role of the item, like `"save"`.
+///
+/// Paths, ids and accelerators must be unique, and a path can't be both an
+/// item and a submenu. Accelerators are compared on every platform, so
+/// `"CmdOrCtrl+S"` and `"Ctrl+S"` conflict too. These are checked at compile time, as are
+/// accelerators and roles.
+///
+/// ```
+/// use menubar::{Menu, MenuCommand};
+///
+/// #[derive(Debug, Clone, PartialEq, Menu)]
+/// enum Command {
+///     #[menu(path = "File/New", accel = "CmdOrCtrl+N", role = "new")]
+///     New,
+///     #[menu(path = "File/Save", accel = "CmdOrCtrl+S", role = "save")]
+///     Save,
+///     #[menu(path = "Edit/Copy", id = "copy", role = "copy")]
+///     Copy,
+///     #[menu(path = "File/Quit", accel = "CmdOrCtrl+Q", role = "quit")]
+///     Quit,
+/// }
+///
+/// let menu = Command::menu();
+/// let save = menu.find(&["File", "Save"]).unwrap().as_item().unwrap();
+/// assert_eq!(save.command(), Some(&Command::Save));
+/// assert_eq!(Command::from_item_id("copy"), Some(Command::Copy));
+/// ```
+#[proc_macro_derive(Menu, attributes(menu))]
+pub fn derive_menu(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match derive::expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
@@ -0,0 +1,291 @@
+//! The `menu!` macro.
+
+use std::collections::HashMap;
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{braced, bracketed, parenthesized, Expr, Ident, LitStr, Token};
+
+use crate::accelerator::Accelerator;
+
+/// A comma-separated list of entries.
+pub(crate) struct Entries(Vec<Entry>);
+
+enum Entry {
+    Separator,
+    Item {
+        title: Expr,
+        attrs: Vec<Attr>,
+        handler: Option<Expr>,
+    },
+    Submenu {
+        title: Expr,
+        attrs: Vec<Attr>,
+        entries: Entries,
+    },
+}
+
+/// A `key = value` pair in the brackets after a title.
+struct Attr {
+    key: Ident,
+    value: Expr,
+}
+
+impl Entries {
+    fn has_commands(&self) -> bool {
+        self.0.iter().any(|entry| match entry {
+            Entry::Separator => false,
+            Entry::Item { attrs, .. } => attrs.iter().any(|attr| attr.key == "command"),
+            Entry::Submenu { entries, .. } => entries.has_commands(),
+        })
+    }
+}
+
+impl Parse for Entries {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let entries = Punctuated::<Entry, Token![,]>::parse_terminated(input)?;
+        Ok(Self(entries.into_iter().collect()))
+    }
+}
+
+impl Parse for Entry {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        if input.peek(Token![-]) {
+            input.parse::<Token![-]>()?;
+            input.parse::<Token![-]>()?;
+            input.parse::<Token![-]>()?;
+            return Ok(Self::Separator);
+        }
+
+        let lookahead = input.lookahead1();
+        let title = if lookahead.peek(LitStr) {
+            let title: LitStr = input.parse()?;
+            syn::parse_quote!(#title)
+        } else if lookahead.peek(syn::token::Paren) {
+            let content;
+            parenthesized!(content in input);
+            content.parse()?
+        } else {
+            return Err(lookahead.error());
+        };
+
+        let mut attrs = Vec::new();
+        if input.peek(syn::token::Bracket) {
+            let content;
+            bracketed!(content in input);
+            attrs = Punctuated::<Attr, Token![,]>::parse_terminated(&content)?
+                .into_iter()
+                .collect();
+        }
+
+        if input.peek(syn::token::Brace) {
+            let content;
+            braced!(content in input);
+            let entries = content.parse()?;
+            Ok(Self::Submenu {
+                title,
+                attrs,
+                entries,
+            })
+        } else if input.peek(Token![=>]) {
+            input.parse::<Token![=>]>()?;
+            let handler = input.parse()?;
+            Ok(Self::Item {
+                title,
+                attrs,
+                handler: Some(handler),
+            })
+        } else {
+            Ok(Self::Item {
+                title,
+                attrs,
+                handler: None,
+            })
+        }
+    }
+}
+
+impl Parse for Attr {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let key = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let value = input.parse()?;
+        Ok(Self { key, value })
+    }
+}
+
+/// Expand the entries to an expression that creates a `menubar::Menu`.
+pub(crate) fn expand(entries: &Entries) -> syn::Result<TokenStream> {
+    let mut expander = Expander {
+        typed: entries.has_commands(),
+        ..Expander::default()
+    };
+    let tokens = expander.menu(entries);
+    match expander.errors {
+        Some(err) => Err(err),
+        None => Ok(tokens),
+    }
+}
+
+#[derive(Default)]
+struct Expander {
+    /// Whether any item has a command. If not, the menu is a `Menu<()>`, so
+    /// that the type doesn't have to be given.
+    typed: bool,
+    /// The ids seen so far, along with where they were first used.
+    ids: HashMap<String, Span>,
+    errors: Option<syn::Error>,
+}
+
+impl Expander {
+    fn error(&mut self, err: syn::Error) {
+        match &mut self.errors {
+            Some(errors) => errors.combine(err),
+            None => self.errors = Some(err),
+        }
+    }
+
+    fn menu(&mut self, entries: &Entries) -> TokenStream {
+        let entries = entries.0.iter().map(|entry| self.entry(entry));
+        // Collect first, so that errors are reported in order.
+        let entries: Vec<_> = entries.collect();
+        let menu = if self.typed {
+            quote!(<::menubar::Menu<_> as ::core::default::Default>::default())
+        } else {
+            quote!(::menubar::Menu::new())
+        };
+        quote!(#menu #(.with(#entries))*)
+    }
+
+    fn entry(&mut self, entry: &Entry) -> TokenStream {
+        match entry {
+            Entry::Separator => quote!(::menubar::Separator),
+            Entry::Item {
+                title,
+                attrs,
+                handler,
+            } => {
+                let mut tokens = quote!(::menubar::MenuItem::new(#title));
+                for attr in self.attrs(
+                    attrs,
+                    &[
+                        "id", "accel", "command", "checked", "group", "enabled", "hidden",
+                    ],
+                ) {
+                    tokens.extend(attr);
+                }
+                if let Some(handler) = handler {
+                    tokens.extend(quote!(.with_handler(#handler)));
+                }
+                tokens
+            }
+            Entry::Submenu {
+                title,
+                attrs,
+                entries,
+            } => {
+                let attrs = self.attrs(attrs, &["id", "enabled", "hidden"]);
+                let menu = self.menu(entries);
+                quote!(::menubar::Submenu::new(#title, #menu) #(#attrs)*)
+            }
+        }
+    }
+
+    /// Expand the attributes to builder method calls.
+    fn attrs(&mut self, attrs: &[Attr], allowed: &[&str]) -> Vec<TokenStream> {
+        let mut seen: Vec<&Ident> = Vec::new();
+        let mut tokens = Vec::new();
+        for Attr { key, value } in attrs {
+            if seen.contains(&key) {
+                self.error(syn::Error::new(
+                    key.span(),
+                    format!("duplicate attribute `{}`", key),
+                ));
+                continue;
+            }
+            seen.push(key);
+
+            if !allowed.iter().any(|allowed| key == allowed) {
+                let msg = if ["accel", "command", "checked", "group"]
+                    .iter()
+                    .any(|item_only| key == item_only)
+                {
+                    format!("`{}` is not supported on submenus", key)
+                } else {
+                    format!(
+                        "unknown attribute `{}`, expected one of: `{}`",
+                        key,
+                        allowed.join("`, `"),
+                    )
+                };
+                self.error(syn::Error::new(key.span(), msg));
+                continue;
+            }
+
+            let key = key.to_string();
+            match &*key {
+                "id" => match self.id(value) {
+                    Ok(id) => tokens.push(quote!(.with_id(#id))),
+                    Err(err) => self.error(err),
+                },
+                "accel" => match accelerator(value) {
+                    Ok(accel) => tokens.push(quote! {
+                        .with_accelerator(
+                            #accel
+                                .parse::<::menubar::Accelerator>()
+                                .expect("accelerator was validated by `menu!`")
+                        )
+                    }),
+                    Err(err) => self.error(err),
+                },
+                "command" => tokens.push(quote!(.with_command(#value))),
+                "checked" => tokens.push(quote!(.with_checked(#value))),
+                "group" => tokens.push(quote!(.with_radio_group(#value))),
+                "enabled" => tokens.push(quote!(.with_enabled(#value))),
+                "hidden" => tokens.push(quote!(.with_hidden(#value))),
+                _ => unreachable!("unhandled attribute {}", key),
+            }
+        }
+        tokens
+    }
+
+    fn id<'a>(&mut self, value: &'a Expr) -> syn::Result<&'a LitStr> {
+        let id = string_literal(value, "ids")?;
+        if let Some(first) = self.ids.get(&id.value()) {
+            let mut err = syn::Error::new(id.span(), format!("duplicate id `{}`", id.value()));
+            err.combine(syn::Error::new(
+                *first,
+                format!("id `{}` is first used here", id.value()),
+            ));
+            return Err(err);
+        }
+        self.ids.insert(id.value(), id.span());
+        Ok(id)
+    }
+}
+
+fn accelerator(value: &Expr) -> syn::Result<&LitStr> {
+    let accel = string_literal(value, "accelerators")?;
+    match accel.value().parse::<Accelerator>() {
+        Ok(_) => Ok(accel),
+        Err(err) => Err(syn::Error::new(
+            accel.span(),
+            format!("invalid accelerator: {}", err),
+        )),
+    }
+}
+
+fn string_literal<'a>(value: &'a Expr, what: &str) -> syn::Result<&'a LitStr> {
+    match value {
+        Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(lit),
+            ..
+        }) => Ok(lit),
+        _ => Err(syn::Error::new_spanned(
+            value,
+            format!("{} must be string literals", what),
+        )),
+    }
+}
@@ -0,0 +1,58 @@
+//! Checking roles at compile time.
+//!
+//! This is a copy of the names of `menubar::Role`, which the tests check
+//! against the main crate.
+
+/// The `snake_case` names of the roles, along with their variants.
+const NAMES: [(&str, &str); 26] = [
+    ("about", "About"),
+    ("preferences", "Preferences"),
+    ("services", "Services"),
+    ("hide", "Hide"),
+    ("hide_others", "HideOthers"),
+    ("show_all", "ShowAll"),
+    ("quit", "Quit"),
+    ("new", "New"),
+    ("open", "Open"),
+    ("save", "Save"),
+    ("save_as", "SaveAs"),
+    ("print", "Print"),
+    ("close", "Close"),
+    ("undo", "Undo"),
+    ("redo", "Redo"),
+    ("cut", "Cut"),
+    ("copy", "Copy"),
+    ("paste", "Paste"),
+    ("delete", "Delete"),
+    ("select_all", "SelectAll"),
+    ("find", "Find"),
+    ("minimize", "Minimize"),
+    ("zoom", "Zoom"),
+    ("toggle_full_screen", "ToggleFullScreen"),
+    ("bring_all_to_front", "BringAllToFront"),
+    ("help", "Help"),
+];
+
+/// The variant of `menubar::Role` with the given name.
+pub(crate) fn parse(name: &str) -> Result<&'static str, String> {
+    NAMES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, variant)| *variant)
+        .ok_or_else(|| format!("unknown role `{}`", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_as_menubar() {
+        for (name, variant) in NAMES {
+            let role: menubar::Role = name.parse().unwrap();
+            assert_eq!(format!("{:?}", role), variant);
+        }
+        let err = "SelectAll".parse::<menubar::Role>().unwrap_err();
+        assert_eq!(parse("SelectAll").unwrap_err(), err.to_string());
+    }
+}
@@ -0,0 +1,80 @@
+use menubar::{Accelerator, Menu, MenuCommand, MenuEntry, Role};
+
+#[derive(Debug, Clone, Copy, PartialEq, Menu)]
+enum Command {
+    #[menu(path = "File/New", accel = "CmdOrCtrl+N", role = "new")]
+    New,
+    #[menu(path = "Edit/Copy", accel = "CmdOrCtrl+C", role = "copy", id = "copy")]
+    Copy,
+    #[menu(path = "File/Export/PNG")]
+    ExportPng,
+    #[menu(path = "File/Quit", accel = "CmdOrCtrl+Q", role = "quit")]
+    Quit,
+    /// Not in the menu.
+    #[allow(dead_code)]
+    Reload(bool),
+}
+
+#[test]
+fn test_derive() {
+    let menu = Command::menu();
+
+    let titles: Vec<_> = menu.entries().iter().filter_map(MenuEntry::title).collect();
+    assert_eq!(titles, ["File", "Edit"]);
+    let file = menu.find(&["File"]).unwrap().as_submenu().unwrap();
+    let titles: Vec<_> = file
+        .menu()
+        .entries()
+        .iter()
+        .filter_map(MenuEntry::title)
+        .collect();
+    assert_eq!(titles, ["New", "Export", "Quit"]);
+
+    let new = menu.find(&["File", "New"]).unwrap().as_item().unwrap();
+    assert_eq!(new.id().unwrap().as_str(), "File/New");
+    assert_eq!(new.command(), Some(&Command::New));
+    assert_eq!(new.role(), Some(Role::New));
+    assert_eq!(
+        new.accelerator(),
+        Some("CmdOrCtrl+N".parse::<Accelerator>().unwrap())
+    );
+
+    let png = menu
+        .find(&["File", "Export", "PNG"])
+        .unwrap()
+        .as_item()
+        .unwrap();
+    assert_eq!(png.command(), Some(&Command::ExportPng));
+    assert_eq!(png.accelerator(), None);
+    assert_eq!(png.role(), None);
+
+    let copy = menu.find_id("copy").unwrap().as_item().unwrap();
+    assert_eq!(copy.command(), Some(&Command::Copy));
+}
+
+#[test]
+fn test_from_item_id() {
+    assert_eq!(Command::from_item_id("File/New"), Some(Command::New));
+    assert_eq!(Command::from_item_id("copy"), Some(Command::Copy));
+    assert_eq!(Command::from_item_id("Edit/Copy"), None);
+    assert_eq!(
+        Command::from_item_id("File/Export/PNG"),
+        Some(Command::ExportPng)
+    );
+    assert_eq!(Command::from_item_id("Reload"), None);
+
+    // Every item maps back to its command.
+    fn check(menu: &Menu<Command>) {
+        for entry in menu.entries() {
+            match entry {
+                MenuEntry::Item(item) => {
+                    let id = item.id().unwrap().as_str();
+                    assert_eq!(Command::from_item_id(id).as_ref(), item.command());
+                }
+                MenuEntry::Submenu(submenu) => check(submenu.menu()),
+                MenuEntry::Separator(_) => {}
+            }
+        }
+    }
+    check(&Command::menu());
+}
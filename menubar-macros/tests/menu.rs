@@ -0,0 +1,102 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use menubar::{menu, Accelerator, MenuEntry};
+
+#[test]
+fn test_menu() {
+    let activated = Rc::new(Cell::new(0));
+    let handler_activated = Rc::clone(&activated);
+    let recent = ["a.txt", "b.txt"];
+
+    let mut menu = menu! {
+        "File" [id = "file"] {
+            "New" [id = "new", accel = "CmdOrCtrl+N"] => move || {
+                handler_activated.set(handler_activated.get() + 1);
+            },
+            ---,
+            "Open Recent" {
+                (recent[0]),
+                (format!("{} (read-only)", recent[1])) [hidden = true],
+            },
+            "Word Wrap" [checked = true],
+            "Close" [enabled = false, accel = "Ctrl+W",],
+        },
+        "Edit" [enabled = false] {},
+    };
+
+    assert_eq!(menu.len(), 2);
+    let file = menu.find_id("file").unwrap().as_submenu().unwrap();
+    assert_eq!(file.title(), "File");
+    assert_eq!(file.menu().len(), 5);
+    assert!(file.menu().entries()[1].is_separator());
+
+    let new = menu.find(&["File", "New"]).unwrap().as_item().unwrap();
+    assert_eq!(new.id().unwrap().as_str(), "new");
+    assert_eq!(
+        new.accelerator(),
+        Some("CmdOrCtrl+N".parse::<Accelerator>().unwrap())
+    );
+    assert_eq!(new.checked(), None);
+
+    let recent = menu.find(&["File", "Open Recent"]).unwrap();
+    let titles: Vec<_> = recent
+        .as_submenu()
+        .unwrap()
+        .menu()
+        .entries()
+        .iter()
+        .filter_map(MenuEntry::title)
+        .collect();
+    assert_eq!(titles, ["a.txt", "b.txt (read-only)"]);
+    let read_only = menu.find(&["File", "Open Recent", "b.txt (read-only)"]);
+    assert!(read_only.unwrap().as_item().unwrap().hidden());
+
+    let word_wrap = menu.find(&["File", "Word Wrap"]).unwrap();
+    assert_eq!(word_wrap.as_item().unwrap().checked(), Some(true));
+    let close = menu.find(&["File", "Close"]).unwrap().as_item().unwrap();
+    assert!(!close.enabled());
+    assert_eq!(close.accelerator(), Some("Ctrl+W".parse().unwrap()));
+
+    let edit = menu.find(&["Edit"]).unwrap().as_submenu().unwrap();
+    assert!(!edit.enabled());
+    assert!(edit.menu().is_empty());
+
+    let new = menu.find_id_mut("new").unwrap().as_item_mut().unwrap();
+    assert!(new.activate());
+    assert_eq!(activated.get(), 1);
+}
+
+#[test]
+fn test_empty() {
+    let menu = menu! {};
+    assert!(menu.is_empty());
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Command {
+    Open(usize),
+    Quit,
+}
+
+#[test]
+fn test_commands() {
+    let menu = menu! {
+        "File" {
+            "Open" [command = Command::Open(0)],
+            "Quit" [accel = "CmdOrCtrl+Q", command = Command::Quit],
+            "About" => || {},
+        },
+    };
+
+    let item = |path: &[&str]| menu.find(path).unwrap().as_item().unwrap();
+    assert_eq!(item(&["File", "Open"]).command(), Some(&Command::Open(0)));
+    assert_eq!(item(&["File", "Quit"]).command(), Some(&Command::Quit));
+    assert_eq!(item(&["File", "About"]).command(), None);
+}
+
+#[test]
+fn test_ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,23 @@
+use menubar::Menu;
+
+#[derive(Clone, Menu)]
+enum Command {
+    #[menu(path = "File/Save", accel = "CmdOrCtrl+S")]
+    Save,
+    #[menu(path = "File/Save")]
+    SaveAgain,
+    #[menu(path = "File/Save/Copy")]
+    SaveCopy,
+    #[menu(path = "File/Save As", accel = "CmdOrCtrl+S")]
+    SaveAs,
+    #[menu(path = "Edit/Copy", id = "File/Save As")]
+    Copy,
+    #[menu(path = "File")]
+    File,
+    #[menu(path = "Edit/Paste", accel = "CmdOrCtrl+V")]
+    Paste,
+    #[menu(path = "Edit/Paste Special", accel = "Ctrl+V")]
+    PasteSpecial,
+}
+
+fn main() {}
@@ -0,0 +1,24 @@
+use menubar::Menu;
+
+#[derive(Clone, Menu)]
+enum Command {
+    #[menu(path = "File/Open", accel = "Ctrl+Foo")]
+    Open,
+    #[menu(path = "File/Save", role = "savee")]
+    Save,
+    #[menu(path = "File//Close")]
+    Close,
+    #[menu(accel = "Ctrl+Q")]
+    Quit,
+    #[menu(path = "File/New", shortcut = "Ctrl+N")]
+    New,
+    #[menu(path = "File/Print", path = "File/Print...")]
+    Print,
+    #[menu(path = "File/Recent")]
+    Recent(usize),
+}
+
+#[derive(Clone, Menu)]
+struct NotAnEnum;
+
+fn main() {}
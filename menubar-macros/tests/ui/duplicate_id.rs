@@ -0,0 +1,13 @@
+use menubar::menu;
+
+fn main() {
+    let _ = menu! {
+        "File" [id = "file"] {
+            "New" [id = "new"],
+            "Open Recent" {
+                "a.txt" [id = "new"],
+            },
+        },
+        "Edit" [id = "file"] {},
+    };
+}
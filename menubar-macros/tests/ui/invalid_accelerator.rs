@@ -0,0 +1,11 @@
+use menubar::menu;
+
+fn main() {
+    let _ = menu! {
+        "File" {
+            "New" [accel = "CmdOrCtrl+N"],
+            "Open" [accel = "Ctrl+Foo"],
+            "Close" [accel = "Ctrl+Shift"],
+        },
+    };
+}
@@ -0,0 +1,11 @@
+use menubar::menu;
+
+fn main() {
+    let _ = menu! {
+        "File" [accel = "Ctrl+F"] {
+            "New" [shortcut = "Ctrl+N"],
+            "Open" [enabled = true, enabled = false],
+            "Close" [id = CLOSE],
+        },
+    };
+}
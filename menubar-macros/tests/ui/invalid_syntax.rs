@@ -0,0 +1,9 @@
+use menubar::menu;
+
+fn main() {
+    let _ = menu! {
+        "File" {
+            New,
+        },
+    };
+}
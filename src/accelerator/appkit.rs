@@ -0,0 +1,284 @@
+//! Conversion to and from AppKit key equivalents.
+//!
+//! `NSMenuItem` describes its shortcut with a key equivalent string and a
+//! modifier mask. Special keys use characters from the private use area,
+//! like `NSF1FunctionKey`, and an uppercase letter implies Shift.
+
+use super::{Accelerator, Key, Modifiers, Platform};
+
+/// `NSEventModifierFlagShift`.
+pub const SHIFT_MASK: usize = 1 << 17;
+/// `NSEventModifierFlagControl`.
+pub const CONTROL_MASK: usize = 1 << 18;
+/// `NSEventModifierFlagOption`.
+pub const OPTION_MASK: usize = 1 << 19;
+/// `NSEventModifierFlagCommand`.
+pub const COMMAND_MASK: usize = 1 << 20;
+/// `NSEventModifierFlagNumericPad`.
+pub const NUMERIC_PAD_MASK: usize = 1 << 21;
+
+const MODIFIERS: [(Modifiers, usize); 4] = [
+    (Modifiers::SHIFT, SHIFT_MASK),
+    (Modifiers::CONTROL, CONTROL_MASK),
+    (Modifiers::ALT, OPTION_MASK),
+    (Modifiers::SUPER, COMMAND_MASK),
+];
+
+/// `NSF1FunctionKey`; the other function keys follow it.
+const F1: u32 = 0xF704;
+/// `NSF35FunctionKey`.
+const F35: u32 = 0xF726;
+
+/// Keys with a fixed key equivalent character.
+const SPECIAL: [(Key, char); 14] = [
+    (Key::Up, '\u{F700}'),
+    (Key::Down, '\u{F701}'),
+    (Key::Left, '\u{F702}'),
+    (Key::Right, '\u{F703}'),
+    (Key::Insert, '\u{F727}'),
+    (Key::Delete, '\u{F728}'),
+    (Key::Home, '\u{F729}'),
+    (Key::End, '\u{F72B}'),
+    (Key::PageUp, '\u{F72C}'),
+    (Key::PageDown, '\u{F72D}'),
+    (Key::PrintScreen, '\u{F72E}'),
+    // `NSDeleteCharacter`, shown as ⌫.
+    (Key::Backspace, '\u{7F}'),
+    (Key::Tab, '\t'),
+    (Key::Escape, '\u{1B}'),
+];
+
+/// Keys on the numeric keypad, other than the digits.
+const NUMPAD: [(Key, char); 5] = [
+    (Key::NumpadAdd, '+'),
+    (Key::NumpadSubtract, '-'),
+    (Key::NumpadMultiply, '*'),
+    (Key::NumpadDivide, '/'),
+    (Key::NumpadDecimal, '.'),
+];
+
+/// The key equivalent and modifier mask of an `NSMenuItem`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KeyEquivalent {
+    pub key: String,
+    /// A combination of `NSEventModifierFlags`.
+    pub modifier_mask: usize,
+}
+
+impl KeyEquivalent {
+    /// No key equivalent.
+    pub fn none() -> Self {
+        Self {
+            key: String::new(),
+            modifier_mask: 0,
+        }
+    }
+}
+
+impl Accelerator {
+    /// The AppKit key equivalent for this accelerator.
+    ///
+    /// [`CMD_OR_CTRL`][Modifiers::CMD_OR_CTRL] becomes Command. Letters are
+    /// given in lowercase, with Shift in the modifier mask.
+    ///
+    /// Returns `None` for keys that can't be used as key equivalents, like
+    /// the media keys.
+    pub fn to_key_equivalent(&self) -> Option<KeyEquivalent> {
+        let mut modifier_mask = 0;
+        let modifiers = self.modifiers().resolve_for(Platform::MacOs);
+        for (modifier, mask) in MODIFIERS {
+            if modifiers.contains(modifier) {
+                modifier_mask |= mask;
+            }
+        }
+
+        let key = match self.key() {
+            Key::Char(c) => c.to_lowercase().collect(),
+            Key::F(n) => char::from_u32(F1 + u32::from(n) - 1)?.to_string(),
+            Key::Numpad(n) => {
+                modifier_mask |= NUMERIC_PAD_MASK;
+                n.to_string()
+            }
+            Key::Enter => "\r".to_owned(),
+            Key::Space => " ".to_owned(),
+            key => {
+                if let Some((_, c)) = NUMPAD.iter().find(|(k, _)| *k == key) {
+                    modifier_mask |= NUMERIC_PAD_MASK;
+                    c.to_string()
+                } else {
+                    let (_, c) = SPECIAL.iter().find(|(k, _)| *k == key)?;
+                    c.to_string()
+                }
+            }
+        };
+
+        Some(KeyEquivalent { key, modifier_mask })
+    }
+
+    /// The accelerator described by an AppKit key equivalent.
+    ///
+    /// An uppercase letter adds Shift. Returns `None` if the key equivalent
+    /// is empty, or not a single key.
+    pub fn from_key_equivalent(key_equivalent: &KeyEquivalent) -> Option<Self> {
+        let mut chars = key_equivalent.key.chars();
+        let c = match (chars.next(), chars.next()) {
+            (Some(c), None) => c,
+            _ => return None,
+        };
+
+        let mut modifiers = Modifiers::from_modifier_mask(key_equivalent.modifier_mask);
+
+        let numpad = key_equivalent.modifier_mask & NUMERIC_PAD_MASK != 0;
+        let numpad_key = NUMPAD.iter().find(|(_, n)| numpad && *n == c);
+        let key = if let Some((key, _)) = SPECIAL.iter().find(|(_, s)| *s == c) {
+            *key
+        } else if let Some((key, _)) = numpad_key {
+            *key
+        } else if (F1..=F35).contains(&u32::from(c)) {
+            let n = (u32::from(c) - F1 + 1) as u8;
+            // We only support up to F24.
+            if n > 24 {
+                return None;
+            }
+            Key::F(n)
+        } else {
+            match c {
+                // `NSBackspaceCharacter`.
+                '\u{8}' => Key::Backspace,
+                // `NSCarriageReturnCharacter`, `NSNewlineCharacter` and `NSEnterCharacter`.
+                '\r' | '\n' | '\u{3}' => Key::Enter,
+                ' ' => Key::Space,
+                '0'..='9' if numpad => Key::Numpad(c as u8 - b'0'),
+                c if c.is_whitespace() || c.is_control() => return None,
+                c => {
+                    if c.is_uppercase() {
+                        modifiers |= Modifiers::SHIFT;
+                    }
+                    Key::Char(c)
+                }
+            }
+        };
+
+        Some(Self::new(modifiers, key))
+    }
+}
+
+impl Modifiers {
+    /// The modifiers in a combination of `NSEventModifierFlags`.
+    ///
+    /// Flags other than the four modifiers, like the numeric pad flag, are
+    /// ignored.
+    pub fn from_modifier_mask(mask: usize) -> Self {
+        let mut modifiers = Modifiers::empty();
+        for (modifier, flag) in MODIFIERS {
+            if mask & flag != 0 {
+                modifiers |= modifier;
+            }
+        }
+        modifiers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_equivalent(key: &str, modifier_mask: usize) -> KeyEquivalent {
+        KeyEquivalent {
+            key: key.to_owned(),
+            modifier_mask,
+        }
+    }
+
+    /// Accelerators, along with their key equivalent, that convert both ways.
+    const ROUNDTRIP: &[(&str, &str, usize)] = &[
+        ("Cmd+N", "n", COMMAND_MASK),
+        ("Cmd+Shift+N", "n", COMMAND_MASK | SHIFT_MASK),
+        ("Ctrl+Alt+Q", "q", CONTROL_MASK | OPTION_MASK),
+        ("Cmd+,", ",", COMMAND_MASK),
+        ("Cmd+Plus", "+", COMMAND_MASK),
+        ("Cmd+F1", "\u{F704}", COMMAND_MASK),
+        ("F12", "\u{F70F}", 0),
+        ("Alt+F24", "\u{F71B}", OPTION_MASK),
+        ("Cmd+Up", "\u{F700}", COMMAND_MASK),
+        ("Cmd+Right", "\u{F703}", COMMAND_MASK),
+        ("Cmd+Backspace", "\u{7F}", COMMAND_MASK),
+        ("Cmd+Delete", "\u{F728}", COMMAND_MASK),
+        ("Cmd+PageDown", "\u{F72D}", COMMAND_MASK),
+        ("Cmd+Home", "\u{F729}", COMMAND_MASK),
+        ("Cmd+Escape", "\u{1B}", COMMAND_MASK),
+        ("Cmd+Tab", "\t", COMMAND_MASK),
+        ("Cmd+Enter", "\r", COMMAND_MASK),
+        ("Cmd+Space", " ", COMMAND_MASK),
+        ("Cmd+Num1", "1", COMMAND_MASK | NUMERIC_PAD_MASK),
+        ("NumAdd", "+", NUMERIC_PAD_MASK),
+        ("Cmd+NumDec", ".", COMMAND_MASK | NUMERIC_PAD_MASK),
+    ];
+
+    #[test]
+    fn test_to_key_equivalent() {
+        for (s, key, mask) in ROUNDTRIP {
+            let accelerator: Accelerator = s.parse().unwrap();
+            let expected = key_equivalent(key, *mask);
+            assert_eq!(accelerator.to_key_equivalent(), Some(expected), "{}", s);
+        }
+
+        // Platform-independent modifiers use Command.
+        let accelerator: Accelerator = "CmdOrCtrl+Shift+S".parse().unwrap();
+        let expected = key_equivalent("s", COMMAND_MASK | SHIFT_MASK);
+        assert_eq!(accelerator.to_key_equivalent(), Some(expected));
+
+        for s in ["MediaPlayPause", "VolumeUp", "Cmd+MediaNextTrack"] {
+            let accelerator: Accelerator = s.parse().unwrap();
+            assert_eq!(accelerator.to_key_equivalent(), None, "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_from_key_equivalent() {
+        for (s, key, mask) in ROUNDTRIP {
+            let expected: Accelerator = s.parse().unwrap();
+            let actual = Accelerator::from_key_equivalent(&key_equivalent(key, *mask));
+            assert_eq!(actual, Some(expected), "{}", s);
+        }
+
+        let cases = [
+            // Uppercase implies Shift.
+            ("N", COMMAND_MASK, Some("Cmd+Shift+N")),
+            ("N", COMMAND_MASK | SHIFT_MASK, Some("Cmd+Shift+N")),
+            ("Ä", OPTION_MASK, Some("Alt+Shift+Ä")),
+            ("ä", OPTION_MASK, Some("Alt+Ä")),
+            // Alternative characters.
+            ("\u{8}", COMMAND_MASK, Some("Cmd+Backspace")),
+            ("\u{3}", 0, Some("Enter")),
+            ("\n", 0, Some("Enter")),
+            // Digits and symbols without the numeric pad flag.
+            ("1", COMMAND_MASK, Some("Cmd+1")),
+            ("+", 0, Some("Plus")),
+            // Not representable.
+            ("", COMMAND_MASK, None),
+            ("ab", COMMAND_MASK, None),
+            ("\u{F71C}", 0, None),
+            ("\u{0}", 0, None),
+        ];
+        for (key, mask, expected) in cases {
+            let expected = expected.map(|s| s.parse::<Accelerator>().unwrap());
+            let actual = Accelerator::from_key_equivalent(&key_equivalent(key, mask));
+            assert_eq!(actual, expected, "{:?}", key);
+        }
+    }
+
+    #[test]
+    fn test_from_modifier_mask() {
+        assert_eq!(Modifiers::from_modifier_mask(0), Modifiers::empty());
+        assert_eq!(
+            Modifiers::from_modifier_mask(COMMAND_MASK | SHIFT_MASK | NUMERIC_PAD_MASK),
+            Modifiers::SUPER | Modifiers::SHIFT
+        );
+        // Caps Lock isn't a modifier.
+        assert_eq!(
+            Modifiers::from_modifier_mask((1 << 16) | OPTION_MASK),
+            Modifiers::ALT
+        );
+    }
+}
@@ -0,0 +1,302 @@
+//! Displaying accelerators to users.
+//!
+//! Each platform has its own conventions for how shortcuts are shown in
+//! menus: macOS uses glyphs like `⇧⌘N`, Windows writes `Ctrl+Shift+N`, and
+//! GTK writes `Shift+Ctrl+N`. The names of keys are localized as well.
+
+use std::borrow::Cow;
+
+use super::{Accelerator, Key, Modifiers};
+
+/// The platform whose conventions to follow.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Platform {
+    MacOs,
+    Windows,
+    /// Linux and other Unix-like desktops, following GTK.
+    Linux,
+}
+
+impl Platform {
+    /// The platform we're compiled for.
+    pub const fn current() -> Self {
+        if cfg!(target_os = "macos") {
+            Self::MacOs
+        } else if cfg!(windows) {
+            Self::Windows
+        } else {
+            Self::Linux
+        }
+    }
+}
+
+/// The language to use for key names.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Locale {
+    #[default]
+    English,
+    German,
+}
+
+impl Locale {
+    /// Find the locale for a language tag like `de-DE` or `de_AT.UTF-8`.
+    ///
+    /// Returns `None` for unsupported languages.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let language = tag.split(['-', '_', '.']).next()?;
+        if language.eq_ignore_ascii_case("en") || language.eq_ignore_ascii_case("c") {
+            Some(Self::English)
+        } else if language.eq_ignore_ascii_case("de") {
+            Some(Self::German)
+        } else {
+            None
+        }
+    }
+}
+
+impl Modifiers {
+    /// Replace [`CMD_OR_CTRL`][Self::CMD_OR_CTRL] with the modifier it means
+    /// on the given platform.
+    pub fn resolve_for(self, platform: Platform) -> Self {
+        if !self.contains(Self::CMD_OR_CTRL) {
+            return self;
+        }
+        let primary = match platform {
+            Platform::MacOs => Self::SUPER,
+            Platform::Windows | Platform::Linux => Self::CONTROL,
+        };
+        self.difference(Self::CMD_OR_CTRL).union(primary)
+    }
+}
+
+impl Accelerator {
+    /// Format the accelerator the way it is shown in menus on the given
+    /// platform.
+    ///
+    /// ```
+    /// use menubar::accelerator::{Accelerator, Locale, Platform};
+    ///
+    /// let accelerator: Accelerator = "CmdOrCtrl+Shift+N".parse().unwrap();
+    /// assert_eq!(accelerator.format(Platform::MacOs, Locale::English), "⇧⌘N");
+    /// assert_eq!(accelerator.format(Platform::Windows, Locale::English), "Ctrl+Shift+N");
+    /// assert_eq!(accelerator.format(Platform::Windows, Locale::German), "Strg+Umschalt+N");
+    /// ```
+    pub fn format(&self, platform: Platform, locale: Locale) -> String {
+        let modifiers = self.modifiers().resolve_for(platform);
+        let key = key_name(self.key(), platform, locale);
+
+        let mut s = String::new();
+        if platform == Platform::MacOs {
+            for (modifier, glyph) in [
+                (Modifiers::CONTROL, "⌃"),
+                (Modifiers::ALT, "⌥"),
+                (Modifiers::SHIFT, "⇧"),
+                (Modifiers::SUPER, "⌘"),
+            ] {
+                if modifiers.contains(modifier) {
+                    s.push_str(glyph);
+                }
+            }
+            s.push_str(&key);
+            return s;
+        }
+
+        let order: [Modifiers; 4] = match platform {
+            Platform::Windows => [
+                Modifiers::SUPER,
+                Modifiers::CONTROL,
+                Modifiers::ALT,
+                Modifiers::SHIFT,
+            ],
+            _ => [
+                Modifiers::SHIFT,
+                Modifiers::CONTROL,
+                Modifiers::ALT,
+                Modifiers::SUPER,
+            ],
+        };
+        for modifier in order {
+            if modifiers.contains(modifier) {
+                s.push_str(modifier_name(modifier, platform, locale));
+                s.push('+');
+            }
+        }
+        s.push_str(&key);
+        s
+    }
+
+    /// Format the accelerator for the current platform.
+    pub fn format_native(&self, locale: Locale) -> String {
+        self.format(Platform::current(), locale)
+    }
+}
+
+fn modifier_name(modifier: Modifiers, platform: Platform, locale: Locale) -> &'static str {
+    match (modifier, locale) {
+        (Modifiers::SHIFT, Locale::English) => "Shift",
+        (Modifiers::SHIFT, Locale::German) => "Umschalt",
+        (Modifiers::CONTROL, Locale::English) => "Ctrl",
+        (Modifiers::CONTROL, Locale::German) => "Strg",
+        (Modifiers::ALT, _) => "Alt",
+        (Modifiers::SUPER, _) if platform == Platform::Windows => "Win",
+        (Modifiers::SUPER, _) => "Super",
+        _ => unreachable!("not a single modifier: {:?}", modifier),
+    }
+}
+
+fn key_name(key: Key, platform: Platform, locale: Locale) -> Cow<'static, str> {
+    if platform == Platform::MacOs {
+        let glyph = match key {
+            Key::Char(c) => return c.to_string().into(),
+            Key::Numpad(n) => return n.to_string().into(),
+            Key::Up => "↑",
+            Key::Down => "↓",
+            Key::Left => "←",
+            Key::Right => "→",
+            Key::Home => "↖",
+            Key::End => "↘",
+            Key::PageUp => "⇞",
+            Key::PageDown => "⇟",
+            Key::Delete => "⌦",
+            Key::Backspace => "⌫",
+            Key::Tab => "⇥",
+            Key::Enter => "↩",
+            Key::Escape => "⎋",
+            Key::NumpadAdd => "+",
+            Key::NumpadSubtract => "-",
+            Key::NumpadMultiply => "*",
+            Key::NumpadDivide => "/",
+            Key::NumpadDecimal => ".",
+            _ => "",
+        };
+        if !glyph.is_empty() {
+            return glyph.into();
+        }
+    }
+
+    let german = locale == Locale::German;
+    let name = match key {
+        Key::Char('+') => "Plus",
+        Key::Char(c) => return c.to_string().into(),
+        Key::F(n) => return format!("F{}", n).into(),
+        Key::Numpad(n) => return format!("Num {}", n).into(),
+        Key::Up if german => "Nach-oben",
+        Key::Up => "Up",
+        Key::Down if german => "Nach-unten",
+        Key::Down => "Down",
+        Key::Left if german => "Nach-links",
+        Key::Left => "Left",
+        Key::Right if german => "Nach-rechts",
+        Key::Right => "Right",
+        Key::Home if german => "Pos1",
+        Key::Home => "Home",
+        Key::End if german => "Ende",
+        Key::End => "End",
+        Key::PageUp if german => "Bild auf",
+        Key::PageUp => "PgUp",
+        Key::PageDown if german => "Bild ab",
+        Key::PageDown => "PgDn",
+        Key::Insert if german => "Einfg",
+        Key::Insert => "Ins",
+        Key::Delete if german => "Entf",
+        Key::Delete => "Del",
+        Key::Backspace if german => "Rücktaste",
+        Key::Backspace => "Backspace",
+        Key::Tab => "Tab",
+        Key::Enter if german => "Eingabe",
+        Key::Enter => "Enter",
+        Key::Escape => "Esc",
+        Key::Space if german => "Leertaste",
+        Key::Space => "Space",
+        Key::PrintScreen if german => "Druck",
+        Key::PrintScreen => "PrtSc",
+        Key::NumpadAdd => "Num +",
+        Key::NumpadSubtract => "Num -",
+        Key::NumpadMultiply => "Num *",
+        Key::NumpadDivide => "Num /",
+        Key::NumpadDecimal if german => "Num ,",
+        Key::NumpadDecimal => "Num .",
+        Key::VolumeUp if german => "Lauter",
+        Key::VolumeUp => "Volume Up",
+        Key::VolumeDown if german => "Leiser",
+        Key::VolumeDown => "Volume Down",
+        Key::VolumeMute if german => "Stumm",
+        Key::VolumeMute => "Mute",
+        Key::MediaPlayPause if german => "Wiedergabe/Pause",
+        Key::MediaPlayPause => "Play/Pause",
+        Key::MediaStop if german => "Stopp",
+        Key::MediaStop => "Stop",
+        Key::MediaNextTrack if german => "Nächster Titel",
+        Key::MediaNextTrack => "Next Track",
+        Key::MediaPreviousTrack if german => "Vorheriger Titel",
+        Key::MediaPreviousTrack => "Previous Track",
+    };
+    name.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        use Locale::*;
+        use Platform::*;
+
+        let cases = [
+            ("CmdOrCtrl+Shift+N", MacOs, English, "⇧⌘N"),
+            ("CmdOrCtrl+Shift+N", Windows, English, "Ctrl+Shift+N"),
+            ("CmdOrCtrl+Shift+N", Linux, English, "Shift+Ctrl+N"),
+            ("CmdOrCtrl+Shift+N", Windows, German, "Strg+Umschalt+N"),
+            ("CmdOrCtrl+Shift+N", Linux, German, "Umschalt+Strg+N"),
+            ("Cmd+Ctrl+Alt+Shift+Q", MacOs, English, "⌃⌥⇧⌘Q"),
+            (
+                "Super+Ctrl+Alt+Shift+Q",
+                Windows,
+                English,
+                "Win+Ctrl+Alt+Shift+Q",
+            ),
+            (
+                "Super+Ctrl+Alt+Shift+Q",
+                Linux,
+                English,
+                "Shift+Ctrl+Alt+Super+Q",
+            ),
+            ("Alt+F4", Windows, English, "Alt+F4"),
+            ("Alt+F4", MacOs, English, "⌥F4"),
+            ("Ctrl+Plus", Windows, English, "Ctrl+Plus"),
+            ("Ctrl+Plus", MacOs, English, "⌃+"),
+            ("Ctrl+Del", Windows, German, "Strg+Entf"),
+            ("Cmd+Backspace", MacOs, German, "⌘⌫"),
+            ("Cmd+Up", MacOs, English, "⌘↑"),
+            ("Ctrl+Space", MacOs, German, "⌃Leertaste"),
+            ("Shift+Num5", Windows, English, "Shift+Num 5"),
+            ("NumDec", Windows, German, "Num ,"),
+            ("MediaPlayPause", Windows, German, "Wiedergabe/Pause"),
+            ("Ctrl+PageDown", Windows, German, "Strg+Bild ab"),
+        ];
+        for (s, platform, locale, expected) in cases {
+            let accelerator: Accelerator = s.parse().unwrap();
+            assert_eq!(
+                accelerator.format(platform, locale),
+                expected,
+                "{} on {:?} in {:?}",
+                s,
+                platform,
+                locale,
+            );
+        }
+    }
+
+    #[test]
+    fn test_locale_from_tag() {
+        assert_eq!(Locale::from_tag("de"), Some(Locale::German));
+        assert_eq!(Locale::from_tag("de-DE"), Some(Locale::German));
+        assert_eq!(Locale::from_tag("de_AT.UTF-8"), Some(Locale::German));
+        assert_eq!(Locale::from_tag("en-US"), Some(Locale::English));
+        assert_eq!(Locale::from_tag("C"), Some(Locale::English));
+        assert_eq!(Locale::from_tag("fr-FR"), None);
+        assert_eq!(Locale::from_tag(""), None);
+    }
+}
@@ -0,0 +1,208 @@
+//! Conversion to GTK key names.
+//!
+//! Linux desktops describe keys by their GDK key names, like `s`, `F1` or
+//! `Page_Up`, which are the names of X11 keysyms. Keys without a name are
+//! described by their Unicode code point, like `U20AC`.
+//!
+//! GTK accelerators use the same names, with the modifiers in angle
+//! brackets, like `<Control>s`.
+
+use super::{Accelerator, Key, Modifiers, Platform};
+
+/// The names of the modifiers used by `com.canonical.dbusmenu`, in the order
+/// they're listed.
+const DBUSMENU_MODIFIERS: [(Modifiers, &str); 4] = [
+    (Modifiers::CONTROL, "Control"),
+    (Modifiers::ALT, "Alt"),
+    (Modifiers::SHIFT, "Shift"),
+    (Modifiers::SUPER, "Super"),
+];
+
+/// The names of the modifiers used by GTK accelerators.
+const GTK_MODIFIERS: [(Modifiers, &str); 4] = [
+    (Modifiers::CONTROL, "<Control>"),
+    (Modifiers::ALT, "<Alt>"),
+    (Modifiers::SHIFT, "<Shift>"),
+    (Modifiers::SUPER, "<Super>"),
+];
+
+/// Characters with a key name that isn't the character itself.
+const SYMBOLS: [(char, &str); 32] = [
+    ('!', "exclam"),
+    ('"', "quotedbl"),
+    ('#', "numbersign"),
+    ('$', "dollar"),
+    ('%', "percent"),
+    ('&', "ampersand"),
+    ('\'', "apostrophe"),
+    ('(', "parenleft"),
+    (')', "parenright"),
+    ('*', "asterisk"),
+    ('+', "plus"),
+    (',', "comma"),
+    ('-', "minus"),
+    ('.', "period"),
+    ('/', "slash"),
+    (':', "colon"),
+    (';', "semicolon"),
+    ('<', "less"),
+    ('=', "equal"),
+    ('>', "greater"),
+    ('?', "question"),
+    ('@', "at"),
+    ('[', "bracketleft"),
+    ('\\', "backslash"),
+    (']', "bracketright"),
+    ('^', "asciicircum"),
+    ('_', "underscore"),
+    ('`', "grave"),
+    ('{', "braceleft"),
+    ('|', "bar"),
+    ('}', "braceright"),
+    ('~', "asciitilde"),
+];
+
+impl Key {
+    /// The GDK name of the key.
+    ///
+    /// Letters use the name of their lowercase form, since that's the key
+    /// that is pressed.
+    pub fn gdk_name(self) -> String {
+        let name = match self {
+            Self::Char(c) if c.is_ascii_alphanumeric() => {
+                return c.to_ascii_lowercase().to_string()
+            }
+            Self::Char(c) => {
+                if let Some((_, name)) = SYMBOLS.iter().find(|(s, _)| *s == c) {
+                    return (*name).to_owned();
+                }
+                let mut lower = c.to_lowercase();
+                let c = match (lower.next(), lower.next()) {
+                    (Some(lower), None) => lower,
+                    _ => c,
+                };
+                return format!("U{:04X}", u32::from(c));
+            }
+            Self::F(n) => return format!("F{}", n),
+            Self::Numpad(n) => return format!("KP_{}", n),
+            Self::Up => "Up",
+            Self::Down => "Down",
+            Self::Left => "Left",
+            Self::Right => "Right",
+            Self::Home => "Home",
+            Self::End => "End",
+            Self::PageUp => "Page_Up",
+            Self::PageDown => "Page_Down",
+            Self::Insert => "Insert",
+            Self::Delete => "Delete",
+            Self::Backspace => "BackSpace",
+            Self::Tab => "Tab",
+            Self::Enter => "Return",
+            Self::Escape => "Escape",
+            Self::Space => "space",
+            Self::PrintScreen => "Print",
+            Self::NumpadAdd => "KP_Add",
+            Self::NumpadSubtract => "KP_Subtract",
+            Self::NumpadMultiply => "KP_Multiply",
+            Self::NumpadDivide => "KP_Divide",
+            Self::NumpadDecimal => "KP_Decimal",
+            Self::VolumeUp => "XF86AudioRaiseVolume",
+            Self::VolumeDown => "XF86AudioLowerVolume",
+            Self::VolumeMute => "XF86AudioMute",
+            Self::MediaPlayPause => "XF86AudioPlay",
+            Self::MediaStop => "XF86AudioStop",
+            Self::MediaNextTrack => "XF86AudioNext",
+            Self::MediaPreviousTrack => "XF86AudioPrev",
+        };
+        name.to_owned()
+    }
+}
+
+impl Accelerator {
+    /// The accelerator as a `com.canonical.dbusmenu` shortcut.
+    ///
+    /// This is the list of modifier names, followed by the name of the key,
+    /// like `["Control", "Shift", "s"]`.
+    pub fn to_dbusmenu_shortcut(&self) -> Vec<String> {
+        let modifiers = self.modifiers().resolve_for(Platform::Linux);
+        DBUSMENU_MODIFIERS
+            .iter()
+            .filter(|(modifier, _)| modifiers.contains(*modifier))
+            .map(|(_, name)| (*name).to_owned())
+            .chain(Some(self.key().gdk_name()))
+            .collect()
+    }
+
+    /// The accelerator in the format of `gtk_accelerator_parse`, like
+    /// `<Control><Shift>s`.
+    pub fn to_gtk_accel(&self) -> String {
+        let modifiers = self.modifiers().resolve_for(Platform::Linux);
+        let mut accel: String = GTK_MODIFIERS
+            .iter()
+            .filter(|(modifier, _)| modifiers.contains(*modifier))
+            .map(|(_, name)| *name)
+            .collect();
+        accel.push_str(&self.key().gdk_name());
+        accel
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gdk_name() {
+        let cases = [
+            (Key::Char('S'), "s"),
+            (Key::Char('1'), "1"),
+            (Key::Char('+'), "plus"),
+            (Key::Char(','), "comma"),
+            (Key::Char('Ä'), "U00E4"),
+            (Key::Char('€'), "U20AC"),
+            (Key::F(12), "F12"),
+            (Key::PageDown, "Page_Down"),
+            (Key::Backspace, "BackSpace"),
+            (Key::Enter, "Return"),
+            (Key::Space, "space"),
+            (Key::Numpad(7), "KP_7"),
+            (Key::NumpadDecimal, "KP_Decimal"),
+            (Key::MediaPlayPause, "XF86AudioPlay"),
+        ];
+        for (key, name) in cases {
+            assert_eq!(key.gdk_name(), name, "{:?}", key);
+        }
+    }
+
+    #[test]
+    fn test_to_dbusmenu_shortcut() {
+        let cases: [(&str, &[&str]); 5] = [
+            ("Ctrl+S", &["Control", "s"]),
+            ("CmdOrCtrl+Shift+N", &["Control", "Shift", "n"]),
+            (
+                "Shift+Alt+Ctrl+Super+Q",
+                &["Control", "Alt", "Shift", "Super", "q"],
+            ),
+            ("Alt+F4", &["Alt", "F4"]),
+            ("Delete", &["Delete"]),
+        ];
+        for (s, expected) in cases {
+            let accelerator: Accelerator = s.parse().unwrap();
+            assert_eq!(accelerator.to_dbusmenu_shortcut(), expected, "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_to_gtk_accel() {
+        let cases = [
+            ("Ctrl+S", "<Control>s"),
+            ("CmdOrCtrl+Shift+N", "<Control><Shift>n"),
+            ("Super+Alt+Left", "<Alt><Super>Left"),
+            ("F5", "F5"),
+        ];
+        for (s, expected) in cases {
+            let accelerator: Accelerator = s.parse().unwrap();
+            assert_eq!(accelerator.to_gtk_accel(), expected, "{}", s);
+        }
+    }
+}
@@ -0,0 +1,605 @@
+//! Keyboard shortcuts for menu items.
+//!
+//! An [`Accelerator`] is a set of [`Modifiers`] and a [`Key`], and can be
+//! parsed from strings like `"CmdOrCtrl+Shift+N"`, `"Alt+F4"` or
+//! `"Ctrl+Plus"`. Formatting an accelerator with [`Display`][fmt::Display]
+//! gives back a string in the same format. Use [`Accelerator::format`] to
+//! show it to users.
+//!
+//! The [`win32`], [`appkit`] and [`gtk`] modules convert between accelerators
+//! and what each platform uses to describe them. They're plain data
+//! conversion, so they're available on all platforms.
+//!
+//! `menubar-macros` has a copy of the parser, to check accelerators at
+//! compile time. Its tests check that the two accept the same strings.
+
+use core::fmt;
+use core::ops::{BitOr, BitOrAssign, Range};
+use core::str::FromStr;
+
+pub mod appkit;
+mod format;
+pub mod gtk;
+pub mod win32;
+
+pub use self::format::{Locale, Platform};
+
+/// A set of modifier keys.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers(u8);
+
+impl Modifiers {
+    pub const SHIFT: Self = Self(1 << 0);
+    pub const CONTROL: Self = Self(1 << 1);
+    /// Alt on Windows and Linux, Option on macOS.
+    pub const ALT: Self = Self(1 << 2);
+    /// Command on macOS, the Windows key on Windows.
+    pub const SUPER: Self = Self(1 << 3);
+    /// Command on macOS, and Control everywhere else.
+    pub const CMD_OR_CTRL: Self = Self(1 << 4);
+
+    /// The modifiers, along with their canonical names, in display order.
+    const NAMES: [(Self, &'static str); 5] = [
+        (Self::CMD_OR_CTRL, "CmdOrCtrl"),
+        (Self::SUPER, "Super"),
+        (Self::CONTROL, "Ctrl"),
+        (Self::ALT, "Alt"),
+        (Self::SHIFT, "Shift"),
+    ];
+
+    /// Accepted alternative names, compared case-insensitively.
+    const ALIASES: [(Self, &'static str); 13] = [
+        (Self::CMD_OR_CTRL, "CmdOrCtrl"),
+        (Self::CMD_OR_CTRL, "CommandOrControl"),
+        (Self::SUPER, "Super"),
+        (Self::SUPER, "Cmd"),
+        (Self::SUPER, "Command"),
+        (Self::SUPER, "Meta"),
+        (Self::SUPER, "Win"),
+        (Self::CONTROL, "Ctrl"),
+        (Self::CONTROL, "Control"),
+        (Self::ALT, "Alt"),
+        (Self::ALT, "Option"),
+        (Self::ALT, "Opt"),
+        (Self::SHIFT, "Shift"),
+    ];
+
+    /// No modifiers.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether all modifiers in `other` are set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub const fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    /// Replace [`CMD_OR_CTRL`][Self::CMD_OR_CTRL] with the modifier it means
+    /// on the current platform.
+    pub fn resolve(self) -> Self {
+        self.resolve_for(Platform::current())
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALIASES
+            .iter()
+            .find(|(_, alias)| alias.eq_ignore_ascii_case(name))
+            .map(|(modifier, _)| *modifier)
+    }
+}
+
+impl BitOr for Modifiers {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        self.union(rhs)
+    }
+}
+
+impl BitOrAssign for Modifiers {
+    fn bitor_assign(&mut self, rhs: Self) {
+        *self = self.union(rhs);
+    }
+}
+
+impl fmt::Debug for Modifiers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("(empty)");
+        }
+        let mut first = true;
+        for (modifier, name) in Self::NAMES {
+            if self.contains(modifier) {
+                if !first {
+                    f.write_str(" | ")?;
+                }
+                f.write_str(name)?;
+                first = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A key on the keyboard, that is not a modifier.
+///
+/// Letters are represented by their uppercase form.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Key {
+    /// A key that produces a character, like `N`, `1` or `/`.
+    ///
+    /// Whitespace and control characters are represented by other variants.
+    Char(char),
+    /// A function key, from `F1` to `F24`.
+    F(u8),
+
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+
+    Insert,
+    Delete,
+    Backspace,
+    Tab,
+    Enter,
+    Escape,
+    Space,
+    PrintScreen,
+
+    /// A digit on the numeric keypad, from `0` to `9`.
+    Numpad(u8),
+    NumpadAdd,
+    NumpadSubtract,
+    NumpadMultiply,
+    NumpadDivide,
+    NumpadDecimal,
+
+    VolumeUp,
+    VolumeDown,
+    VolumeMute,
+    MediaPlayPause,
+    MediaStop,
+    MediaNextTrack,
+    MediaPreviousTrack,
+}
+
+impl Key {
+    /// Named keys, along with their canonical names.
+    const NAMES: [(Self, &'static str); 29] = [
+        (Self::Char('+'), "Plus"),
+        (Self::Up, "Up"),
+        (Self::Down, "Down"),
+        (Self::Left, "Left"),
+        (Self::Right, "Right"),
+        (Self::Home, "Home"),
+        (Self::End, "End"),
+        (Self::PageUp, "PageUp"),
+        (Self::PageDown, "PageDown"),
+        (Self::Insert, "Insert"),
+        (Self::Delete, "Delete"),
+        (Self::Backspace, "Backspace"),
+        (Self::Tab, "Tab"),
+        (Self::Enter, "Enter"),
+        (Self::Escape, "Escape"),
+        (Self::Space, "Space"),
+        (Self::PrintScreen, "PrintScreen"),
+        (Self::NumpadAdd, "NumAdd"),
+        (Self::NumpadSubtract, "NumSub"),
+        (Self::NumpadMultiply, "NumMult"),
+        (Self::NumpadDivide, "NumDiv"),
+        (Self::NumpadDecimal, "NumDec"),
+        (Self::VolumeUp, "VolumeUp"),
+        (Self::VolumeDown, "VolumeDown"),
+        (Self::VolumeMute, "VolumeMute"),
+        (Self::MediaPlayPause, "MediaPlayPause"),
+        (Self::MediaStop, "MediaStop"),
+        (Self::MediaNextTrack, "MediaNextTrack"),
+        (Self::MediaPreviousTrack, "MediaPreviousTrack"),
+    ];
+
+    /// Accepted alternative names, in addition to the canonical ones.
+    const ALIASES: [(Self, &'static str); 13] = [
+        (Self::Up, "ArrowUp"),
+        (Self::Down, "ArrowDown"),
+        (Self::Left, "ArrowLeft"),
+        (Self::Right, "ArrowRight"),
+        (Self::PageUp, "PgUp"),
+        (Self::PageDown, "PgDn"),
+        (Self::Insert, "Ins"),
+        (Self::Delete, "Del"),
+        (Self::Enter, "Return"),
+        (Self::Escape, "Esc"),
+        (Self::PrintScreen, "PrtSc"),
+        (Self::VolumeMute, "Mute"),
+        (Self::MediaPlayPause, "PlayPause"),
+    ];
+
+    /// Whether the key is within the documented ranges.
+    pub(crate) fn is_valid(self) -> bool {
+        match self {
+            Self::Char(c) => !c.is_whitespace() && !c.is_control(),
+            Self::F(n) => (1..=24).contains(&n),
+            Self::Numpad(n) => n <= 9,
+            _ => true,
+        }
+    }
+
+    /// Use the uppercase form of letters.
+    fn normalize(self) -> Self {
+        match self {
+            Self::Char(c) => {
+                let mut upper = c.to_uppercase();
+                match (upper.next(), upper.next()) {
+                    (Some(upper), None) => Self::Char(upper),
+                    _ => self,
+                }
+            }
+            _ => self,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        let mut chars = name.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            let key = Self::Char(c).normalize();
+            return Some(key).filter(|key| c != '+' && key.is_valid());
+        }
+
+        let named = Self::NAMES.iter().chain(Self::ALIASES.iter());
+        if let Some((key, _)) = named.clone().find(|(_, n)| n.eq_ignore_ascii_case(name)) {
+            return Some(*key);
+        }
+
+        let number = |prefix: &str| {
+            let head = name.get(..prefix.len())?;
+            let digits = &name[prefix.len()..];
+            if !head.eq_ignore_ascii_case(prefix) || digits.starts_with('0') && digits != "0" {
+                return None;
+            }
+            digits.parse::<u8>().ok()
+        };
+        number("Num")
+            .map(Self::Numpad)
+            .or_else(|| number("F").map(Self::F))
+            .filter(|key| key.is_valid())
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((_, name)) = Self::NAMES.iter().find(|(key, _)| key == self) {
+            return f.write_str(name);
+        }
+        match self {
+            Self::Char(c) => write!(f, "{}", c),
+            Self::F(n) => write!(f, "F{}", n),
+            Self::Numpad(n) => write!(f, "Num{}", n),
+            _ => unreachable!("named key without a name"),
+        }
+    }
+}
+
+impl FromStr for Key {
+    type Err = ParseAcceleratorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let span = 0..s.len();
+        Self::from_name(s.trim()).ok_or_else(|| ParseAcceleratorError::unknown_key(s, span))
+    }
+}
+
+/// A keyboard shortcut.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Accelerator {
+    modifiers: Modifiers,
+    key: Key,
+}
+
+impl Accelerator {
+    /// Create an accelerator from modifiers and a key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is outside of its documented range, like `F(0)`, or
+    /// a whitespace character.
+    pub fn new(modifiers: Modifiers, key: Key) -> Self {
+        assert!(key.is_valid(), "invalid key {:?}", key);
+        Self {
+            modifiers,
+            key: key.normalize(),
+        }
+    }
+
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    pub fn key(&self) -> Key {
+        self.key
+    }
+
+    /// Whether a key press triggers this accelerator on the current platform.
+    ///
+    /// [`Modifiers::CMD_OR_CTRL`] is resolved on both sides.
+    pub fn matches(&self, pressed: &Accelerator) -> bool {
+        self.key == pressed.key && self.modifiers.resolve() == pressed.modifiers.resolve()
+    }
+}
+
+impl From<Key> for Accelerator {
+    fn from(key: Key) -> Self {
+        Self::new(Modifiers::empty(), key)
+    }
+}
+
+impl fmt::Display for Accelerator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in Modifiers::NAMES {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{}+", name)?;
+            }
+        }
+        fmt::Display::fmt(&self.key, f)
+    }
+}
+
+impl FromStr for Accelerator {
+    type Err = ParseAcceleratorError;
+
+    /// Parse an accelerator like `"CmdOrCtrl+Shift+N"`.
+    ///
+    /// Names are case-insensitive, and whitespace around each part is
+    /// ignored. The key comes last; `+` itself is written as `Plus`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err(ParseAcceleratorError::new(ErrorKind::Empty, 0..s.len()));
+        }
+
+        // Split into parts, keeping track of where each part is.
+        let mut parts = Vec::new();
+        let mut start = 0;
+        for part in s.split('+') {
+            let trimmed_start = start + (part.len() - part.trim_start().len());
+            let name = part.trim();
+            parts.push((name, trimmed_start..trimmed_start + name.len()));
+            start += part.len() + 1;
+        }
+
+        let (key, modifiers) = parts.split_last().unwrap();
+        let mut result = Modifiers::empty();
+        for (name, span) in modifiers {
+            if name.is_empty() {
+                return Err(ParseAcceleratorError::new(
+                    ErrorKind::EmptyPart,
+                    span.clone(),
+                ));
+            }
+            let modifier = match Modifiers::from_name(name) {
+                Some(modifier) => modifier,
+                None if Key::from_name(name).is_some() => {
+                    let kind = ErrorKind::KeyNotLast(name.to_string());
+                    return Err(ParseAcceleratorError::new(kind, span.clone()));
+                }
+                None => {
+                    let kind = ErrorKind::UnknownModifier(name.to_string());
+                    return Err(ParseAcceleratorError::new(kind, span.clone()));
+                }
+            };
+            if result.contains(modifier) {
+                let kind = ErrorKind::DuplicateModifier(name.to_string());
+                return Err(ParseAcceleratorError::new(kind, span.clone()));
+            }
+            result |= modifier;
+        }
+
+        let (name, span) = key;
+        if name.is_empty() {
+            return Err(ParseAcceleratorError::new(
+                ErrorKind::EmptyPart,
+                span.clone(),
+            ));
+        }
+        if Modifiers::from_name(name).is_some() {
+            let kind = ErrorKind::MissingKey(name.to_string());
+            return Err(ParseAcceleratorError::new(kind, span.clone()));
+        }
+        match Key::from_name(name) {
+            Some(key) => Ok(Self::new(result, key)),
+            None => Err(ParseAcceleratorError::unknown_key(name, span.clone())),
+        }
+    }
+}
+
+/// Error returned when parsing an [`Accelerator`] or a [`Key`] fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseAcceleratorError {
+    kind: ErrorKind,
+    span: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ErrorKind {
+    Empty,
+    EmptyPart,
+    UnknownModifier(String),
+    UnknownKey(String),
+    DuplicateModifier(String),
+    KeyNotLast(String),
+    MissingKey(String),
+}
+
+impl ParseAcceleratorError {
+    fn new(kind: ErrorKind, span: Range<usize>) -> Self {
+        Self { kind, span }
+    }
+
+    fn unknown_key(name: &str, span: Range<usize>) -> Self {
+        Self::new(ErrorKind::UnknownKey(name.to_string()), span)
+    }
+
+    /// The byte range in the input that the error refers to.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+}
+
+impl fmt::Display for ParseAcceleratorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ErrorKind::Empty => f.write_str("empty accelerator")?,
+            ErrorKind::EmptyPart => f.write_str("expected a modifier or key")?,
+            ErrorKind::UnknownModifier(name) => write!(f, "unknown modifier `{}`", name)?,
+            ErrorKind::UnknownKey(name) => write!(f, "unknown key `{}`", name)?,
+            ErrorKind::DuplicateModifier(name) => write!(f, "duplicate modifier `{}`", name)?,
+            ErrorKind::KeyNotLast(name) => write!(f, "key `{}` must come last", name)?,
+            ErrorKind::MissingKey(name) => write!(f, "missing key after `{}`", name)?,
+        }
+        write!(f, " at position {}", self.span.start)
+    }
+}
+
+impl std::error::Error for ParseAcceleratorError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Accelerator {
+        s.parse().unwrap_or_else(|err| panic!("{:?}: {}", s, err))
+    }
+
+    fn error(s: &str) -> String {
+        s.parse::<Accelerator>().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_parse() {
+        let cases = [
+            (
+                "CmdOrCtrl+Shift+N",
+                Modifiers::CMD_OR_CTRL | Modifiers::SHIFT,
+                Key::Char('N'),
+            ),
+            ("Alt+F4", Modifiers::ALT, Key::F(4)),
+            ("Ctrl+Plus", Modifiers::CONTROL, Key::Char('+')),
+            (
+                "ctrl + shift + n",
+                Modifiers::CONTROL | Modifiers::SHIFT,
+                Key::Char('N'),
+            ),
+            (
+                "Cmd+Option+Esc",
+                Modifiers::SUPER | Modifiers::ALT,
+                Key::Escape,
+            ),
+            ("F24", Modifiers::empty(), Key::F(24)),
+            ("Shift+Num0", Modifiers::SHIFT, Key::Numpad(0)),
+            ("NumAdd", Modifiers::empty(), Key::NumpadAdd),
+            ("Super+ArrowUp", Modifiers::SUPER, Key::Up),
+            ("MediaPlayPause", Modifiers::empty(), Key::MediaPlayPause),
+            ("Ctrl+/", Modifiers::CONTROL, Key::Char('/')),
+            ("Ctrl+ß", Modifiers::CONTROL, Key::Char('ß')),
+            ("Alt+é", Modifiers::ALT, Key::Char('É')),
+            ("f", Modifiers::empty(), Key::Char('F')),
+        ];
+        for (s, modifiers, key) in cases {
+            assert_eq!(parse(s), Accelerator::new(modifiers, key), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let cases = [
+            ("", "empty accelerator at position 0"),
+            ("  ", "empty accelerator at position 0"),
+            ("Ctrl++", "expected a modifier or key at position 5"),
+            ("Ctrl+ +N", "expected a modifier or key at position 6"),
+            ("+N", "expected a modifier or key at position 0"),
+            ("Hyper+N", "unknown modifier `Hyper` at position 0"),
+            ("Ctrl+Foo", "unknown key `Foo` at position 5"),
+            ("Ctrl+F0", "unknown key `F0` at position 5"),
+            ("Ctrl+F25", "unknown key `F25` at position 5"),
+            ("Ctrl+F01", "unknown key `F01` at position 5"),
+            ("Num10", "unknown key `Num10` at position 0"),
+            (
+                "Ctrl+Control+N",
+                "duplicate modifier `Control` at position 5",
+            ),
+            ("N+Ctrl", "key `N` must come last at position 0"),
+            ("Ctrl+ Shift", "missing key after `Shift` at position 6"),
+        ];
+        for (s, expected) in cases {
+            assert_eq!(error(s), expected, "{:?}", s);
+        }
+
+        let err = "Ctrl+Foo".parse::<Accelerator>().unwrap_err();
+        assert_eq!(err.span(), 5..8);
+    }
+
+    #[test]
+    fn test_display_roundtrip() {
+        let cases = [
+            ("CmdOrCtrl+Shift+N", "CmdOrCtrl+Shift+N"),
+            ("shift+cmdorctrl+n", "CmdOrCtrl+Shift+N"),
+            ("Alt+F4", "Alt+F4"),
+            ("Ctrl+Plus", "Ctrl+Plus"),
+            ("Shift+Alt+Ctrl+Cmd+Del", "Super+Ctrl+Alt+Shift+Delete"),
+            ("Num7", "Num7"),
+            ("Ctrl+-", "Ctrl+-"),
+            ("Esc", "Escape"),
+        ];
+        for (s, expected) in cases {
+            let accelerator = parse(s);
+            assert_eq!(accelerator.to_string(), expected);
+            assert_eq!(parse(&accelerator.to_string()), accelerator);
+        }
+
+        for (key, name) in Key::NAMES.iter().chain(Key::ALIASES.iter()) {
+            assert_eq!(name.parse::<Key>().unwrap(), *key, "{:?}", name);
+            let accelerator = Accelerator::from(*key);
+            assert_eq!(parse(&accelerator.to_string()), accelerator);
+        }
+        for n in 1..=24 {
+            let accelerator = Accelerator::new(Modifiers::SHIFT, Key::F(n));
+            assert_eq!(parse(&accelerator.to_string()), accelerator);
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let accelerator = parse("CmdOrCtrl+S");
+        let primary = if cfg!(target_os = "macos") {
+            "Cmd+S"
+        } else {
+            "Ctrl+S"
+        };
+        assert!(accelerator.matches(&parse(primary)));
+        assert!(accelerator.matches(&accelerator));
+        assert!(!accelerator.matches(&parse("Alt+S")));
+        assert!(!accelerator.matches(&parse("CmdOrCtrl+Shift+S")));
+    }
+
+    #[test]
+    #[should_panic = "invalid key"]
+    fn test_invalid_key() {
+        Accelerator::new(Modifiers::empty(), Key::F(0));
+    }
+}
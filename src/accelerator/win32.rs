@@ -0,0 +1,198 @@
+//! Conversion to Win32 accelerator tables.
+//!
+//! Win32 handles keyboard shortcuts separately from menus: an `ACCEL` table
+//! maps virtual-key codes and modifier flags to command IDs, and
+//! `TranslateAccelerator` turns matching key presses into `WM_COMMAND`.
+
+use super::{Accelerator, Key, Modifiers, Platform};
+
+/// The key is a virtual-key code, rather than a character code.
+pub const FVIRTKEY: u8 = 0x01;
+pub const FSHIFT: u8 = 0x04;
+pub const FCONTROL: u8 = 0x08;
+pub const FALT: u8 = 0x10;
+
+/// An entry in an accelerator table, with the same layout as `ACCEL`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct Accel {
+    /// A combination of `FVIRTKEY`, `FSHIFT`, `FCONTROL` and `FALT`.
+    pub flags: u8,
+    pub key: u16,
+    pub cmd: u16,
+}
+
+/// Virtual-key codes of characters that aren't letters or digits.
+///
+/// These are the keys of the US layout.
+const OEM_KEYS: [(char, u16); 12] = [
+    ('+', 0xBB),  // VK_OEM_PLUS
+    ('=', 0xBB),  // VK_OEM_PLUS
+    (',', 0xBC),  // VK_OEM_COMMA
+    ('-', 0xBD),  // VK_OEM_MINUS
+    ('.', 0xBE),  // VK_OEM_PERIOD
+    (';', 0xBA),  // VK_OEM_1
+    ('/', 0xBF),  // VK_OEM_2
+    ('`', 0xC0),  // VK_OEM_3
+    ('[', 0xDB),  // VK_OEM_4
+    ('\\', 0xDC), // VK_OEM_5
+    (']', 0xDD),  // VK_OEM_6
+    ('\'', 0xDE), // VK_OEM_7
+];
+
+impl Key {
+    /// The Win32 virtual-key code of the key.
+    ///
+    /// Returns `None` for characters without a layout-independent key.
+    pub fn virtual_key(self) -> Option<u16> {
+        let vk = match self {
+            Self::Char(c @ 'A'..='Z') | Self::Char(c @ '0'..='9') => c as u16,
+            Self::Char(c) => return OEM_KEYS.iter().find(|(o, _)| *o == c).map(|(_, vk)| *vk),
+            // VK_F1 to VK_F24.
+            Self::F(n) => 0x70 + u16::from(n) - 1,
+            Self::Up => 0x26,
+            Self::Down => 0x28,
+            Self::Left => 0x25,
+            Self::Right => 0x27,
+            Self::Home => 0x24,
+            Self::End => 0x23,
+            Self::PageUp => 0x21,   // VK_PRIOR
+            Self::PageDown => 0x22, // VK_NEXT
+            Self::Insert => 0x2D,
+            Self::Delete => 0x2E,
+            Self::Backspace => 0x08, // VK_BACK
+            Self::Tab => 0x09,
+            Self::Enter => 0x0D,  // VK_RETURN
+            Self::Escape => 0x1B, // VK_ESCAPE
+            Self::Space => 0x20,
+            Self::PrintScreen => 0x2C, // VK_SNAPSHOT
+            // VK_NUMPAD0 to VK_NUMPAD9.
+            Self::Numpad(n) => 0x60 + u16::from(n),
+            Self::NumpadMultiply => 0x6A,
+            Self::NumpadAdd => 0x6B,
+            Self::NumpadSubtract => 0x6D,
+            Self::NumpadDecimal => 0x6E,
+            Self::NumpadDivide => 0x6F,
+            Self::VolumeMute => 0xAD,
+            Self::VolumeDown => 0xAE,
+            Self::VolumeUp => 0xAF,
+            Self::MediaNextTrack => 0xB0,
+            Self::MediaPreviousTrack => 0xB1,
+            Self::MediaStop => 0xB2,
+            Self::MediaPlayPause => 0xB3,
+        };
+        Some(vk)
+    }
+}
+
+impl Accelerator {
+    /// The accelerator table entry for this accelerator, with the given
+    /// command ID.
+    ///
+    /// [`CMD_OR_CTRL`][Modifiers::CMD_OR_CTRL] becomes Control. Returns
+    /// `None` if the key has no virtual-key code, or if the Windows key is
+    /// used, since accelerator tables don't support it.
+    pub fn to_accel(&self, cmd: u16) -> Option<Accel> {
+        let modifiers = self.modifiers().resolve_for(Platform::Windows);
+        if modifiers.contains(Modifiers::SUPER) {
+            return None;
+        }
+
+        let mut flags = FVIRTKEY;
+        for (modifier, flag) in [
+            (Modifiers::SHIFT, FSHIFT),
+            (Modifiers::CONTROL, FCONTROL),
+            (Modifiers::ALT, FALT),
+        ] {
+            if modifiers.contains(modifier) {
+                flags |= flag;
+            }
+        }
+
+        let key = self.key().virtual_key()?;
+        Some(Accel { flags, key, cmd })
+    }
+}
+
+/// Build an accelerator table from command IDs and their accelerators.
+///
+/// Accelerators that can't be represented are skipped. If several commands
+/// use the same key combination, the first one wins.
+pub fn accel_table(commands: impl IntoIterator<Item = (u16, Accelerator)>) -> Vec<Accel> {
+    let mut table: Vec<Accel> = Vec::new();
+    for (cmd, accelerator) in commands {
+        if let Some(accel) = accelerator.to_accel(cmd) {
+            let taken = table
+                .iter()
+                .any(|other| other.flags == accel.flags && other.key == accel.key);
+            if !taken {
+                table.push(accel);
+            }
+        }
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_accel() {
+        let cases = [
+            ("Ctrl+N", FVIRTKEY | FCONTROL, 0x4E),
+            ("CmdOrCtrl+Shift+N", FVIRTKEY | FCONTROL | FSHIFT, 0x4E),
+            ("Alt+F4", FVIRTKEY | FALT, 0x73),
+            ("F24", FVIRTKEY, 0x87),
+            (
+                "Ctrl+Alt+Shift+1",
+                FVIRTKEY | FCONTROL | FALT | FSHIFT,
+                0x31,
+            ),
+            ("Ctrl+Plus", FVIRTKEY | FCONTROL, 0xBB),
+            ("Ctrl+-", FVIRTKEY | FCONTROL, 0xBD),
+            ("Ctrl+/", FVIRTKEY | FCONTROL, 0xBF),
+            ("Ctrl+PageUp", FVIRTKEY | FCONTROL, 0x21),
+            ("Delete", FVIRTKEY, 0x2E),
+            ("Shift+Backspace", FVIRTKEY | FSHIFT, 0x08),
+            ("Ctrl+Enter", FVIRTKEY | FCONTROL, 0x0D),
+            ("Escape", FVIRTKEY, 0x1B),
+            ("Ctrl+Space", FVIRTKEY | FCONTROL, 0x20),
+            ("Alt+Up", FVIRTKEY | FALT, 0x26),
+            ("Num0", FVIRTKEY, 0x60),
+            ("Ctrl+Num9", FVIRTKEY | FCONTROL, 0x69),
+            ("NumAdd", FVIRTKEY, 0x6B),
+            ("NumDiv", FVIRTKEY, 0x6F),
+            ("MediaPlayPause", FVIRTKEY, 0xB3),
+            ("VolumeMute", FVIRTKEY, 0xAD),
+        ];
+        for (s, flags, key) in cases {
+            let accelerator: Accelerator = s.parse().unwrap();
+            let expected = Accel { flags, key, cmd: 7 };
+            assert_eq!(accelerator.to_accel(7), Some(expected), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_to_accel_unsupported() {
+        for s in ["Super+N", "Cmd+Q", "Ctrl+É", "Ctrl+§"] {
+            let accelerator: Accelerator = s.parse().unwrap();
+            assert_eq!(accelerator.to_accel(0), None, "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_accel_table() {
+        let parse = |s: &str| s.parse::<Accelerator>().unwrap();
+        let table = accel_table([
+            (1, parse("Ctrl+N")),
+            (2, parse("Super+N")),
+            (3, parse("Ctrl+Shift+N")),
+            // Same as the first.
+            (4, parse("CmdOrCtrl+N")),
+            (5, parse("F5")),
+        ]);
+        let cmds: Vec<_> = table.iter().map(|accel| accel.cmd).collect();
+        assert_eq!(cmds, [1, 3, 5]);
+    }
+}
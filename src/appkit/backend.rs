@@ -0,0 +1,336 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ptr::NonNull;
+
+use icrate::AppKit::{NSApplication, NSEventTypeKeyDown, NSMenu, NSMenuItem};
+use icrate::Foundation::{MainThreadMarker, NSObject, NSString};
+use objc2::declare::{Ivar, IvarDrop};
+use objc2::rc::Id;
+use objc2::runtime::AnyObject;
+use objc2::{declare_class, msg_send, msg_send_id, mutability, sel, ClassType};
+use raw_window_handle::{AppKitWindowHandle, RawWindowHandle};
+
+use super::{MenuItemState, MenuItemWrapper, MenuWrapper};
+use crate::backend::{
+    Activation, ActivationSource, Activator, Backend, ItemKind, ItemProperty, ItemProps, ItemToken,
+    MenuKind, MenuToken,
+};
+use crate::model::Handler;
+use crate::{ActivationContext, Error, HandlerContext, ItemId, Modifiers};
+
+declare_class!(
+    /// The target of all items created by an [`AppKitBackend`], or from the
+    /// same model.
+    ///
+    /// The item token is stored in the tag of each item.
+    pub(super) struct ActionTarget {
+        activator: IvarDrop<Box<RefCell<Option<Activator>>>, "_activator">,
+    }
+
+    mod ivars;
+
+    unsafe impl ClassType for ActionTarget {
+        type Super = NSObject;
+        type Mutability = mutability::InteriorMutable;
+        const NAME: &'static str = "MenubarActionTarget";
+    }
+
+    unsafe impl ActionTarget {
+        #[method(init)]
+        unsafe fn init(this: *mut Self) -> Option<NonNull<Self>> {
+            let this: Option<&mut Self> = unsafe { msg_send![super(this), init] };
+            this.map(|this| {
+                Ivar::write(&mut this.activator, Box::new(RefCell::new(None)));
+                NonNull::from(this)
+            })
+        }
+
+        #[method(menubarItemActivated:)]
+        fn item_activated(&self, sender: &NSMenuItem) {
+            let item = ItemToken(unsafe { sender.tag() } as u32);
+
+            // Don't hold the borrow while calling the handler.
+            let activator = self.activator.borrow().clone();
+            if let Some(activator) = activator {
+                activator.activate(item, current_activation());
+            }
+        }
+    }
+);
+
+/// Describe the activation from the event that AppKit is handling.
+fn current_activation() -> Activation {
+    let app = unsafe { NSApplication::sharedApplication() };
+    let mut activation = Activation::new(ActivationSource::Click);
+    if let Some(event) = unsafe { app.currentEvent() } {
+        // Key equivalents are performed while handling the key down event.
+        if unsafe { event.r#type() } == NSEventTypeKeyDown {
+            activation.source = ActivationSource::Accelerator;
+        }
+        activation.modifiers = Modifiers::from_modifier_mask(unsafe { event.modifierFlags() });
+    }
+    if let Some(window) = unsafe { app.keyWindow() } {
+        let mut handle = AppKitWindowHandle::empty();
+        handle.ns_window = Id::as_ptr(&window) as *mut _;
+        if let Some(view) = unsafe { window.contentView() } {
+            handle.ns_view = Id::as_ptr(&view) as *mut _;
+        }
+        activation.window = Some(RawWindowHandle::AppKit(handle));
+    }
+    activation
+}
+
+impl ActionTarget {
+    fn new() -> Id<Self> {
+        unsafe { msg_send_id![Self::alloc(), init] }
+    }
+
+    fn set_activator(&self, activator: Option<Activator>) {
+        *self.activator.borrow_mut() = activator;
+    }
+}
+
+/// The handlers of items created from a model.
+///
+/// There's no attached menu to call them, so they're called through a
+/// target of their own, with a detached [`HandlerContext`].
+pub(super) struct ModelHandlers {
+    target: Id<ActionTarget>,
+    handlers: Vec<(Option<ItemId>, Option<Handler<()>>)>,
+}
+
+impl ModelHandlers {
+    pub(super) fn new() -> Self {
+        Self {
+            target: ActionTarget::new(),
+            handlers: Vec::new(),
+        }
+    }
+
+    /// Call the handler when the item is activated.
+    pub(super) fn bind(
+        &mut self,
+        native: &MenuItemWrapper,
+        id: Option<ItemId>,
+        handler: Handler<()>,
+    ) {
+        let target: &AnyObject = &self.target;
+        native.set_tag(self.handlers.len() as isize);
+        unsafe { native.0.setAction(Some(sel!(menubarItemActivated:))) };
+        unsafe { native.0.setTarget(Some(target)) };
+        // Items only hold a weak reference to their target, but keep their
+        // represented object alive.
+        unsafe { native.0.setRepresentedObject(Some(target)) };
+        self.handlers.push((id, Some(handler)));
+    }
+
+    /// Start calling the handlers of the bound items.
+    pub(super) fn finish(self) {
+        let handlers = RefCell::new(self.handlers);
+        let activator = Activator::new(move |item, activation| {
+            // Don't hold the borrow while calling the handler, which may
+            // activate another item.
+            let (id, handler) = match handlers.borrow_mut().get_mut(item.0 as usize) {
+                Some((id, handler)) => (id.clone(), handler.take()),
+                None => return,
+            };
+            if let Some(mut handler) = handler {
+                let activation = ActivationContext::new(
+                    id,
+                    activation.source,
+                    activation.modifiers,
+                    activation.window,
+                );
+                handler.call(&mut HandlerContext::detached(activation));
+                handlers.borrow_mut()[item.0 as usize].1 = Some(handler);
+            }
+        });
+        self.target.set_activator(Some(activator));
+    }
+}
+
+/// [`Backend`] for AppKit windows.
+///
+/// Menus in AppKit belong to the application, so attaching sets the
+/// application's main menu.
+pub struct AppKitBackend {
+    mtm: MainThreadMarker,
+
+    /// Kept alive here, since items only hold a weak reference to it.
+    target: Id<ActionTarget>,
+
+    menus: HashMap<MenuToken, MenuWrapper>,
+
+    /// Each item, along with the menu it is in.
+    items: HashMap<ItemToken, (MenuToken, MenuItemWrapper, ItemKind)>,
+
+    /// The main menu before attaching, restored when detaching.
+    previous: Option<Id<NSMenu>>,
+}
+
+impl AppKitBackend {
+    /// Create a new backend.
+    pub fn new(mtm: MainThreadMarker) -> Self {
+        Self {
+            mtm,
+            target: ActionTarget::new(),
+            menus: HashMap::new(),
+            items: HashMap::new(),
+            previous: None,
+        }
+    }
+
+    fn create_item(&self, item: ItemToken, props: &ItemProps) -> MenuItemWrapper {
+        let native = match props.kind {
+            ItemKind::Separator => MenuItemWrapper::new_separator(),
+            ItemKind::Item => MenuItemWrapper::new(
+                &props.title,
+                props.accelerator,
+                Some(sel!(menubarItemActivated:)),
+            ),
+            ItemKind::Submenu(submenu) => {
+                let native = MenuItemWrapper::new(&props.title, None, None);
+                let submenu = self.menus[&submenu].clone();
+                submenu.set_title(&props.title);
+                native.set_submenu(Some(submenu));
+                native
+            }
+        };
+
+        if props.kind == ItemKind::Item {
+            let target: &AnyObject = &self.target;
+            unsafe { native.0.setTarget(Some(target)) };
+        }
+        native.set_tag(item.0 as isize);
+        native.set_enabled(props.enabled);
+        native.set_hidden(props.hidden);
+        if props.checked == Some(true) {
+            native.set_state(MenuItemState::On);
+        }
+        native
+    }
+
+    fn forget_item(&mut self, item: ItemToken) {
+        if let Some((_, _, ItemKind::Submenu(submenu))) = self.items.remove(&item) {
+            let children: Vec<_> = self
+                .items
+                .iter()
+                .filter(|(_, (menu, _, _))| *menu == submenu)
+                .map(|(item, _)| *item)
+                .collect();
+            for child in children {
+                self.forget_item(child);
+            }
+            self.menus.remove(&submenu);
+        }
+    }
+}
+
+impl Backend for AppKitBackend {
+    fn supports(&self, window: RawWindowHandle) -> bool {
+        matches!(window, RawWindowHandle::AppKit(_))
+    }
+
+    fn set_activator(&mut self, activator: Activator) {
+        self.target.set_activator(Some(activator));
+    }
+
+    fn create_menu(&mut self, menu: MenuToken, kind: MenuKind) -> Result<(), Error> {
+        let native = MenuWrapper::new(self.mtm);
+        // Let the backend decide which items are enabled.
+        native.set_autoenables_items(false);
+        self.menus.insert(menu, native);
+        Ok(())
+    }
+
+    fn destroy_menu(&mut self, menu: MenuToken) {
+        self.menus.remove(&menu);
+    }
+
+    fn insert_item(
+        &mut self,
+        menu: MenuToken,
+        index: usize,
+        item: ItemToken,
+        props: &ItemProps,
+    ) -> Result<(), Error> {
+        let native = self.create_item(item, props);
+        self.menus[&menu].insert(native.clone(), index);
+        self.items.insert(item, (menu, native, props.kind));
+        Ok(())
+    }
+
+    fn remove_item(&mut self, item: ItemToken) -> Result<(), Error> {
+        let (menu, native, _) = &self.items[&item];
+        self.menus[menu].remove(native);
+        self.forget_item(item);
+        Ok(())
+    }
+
+    fn update_item(&mut self, item: ItemToken, property: &ItemProperty) -> Result<(), Error> {
+        let (_, native, _) = &self.items[&item];
+        match property {
+            ItemProperty::Title(title) => {
+                native.set_title(title);
+                if let Some(submenu) = native.submenu() {
+                    submenu.set_title(title);
+                }
+            }
+            ItemProperty::Accelerator(accelerator) => native.set_accelerator(*accelerator),
+            ItemProperty::Enabled(enabled) => native.set_enabled(*enabled),
+            ItemProperty::Checked(checked) => native.set_state(if *checked == Some(true) {
+                MenuItemState::On
+            } else {
+                MenuItemState::Off
+            }),
+            ItemProperty::Hidden(hidden) => native.set_hidden(*hidden),
+            // Checked items of radio groups look the same as other checked
+            // items.
+            ItemProperty::RadioGroup(_) => {}
+        }
+        Ok(())
+    }
+
+    fn move_item(&mut self, item: ItemToken, menu: MenuToken, index: usize) -> Result<(), Error> {
+        let (old, native, _) = self.items.get_mut(&item).expect("unknown item token");
+        // An item can only be in one menu at a time, so take it out first.
+        self.menus[old].remove(native);
+        self.menus[&menu].insert(native.clone(), index);
+        *old = menu;
+        Ok(())
+    }
+
+    fn attach(&mut self, menu: MenuToken, window: RawWindowHandle) -> Result<(), Error> {
+        if !self.supports(window) {
+            return Err(Error::unexpected_window_type());
+        }
+        let app = unsafe { NSApplication::sharedApplication() };
+        self.previous = unsafe { app.mainMenu() };
+        unsafe { app.setMainMenu(Some(&self.menus[&menu].0)) };
+        Ok(())
+    }
+
+    fn detach(&mut self, menu: MenuToken) {
+        // AppKit may hold on to the items for a while, so make sure they
+        // can't be activated anymore.
+        self.target.set_activator(None);
+        for (_, native, _) in self.items.values() {
+            unsafe { native.0.setTarget(None) };
+            unsafe { native.0.setAction(None) };
+        }
+
+        let app = unsafe { NSApplication::sharedApplication() };
+        let current = unsafe { app.mainMenu() };
+        let ours = self.menus.get(&menu).map(|native| Id::as_ptr(&native.0));
+        let previous = self.previous.take();
+        // Leave the main menu alone if it was replaced since attaching.
+        if current.as_ref().map(Id::as_ptr) == ours {
+            // Setting the main menu to `nil` doesn't work properly, so fall
+            // back to an empty one.
+            let previous = previous.unwrap_or_else(|| unsafe { NSMenu::new() });
+            unsafe { app.setMainMenu(Some(&previous)) };
+        }
+        self.menus.clear();
+        self.items.clear();
+    }
+}
@@ -5,6 +5,7 @@ use icrate::Foundation::{MainThreadMarker, NSArray, NSSize, NSString};
 use objc2::rc::Id;
 use objc2::runtime::ProtocolObject;
 
+use super::backend::ModelHandlers;
 use super::MenuItemWrapper;
 
 /// The maximum number of items a menu can hold is 65534
@@ -17,6 +18,32 @@ impl MenuWrapper {
         Self(unsafe { NSMenu::new() })
     }
 
+    /// Create a menu from a platform-agnostic [`crate::Menu`].
+    ///
+    /// Handlers are called with a detached
+    /// [`HandlerContext`][crate::HandlerContext], since the menu isn't
+    /// attached.
+    pub fn from_model(mtm: MainThreadMarker, menu: crate::Menu) -> Self {
+        let this = Self::new(mtm);
+        let mut handlers = ModelHandlers::new();
+        this.extend_from_model(mtm, menu, &mut handlers);
+        handlers.finish();
+        this
+    }
+
+    pub(super) fn extend_from_model(
+        &self,
+        mtm: MainThreadMarker,
+        menu: crate::Menu,
+        handlers: &mut ModelHandlers,
+    ) {
+        // Let the model decide which items are enabled.
+        self.set_autoenables_items(false);
+        for entry in menu.into_entries() {
+            self.add(MenuItemWrapper::from_model_with(mtm, entry, handlers));
+        }
+    }
+
     // Public only locally to allow for construction in Menubar
     #[doc(alias = "initWithTitle")]
     #[doc(alias = "initWithTitle:")]
@@ -75,12 +102,17 @@ impl MenuWrapper {
 
     // There exists `addItemWithTitle_action_keyEquivalent`
 
-    // Can't use this yet, we need to find a way to let users have references to menu items safely!
-    // #[doc(alias = "removeItem")]
-    // #[doc(alias = "removeItem:")]
-    // fn remove(&self, item: &NSMenuItem) {
-    //     unsafe { msg_send![self, removeItem: item] }
-    // }
+    /// Remove an item from this menu.
+    ///
+    /// The item can then be inserted into a menu again.
+    #[doc(alias = "removeItem")]
+    #[doc(alias = "removeItem:")]
+    pub fn remove(&self, item: &MenuItemWrapper) {
+        // SAFETY: Reference is valid, and removing an item that is not in
+        // the menu does nothing.
+        unsafe { self.0.removeItem(&item.0) }
+    }
+
     // #[doc(alias = "removeItemAtIndex")]
     // #[doc(alias = "removeItemAtIndex:")]
     // fn remove_at_index(&self, at: isize) {
@@ -183,14 +215,14 @@ impl MenuWrapper {
 
     /// Default on
     #[doc(alias = "autoenablesItems")]
-    fn autoenables_items(&self) -> bool {
-        unimplemented!()
+    pub fn autoenables_items(&self) -> bool {
+        unsafe { self.0.autoenablesItems() }
     }
 
     #[doc(alias = "setAutoenablesItems")]
     #[doc(alias = "setAutoenablesItems:")]
-    fn set_autoenables_items(&self, state: bool) {
-        unimplemented!()
+    pub fn set_autoenables_items(&self, state: bool) {
+        unsafe { self.0.setAutoenablesItems(state) }
     }
 
     #[doc(alias = "update")]
@@ -351,8 +383,14 @@ impl fmt::Debug for MenuWrapper {
 
 #[cfg(test)]
 mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
     use super::*;
-    use crate::{appkit::menuitem::MenuItemWrapper, test_util::STRINGS};
+    use crate::{
+        appkit::menuitem::{MenuItemState, MenuItemWrapper},
+        test_util::STRINGS,
+    };
 
     #[test]
     fn test_title() {
@@ -374,6 +412,41 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_from_model() {
+        let mtm = unsafe { MainThreadMarker::new_unchecked() };
+        let calls = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&calls);
+        let model = crate::Menu::new()
+            .with(crate::MenuItem::new("a").with_enabled(false))
+            .with(crate::Separator)
+            .with(crate::Submenu::new(
+                "b",
+                crate::Menu::new().with(
+                    crate::MenuItem::new("c")
+                        .with_checked(true)
+                        .with_handler(move || counter.set(counter.get() + 1)),
+                ),
+            ));
+        let menu = MenuWrapper::from_model(mtm, model);
+        assert_eq!(menu.len(), 3);
+        assert!(!menu.autoenables_items());
+
+        let items = menu.get_all_items();
+        let a = MenuItemWrapper(unsafe { items.objectAtIndex(0) });
+        assert_eq!(a.title(), "a");
+        assert!(!a.enabled());
+        let b = MenuItemWrapper(unsafe { items.objectAtIndex(2) });
+        let submenu = b.submenu().unwrap();
+        assert_eq!(submenu.title(), "b");
+        let c = MenuItemWrapper(unsafe { submenu.get_all_items().objectAtIndex(0) });
+        assert_eq!(c.state(), MenuItemState::On);
+
+        // The menu isn't attached, but its handlers are still called.
+        unsafe { submenu.0.performActionForItemAtIndex(0) };
+        assert_eq!(calls.get(), 1);
+    }
+
     #[test]
     fn test_length() {
         let mtm = unsafe { MainThreadMarker::new_unchecked() };
@@ -383,9 +456,9 @@ mod tests {
         assert_eq!(menu.len(), 1);
         menu.add(MenuItemWrapper::new_separator());
         assert_eq!(menu.len(), 2);
-        menu.add(MenuItemWrapper::new("test", "", None));
+        menu.add(MenuItemWrapper::new("test", None, None));
         assert_eq!(menu.len(), 3);
-        menu.insert(MenuItemWrapper::new("test", "", None), 2);
+        menu.insert(MenuItemWrapper::new("test", None, None), 2);
         assert_eq!(menu.len(), 4);
         menu.remove_all();
         assert_eq!(menu.len(), 0);
@@ -440,7 +513,7 @@ mod tests {
         let menu = MenuWrapper::new(mtm);
         const COUNT: usize = 65534;
         for i in 1..=COUNT {
-            menu.add(MenuItemWrapper::new(&format!("item {}", i), "", None));
+            menu.add(MenuItemWrapper::new(&format!("item {}", i), None, None));
         }
         assert_eq!(menu.len(), COUNT);
 
@@ -448,7 +521,7 @@ mod tests {
 
         menu.add(MenuItemWrapper::new(
             &format!("item {}", COUNT + 1),
-            "",
+            None,
             None,
         ));
 
@@ -31,6 +31,14 @@ impl MenuBar {
         menubar
     }
 
+    /// Create a menubar from a platform-agnostic [`crate::Menu`].
+    ///
+    /// Each top-level entry becomes a menu in the menubar; the first one is
+    /// the application menu, and its title is therefore irrelevant.
+    pub fn from_model(mtm: MainThreadMarker, menu: crate::Menu) -> Self {
+        Self(MenuWrapper::from_model(mtm, menu))
+    }
+
     fn add_menu(&mut self, menu: MenuWrapper) -> MenuWrapper {
         // All parameters on menu items irrelevant in the menu bar
         let item = MenuItemWrapper::new_empty();
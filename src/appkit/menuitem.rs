@@ -6,12 +6,15 @@ use core::ptr;
 use icrate::AppKit::{
     NSControlStateValueMixed, NSControlStateValueOff, NSControlStateValueOn, NSMenu, NSMenuItem,
 };
-use icrate::Foundation::{NSInteger, NSString};
+use icrate::Foundation::{MainThreadMarker, NSInteger, NSString};
 use objc2::rc::Id;
 use objc2::runtime::{AnyObject, Sel};
 use objc2::ClassType;
 
+use super::backend::ModelHandlers;
 use super::MenuWrapper;
+use crate::accelerator::appkit::KeyEquivalent;
+use crate::{Accelerator, MenuEntry};
 
 type Target = AnyObject; // Normal NSObject. Should return YES in worksWhenModal.
 struct Image;
@@ -41,17 +44,25 @@ impl MenuItemWrapper {
 
     #[doc(alias = "initWithTitle")]
     #[doc(alias = "initWithTitle:action:keyEquivalent:")]
-    pub fn new(title: &str, key_equivalent: &str, action: Option<Sel>) -> Self {
+    ///
+    /// Accelerators that AppKit can't represent, like media keys, are
+    /// ignored.
+    pub fn new(title: &str, accelerator: Option<Accelerator>, action: Option<Sel>) -> Self {
         let title = NSString::from_str(title);
-        let key_equivalent = NSString::from_str(key_equivalent);
-        Self(unsafe {
+        let key_equivalent = accelerator
+            .and_then(|accelerator| accelerator.to_key_equivalent())
+            .unwrap_or_else(KeyEquivalent::none);
+        let key = NSString::from_str(&key_equivalent.key);
+        let item = unsafe {
             NSMenuItem::initWithTitle_action_keyEquivalent(
                 NSMenuItem::alloc(),
                 &title,
                 action,
-                &key_equivalent,
+                &key,
             )
-        })
+        };
+        unsafe { item.setKeyEquivalentModifierMask(key_equivalent.modifier_mask) };
+        Self(item)
     }
 
     #[doc(alias = "separatorItem")]
@@ -59,16 +70,63 @@ impl MenuItemWrapper {
         Self(unsafe { NSMenuItem::separatorItem() })
     }
 
+    /// Create an item from an entry in a platform-agnostic [`crate::Menu`].
+    ///
+    /// Like with [`MenuWrapper::from_model`], handlers are called with a
+    /// detached context.
+    pub fn from_model(mtm: MainThreadMarker, entry: MenuEntry) -> Self {
+        let mut handlers = ModelHandlers::new();
+        let item = Self::from_model_with(mtm, entry, &mut handlers);
+        handlers.finish();
+        item
+    }
+
+    pub(super) fn from_model_with(
+        mtm: MainThreadMarker,
+        entry: MenuEntry,
+        handlers: &mut ModelHandlers,
+    ) -> Self {
+        match entry {
+            MenuEntry::Separator(_) => Self::new_separator(),
+            MenuEntry::Submenu(submenu) => {
+                let item = Self::new(submenu.title(), None, None);
+                item.set_enabled(submenu.enabled());
+                item.set_hidden(submenu.hidden());
+                let menu = MenuWrapper::new_with_title(mtm, submenu.title());
+                menu.extend_from_model(mtm, submenu.into_menu(), handlers);
+                item.set_submenu(Some(menu));
+                item
+            }
+            MenuEntry::Item(mut model) => {
+                let item = Self::new(model.title(), model.accelerator(), None);
+                item.set_enabled(model.enabled());
+                item.set_hidden(model.hidden());
+                if model.checked() == Some(true) {
+                    item.set_state(MenuItemState::On);
+                }
+                if let Some(handler) = model.take_handler() {
+                    handlers.bind(&item, model.id().cloned(), handler);
+                }
+                item
+            }
+        }
+    }
+
     // Enabling
 
-    fn enabled(&self) -> bool {
-        unimplemented!()
+    /// Whether the menu item is enabled or not.
+    ///
+    /// This is only respected if the parent menu doesn't automatically enable
+    /// its items.
+    #[doc(alias = "isEnabled")]
+    pub fn enabled(&self) -> bool {
+        unsafe { self.0.isEnabled() }
     }
 
     #[doc(alias = "setEnabled")]
     #[doc(alias = "setEnabled:")]
-    fn set_enabled(&self, state: bool) {
-        unimplemented!()
+    pub fn set_enabled(&self, state: bool) {
+        unsafe { self.0.setEnabled(state) }
     }
 
     // Managing Hidden Status
@@ -132,16 +190,52 @@ impl MenuItemWrapper {
     // #[doc(alias = "setAttributedTitle:")]
     // pub fn set_attributed_title(&self, title: ???) { unimplemented!() }
 
+    // Key equivalent
+
+    /// The keyboard shortcut of the menu item.
+    ///
+    /// Returns `None` if there is none, or if it can't be represented as an
+    /// [`Accelerator`].
+    #[doc(alias = "keyEquivalent")]
+    #[doc(alias = "keyEquivalentModifierMask")]
+    pub fn accelerator(&self) -> Option<Accelerator> {
+        let key_equivalent = KeyEquivalent {
+            key: unsafe { self.0.keyEquivalent() }.to_string(),
+            modifier_mask: unsafe { self.0.keyEquivalentModifierMask() },
+        };
+        Accelerator::from_key_equivalent(&key_equivalent)
+    }
+
+    /// Set the keyboard shortcut of the menu item.
+    ///
+    /// Accelerators that AppKit can't represent, like media keys, remove the
+    /// shortcut.
+    #[doc(alias = "setKeyEquivalent")]
+    #[doc(alias = "setKeyEquivalent:")]
+    #[doc(alias = "setKeyEquivalentModifierMask")]
+    #[doc(alias = "setKeyEquivalentModifierMask:")]
+    pub fn set_accelerator(&self, accelerator: Option<Accelerator>) {
+        let key_equivalent = accelerator
+            .and_then(|accelerator| accelerator.to_key_equivalent())
+            .unwrap_or_else(KeyEquivalent::none);
+        let key = NSString::from_str(&key_equivalent.key);
+        unsafe {
+            self.0.setKeyEquivalent(&key);
+            self.0
+                .setKeyEquivalentModifierMask(key_equivalent.modifier_mask);
+        }
+    }
+
     // Tag
 
-    fn tag(&self) -> isize {
-        unimplemented!()
+    pub fn tag(&self) -> isize {
+        unsafe { self.0.tag() }
     }
 
     #[doc(alias = "setTag")]
     #[doc(alias = "setTag:")]
-    fn set_tag(&self, tag: isize) {
-        unimplemented!()
+    pub fn set_tag(&self, tag: isize) {
+        unsafe { self.0.setTag(tag) }
     }
 
     /// Get the menu item's state
@@ -350,7 +444,18 @@ mod tests {
     fn for_each_item(mut f: impl FnMut(&MenuItemWrapper)) {
         f(&MenuItemWrapper::new_separator());
         f(&MenuItemWrapper::new_empty());
-        f(&MenuItemWrapper::new("", "", None));
+        f(&MenuItemWrapper::new("", None, None));
+    }
+
+    #[test]
+    fn test_enabled() {
+        for_each_item(|item| {
+            assert!(item.enabled());
+            item.set_enabled(false);
+            assert!(!item.enabled());
+            item.set_enabled(true);
+            assert!(item.enabled());
+        })
     }
 
     #[test]
@@ -377,7 +482,7 @@ mod tests {
     #[test]
     fn test_title_init() {
         STRINGS.iter().for_each(|&title| {
-            let item = MenuItemWrapper::new(title, "", None);
+            let item = MenuItemWrapper::new(title, None, None);
             assert_eq!(item.title(), title);
         });
     }
@@ -390,13 +495,34 @@ mod tests {
         assert_eq!(item.title(), "");
     }
 
+    #[test]
+    fn test_accelerator() {
+        let item = MenuItemWrapper::new("", None, None);
+        assert_eq!(item.accelerator(), None);
+
+        for s in ["Cmd+Shift+N", "Ctrl+Alt+F5", "Cmd+Backspace", "Cmd+Num1"] {
+            let accelerator = s.parse().unwrap();
+            item.set_accelerator(Some(accelerator));
+            assert_eq!(item.accelerator(), Some(accelerator));
+        }
+
+        let accelerator = "Cmd+Shift+N".parse().unwrap();
+        let item = MenuItemWrapper::new("", Some(accelerator), None);
+        assert_eq!(item.accelerator(), Some(accelerator));
+
+        item.set_accelerator(Some("MediaStop".parse().unwrap()));
+        assert_eq!(item.accelerator(), None);
+        item.set_accelerator(None);
+        assert_eq!(item.accelerator(), None);
+    }
+
     #[test]
     fn test_separator() {
         let item = MenuItemWrapper::new_separator();
         assert!(item.separator());
         let item = MenuItemWrapper::new_empty();
         assert!(!item.separator());
-        let item = MenuItemWrapper::new("", "", None);
+        let item = MenuItemWrapper::new("", None, None);
         assert!(!item.separator());
     }
 
@@ -1,8 +1,10 @@
+mod backend;
 mod global;
 mod menu;
 mod menubar;
 mod menuitem;
 
+pub use self::backend::AppKitBackend;
 pub use self::global::InitializedApplication;
 pub use self::menu::MenuWrapper;
 pub use self::menubar::MenuBar;
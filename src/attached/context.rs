@@ -0,0 +1,564 @@
+//! What handlers are given when an item is activated.
+
+use core::fmt;
+use std::cell::RefCell;
+use std::rc::Weak;
+
+use raw_window_handle::RawWindowHandle;
+
+use super::{submenu, ItemHandle, State};
+use crate::backend::{ActivationSource, ItemKind, ItemProperty, MenuToken};
+use crate::model::Handler;
+use crate::{Accelerator, Error, ItemId, MenuEntry, Modifiers};
+
+/// How and where an item was activated.
+///
+/// Given to handlers through [`HandlerContext::activation`], and to
+/// dispatchers set with
+/// [`AttachedMenu::set_context_dispatcher`][crate::AttachedMenu::set_context_dispatcher].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActivationContext {
+    id: Option<ItemId>,
+    source: ActivationSource,
+    modifiers: Modifiers,
+    window: Option<RawWindowHandle>,
+}
+
+impl ActivationContext {
+    pub(crate) fn new(
+        id: Option<ItemId>,
+        source: ActivationSource,
+        modifiers: Modifiers,
+        window: Option<RawWindowHandle>,
+    ) -> Self {
+        Self {
+            id,
+            source,
+            modifiers,
+            window,
+        }
+    }
+
+    /// The id of the item that was activated.
+    pub fn id(&self) -> Option<&ItemId> {
+        self.id.as_ref()
+    }
+
+    pub fn source(&self) -> ActivationSource {
+        self.source
+    }
+
+    /// The modifier keys that were held.
+    ///
+    /// Empty for programmatic activations.
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    /// The window the item was activated in.
+    ///
+    /// This is the window the menu was attached to, or the key window on
+    /// macOS, where the menu belongs to the whole application. `None` for
+    /// handlers invoked with [`MenuItem::activate`][crate::MenuItem::activate],
+    /// and for menus without a window, like those of tray icons.
+    pub fn window(&self) -> Option<RawWindowHandle> {
+        self.window
+    }
+}
+
+/// Given to handlers set with
+/// [`MenuItem::with_context_handler`][crate::MenuItem::with_context_handler].
+///
+/// Changes made through the context are queued, and applied in order once
+/// the handler returns, so that handlers can change any part of the menu,
+/// including their own item. Changes that fail when they are applied, for
+/// example because an earlier change removed the item, are skipped, and
+/// reported to the hook set with [`set_error_hook`][crate::set_error_hook].
+pub struct HandlerContext<C = ()> {
+    state: Weak<RefCell<State<C>>>,
+    item: Option<ItemHandle<C>>,
+    activation: ActivationContext,
+    deferred: Vec<Deferred<C>>,
+}
+
+enum Deferred<C> {
+    Update(ItemHandle<C>, ItemProperty),
+    Remove(ItemHandle<C>),
+    Insert {
+        parent: Option<ItemHandle<C>>,
+        /// `None` to add the entry to the end.
+        index: Option<usize>,
+        entry: MenuEntry<C>,
+    },
+    SetHandler(ItemHandle<C>, Handler<C>),
+}
+
+impl<C> HandlerContext<C> {
+    pub(super) fn new(
+        state: Weak<RefCell<State<C>>>,
+        item: ItemHandle<C>,
+        activation: ActivationContext,
+    ) -> Self {
+        Self {
+            state,
+            item: Some(item),
+            activation,
+            deferred: Vec::new(),
+        }
+    }
+
+    /// A context that isn't part of an attached menu.
+    pub(crate) fn detached(activation: ActivationContext) -> Self {
+        Self {
+            state: Weak::new(),
+            item: None,
+            activation,
+            deferred: Vec::new(),
+        }
+    }
+
+    /// How and where the item was activated.
+    pub fn activation(&self) -> &ActivationContext {
+        &self.activation
+    }
+
+    /// The item that was activated.
+    ///
+    /// `None` if the handler was invoked with
+    /// [`MenuItem::activate`][crate::MenuItem::activate].
+    pub fn item(&self) -> Option<ItemHandle<C>> {
+        self.item.clone()
+    }
+
+    pub fn set_title(&mut self, item: &ItemHandle<C>, title: impl Into<String>) {
+        self.update(item, ItemProperty::Title(title.into()));
+    }
+
+    pub fn set_accelerator(&mut self, item: &ItemHandle<C>, accelerator: Option<Accelerator>) {
+        self.update(item, ItemProperty::Accelerator(accelerator));
+    }
+
+    pub fn set_enabled(&mut self, item: &ItemHandle<C>, enabled: bool) {
+        self.update(item, ItemProperty::Enabled(enabled));
+    }
+
+    pub fn set_checked(&mut self, item: &ItemHandle<C>, checked: Option<bool>) {
+        self.update(item, ItemProperty::Checked(checked));
+    }
+
+    pub fn set_radio_group(&mut self, item: &ItemHandle<C>, group: Option<String>) {
+        self.update(item, ItemProperty::RadioGroup(group));
+    }
+
+    pub fn set_hidden(&mut self, item: &ItemHandle<C>, hidden: bool) {
+        self.update(item, ItemProperty::Hidden(hidden));
+    }
+
+    /// Change a property of the item, like [`ItemHandle::update`].
+    pub fn update(&mut self, item: &ItemHandle<C>, property: ItemProperty) {
+        self.deferred.push(Deferred::Update(item.clone(), property));
+    }
+
+    /// Remove the item, and everything in it if it's a submenu.
+    pub fn remove(&mut self, item: &ItemHandle<C>) {
+        self.deferred.push(Deferred::Remove(item.clone()));
+    }
+
+    /// Insert an entry into the submenu, or into the top-level menu if
+    /// `parent` is `None`.
+    ///
+    /// Fails when applied if the index is larger than the number of entries.
+    pub fn insert(
+        &mut self,
+        parent: Option<&ItemHandle<C>>,
+        index: usize,
+        entry: impl Into<MenuEntry<C>>,
+    ) {
+        self.deferred.push(Deferred::Insert {
+            parent: parent.cloned(),
+            index: Some(index),
+            entry: entry.into(),
+        });
+    }
+
+    /// Add an entry to the end of the submenu, or of the top-level menu if
+    /// `parent` is `None`.
+    pub fn push(&mut self, parent: Option<&ItemHandle<C>>, entry: impl Into<MenuEntry<C>>) {
+        self.deferred.push(Deferred::Insert {
+            parent: parent.cloned(),
+            index: None,
+            entry: entry.into(),
+        });
+    }
+
+    /// Replace the handler of the item.
+    ///
+    /// Submenus and separators don't have handlers, so this is skipped for
+    /// them.
+    pub fn set_handler(&mut self, item: &ItemHandle<C>, handler: impl FnMut() + 'static) {
+        let handler = Handler::Plain(Box::new(handler));
+        self.deferred
+            .push(Deferred::SetHandler(item.clone(), handler));
+    }
+
+    /// Replace the handler of the item with one that is given a context.
+    pub fn set_context_handler(
+        &mut self,
+        item: &ItemHandle<C>,
+        handler: impl FnMut(&mut HandlerContext<C>) + 'static,
+    ) {
+        let handler = Handler::Context(Box::new(handler));
+        self.deferred
+            .push(Deferred::SetHandler(item.clone(), handler));
+    }
+
+    /// Apply the queued changes, returning the errors of those that failed.
+    ///
+    /// Must be called without borrowing the state.
+    pub(super) fn apply(self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for deferred in self.deferred {
+            let res = match deferred {
+                Deferred::Update(item, property) => item.update(property),
+                Deferred::Remove(item) => item.remove(),
+                Deferred::Insert {
+                    parent: Some(parent),
+                    index,
+                    entry,
+                } => parent.with(|state, live| {
+                    let menu = submenu(live)?;
+                    state.insert_at(menu, index, entry)
+                }),
+                Deferred::Insert {
+                    parent: None,
+                    index,
+                    entry,
+                } => match self.state.upgrade() {
+                    Some(state) => {
+                        let mut state = state.borrow_mut();
+                        let root = state.root;
+                        state.insert_at(root, index, entry)
+                    }
+                    None => Ok(()),
+                },
+                Deferred::SetHandler(item, handler) => item.with(|state, live| {
+                    if live.props.kind == ItemKind::Item {
+                        state.handlers.insert(item.item, handler);
+                    }
+                    Ok(())
+                }),
+            };
+            if let Err(err) = res {
+                errors.push(err);
+            }
+        }
+        errors
+    }
+}
+
+impl<C> State<C> {
+    /// Insert an entry at the index, or at the end if it's `None`.
+    fn insert_at(
+        &mut self,
+        menu: MenuToken,
+        index: Option<usize>,
+        entry: MenuEntry<C>,
+    ) -> Result<(), Error> {
+        let index = index.unwrap_or_else(|| self.menus[&menu].len());
+        self.insert(menu, index, entry).map(drop)
+    }
+}
+
+impl<C> fmt::Debug for HandlerContext<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HandlerContext")
+            .field("item", &self.item)
+            .field("activation", &self.activation)
+            .field("deferred", &self.deferred.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    use crate::headless::{HeadlessBackend, Op};
+    use crate::{
+        ActivationContext, ActivationSource, HandlerContext, ItemId, Menu, MenuItem, Modifiers,
+        Separator, Submenu,
+    };
+
+    #[test]
+    fn test_remove_self() {
+        let backend = HeadlessBackend::new();
+        let menu = Menu::default().with(Submenu::new(
+            "File",
+            Menu::default()
+                .with(
+                    MenuItem::new("Once")
+                        .with_id("once")
+                        .with_command("once")
+                        .with_context_handler(|ctx: &mut HandlerContext<&str>| {
+                            let item = ctx.item().unwrap();
+                            ctx.remove(&item);
+                        }),
+                )
+                .with(MenuItem::new("Quit")),
+        ));
+        let attached = backend.attach(menu).unwrap();
+        let once = attached.item("once").unwrap();
+        backend.take_ops();
+
+        assert!(backend.click(once.token()));
+        assert_eq!(backend.take_ops(), [Op::RemoveItem { item: once.token() }]);
+        assert!(!once.exists());
+        assert_eq!(backend.find(&["File", "Once"]), None);
+        // The command is delivered even though the item is gone.
+        assert_eq!(attached.take_commands(), ["once"]);
+        assert!(!backend.click(once.token()));
+    }
+
+    #[test]
+    fn test_clear_recent() {
+        let backend = HeadlessBackend::new();
+        let menu = Menu::new().with(Submenu::new(
+            "File",
+            Menu::new()
+                .with(
+                    Submenu::new(
+                        "Open Recent",
+                        Menu::new()
+                            .with(MenuItem::new("a.txt"))
+                            .with(MenuItem::new("b.txt")),
+                    )
+                    .with_id("recent"),
+                )
+                .with(
+                    MenuItem::new("Clear Recent")
+                        .with_id("clear")
+                        .with_context_handler(|ctx| {
+                            let clear = ctx.item().unwrap();
+                            let recent =
+                                clear.parent().unwrap().unwrap().items().unwrap()[0].clone();
+                            for item in recent.items().unwrap() {
+                                ctx.remove(&item);
+                            }
+                            ctx.push(
+                                Some(&recent),
+                                MenuItem::new("No Recent Items").with_enabled(false),
+                            );
+                            ctx.set_enabled(&clear, false);
+                        }),
+                ),
+        ));
+        let attached = backend.attach(menu).unwrap();
+
+        assert!(backend.click_path(&["File", "Clear Recent"]));
+        let recent = attached.item("recent").unwrap();
+        let titles: Vec<_> = recent
+            .items()
+            .unwrap()
+            .iter()
+            .map(|item| item.title().unwrap())
+            .collect();
+        assert_eq!(titles, ["No Recent Items"]);
+        assert!(!attached.item("clear").unwrap().enabled().unwrap());
+        assert!(!backend.click_path(&["File", "Clear Recent"]));
+    }
+
+    #[test]
+    fn test_set_handler() {
+        let backend = HeadlessBackend::new();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let first_log = Rc::clone(&log);
+        let menu = Menu::new().with(Submenu::new(
+            "View",
+            Menu::new().with(MenuItem::new("Toggle").with_context_handler(move |ctx| {
+                first_log.borrow_mut().push("first");
+                let second_log = Rc::clone(&first_log);
+                ctx.set_handler(&ctx.item().unwrap(), move || {
+                    second_log.borrow_mut().push("second")
+                });
+            })),
+        ));
+        let _attached = backend.attach(menu).unwrap();
+
+        assert!(backend.click_path(&["View", "Toggle"]));
+        assert!(backend.click_path(&["View", "Toggle"]));
+        assert!(backend.click_path(&["View", "Toggle"]));
+        assert_eq!(*log.borrow(), ["first", "second", "second"]);
+    }
+
+    #[test]
+    fn test_insert_top_level() {
+        let backend = HeadlessBackend::new();
+        let menu = Menu::new().with(Submenu::new(
+            "File",
+            Menu::new().with(MenuItem::new("Add Menu").with_context_handler(|ctx| {
+                ctx.insert(None, 0, Submenu::new("Extra", Menu::new()));
+                ctx.push(None, Submenu::new("Last", Menu::new()));
+            })),
+        ));
+        let attached = backend.attach(menu).unwrap();
+
+        assert!(backend.click_path(&["File", "Add Menu"]));
+        let titles: Vec<_> = attached
+            .items()
+            .iter()
+            .map(|item| item.title().unwrap())
+            .collect();
+        assert_eq!(titles, ["Extra", "File", "Last"]);
+    }
+
+    #[test]
+    fn test_failed_changes_are_reported() {
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        let hook_errors = Rc::clone(&errors);
+        crate::set_error_hook(move |err| hook_errors.borrow_mut().push(err.to_string()));
+
+        let backend = HeadlessBackend::new();
+        let menu = Menu::new().with(Submenu::new(
+            "File",
+            Menu::new().with(
+                MenuItem::new("Break")
+                    .with_id("break")
+                    .with_context_handler(|ctx| {
+                        let item = ctx.item().unwrap();
+                        let file = item.parent().unwrap().unwrap();
+                        ctx.insert(Some(&file), 5, MenuItem::new("Far"));
+                        ctx.remove(&item);
+                        ctx.set_title(&item, "Gone");
+                        ctx.insert(None, 1, Submenu::new("Help", Menu::new()));
+                    }),
+            ),
+        ));
+        let attached = backend.attach(menu).unwrap();
+
+        assert!(backend.click_path(&["File", "Break"]));
+        assert_eq!(
+            *errors.borrow(),
+            [
+                "index 5 is out of bounds for a menu with 1 entries",
+                "menu item `break` has been removed",
+            ]
+        );
+        // The changes that didn't fail were still made.
+        let titles: Vec<_> = attached
+            .items()
+            .iter()
+            .map(|item| item.title().unwrap())
+            .collect();
+        assert_eq!(titles, ["File", "Help"]);
+        assert_eq!(backend.find(&["File", "Break"]), None);
+    }
+
+    #[test]
+    fn test_activate_detached() {
+        let called = Rc::new(Cell::new(false));
+        let handler_called = Rc::clone(&called);
+        let mut item = MenuItem::<()>::new("Item").with_context_handler(move |ctx| {
+            assert!(ctx.item().is_none());
+            handler_called.set(true);
+        });
+        assert!(item.activate());
+        assert!(called.get());
+    }
+
+    type Activations = Rc<RefCell<Vec<ActivationContext>>>;
+
+    #[test]
+    fn test_activation() {
+        let backend = HeadlessBackend::new();
+        let activations: Activations = Rc::default();
+        let handler_activations = Rc::clone(&activations);
+        let menu = Menu::new().with(Submenu::new(
+            "File",
+            Menu::new().with(
+                MenuItem::new("Save")
+                    .with_id("save")
+                    .with_accelerator("Ctrl+S".parse().unwrap())
+                    .with_context_handler(move |ctx| {
+                        handler_activations
+                            .borrow_mut()
+                            .push(ctx.activation().clone());
+                    }),
+            ),
+        ));
+        let attached = backend.attach(menu).unwrap();
+        let save = attached.item("save").unwrap();
+
+        assert!(backend.click_with_modifiers(save.token(), Modifiers::SHIFT));
+        assert!(backend.send_key("Ctrl+S".parse().unwrap()));
+        save.activate().unwrap();
+
+        // Headless menus aren't attached to a window.
+        let window = None;
+        let id = Some(ItemId::from("save"));
+        assert_eq!(
+            *activations.borrow(),
+            [
+                ActivationContext::new(
+                    id.clone(),
+                    ActivationSource::Click,
+                    Modifiers::SHIFT,
+                    window
+                ),
+                ActivationContext::new(
+                    id.clone(),
+                    ActivationSource::Accelerator,
+                    Modifiers::CONTROL,
+                    window
+                ),
+                ActivationContext::new(
+                    id,
+                    ActivationSource::Programmatic,
+                    Modifiers::empty(),
+                    window
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_context_dispatcher() {
+        let backend = HeadlessBackend::new();
+        let menu = Menu::default().with(Submenu::new(
+            "Edit",
+            Menu::default()
+                .with(MenuItem::new("Copy").with_id("copy").with_command("copy"))
+                .with(MenuItem::new("Paste").with_command("paste"))
+                .with(Separator),
+        ));
+        let attached = backend.attach(menu).unwrap();
+
+        // Commands queued before the dispatcher is set keep their context.
+        assert!(backend.click_path(&["Edit", "Copy"]));
+        let delivered = Rc::new(RefCell::new(Vec::new()));
+        let dispatcher_delivered = Rc::clone(&delivered);
+        attached.set_context_dispatcher(move |command, activation: &ActivationContext| {
+            dispatcher_delivered.borrow_mut().push((
+                command,
+                activation.id().cloned(),
+                activation.source(),
+            ));
+        });
+        let paste = backend.find(&["Edit", "Paste"]).unwrap();
+        assert!(backend.click_with_modifiers(paste, Modifiers::ALT));
+
+        assert_eq!(
+            *delivered.borrow(),
+            [
+                ("copy", Some(ItemId::from("copy")), ActivationSource::Click),
+                ("paste", None, ActivationSource::Click),
+            ]
+        );
+
+        let separator = attached.items()[0].items().unwrap().remove(2);
+        let err = separator.activate().unwrap_err();
+        assert_eq!(err.to_string(), "activation is not supported on separators");
+        let err = attached.items()[0].activate().unwrap_err();
+        assert_eq!(err.to_string(), "activation is not supported on submenus");
+    }
+}
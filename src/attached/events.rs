@@ -0,0 +1,434 @@
+//! Receiving activations as a stream of events.
+
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use futures_core::Stream;
+
+use super::{ActivationContext, AttachedMenu};
+use crate::ItemId;
+
+impl<C: Clone> AttachedMenu<C> {
+    /// Receive every activation in this menu as an event.
+    ///
+    /// Up to `capacity` events are buffered until they're received, after
+    /// which `overflow` decides which events are lost. Events are sent once
+    /// the item's handler has returned, and before the command is delivered
+    /// to the dispatcher. Commands aren't queued for
+    /// [`take_commands`][AttachedMenu::take_commands] while there are
+    /// receivers.
+    ///
+    /// The receiver is closed when the menu is dropped.
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn events(&self, capacity: usize, overflow: Overflow) -> Events<C> {
+        assert!(capacity > 0, "the capacity must be at least 1");
+        let shared = Arc::new(Shared {
+            inner: Mutex::new(Inner {
+                buffer: VecDeque::with_capacity(capacity),
+                capacity,
+                overflow,
+                dropped: 0,
+                closed: false,
+                waker: None,
+            }),
+            ready: Condvar::new(),
+        });
+        let mut state = self.state.borrow_mut();
+        state.senders.retain(|sender| !sender.is_disconnected());
+        state.senders.push(Sender(Arc::clone(&shared)));
+        Events(shared)
+    }
+}
+
+/// An item that was activated, received through [`Events`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MenuEvent<C> {
+    command: Option<C>,
+    activation: ActivationContext,
+}
+
+// SAFETY: The window handle in the activation is only an identifier, and
+// using it requires `unsafe` code that must check which thread it's on.
+unsafe impl<C: Send> Send for MenuEvent<C> {}
+unsafe impl<C: Sync> Sync for MenuEvent<C> {}
+
+impl<C> MenuEvent<C> {
+    /// The id of the item that was activated.
+    pub fn id(&self) -> Option<&ItemId> {
+        self.activation.id()
+    }
+
+    /// The command of the item, if it has one.
+    pub fn command(&self) -> Option<&C> {
+        self.command.as_ref()
+    }
+
+    pub fn into_command(self) -> Option<C> {
+        self.command
+    }
+
+    /// How and where the item was activated.
+    pub fn activation(&self) -> &ActivationContext {
+        &self.activation
+    }
+}
+
+/// What happens to events that arrive while the buffer is full.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Overflow {
+    /// Keep the buffered events, and drop the new one.
+    DropNewest,
+
+    /// Drop the oldest buffered event to make room for the new one.
+    DropOldest,
+}
+
+/// The receiving end of [`AttachedMenu::events`].
+///
+/// Events can be received by blocking with [`recv`][Events::recv], or
+/// asynchronously through the [`Stream`] implementation, which ends when
+/// the menu is dropped. The receiver can be sent to another thread if the
+/// command type allows it.
+pub struct Events<C>(Arc<Shared<C>>);
+
+impl<C> Events<C> {
+    /// Wait for the next event.
+    ///
+    /// Returns `None` once the menu has been dropped and every buffered
+    /// event has been received. Since menus handle activations on the
+    /// thread running the event loop, calling this on that thread blocks
+    /// forever if no event is buffered.
+    pub fn recv(&self) -> Option<MenuEvent<C>> {
+        let mut inner = self.0.lock();
+        loop {
+            if let Some(event) = inner.buffer.pop_front() {
+                return Some(event);
+            }
+            if inner.closed {
+                return None;
+            }
+            inner = self
+                .0
+                .ready
+                .wait(inner)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+    }
+
+    /// Wait for the next event, for at most `timeout`.
+    ///
+    /// Returns `None` if no event arrived in time, or the menu has been
+    /// dropped.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<MenuEvent<C>> {
+        let deadline = Instant::now() + timeout;
+        let mut inner = self.0.lock();
+        loop {
+            if let Some(event) = inner.buffer.pop_front() {
+                return Some(event);
+            }
+            let now = Instant::now();
+            if inner.closed || now >= deadline {
+                return None;
+            }
+            let (guard, _) = self
+                .0
+                .ready
+                .wait_timeout(inner, deadline - now)
+                .unwrap_or_else(|err| err.into_inner());
+            inner = guard;
+        }
+    }
+
+    /// The next event, if one is buffered.
+    pub fn try_recv(&self) -> Option<MenuEvent<C>> {
+        self.0.lock().buffer.pop_front()
+    }
+
+    /// Whether the menu has been dropped.
+    ///
+    /// Events may still be buffered.
+    pub fn is_closed(&self) -> bool {
+        self.0.lock().closed
+    }
+
+    /// The number of events lost because the buffer was full.
+    pub fn dropped(&self) -> u64 {
+        self.0.lock().dropped
+    }
+}
+
+impl<C> Stream for Events<C> {
+    type Item = MenuEvent<C>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut inner = self.0.lock();
+        if let Some(event) = inner.buffer.pop_front() {
+            Poll::Ready(Some(event))
+        } else if inner.closed {
+            Poll::Ready(None)
+        } else {
+            inner.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let inner = self.0.lock();
+        let upper = if inner.closed {
+            Some(inner.buffer.len())
+        } else {
+            None
+        };
+        (inner.buffer.len(), upper)
+    }
+}
+
+impl<C> core::fmt::Debug for Events<C> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let inner = self.0.lock();
+        f.debug_struct("Events")
+            .field("buffered", &inner.buffer.len())
+            .field("capacity", &inner.capacity)
+            .field("overflow", &inner.overflow)
+            .field("dropped", &inner.dropped)
+            .field("closed", &inner.closed)
+            .finish()
+    }
+}
+
+struct Shared<C> {
+    inner: Mutex<Inner<C>>,
+    /// Notified when an event is buffered, or the menu is dropped.
+    ready: Condvar,
+}
+
+impl<C> Shared<C> {
+    fn lock(&self) -> MutexGuard<'_, Inner<C>> {
+        // The lock is never held while calling out, so a panic can't leave
+        // the buffer in a bad state.
+        self.inner.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn notify(&self, mut inner: MutexGuard<'_, Inner<C>>) {
+        let waker = inner.waker.take();
+        drop(inner);
+        self.ready.notify_all();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+struct Inner<C> {
+    buffer: VecDeque<MenuEvent<C>>,
+    capacity: usize,
+    overflow: Overflow,
+    dropped: u64,
+    /// Whether the menu has been dropped.
+    closed: bool,
+    /// The task waiting on the stream.
+    waker: Option<Waker>,
+}
+
+/// The sending end of [`Events`], owned by the attached menu.
+pub(super) struct Sender<C>(Arc<Shared<C>>);
+
+impl<C> Sender<C> {
+    /// Whether the receiver has been dropped.
+    pub(super) fn is_disconnected(&self) -> bool {
+        Arc::strong_count(&self.0) == 1
+    }
+
+    pub(super) fn send(&self, command: Option<C>, activation: ActivationContext) {
+        let mut inner = self.0.lock();
+        if inner.buffer.len() == inner.capacity {
+            inner.dropped += 1;
+            match inner.overflow {
+                Overflow::DropNewest => return,
+                Overflow::DropOldest => {
+                    inner.buffer.pop_front();
+                }
+            }
+        }
+        inner.buffer.push_back(MenuEvent {
+            command,
+            activation,
+        });
+        self.0.notify(inner);
+    }
+}
+
+impl<C> Drop for Sender<C> {
+    fn drop(&mut self) {
+        let mut inner = self.0.lock();
+        inner.closed = true;
+        self.0.notify(inner);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::Wake;
+    use std::thread;
+
+    use super::*;
+    use crate::headless::HeadlessBackend;
+    use crate::{ActivationSource, Menu, MenuItem, Modifiers, Submenu};
+
+    fn menu() -> Menu<&'static str> {
+        Menu::default().with(Submenu::new(
+            "File",
+            Menu::default()
+                .with(MenuItem::new("New").with_id("new").with_command("new"))
+                .with(MenuItem::new("Open").with_command("open"))
+                .with(MenuItem::new("About").with_id("about")),
+        ))
+    }
+
+    fn commands(events: &Events<&'static str>) -> Vec<Option<&'static str>> {
+        core::iter::from_fn(|| events.try_recv())
+            .map(MenuEvent::into_command)
+            .collect()
+    }
+
+    /// A waker that counts how often it's woken.
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_events() {
+        let backend = HeadlessBackend::new();
+        let attached = backend.attach(menu()).unwrap();
+        let events = attached.events(8, Overflow::DropNewest);
+
+        let new = attached.item("new").unwrap();
+        assert!(backend.click_with_modifiers(new.token(), Modifiers::SHIFT));
+        assert!(backend.click_path(&["File", "About"]));
+
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.id(), Some(&ItemId::from("new")));
+        assert_eq!(event.command(), Some(&"new"));
+        assert_eq!(event.activation().source(), ActivationSource::Click);
+        assert_eq!(event.activation().modifiers(), Modifiers::SHIFT);
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.id(), Some(&ItemId::from("about")));
+        assert_eq!(event.command(), None);
+        assert_eq!(events.try_recv(), None);
+
+        // Commands go to the receiver instead of the queue.
+        assert!(attached.take_commands().is_empty());
+    }
+
+    #[test]
+    fn test_overflow() {
+        let backend = HeadlessBackend::new();
+        let attached = backend.attach(menu()).unwrap();
+        let newest = attached.events(2, Overflow::DropNewest);
+        let oldest = attached.events(2, Overflow::DropOldest);
+
+        for path in [["File", "New"], ["File", "Open"], ["File", "About"]] {
+            assert!(backend.click_path(&path));
+        }
+        assert_eq!(commands(&newest), [Some("new"), Some("open")]);
+        assert_eq!(commands(&oldest), [Some("open"), None]);
+        assert_eq!(newest.dropped(), 1);
+        assert_eq!(oldest.dropped(), 1);
+    }
+
+    #[test]
+    fn test_dropped_receiver() {
+        let backend = HeadlessBackend::new();
+        let attached = backend.attach(menu()).unwrap();
+        let events = attached.events(1, Overflow::DropNewest);
+        drop(events);
+
+        // Commands are queued again once every receiver is gone.
+        assert!(backend.click_path(&["File", "New"]));
+        let events = attached.events(1, Overflow::DropNewest);
+        assert!(backend.click_path(&["File", "Open"]));
+        assert_eq!(attached.take_commands(), ["new"]);
+        assert_eq!(commands(&events), [Some("open")]);
+    }
+
+    #[test]
+    fn test_stream() {
+        let backend = HeadlessBackend::new();
+        let attached = backend.attach(menu()).unwrap();
+        let mut events = attached.events(4, Overflow::DropOldest);
+
+        let counter = Arc::new(CountingWaker::default());
+        let waker = Waker::from(Arc::clone(&counter));
+        let mut cx = Context::from_waker(&waker);
+        let mut poll = || Pin::new(&mut events).poll_next(&mut cx);
+
+        assert_eq!(poll(), Poll::Pending);
+        assert!(backend.click_path(&["File", "New"]));
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+        let event = match poll() {
+            Poll::Ready(Some(event)) => event,
+            other => panic!("expected an event, got {:?}", other),
+        };
+        assert_eq!(event.into_command(), Some("new"));
+        assert_eq!(poll(), Poll::Pending);
+
+        // The stream ends when the menu is dropped.
+        drop(attached);
+        assert_eq!(counter.0.load(Ordering::SeqCst), 2);
+        assert_eq!(poll(), Poll::Ready(None));
+    }
+
+    #[test]
+    fn test_recv_on_other_thread() {
+        let backend = HeadlessBackend::new();
+        let attached = backend.attach(menu()).unwrap();
+        let events = attached.events(16, Overflow::DropNewest);
+
+        let receiver = thread::spawn(move || {
+            let mut received = Vec::new();
+            while let Some(event) = events.recv() {
+                received.push(event.into_command());
+            }
+            received
+        });
+        assert!(backend.click_path(&["File", "New"]));
+        assert!(backend.click_path(&["File", "About"]));
+        drop(attached);
+
+        assert_eq!(receiver.join().unwrap(), [Some("new"), None]);
+    }
+
+    #[test]
+    fn test_recv_timeout() {
+        let backend = HeadlessBackend::new();
+        let attached = backend.attach(menu()).unwrap();
+        let events = attached.events(1, Overflow::DropNewest);
+
+        assert_eq!(events.recv_timeout(Duration::from_millis(10)), None);
+        assert!(!events.is_closed());
+        assert!(backend.click_path(&["File", "Open"]));
+        drop(attached);
+        assert!(events.is_closed());
+        let event = events.recv_timeout(Duration::from_millis(10)).unwrap();
+        assert_eq!(event.into_command(), Some("open"));
+        assert_eq!(events.recv(), None);
+    }
+
+    #[test]
+    #[should_panic = "the capacity must be at least 1"]
+    fn test_zero_capacity() {
+        let backend = HeadlessBackend::new();
+        let attached = backend.attach(menu()).unwrap();
+        let _ = attached.events(0, Overflow::DropNewest);
+    }
+}
@@ -0,0 +1,1460 @@
+//! Menus that have been realized through a backend.
+
+use core::fmt;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::rc::{Rc, Weak};
+use std::sync::Arc;
+
+use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+
+use crate::backend::{
+    self, Activation, ActivationSource, Activator, Backend, ItemKind, ItemProperty, ItemProps,
+    ItemToken, MenuKind, MenuToken,
+};
+use crate::headless::NoWindow;
+use crate::model::Handler;
+use crate::panic::{self, PanicPolicy};
+use crate::{Accelerator, Error, ItemId, Menu, MenuEntry, MenuItem, Role, Separator, Submenu};
+
+mod context;
+#[cfg(feature = "events")]
+mod events;
+mod reconcile;
+mod transaction;
+
+pub use self::context::{ActivationContext, HandlerContext};
+#[cfg(feature = "events")]
+pub use self::events::{Events, MenuEvent, Overflow};
+pub use self::reconcile::update;
+pub use self::transaction::Transaction;
+
+/// Attach a menu to a window, using the first backend that supports it.
+///
+/// Backends registered with [`register_backend`][crate::register_backend]
+/// are tried before the built-in ones. On Linux, the built-in backend exports
+/// the menu over D-Bus, and its activations have to be dispatched with
+/// [`AttachedMenu::dispatch`].
+pub fn attach<C: Clone + 'static>(
+    menu: Menu<C>,
+    window: &impl HasRawWindowHandle,
+) -> Result<AttachedMenu<C>, Error> {
+    let handle = window.raw_window_handle();
+    for backend in backend::candidates() {
+        if backend.supports(handle) {
+            return attach_with(menu, window, backend);
+        }
+    }
+    Err(Error::unexpected_window_type())
+}
+
+/// Attach a menu to a window, using the given backend.
+pub fn attach_with<C: Clone + 'static>(
+    menu: Menu<C>,
+    window: &impl HasRawWindowHandle,
+    backend: impl Backend + 'static,
+) -> Result<AttachedMenu<C>, Error> {
+    let handle = window.raw_window_handle();
+    if !backend.supports(handle) {
+        return Err(Error::unexpected_window_type());
+    }
+
+    let state = Rc::new(RefCell::new(State {
+        backend: Box::new(backend),
+        window: Some(handle).filter(|handle| !NoWindow::is(*handle)),
+        root: MenuToken(0),
+        menus: HashMap::new(),
+        items: HashMap::new(),
+        ids: HashMap::new(),
+        handlers: HashMap::new(),
+        cleared: HashSet::new(),
+        commands: HashMap::new(),
+        dispatcher: None,
+        queue: VecDeque::new(),
+        #[cfg(feature = "events")]
+        senders: Vec::new(),
+        next_menu: 0,
+        next_item: 0,
+    }));
+
+    let weak = Rc::downgrade(&state);
+    let activator = Activator::new(move |item, activation| dispatch(&weak, item, activation));
+
+    let root = {
+        let mut state = state.borrow_mut();
+        // Creating the bar fails if the backend already has a menu attached,
+        // so this must come before anything that would replace its state.
+        let root = state.create_menu(MenuKind::Bar)?;
+        state.root = root;
+        state.backend.set_activator(activator);
+        let attached = menu
+            .into_entries()
+            .into_iter()
+            .enumerate()
+            .try_for_each(|(index, entry)| state.insert(root, index, entry).map(drop))
+            .and_then(|()| state.backend.attach(root, handle));
+        if let Err(err) = attached {
+            // Backends can outlive the menu, so don't leave it half-built.
+            state.backend.detach(root);
+            return Err(err);
+        }
+        root
+    };
+
+    Ok(AttachedMenu { state, root })
+}
+
+/// A menu that is attached to a window.
+///
+/// When an item is activated, its handler is called, and then its command,
+/// if any, is delivered. Commands go to the dispatcher set with
+/// [`set_dispatcher`][Self::set_dispatcher], or are queued until they are
+/// taken with [`take_commands`][Self::take_commands] if there is none.
+///
+/// Items can be referred to after attaching through an [`ItemHandle`],
+/// obtained from their id with [`item`][Self::item]. Entries are inserted by
+/// value, and [`ItemHandle::detach`] gives them back, so an entry is always
+/// in exactly one place: in a model, or in a single attached menu.
+///
+/// The menu is detached from the window when this is dropped, with
+/// [`Backend::detach`]. Native menus owned by the backend are destroyed
+/// along with it, once the last clone of the backend is dropped.
+pub struct AttachedMenu<C = ()> {
+    state: Rc<RefCell<State<C>>>,
+    root: MenuToken,
+}
+
+impl<C> AttachedMenu<C> {
+    /// The token of the top-level menu.
+    pub fn root(&self) -> MenuToken {
+        self.root
+    }
+
+    /// A handle to the item or submenu with the given id.
+    ///
+    /// Returns `None` if no entry in the menu has the id.
+    pub fn item(&self, id: &str) -> Option<ItemHandle<C>> {
+        let state = self.state.borrow();
+        let item = *state.ids.get(id)?;
+        Some(state.handle(&Rc::downgrade(&self.state), item))
+    }
+
+    /// Handles to the top-level entries, including separators.
+    pub fn items(&self) -> Vec<ItemHandle<C>> {
+        let state = self.state.borrow();
+        state.handles(&Rc::downgrade(&self.state), self.root)
+    }
+
+    /// Insert an entry at the given top-level index.
+    ///
+    /// Fails if the index is larger than the number of top-level entries.
+    pub fn insert(
+        &self,
+        index: usize,
+        entry: impl Into<MenuEntry<C>>,
+    ) -> Result<ItemHandle<C>, Error> {
+        let mut state = self.state.borrow_mut();
+        let item = state.insert(self.root, index, entry.into())?;
+        Ok(state.handle(&Rc::downgrade(&self.state), item))
+    }
+
+    /// Add an entry to the end of the top-level menu.
+    pub fn push(&self, entry: impl Into<MenuEntry<C>>) -> Result<ItemHandle<C>, Error> {
+        let index = self.state.borrow().menus[&self.root].len();
+        self.insert(index, entry)
+    }
+
+    /// Remove the entry with the given id, and everything in it.
+    pub fn remove(&self, id: &str) -> Result<(), Error> {
+        match self.item(id) {
+            Some(handle) => handle.remove(),
+            None => Err(Error::unknown_id(id)),
+        }
+    }
+
+    /// Remove the top-level entry at the given index.
+    ///
+    /// Fails if the index is out of bounds.
+    pub fn remove_at(&self, index: usize) -> Result<(), Error> {
+        let mut state = self.state.borrow_mut();
+        let item = state.item_at(self.root, index)?;
+        state.remove(item)
+    }
+
+    /// Deliver commands to the given function from now on.
+    ///
+    /// Commands that were queued before this is called are delivered
+    /// immediately, in order.
+    pub fn set_dispatcher(&self, dispatcher: impl FnMut(C) + 'static) {
+        self.replace_dispatcher(Dispatcher::Plain(Box::new(dispatcher)));
+    }
+
+    /// Deliver commands to the given function from now on, along with how
+    /// and where their item was activated.
+    pub fn set_context_dispatcher(&self, dispatcher: impl FnMut(C, &ActivationContext) + 'static) {
+        self.replace_dispatcher(Dispatcher::Context(Box::new(dispatcher)));
+    }
+
+    fn replace_dispatcher(&self, mut dispatcher: Dispatcher<C>) {
+        let queued = core::mem::take(&mut self.state.borrow_mut().queue);
+        for (command, activation) in queued {
+            dispatcher.call(command, &activation);
+        }
+        self.state.borrow_mut().dispatcher = Some(dispatcher);
+    }
+
+    /// Take the commands that have been queued, oldest first.
+    ///
+    /// Commands are only queued while no dispatcher is set, and nothing
+    /// receives events.
+    pub fn take_commands(&self) -> Vec<C> {
+        let mut state = self.state.borrow_mut();
+        state.queue.drain(..).map(|(command, _)| command).collect()
+    }
+}
+
+impl<C: Clone> AttachedMenu<C> {
+    /// Set the function to call when the backend queued activations, so
+    /// that the event loop can wake up and call [`dispatch`][Self::dispatch].
+    ///
+    /// Only backends that receive activations on another thread, like the
+    /// D-Bus ones, queue them. The function is called on that thread.
+    pub fn set_wakeup(&self, wakeup: impl Fn() + Send + Sync + 'static) {
+        self.state.borrow_mut().backend.set_wakeup(Arc::new(wakeup));
+    }
+
+    /// Call the handlers of the items whose activations the backend queued.
+    ///
+    /// Returns the number of activations.
+    pub fn dispatch(&self) -> usize {
+        let activations = self.state.borrow_mut().backend.take_activations();
+        let weak = Rc::downgrade(&self.state);
+        for (item, activation) in &activations {
+            dispatch(&weak, *item, *activation);
+        }
+        activations.len()
+    }
+}
+
+impl<C> Drop for AttachedMenu<C> {
+    fn drop(&mut self) {
+        // The state is only borrowed here if a handler drops the menu it
+        // belongs to, and then the backend is dropped right after.
+        if let Ok(mut state) = self.state.try_borrow_mut() {
+            state.backend.detach(self.root);
+        }
+    }
+}
+
+impl<C> fmt::Debug for AttachedMenu<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AttachedMenu")
+            .field("root", &self.root)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A reference to an item or submenu in an [`AttachedMenu`].
+///
+/// Handles are cheap to clone, and don't keep the menu alive. Once the item
+/// has been removed, or the menu has been dropped, every operation fails with
+/// an error for which [`Error::is_item_removed`] returns `true`.
+pub struct ItemHandle<C = ()> {
+    state: Weak<RefCell<State<C>>>,
+    item: ItemToken,
+    id: Option<ItemId>,
+}
+
+impl<C> ItemHandle<C> {
+    /// The token that the backend knows the item by.
+    pub fn token(&self) -> ItemToken {
+        self.item
+    }
+
+    /// The id of the item.
+    pub fn id(&self) -> Option<&ItemId> {
+        self.id.as_ref()
+    }
+
+    /// Whether the item is still in the menu.
+    pub fn exists(&self) -> bool {
+        self.with(|_, _| Ok(())).is_ok()
+    }
+
+    pub fn title(&self) -> Result<String, Error> {
+        self.with(|_, live| Ok(live.props.title.clone()))
+    }
+
+    pub fn accelerator(&self) -> Result<Option<Accelerator>, Error> {
+        self.with(|_, live| Ok(live.props.accelerator))
+    }
+
+    pub fn enabled(&self) -> Result<bool, Error> {
+        self.with(|_, live| Ok(live.props.enabled))
+    }
+
+    /// `None` if the item is not checkable.
+    pub fn checked(&self) -> Result<Option<bool>, Error> {
+        self.with(|_, live| Ok(live.props.checked))
+    }
+
+    pub fn radio_group(&self) -> Result<Option<String>, Error> {
+        self.with(|_, live| Ok(live.props.radio_group.clone()))
+    }
+
+    pub fn hidden(&self) -> Result<bool, Error> {
+        self.with(|_, live| Ok(live.props.hidden))
+    }
+
+    /// Change the title of the item or submenu.
+    pub fn set_title(&self, title: impl Into<String>) -> Result<(), Error> {
+        self.update(ItemProperty::Title(title.into()))
+    }
+
+    /// Change the accelerator of the item.
+    ///
+    /// Fails on submenus and separators.
+    pub fn set_accelerator(&self, accelerator: Option<Accelerator>) -> Result<(), Error> {
+        self.update(ItemProperty::Accelerator(accelerator))
+    }
+
+    pub fn set_enabled(&self, enabled: bool) -> Result<(), Error> {
+        self.update(ItemProperty::Enabled(enabled))
+    }
+
+    /// Change the state of the checkmark, or make the item uncheckable with
+    /// `None`.
+    ///
+    /// Fails on submenus and separators.
+    pub fn set_checked(&self, checked: Option<bool>) -> Result<(), Error> {
+        self.update(ItemProperty::Checked(checked))
+    }
+
+    /// Change the radio group of the item, see
+    /// [`MenuItem::set_radio_group`][crate::MenuItem::set_radio_group].
+    ///
+    /// Fails on submenus and separators.
+    pub fn set_radio_group(&self, group: Option<String>) -> Result<(), Error> {
+        self.update(ItemProperty::RadioGroup(group))
+    }
+
+    pub fn set_hidden(&self, hidden: bool) -> Result<(), Error> {
+        self.update(ItemProperty::Hidden(hidden))
+    }
+
+    /// Change a property of the native item in place.
+    ///
+    /// Nothing is sent to the backend if the value doesn't change.
+    pub fn update(&self, property: ItemProperty) -> Result<(), Error> {
+        self.with(|state, _| state.update(self.item, property))
+    }
+
+    /// Remove the item from its menu.
+    ///
+    /// If the item is a submenu, everything in it is removed as well.
+    pub fn remove(&self) -> Result<(), Error> {
+        self.with(|state, _| state.remove(self.item))
+    }
+
+    /// Remove the item from its menu, and return it as a model entry.
+    ///
+    /// The entry keeps its handler and command, and everything in it if it's
+    /// a submenu, so it can be inserted again, in this menu or another one.
+    /// Handles to the removed items are not valid for the new copy.
+    pub fn detach(&self) -> Result<MenuEntry<C>, Error> {
+        self.with(|state, _| state.detach(self.item))
+    }
+
+    /// The submenu that the item is in, or `None` at the top level.
+    pub fn parent(&self) -> Result<Option<ItemHandle<C>>, Error> {
+        self.with(|state, live| {
+            Ok(state
+                .parent(live.menu)
+                .map(|parent| state.handle(&self.state, parent)))
+        })
+    }
+
+    /// The position of the item in its menu, counting all items.
+    pub fn index(&self) -> Result<usize, Error> {
+        self.with(|state, live| Ok(state.index(live.menu, self.item)))
+    }
+
+    /// Handles to the entries in the submenu, including separators.
+    ///
+    /// Fails if the item is not a submenu.
+    pub fn items(&self) -> Result<Vec<ItemHandle<C>>, Error> {
+        self.with(|state, live| Ok(state.handles(&self.state, submenu(live)?)))
+    }
+
+    /// Insert an entry into the submenu at the given index.
+    ///
+    /// Fails if the item is not a submenu, or if the index is larger than the
+    /// number of entries in it.
+    pub fn insert(
+        &self,
+        index: usize,
+        entry: impl Into<MenuEntry<C>>,
+    ) -> Result<ItemHandle<C>, Error> {
+        self.with(|state, live| {
+            let item = state.insert(submenu(live)?, index, entry.into())?;
+            Ok(state.handle(&self.state, item))
+        })
+    }
+
+    /// Add an entry to the end of the submenu.
+    pub fn push(&self, entry: impl Into<MenuEntry<C>>) -> Result<ItemHandle<C>, Error> {
+        let index = self.with(|state, live| Ok(state.menus[&submenu(live)?].len()))?;
+        self.insert(index, entry)
+    }
+
+    /// Remove the entry at the given index from the submenu.
+    ///
+    /// Fails if the item is not a submenu, or if the index is out of bounds.
+    pub fn remove_at(&self, index: usize) -> Result<(), Error> {
+        self.with(|state, live| {
+            let item = state.item_at(submenu(live)?, index)?;
+            state.remove(item)
+        })
+    }
+
+    /// Move the item to another position in the same menu.
+    ///
+    /// Fails if the index is out of bounds.
+    pub fn move_to(&self, index: usize) -> Result<(), Error> {
+        self.with(|state, live| state.move_item(self.item, live.menu, index))
+    }
+
+    /// Move the item into the given submenu, or to the top level if `None`,
+    /// at the given index.
+    ///
+    /// The item keeps its handle, and everything in it if it's a submenu.
+    /// Fails if the parent is not a submenu in the same menu, or if the item
+    /// would end up inside itself, or if the index is larger than the number
+    /// of entries in the parent.
+    pub fn move_into(&self, parent: Option<&ItemHandle<C>>, index: usize) -> Result<(), Error> {
+        if let Some(parent) = parent {
+            if !Weak::ptr_eq(&self.state, &parent.state) {
+                return Err(Error::invalid_parent("the parent is in a different menu"));
+            }
+        }
+        self.with(|state, _| {
+            let menu = match parent {
+                Some(parent) => match state.items.get(&parent.item) {
+                    Some(live) => submenu(live)?,
+                    None => return Err(Error::item_removed(parent.id.clone())),
+                },
+                None => state.root,
+            };
+            state.move_item(self.item, menu, index)
+        })
+    }
+
+    fn with<T>(
+        &self,
+        f: impl FnOnce(&mut State<C>, &Live) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let state = self
+            .state
+            .upgrade()
+            .ok_or_else(|| Error::item_removed(self.id.clone()))?;
+        let mut state = state.borrow_mut();
+        let live = match state.items.get(&self.item) {
+            Some(live) => live.clone(),
+            None => return Err(Error::item_removed(self.id.clone())),
+        };
+        f(&mut state, &live)
+    }
+}
+
+impl<C: Clone> ItemHandle<C> {
+    /// Activate the item, as if the user chose it.
+    ///
+    /// The handler is called and the command is delivered before this
+    /// returns, with [`ActivationSource::Programmatic`]. Fails on submenus
+    /// and separators.
+    pub fn activate(&self) -> Result<(), Error> {
+        self.with(|_, live| match live.props.kind {
+            ItemKind::Item => Ok(()),
+            ItemKind::Submenu(_) => Err(Error::unsupported("activation", "submenus")),
+            ItemKind::Separator => Err(Error::unsupported("activation", "separators")),
+        })?;
+        let activation = Activation::new(ActivationSource::Programmatic);
+        dispatch(&self.state, self.item, activation);
+        Ok(())
+    }
+}
+
+impl<C> Clone for ItemHandle<C> {
+    fn clone(&self) -> Self {
+        Self {
+            state: Weak::clone(&self.state),
+            item: self.item,
+            id: self.id.clone(),
+        }
+    }
+}
+
+impl<C> PartialEq for ItemHandle<C> {
+    fn eq(&self, other: &Self) -> bool {
+        Weak::ptr_eq(&self.state, &other.state) && self.item == other.item
+    }
+}
+
+impl<C> Eq for ItemHandle<C> {}
+
+impl<C> fmt::Debug for ItemHandle<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ItemHandle")
+            .field("item", &self.item)
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The menu opened by a submenu item.
+fn submenu(live: &Live) -> Result<MenuToken, Error> {
+    match live.props.kind {
+        ItemKind::Submenu(menu) => Ok(menu),
+        _ => Err(Error::invalid_parent("the parent is not a submenu")),
+    }
+}
+
+/// An item that has been inserted through the backend.
+#[derive(Clone)]
+struct Live {
+    id: Option<ItemId>,
+    role: Option<Role>,
+    /// The menu the item is in.
+    menu: MenuToken,
+    props: ItemProps,
+}
+
+/// Where commands are delivered.
+enum Dispatcher<C> {
+    Plain(Box<dyn FnMut(C)>),
+    Context(ContextDispatcher<C>),
+}
+
+type ContextDispatcher<C> = Box<dyn FnMut(C, &ActivationContext)>;
+
+impl<C> Dispatcher<C> {
+    fn call(&mut self, command: C, activation: &ActivationContext) {
+        match self {
+            Self::Plain(dispatcher) => dispatcher(command),
+            Self::Context(dispatcher) => dispatcher(command, activation),
+        }
+    }
+}
+
+struct State<C> {
+    backend: Box<dyn Backend>,
+    /// The window the menu is attached to, or `None` if it was attached
+    /// without one, like the menu of a tray icon.
+    window: Option<RawWindowHandle>,
+    /// The top-level menu.
+    root: MenuToken,
+    /// The items in each menu, in order.
+    menus: HashMap<MenuToken, Vec<ItemToken>>,
+    items: HashMap<ItemToken, Live>,
+    ids: HashMap<ItemId, ItemToken>,
+    handlers: HashMap<ItemToken, Handler<C>>,
+    /// Items whose handler was removed, so that a handler that is running at
+    /// the time isn't put back once it returns.
+    cleared: HashSet<ItemToken>,
+    commands: HashMap<ItemToken, C>,
+    dispatcher: Option<Dispatcher<C>>,
+    /// Commands that were activated while there was no dispatcher.
+    queue: VecDeque<(C, ActivationContext)>,
+    /// Where activations are sent as events.
+    #[cfg(feature = "events")]
+    senders: Vec<events::Sender<C>>,
+    next_menu: u32,
+    next_item: u32,
+}
+
+impl<C> State<C> {
+    fn create_menu(&mut self, kind: MenuKind) -> Result<MenuToken, Error> {
+        let menu = MenuToken(self.next_menu);
+        self.next_menu = self.next_menu.checked_add(1).expect("too many menus");
+        self.backend.create_menu(menu, kind)?;
+        self.menus.insert(menu, Vec::new());
+        Ok(menu)
+    }
+
+    fn next_item(&mut self) -> ItemToken {
+        let item = ItemToken(self.next_item);
+        self.next_item = self.next_item.checked_add(1).expect("too many items");
+        item
+    }
+
+    fn handle(&self, state: &Weak<RefCell<Self>>, item: ItemToken) -> ItemHandle<C> {
+        ItemHandle {
+            state: Weak::clone(state),
+            item,
+            id: self.items[&item].id.clone(),
+        }
+    }
+
+    fn handles(&self, state: &Weak<RefCell<Self>>, menu: MenuToken) -> Vec<ItemHandle<C>> {
+        self.menus[&menu]
+            .iter()
+            .map(|item| self.handle(state, *item))
+            .collect()
+    }
+
+    /// The item that opens the given menu.
+    fn parent(&self, menu: MenuToken) -> Option<ItemToken> {
+        self.items
+            .iter()
+            .find(|(_, live)| live.props.kind == ItemKind::Submenu(menu))
+            .map(|(item, _)| *item)
+    }
+
+    /// The item at the given index of a menu.
+    fn item_at(&self, menu: MenuToken, index: usize) -> Result<ItemToken, Error> {
+        let items = &self.menus[&menu];
+        items
+            .get(index)
+            .copied()
+            .ok_or_else(|| Error::index_out_of_bounds(index, items.len()))
+    }
+
+    fn index(&self, menu: MenuToken, item: ItemToken) -> usize {
+        self.menus[&menu]
+            .iter()
+            .position(|other| *other == item)
+            .expect("item is in its menu")
+    }
+
+    /// Insert an entry of the model into the given menu.
+    ///
+    /// Ids are checked before anything is inserted.
+    fn insert(
+        &mut self,
+        menu: MenuToken,
+        index: usize,
+        entry: MenuEntry<C>,
+    ) -> Result<ItemToken, Error> {
+        let len = self.menus[&menu].len();
+        if index > len {
+            return Err(Error::index_out_of_bounds(index, len));
+        }
+
+        let mut ids = Vec::new();
+        collect_ids(&entry, &mut ids);
+        for (i, id) in ids.iter().enumerate() {
+            if self.ids.contains_key(*id) || ids[..i].contains(id) {
+                return Err(Error::duplicate_id((*id).clone()));
+            }
+        }
+        self.realize(menu, index, entry)
+    }
+
+    /// Insert an entry of the model, and everything in it.
+    ///
+    /// If any part fails, whatever was inserted already is removed again.
+    fn realize(
+        &mut self,
+        menu: MenuToken,
+        index: usize,
+        entry: MenuEntry<C>,
+    ) -> Result<ItemToken, Error> {
+        let item = self.next_item();
+        if let Err(err) = self.realize_as(item, menu, index, entry) {
+            if self.items.contains_key(&item) {
+                // The error that got us here is the one worth reporting.
+                let _ = self.backend.remove_item(item);
+                self.forget(item);
+            }
+            return Err(err);
+        }
+        Ok(item)
+    }
+
+    fn realize_as(
+        &mut self,
+        item: ItemToken,
+        menu: MenuToken,
+        index: usize,
+        mut entry: MenuEntry<C>,
+    ) -> Result<(), Error> {
+        let submenu = match &entry {
+            MenuEntry::Submenu(_) => Some(self.create_menu(MenuKind::Submenu)?),
+            _ => None,
+        };
+
+        let props = ItemProps::from_entry(&entry, submenu);
+        if let Err(err) = self.backend.insert_item(menu, index, item, &props) {
+            // Nothing refers to the submenu, so removing can't destroy it.
+            if let Some(submenu) = submenu {
+                self.backend.destroy_menu(submenu);
+                self.menus.remove(&submenu);
+            }
+            return Err(err);
+        }
+        self.menus.get_mut(&menu).unwrap().insert(index, item);
+        let id = entry.id().cloned();
+        if let Some(id) = &id {
+            self.ids.insert(id.clone(), item);
+        }
+        let role = entry.as_item().and_then(MenuItem::role);
+        self.items.insert(
+            item,
+            Live {
+                id,
+                role,
+                menu,
+                props,
+            },
+        );
+
+        match entry {
+            MenuEntry::Item(ref mut model) => {
+                if let Some(handler) = model.take_handler() {
+                    self.handlers.insert(item, handler);
+                }
+                if let Some(command) = model.take_command() {
+                    self.commands.insert(item, command);
+                }
+            }
+            MenuEntry::Submenu(model) => {
+                let submenu = submenu.unwrap();
+                for (index, entry) in model.into_menu().into_entries().into_iter().enumerate() {
+                    let child = self.next_item();
+                    self.realize_as(child, submenu, index, entry)?;
+                }
+            }
+            MenuEntry::Separator(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Change a property of an item through the backend.
+    fn update(&mut self, item: ItemToken, property: ItemProperty) -> Result<(), Error> {
+        let live = self.items.get_mut(&item).expect("unknown item token");
+        let mut props = live.props.clone();
+        props.apply(&property);
+        if props == live.props {
+            return Ok(());
+        }
+        check_supported(&live.props.kind, &property)?;
+        self.backend.update_item(item, &property)?;
+        self.items.get_mut(&item).unwrap().props = props;
+        Ok(())
+    }
+
+    /// Move an item through the backend.
+    fn move_item(&mut self, item: ItemToken, menu: MenuToken, index: usize) -> Result<(), Error> {
+        let old = self.items[&item].menu;
+        let len = self.menus[&menu].len() - usize::from(old == menu);
+        if index > len {
+            return Err(Error::index_out_of_bounds(index, len));
+        }
+
+        // Don't move a submenu into itself.
+        let mut ancestor = Some(menu);
+        while let Some(current) = ancestor {
+            if self.items[&item].props.kind == ItemKind::Submenu(current) {
+                return Err(Error::invalid_parent(
+                    "a submenu can't be moved into itself",
+                ));
+            }
+            ancestor = self.parent(current).map(|parent| self.items[&parent].menu);
+        }
+
+        self.backend.move_item(item, menu, index)?;
+        self.menus.get_mut(&old).unwrap().retain(|i| *i != item);
+        self.menus.get_mut(&menu).unwrap().insert(index, item);
+        self.items.get_mut(&item).unwrap().menu = menu;
+        Ok(())
+    }
+
+    /// Remove an item through the backend, and forget about it.
+    fn remove(&mut self, item: ItemToken) -> Result<(), Error> {
+        self.backend.remove_item(item)?;
+        self.forget(item);
+        Ok(())
+    }
+
+    /// Remove an item through the backend, and turn it back into a model.
+    fn detach(&mut self, item: ItemToken) -> Result<MenuEntry<C>, Error> {
+        self.backend.remove_item(item)?;
+        let entry = self.unrealize(item);
+        self.forget(item);
+        Ok(entry)
+    }
+
+    /// Recreate the model of an item, taking its handler and command.
+    fn unrealize(&mut self, item: ItemToken) -> MenuEntry<C> {
+        let live = self.items[&item].clone();
+        let props = live.props;
+        let mut entry: MenuEntry<C> = match props.kind {
+            ItemKind::Separator => Separator.into(),
+            ItemKind::Item => {
+                let mut model = MenuItem::new(props.title)
+                    .with_enabled(props.enabled)
+                    .with_hidden(props.hidden);
+                model.set_accelerator(props.accelerator);
+                model.set_checked(props.checked);
+                model.set_radio_group(props.radio_group);
+                model.set_role(live.role);
+                if let Some(handler) = self.handlers.remove(&item) {
+                    model.restore_handler(handler);
+                }
+                model.set_command(self.commands.remove(&item));
+                model.into()
+            }
+            ItemKind::Submenu(submenu) => {
+                let mut menu = Menu::default();
+                for child in self.menus[&submenu].clone() {
+                    menu.push(self.unrealize(child));
+                }
+                Submenu::new(props.title, menu)
+                    .with_enabled(props.enabled)
+                    .with_hidden(props.hidden)
+                    .into()
+            }
+        };
+        match &mut entry {
+            MenuEntry::Item(model) => model.set_id(live.id),
+            MenuEntry::Submenu(model) => model.set_id(live.id),
+            MenuEntry::Separator(_) => {}
+        }
+        entry
+    }
+
+    /// Forget an item, and everything in it if it's a submenu.
+    fn forget(&mut self, item: ItemToken) {
+        let live = match self.items.remove(&item) {
+            Some(live) => live,
+            None => return,
+        };
+        if let Some(menu) = self.menus.get_mut(&live.menu) {
+            menu.retain(|i| *i != item);
+        }
+        if let Some(id) = &live.id {
+            self.ids.remove(id);
+        }
+        self.handlers.remove(&item);
+        self.cleared.remove(&item);
+        self.commands.remove(&item);
+
+        if let ItemKind::Submenu(submenu) = live.props.kind {
+            for child in self.menus.remove(&submenu).unwrap_or_default() {
+                self.forget(child);
+            }
+        }
+    }
+}
+
+fn collect_ids<'a, C>(entry: &'a MenuEntry<C>, ids: &mut Vec<&'a ItemId>) {
+    ids.extend(entry.id());
+    if let MenuEntry::Submenu(submenu) = entry {
+        for entry in submenu.menu().entries() {
+            collect_ids(entry, ids);
+        }
+    }
+}
+
+/// Check that the property makes sense for the kind of item.
+fn check_supported(kind: &ItemKind, property: &ItemProperty) -> Result<(), Error> {
+    let name = match (kind, property) {
+        (ItemKind::Item, _) => return Ok(()),
+        (_, ItemProperty::Enabled(_) | ItemProperty::Hidden(_)) => return Ok(()),
+        (ItemKind::Submenu(_), ItemProperty::Title(_)) => return Ok(()),
+        (_, ItemProperty::Title(_)) => "title",
+        (_, ItemProperty::Accelerator(_)) => "accelerator",
+        (_, ItemProperty::Checked(_)) => "checkmark",
+        (_, ItemProperty::RadioGroup(_)) => "radio group",
+    };
+    let kind = match kind {
+        ItemKind::Submenu(_) => "submenus",
+        _ => "separators",
+    };
+    Err(Error::unsupported(name, kind))
+}
+
+/// Call the handler of the given item, and deliver its command.
+///
+/// The handler and dispatcher are taken out of the state while they run, so
+/// that they can access the menu without running into a borrowing error.
+/// The command is taken before the handler runs, so that it's delivered even
+/// if the handler removes the item.
+///
+/// Panics are handled according to the [`PanicPolicy`]. If the handler
+/// panics, its queued changes are dropped and the command isn't delivered.
+/// Queued changes that fail are reported with [`panic::report_error`].
+fn dispatch<C: Clone>(weak: &Weak<RefCell<State<C>>>, item: ItemToken, activation: Activation) {
+    let state = match weak.upgrade() {
+        Some(state) => state,
+        // The menu was dropped.
+        None => return,
+    };
+
+    let (handle, activation, handler, command) = match state.try_borrow_mut() {
+        Ok(mut state) => {
+            let id = match state.items.get(&item) {
+                Some(live) => live.id.clone(),
+                None => return,
+            };
+            let activation = ActivationContext::new(
+                id,
+                activation.source,
+                activation.modifiers,
+                activation.window.or(state.window),
+            );
+            let handler = state.handlers.remove(&item);
+            if handler.is_some() {
+                state.cleared.remove(&item);
+            }
+            let command = state.commands.get(&item).cloned();
+            (state.handle(weak, item), activation, handler, command)
+        }
+        // Activated while the backend was being called, ignore it.
+        Err(_) => return,
+    };
+
+    if let Some(mut handler) = handler {
+        let mut context =
+            HandlerContext::new(Weak::clone(weak), handle.clone(), activation.clone());
+        let res = panic::guard(handle.id(), || handler.call(&mut context));
+        {
+            let mut state = state.borrow_mut();
+            // Unless the item was removed while the handler ran, or given
+            // another handler or none at all.
+            let cleared = state.cleared.remove(&item);
+            if state.items.contains_key(&item) && !cleared {
+                state.handlers.entry(item).or_insert(handler);
+            }
+        }
+        if let Err(policy) = res {
+            return caught(&state, item, policy);
+        }
+        for error in context.apply() {
+            panic::report_error(&error);
+        }
+    }
+
+    #[cfg(feature = "events")]
+    let receiving = {
+        let mut state = state.borrow_mut();
+        state.senders.retain(|sender| !sender.is_disconnected());
+        for sender in &state.senders {
+            sender.send(command.clone(), activation.clone());
+        }
+        !state.senders.is_empty()
+    };
+    #[cfg(not(feature = "events"))]
+    let receiving = false;
+
+    let command = match command {
+        Some(command) => command,
+        None => return,
+    };
+    let mut dispatcher = {
+        let mut state = state.borrow_mut();
+        match state.dispatcher.take() {
+            Some(dispatcher) => dispatcher,
+            None => {
+                if !receiving {
+                    state.queue.push_back((command, activation));
+                }
+                return;
+            }
+        }
+    };
+
+    let res = panic::guard(handle.id(), || dispatcher.call(command, &activation));
+    let mut state_ref = state.borrow_mut();
+    // Unless the dispatcher was replaced while it ran.
+    if state_ref.dispatcher.is_none() {
+        state_ref.dispatcher = Some(dispatcher);
+    }
+    drop(state_ref);
+    if let Err(policy) = res {
+        caught(&state, item, policy);
+    }
+}
+
+/// Apply the policy after a panic was caught while handling the item.
+fn caught<C>(state: &RefCell<State<C>>, item: ItemToken, policy: PanicPolicy) {
+    let mut state = state.borrow_mut();
+    if policy == PanicPolicy::Disable && state.items.contains_key(&item) {
+        // The panic has already been reported, there's nothing more to do if
+        // this fails too.
+        let _ = state.update(item, ItemProperty::Enabled(false));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use raw_window_handle::{RawWindowHandle, XlibWindowHandle};
+
+    use super::*;
+    use crate::backend::{ItemKind, ItemProperty};
+    use crate::headless::{HeadlessBackend, Op};
+    use crate::{MenuItem, Separator, Submenu};
+
+    struct Window;
+
+    unsafe impl HasRawWindowHandle for Window {
+        fn raw_window_handle(&self) -> RawWindowHandle {
+            RawWindowHandle::Xlib(XlibWindowHandle::empty())
+        }
+    }
+
+    /// Records the calls it receives.
+    #[derive(Default)]
+    struct Recorder {
+        calls: Rc<RefCell<Vec<String>>>,
+        activator: Rc<RefCell<Option<Activator>>>,
+    }
+
+    impl Backend for Recorder {
+        fn supports(&self, window: RawWindowHandle) -> bool {
+            matches!(window, RawWindowHandle::Xlib(_))
+        }
+
+        fn set_activator(&mut self, activator: Activator) {
+            *self.activator.borrow_mut() = Some(activator);
+        }
+
+        fn create_menu(&mut self, menu: MenuToken, kind: MenuKind) -> Result<(), Error> {
+            let call = format!("create {:?} {:?}", menu.0, kind);
+            self.calls.borrow_mut().push(call);
+            Ok(())
+        }
+
+        fn insert_item(
+            &mut self,
+            menu: MenuToken,
+            index: usize,
+            item: ItemToken,
+            props: &ItemProps,
+        ) -> Result<(), Error> {
+            let kind = match props.kind {
+                ItemKind::Item => "item".to_owned(),
+                ItemKind::Submenu(submenu) => format!("submenu {}", submenu.0),
+                ItemKind::Separator => "separator".to_owned(),
+            };
+            let call = format!("insert {} {} {} {}", menu.0, index, item.0, kind);
+            self.calls.borrow_mut().push(call);
+            Ok(())
+        }
+
+        fn remove_item(&mut self, item: ItemToken) -> Result<(), Error> {
+            self.calls.borrow_mut().push(format!("remove {}", item.0));
+            Ok(())
+        }
+
+        fn update_item(&mut self, item: ItemToken, property: &ItemProperty) -> Result<(), Error> {
+            let call = format!("update {} {:?}", item.0, property);
+            self.calls.borrow_mut().push(call);
+            Ok(())
+        }
+
+        fn move_item(
+            &mut self,
+            item: ItemToken,
+            menu: MenuToken,
+            index: usize,
+        ) -> Result<(), Error> {
+            let call = format!("move {} {} {}", item.0, menu.0, index);
+            self.calls.borrow_mut().push(call);
+            Ok(())
+        }
+
+        fn attach(&mut self, menu: MenuToken, window: RawWindowHandle) -> Result<(), Error> {
+            self.calls.borrow_mut().push(format!("attach {}", menu.0));
+            Ok(())
+        }
+
+        fn detach(&mut self, menu: MenuToken) {
+            self.calls.borrow_mut().push(format!("detach {}", menu.0));
+        }
+    }
+
+    #[test]
+    fn test_no_backend() {
+        let err = attach(Menu::new(), &NoWindow).unwrap_err();
+        assert_eq!(err.to_string(), "unexpected window type");
+    }
+
+    #[test]
+    fn test_registered_backend() {
+        crate::register_backend(|| Some(Box::new(Recorder::default())));
+        assert!(attach(Menu::new(), &Window).is_ok());
+    }
+
+    #[test]
+    fn test_realize() {
+        let backend = Recorder::default();
+        let calls = Rc::clone(&backend.calls);
+        let menu = Menu::new()
+            .with(Submenu::new(
+                "File",
+                Menu::new()
+                    .with(MenuItem::new("New"))
+                    .with(Separator)
+                    .with(MenuItem::new("Quit")),
+            ))
+            .with(Submenu::new("Edit", Menu::new()));
+
+        let attached = attach_with(menu, &Window, backend).unwrap();
+        assert_eq!(attached.root(), MenuToken(0));
+        assert_eq!(
+            *calls.borrow(),
+            [
+                "create 0 Bar",
+                "create 1 Submenu",
+                "insert 0 0 0 submenu 1",
+                "insert 1 0 1 item",
+                "insert 1 1 2 separator",
+                "insert 1 2 3 item",
+                "create 2 Submenu",
+                "insert 0 1 4 submenu 2",
+                "attach 0",
+            ]
+        );
+
+        calls.borrow_mut().clear();
+        let quit = &attached.items()[0].items().unwrap()[2];
+        quit.set_enabled(false).unwrap();
+        drop(attached);
+        assert_eq!(*calls.borrow(), ["update 3 Enabled(false)", "detach 0"]);
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let backend = Recorder::default();
+        let activator = Rc::clone(&backend.activator);
+        let count = Rc::new(Cell::new(0));
+        let handler_count = Rc::clone(&count);
+        let menu = Menu::new()
+            .with(
+                MenuItem::new("a").with_handler(move || handler_count.set(handler_count.get() + 1)),
+            )
+            .with(MenuItem::new("b"));
+
+        let attached = attach_with(menu, &Window, backend).unwrap();
+        let activator = activator.borrow().clone().unwrap();
+        activator.activate(ItemToken(0), Activation::new(ActivationSource::Click));
+        activator.activate(ItemToken(0), Activation::new(ActivationSource::Click));
+        // Items without handlers, and unknown items, are ignored.
+        activator.activate(ItemToken(1), Activation::new(ActivationSource::Click));
+        activator.activate(ItemToken(42), Activation::new(ActivationSource::Click));
+        assert_eq!(count.get(), 2);
+
+        // Nothing happens once the menu is gone.
+        drop(attached);
+        activator.activate(ItemToken(0), Activation::new(ActivationSource::Click));
+        assert_eq!(count.get(), 2);
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Command {
+        Open,
+        Quit,
+    }
+
+    #[test]
+    fn test_commands() {
+        let backend = Recorder::default();
+        let activator = Rc::clone(&backend.activator);
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let handler_log = Rc::clone(&log);
+        let menu = Menu::default()
+            .with(MenuItem::new("Open").with_command(Command::Open))
+            .with(
+                MenuItem::new("Quit")
+                    .with_command(Command::Quit)
+                    .with_handler(move || handler_log.borrow_mut().push("handler")),
+            )
+            .with(MenuItem::new("None"));
+
+        let attached = attach_with(menu, &Window, backend).unwrap();
+        let activator = activator.borrow().clone().unwrap();
+
+        // Queued while there's no dispatcher.
+        activator.activate(ItemToken(1), Activation::new(ActivationSource::Click));
+        activator.activate(ItemToken(0), Activation::new(ActivationSource::Click));
+        activator.activate(ItemToken(2), Activation::new(ActivationSource::Click));
+        assert_eq!(*log.borrow(), ["handler"]);
+        assert_eq!(attached.take_commands(), [Command::Quit, Command::Open]);
+        assert_eq!(attached.take_commands(), []);
+
+        // Queued commands are delivered when the dispatcher is set.
+        activator.activate(ItemToken(0), Activation::new(ActivationSource::Click));
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let dispatcher_received = Rc::clone(&received);
+        let dispatcher_log = Rc::clone(&log);
+        attached.set_dispatcher(move |command| {
+            dispatcher_log.borrow_mut().push("dispatcher");
+            dispatcher_received.borrow_mut().push(command);
+        });
+        assert_eq!(*received.borrow(), [Command::Open]);
+
+        // The handler runs before the command is delivered.
+        activator.activate(ItemToken(1), Activation::new(ActivationSource::Click));
+        assert_eq!(*received.borrow(), [Command::Open, Command::Quit]);
+        assert_eq!(
+            *log.borrow(),
+            ["handler", "dispatcher", "handler", "dispatcher"]
+        );
+        assert_eq!(attached.take_commands(), []);
+    }
+
+    #[test]
+    fn test_replace_dispatcher() {
+        let backend = Recorder::default();
+        let activator = Rc::clone(&backend.activator);
+        let menu = Menu::default().with(MenuItem::new("Open").with_command(Command::Open));
+        let attached = Rc::new(attach_with(menu, &Window, backend).unwrap());
+        let activator = activator.borrow().clone().unwrap();
+
+        // A dispatcher that replaces itself while running.
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let weak = Rc::downgrade(&attached);
+        let second = Rc::clone(&received);
+        attached.set_dispatcher(move |_| {
+            let second = Rc::clone(&second);
+            weak.upgrade()
+                .unwrap()
+                .set_dispatcher(move |command| second.borrow_mut().push(command));
+        });
+        activator.activate(ItemToken(0), Activation::new(ActivationSource::Click));
+        assert!(received.borrow().is_empty());
+        activator.activate(ItemToken(0), Activation::new(ActivationSource::Click));
+        assert_eq!(*received.borrow(), [Command::Open]);
+    }
+
+    #[test]
+    fn test_handles() {
+        let backend = Recorder::default();
+        let calls = Rc::clone(&backend.calls);
+        let menu = Menu::new().with(
+            Submenu::new(
+                "File",
+                Menu::new()
+                    .with(
+                        MenuItem::new("Save")
+                            .with_id("save")
+                            .with_accelerator("CmdOrCtrl+S".parse().unwrap()),
+                    )
+                    .with(
+                        MenuItem::new("Word Wrap")
+                            .with_id("wrap")
+                            .with_checked(true),
+                    )
+                    .with(MenuItem::new("Close").with_enabled(false)),
+            )
+            .with_id("file"),
+        );
+        let attached = attach_with(menu, &Window, backend).unwrap();
+        assert!(attached.item("missing").is_none());
+
+        let save = attached.item("save").unwrap();
+        assert_eq!(save.id().unwrap(), "save");
+        assert_eq!(save.token(), ItemToken(1));
+        assert_eq!(save.title().unwrap(), "Save");
+        assert_eq!(
+            save.accelerator().unwrap(),
+            Some("CmdOrCtrl+S".parse().unwrap())
+        );
+        assert!(save.enabled().unwrap());
+        assert_eq!(save.checked().unwrap(), None);
+        assert!(!save.hidden().unwrap());
+        assert_eq!(
+            attached.item("wrap").unwrap().checked().unwrap(),
+            Some(true)
+        );
+        assert_eq!(save, save.clone());
+        assert_ne!(save, attached.item("file").unwrap());
+
+        // Removing a submenu removes everything in it.
+        calls.borrow_mut().clear();
+        let file = attached.item("file").unwrap();
+        file.remove().unwrap();
+        assert_eq!(*calls.borrow(), ["remove 0"]);
+        assert!(!file.exists());
+        assert!(!save.exists());
+        assert!(attached.item("save").is_none());
+
+        let err = save.title().unwrap_err();
+        assert!(err.is_item_removed());
+        assert_eq!(err.to_string(), "menu item `save` has been removed");
+        assert!(file.remove().unwrap_err().is_item_removed());
+        assert_eq!(*calls.borrow(), ["remove 0"]);
+    }
+
+    #[test]
+    fn test_handle_outlives_menu() {
+        let menu = Menu::new().with(MenuItem::new("a").with_id("a"));
+        let attached = attach_with(menu, &Window, Recorder::default()).unwrap();
+        let handle = attached.item("a").unwrap();
+        assert!(handle.exists());
+        drop(attached);
+        assert!(!handle.exists());
+        assert!(handle.enabled().unwrap_err().is_item_removed());
+    }
+
+    #[test]
+    fn test_duplicate_id() {
+        let menu = Menu::new()
+            .with(MenuItem::new("a").with_id("a"))
+            .with(Submenu::new(
+                "b",
+                Menu::new().with(MenuItem::new("c").with_id("a")),
+            ));
+        let err = attach_with(menu, &Window, Recorder::default()).unwrap_err();
+        assert!(!err.is_item_removed());
+        assert_eq!(err.to_string(), "duplicate item id `a`");
+    }
+
+    #[test]
+    fn test_insert_fails() {
+        let backend = HeadlessBackend::new();
+        let menu = Menu::new().with(Submenu::new("Edit", Menu::new()));
+        let attached = backend.attach(menu).unwrap();
+        backend.take_ops();
+
+        // The innermost submenu fails, after its ancestors were inserted.
+        let recent = Menu::new().with(Submenu::new("Older", Menu::new()));
+        let file = Menu::new()
+            .with(MenuItem::new("New"))
+            .with(Submenu::new("Recent", recent));
+        backend.set_failing(Some(ItemToken(4)));
+        assert!(attached.push(Submenu::new("File", file)).is_err());
+
+        // Everything is removed again, and the submenu that never made it in
+        // is destroyed.
+        let ops = backend.take_ops();
+        assert_eq!(
+            ops[ops.len() - 2..],
+            [
+                Op::DestroyMenu { menu: MenuToken(4) },
+                Op::RemoveItem { item: ItemToken(1) },
+            ]
+        );
+        assert_eq!(backend.items(MenuToken(0)), [ItemToken(0)]);
+        let state = attached.state.borrow();
+        assert_eq!(state.items.len(), 1);
+        let mut menus: Vec<_> = state.menus.keys().copied().collect();
+        menus.sort();
+        assert_eq!(menus, [MenuToken(0), MenuToken(1)]);
+    }
+
+    #[test]
+    fn test_insert_duplicate_id() {
+        let backend = Recorder::default();
+        let calls = Rc::clone(&backend.calls);
+        let menu = Menu::new().with(MenuItem::new("a").with_id("a"));
+        let attached = attach_with(menu, &Window, backend).unwrap();
+        calls.borrow_mut().clear();
+
+        // Nothing is inserted if any id in the entry is taken.
+        let submenu = Submenu::new(
+            "b",
+            Menu::new()
+                .with(MenuItem::new("c").with_id("c"))
+                .with(MenuItem::new("a").with_id("a")),
+        );
+        let err = attached.push(submenu).unwrap_err();
+        assert_eq!(err.to_string(), "duplicate item id `a`");
+        let submenu =
+            Submenu::new("b", Menu::new().with(MenuItem::new("c").with_id("c"))).with_id("c");
+        let err = attached.push(submenu).unwrap_err();
+        assert_eq!(err.to_string(), "duplicate item id `c`");
+        assert!(calls.borrow().is_empty());
+        assert_eq!(attached.items().len(), 1);
+    }
+
+    #[test]
+    fn test_index_out_of_bounds() {
+        let backend = Recorder::default();
+        let calls = Rc::clone(&backend.calls);
+        let menu = Menu::new()
+            .with(Submenu::new("a", Menu::new().with(MenuItem::new("a1"))).with_id("a"))
+            .with(MenuItem::new("b").with_id("b"));
+        let attached = attach_with(menu, &Window, backend).unwrap();
+        let a = attached.item("a").unwrap();
+        let b = attached.item("b").unwrap();
+        calls.borrow_mut().clear();
+
+        let message = |err: Error| err.to_string();
+        assert_eq!(
+            message(attached.insert(3, MenuItem::new("c")).unwrap_err()),
+            "index 3 is out of bounds for a menu with 2 entries"
+        );
+        assert_eq!(
+            message(attached.remove_at(2).unwrap_err()),
+            "index 2 is out of bounds for a menu with 2 entries"
+        );
+        assert_eq!(
+            message(a.insert(2, MenuItem::new("c")).unwrap_err()),
+            "index 2 is out of bounds for a menu with 1 entries"
+        );
+        assert_eq!(
+            message(a.remove_at(1).unwrap_err()),
+            "index 1 is out of bounds for a menu with 1 entries"
+        );
+        assert_eq!(
+            message(b.move_to(2).unwrap_err()),
+            "index 2 is out of bounds for a menu with 1 entries"
+        );
+        assert_eq!(
+            message(b.move_into(Some(&a), 2).unwrap_err()),
+            "index 2 is out of bounds for a menu with 1 entries"
+        );
+        assert!(calls.borrow().is_empty());
+        assert_eq!(attached.items(), [a.clone(), b]);
+        assert_eq!(a.items().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_move() {
+        let backend = Recorder::default();
+        let calls = Rc::clone(&backend.calls);
+        let menu = Menu::new()
+            .with(Submenu::new("a", Menu::new().with(MenuItem::new("a1"))).with_id("a"))
+            .with(Submenu::new("b", Menu::new()).with_id("b"))
+            .with(MenuItem::new("c").with_id("c"));
+        let attached = attach_with(menu, &Window, backend).unwrap();
+        let a = attached.item("a").unwrap();
+        let b = attached.item("b").unwrap();
+        let c = attached.item("c").unwrap();
+        calls.borrow_mut().clear();
+
+        c.move_to(0).unwrap();
+        assert_eq!(attached.items(), [c.clone(), a.clone(), b.clone()]);
+        a.move_into(Some(&b), 0).unwrap();
+        assert_eq!(a.parent().unwrap(), Some(b.clone()));
+        assert_eq!(b.items().unwrap().len(), 1);
+        assert_eq!(b.items().unwrap()[0], a);
+        assert_eq!(attached.items(), [c.clone(), b.clone()]);
+        assert_eq!(*calls.borrow(), ["move 3 0 0", "move 0 2 0"]);
+
+        // Moving a submenu into itself, directly or not.
+        let err = b.move_into(Some(&b), 0).unwrap_err();
+        assert_eq!(err.to_string(), "a submenu can't be moved into itself");
+        assert!(b.move_into(Some(&a), 0).is_err());
+        let err = c.move_into(Some(&c), 0).unwrap_err();
+        assert_eq!(err.to_string(), "the parent is not a submenu");
+
+        let other = attach_with(
+            Menu::new().with(Submenu::new("d", Menu::new()).with_id("d")),
+            &Window,
+            Recorder::default(),
+        )
+        .unwrap();
+        let err = c.move_into(other.item("d").as_ref(), 0).unwrap_err();
+        assert_eq!(err.to_string(), "the parent is in a different menu");
+
+        a.move_into(None, 2).unwrap();
+        assert_eq!(a.parent().unwrap(), None);
+        assert_eq!(a.index().unwrap(), 2);
+        assert_eq!(calls.borrow().len(), 3);
+    }
+}
@@ -0,0 +1,612 @@
+//! Updating an attached menu to match a new model.
+
+use std::collections::HashSet;
+
+use super::{collect_ids, AttachedMenu, State};
+use crate::backend::{ItemKind, ItemProperty, ItemProps, ItemToken, MenuToken};
+use crate::{Error, ItemId, Menu, MenuEntry, MenuItem};
+
+/// Update an attached menu to match the given model.
+///
+/// Entries are matched with the live items by id. Entries without an id are
+/// matched with items without an id in the same menu, that have the same kind
+/// and title. Matched items are moved and updated in place, keeping their
+/// handles and native submenus, and only the properties that changed are sent
+/// to the backend. Other entries are inserted, and the remaining items are
+/// removed.
+///
+/// The changes are sent to the backend in a single batch. Fails without
+/// changing anything if the model contains duplicate ids. If the backend
+/// fails, the menu is left partially updated.
+pub fn update<C>(live: &mut AttachedMenu<C>, model: Menu<C>) -> Result<(), Error> {
+    let mut state = live.state.borrow_mut();
+    let root = state.root;
+
+    let mut ids: Vec<(ItemId, bool)> = Vec::new();
+    for entry in model.entries() {
+        let mut entry_ids = Vec::new();
+        collect_ids(entry, &mut entry_ids);
+        for id in entry_ids {
+            if ids.iter().any(|(other, _)| other == id) {
+                return Err(Error::duplicate_id(id.clone()));
+            }
+            let is_submenu = model.find_id(id.as_str()).unwrap().as_submenu().is_some();
+            ids.push((id.clone(), is_submenu));
+        }
+    }
+
+    // An item can't become a submenu or the other way around, so those are
+    // replaced. Forget the id of the old one, so that the new one can use it.
+    for (id, is_submenu) in ids {
+        if let Some(item) = state.ids.get(&id).copied() {
+            let live_is_submenu = matches!(state.items[&item].props.kind, ItemKind::Submenu(_));
+            if live_is_submenu != is_submenu {
+                state.ids.remove(&id);
+                state.items.get_mut(&item).unwrap().id = None;
+            }
+        }
+    }
+
+    state.backend.begin_batch();
+    let mut reconciler = Reconciler {
+        state: &mut state,
+        keep: HashSet::new(),
+    };
+    let result = reconciler
+        .menu(root, model)
+        .and_then(|()| reconciler.prune(root));
+    let ended = state.backend.end_batch();
+    result.and(ended)
+}
+
+struct Reconciler<'a, C> {
+    state: &'a mut State<C>,
+    /// The items that are part of the new menu, either reused or inserted.
+    keep: HashSet<ItemToken>,
+}
+
+impl<C> Reconciler<'_, C> {
+    /// Make the entries of the menu match the model.
+    ///
+    /// The items that are already in the menu, and in the longest run that is
+    /// in the right order, stay where they are. Every other entry is moved or
+    /// inserted right after the entry before it. Items that aren't reused are
+    /// left in place, and removed by [`prune`][Self::prune].
+    fn menu(&mut self, menu: MenuToken, model: Menu<C>) -> Result<(), Error> {
+        let mut entries = Vec::new();
+        for mut entry in model.into_entries() {
+            // Submenus are matched and inserted without their entries, so
+            // that the entries can be matched with items anywhere in the menu.
+            let children = entry
+                .as_submenu_mut()
+                .map(|submenu| core::mem::take(submenu.menu_mut()));
+            let item = self.find(menu, &entry);
+            self.keep.extend(item);
+            entries.push((entry, children, item));
+        }
+
+        let positions: Vec<_> = entries
+            .iter()
+            .map(|(_, _, item)| {
+                let item = (*item)?;
+                (self.state.items[&item].menu == menu).then(|| self.state.index(menu, item))
+            })
+            .collect();
+        let stable = longest_increasing(&positions);
+
+        let mut previous = None;
+        for ((entry, children, item), stable) in entries.into_iter().zip(stable) {
+            let item = match item {
+                Some(item) => {
+                    if !stable {
+                        let index = self.index_after(menu, previous, Some(item));
+                        self.state.move_item(item, menu, index)?;
+                    }
+                    self.apply(item, entry)?;
+                    item
+                }
+                None => {
+                    let index = self.index_after(menu, previous, None);
+                    let item = self.state.realize(menu, index, entry)?;
+                    self.keep.insert(item);
+                    item
+                }
+            };
+
+            if let Some(children) = children {
+                match self.state.items[&item].props.kind {
+                    ItemKind::Submenu(submenu) => self.menu(submenu, children)?,
+                    _ => unreachable!("submenus are matched with submenus"),
+                }
+            }
+            previous = Some(item);
+        }
+        Ok(())
+    }
+
+    /// The index right after `previous`, or the start of the menu, once
+    /// `moving` has been taken out of the menu.
+    fn index_after(
+        &self,
+        menu: MenuToken,
+        previous: Option<ItemToken>,
+        moving: Option<ItemToken>,
+    ) -> usize {
+        let previous = match previous {
+            Some(previous) => previous,
+            None => return 0,
+        };
+        self.state.menus[&menu]
+            .iter()
+            .filter(|item| Some(**item) != moving)
+            .position(|item| *item == previous)
+            .expect("previous entry is in the menu")
+            + 1
+    }
+
+    /// Find the live item to reuse for the entry.
+    fn find(&self, menu: MenuToken, entry: &MenuEntry<C>) -> Option<ItemToken> {
+        if let Some(id) = entry.id() {
+            return self.state.ids.get(id).copied();
+        }
+        self.state.menus[&menu].iter().copied().find(|item| {
+            let live = &self.state.items[item];
+            let same = match (live.props.kind, entry) {
+                (ItemKind::Separator, MenuEntry::Separator(_)) => true,
+                (ItemKind::Item, MenuEntry::Item(model)) => live.props.title == model.title(),
+                (ItemKind::Submenu(_), MenuEntry::Submenu(model)) => {
+                    live.props.title == model.title()
+                }
+                _ => false,
+            };
+            same && live.id.is_none() && !self.keep.contains(item)
+        })
+    }
+
+    /// Update a reused item to match the entry.
+    fn apply(&mut self, item: ItemToken, mut entry: MenuEntry<C>) -> Result<(), Error> {
+        let live = &self.state.items[&item];
+        let submenu = match live.props.kind {
+            ItemKind::Submenu(submenu) => Some(submenu),
+            _ => None,
+        };
+        let new = ItemProps::from_entry(&entry, submenu);
+        let old = &live.props;
+
+        let mut properties = Vec::new();
+        if new.title != old.title {
+            properties.push(ItemProperty::Title(new.title));
+        }
+        if new.accelerator != old.accelerator {
+            properties.push(ItemProperty::Accelerator(new.accelerator));
+        }
+        if new.enabled != old.enabled {
+            properties.push(ItemProperty::Enabled(new.enabled));
+        }
+        if new.checked != old.checked {
+            properties.push(ItemProperty::Checked(new.checked));
+        }
+        if new.radio_group != old.radio_group {
+            properties.push(ItemProperty::RadioGroup(new.radio_group));
+        }
+        if new.hidden != old.hidden {
+            properties.push(ItemProperty::Hidden(new.hidden));
+        }
+        for property in properties {
+            self.state.update(item, property)?;
+        }
+
+        self.state.items.get_mut(&item).unwrap().role = entry.as_item().and_then(MenuItem::role);
+        if let MenuEntry::Item(model) = &mut entry {
+            match model.take_handler() {
+                Some(handler) => {
+                    self.state.handlers.insert(item, handler);
+                }
+                None => {
+                    self.state.handlers.remove(&item);
+                    self.state.cleared.insert(item);
+                }
+            }
+            match model.take_command() {
+                Some(command) => self.state.commands.insert(item, command),
+                None => self.state.commands.remove(&item),
+            };
+        }
+        Ok(())
+    }
+
+    /// Remove the items that are not part of the new menu.
+    fn prune(&mut self, menu: MenuToken) -> Result<(), Error> {
+        for item in self.state.menus[&menu].clone() {
+            if !self.keep.contains(&item) {
+                self.state.remove(item)?;
+            } else if let ItemKind::Submenu(submenu) = self.state.items[&item].props.kind {
+                self.prune(submenu)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Mark a longest strictly increasing subsequence of the positions, ignoring
+/// `None`.
+fn longest_increasing(positions: &[Option<usize>]) -> Vec<bool> {
+    // `tails[k]` is the index of the smallest position that ends an
+    // increasing subsequence of length `k + 1`.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous = vec![None; positions.len()];
+    for (i, position) in positions.iter().enumerate() {
+        let position = match position {
+            Some(position) => *position,
+            None => continue,
+        };
+        let k = tails.partition_point(|tail| positions[*tail] < Some(position));
+        if k > 0 {
+            previous[i] = Some(tails[k - 1]);
+        }
+        if k == tails.len() {
+            tails.push(i);
+        } else {
+            tails[k] = i;
+        }
+    }
+
+    let mut stable = vec![false; positions.len()];
+    let mut current = tails.last().copied();
+    while let Some(i) = current {
+        stable[i] = true;
+        current = previous[i];
+    }
+    stable
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::headless::{HeadlessBackend, Op};
+    use crate::{Separator, Submenu};
+
+    fn item(id: &'static str) -> MenuItem {
+        MenuItem::new(id.to_uppercase()).with_id(id)
+    }
+
+    fn submenu(id: &'static str, menu: Menu) -> Submenu {
+        Submenu::new(id.to_uppercase(), menu).with_id(id)
+    }
+
+    fn setup(menu: Menu) -> (HeadlessBackend, AttachedMenu) {
+        let backend = HeadlessBackend::new();
+        let attached = backend.attach(menu).unwrap();
+        backend.take_ops();
+        (backend, attached)
+    }
+
+    /// Take the ops of an update, which are all in one batch.
+    fn take_batch(backend: &HeadlessBackend) -> Vec<Op> {
+        let mut ops = backend.take_ops();
+        assert_eq!(ops.first(), Some(&Op::BeginBatch));
+        assert_eq!(ops.pop(), Some(Op::EndBatch));
+        ops.remove(0);
+        ops
+    }
+
+    fn token(attached: &AttachedMenu, id: &str) -> ItemToken {
+        attached.item(id).unwrap().token()
+    }
+
+    fn submenu_token(backend: &HeadlessBackend, attached: &AttachedMenu, id: &str) -> MenuToken {
+        match backend.props(token(attached, id)).unwrap().kind {
+            ItemKind::Submenu(menu) => menu,
+            kind => panic!("unexpected kind {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn test_unchanged() {
+        let model = || {
+            Menu::new()
+                .with(submenu(
+                    "file",
+                    Menu::new().with(item("new")).with(Separator),
+                ))
+                .with(MenuItem::new("No id"))
+        };
+        let (backend, mut attached) = setup(model());
+        update(&mut attached, model()).unwrap();
+        assert_eq!(take_batch(&backend), []);
+    }
+
+    #[test]
+    fn test_swapped_siblings() {
+        let (backend, mut attached) =
+            setup(Menu::new().with(item("a")).with(item("b")).with(item("c")));
+        let root = backend.root().unwrap();
+        let b = token(&attached, "b");
+        let c = token(&attached, "c");
+
+        let model = Menu::new().with(item("c")).with(item("b")).with(item("a"));
+        update(&mut attached, model).unwrap();
+        assert_eq!(
+            take_batch(&backend),
+            [
+                Op::MoveItem {
+                    item: c,
+                    menu: root,
+                    index: 0,
+                },
+                Op::MoveItem {
+                    item: b,
+                    menu: root,
+                    index: 1,
+                },
+            ]
+        );
+
+        let model = Menu::new().with(item("b")).with(item("c")).with(item("a"));
+        update(&mut attached, model).unwrap();
+        assert_eq!(
+            take_batch(&backend),
+            [Op::MoveItem {
+                item: b,
+                menu: root,
+                index: 0,
+            }]
+        );
+
+        // Only the item that is out of order moves.
+        let model = Menu::new().with(item("c")).with(item("a")).with(item("b"));
+        update(&mut attached, model).unwrap();
+        assert_eq!(
+            take_batch(&backend),
+            [Op::MoveItem {
+                item: b,
+                menu: root,
+                index: 2,
+            }]
+        );
+        let titles: Vec<_> = attached
+            .items()
+            .iter()
+            .map(|handle| handle.title().unwrap())
+            .collect();
+        assert_eq!(titles, ["C", "A", "B"]);
+    }
+
+    #[test]
+    fn test_submenu_between_parents() {
+        let (backend, mut attached) = setup(
+            Menu::new()
+                .with(submenu(
+                    "file",
+                    Menu::new().with(item("new")).with(submenu(
+                        "recent",
+                        Menu::new().with(item("x")).with(item("y")),
+                    )),
+                ))
+                .with(submenu("edit", Menu::new().with(item("undo")))),
+        );
+        let recent = attached.item("recent").unwrap();
+        let x = token(&attached, "x");
+        let edit = submenu_token(&backend, &attached, "edit");
+
+        let model = Menu::new()
+            .with(submenu("file", Menu::new().with(item("new"))))
+            .with(submenu(
+                "edit",
+                Menu::new().with(item("undo")).with(submenu(
+                    "recent",
+                    Menu::new().with(item("x")).with(item("y")),
+                )),
+            ));
+        update(&mut attached, model).unwrap();
+        assert_eq!(
+            take_batch(&backend),
+            [Op::MoveItem {
+                item: recent.token(),
+                menu: edit,
+                index: 1,
+            }]
+        );
+        assert_eq!(backend.find(&["EDIT", "RECENT", "X"]), Some(x));
+        assert_eq!(recent.parent().unwrap(), attached.item("edit"));
+
+        // Into a submenu that is itself moved out of the old parent.
+        let model = Menu::new()
+            .with(submenu(
+                "recent",
+                Menu::new()
+                    .with(item("x"))
+                    .with(item("y"))
+                    .with(submenu("edit", Menu::new().with(item("undo")))),
+            ))
+            .with(submenu("file", Menu::new().with(item("new"))));
+        update(&mut attached, model).unwrap();
+        let root = backend.root().unwrap();
+        let recent_menu = submenu_token(&backend, &attached, "recent");
+        assert_eq!(
+            take_batch(&backend),
+            [
+                Op::MoveItem {
+                    item: recent.token(),
+                    menu: root,
+                    index: 0,
+                },
+                Op::MoveItem {
+                    item: token(&attached, "edit"),
+                    menu: recent_menu,
+                    index: 2,
+                },
+            ]
+        );
+        assert!(backend.find(&["RECENT", "EDIT", "UNDO"]).is_some());
+    }
+
+    #[test]
+    fn test_insert_remove_update() {
+        let (backend, mut attached) = setup(
+            Menu::new().with(submenu(
+                "file",
+                Menu::new()
+                    .with(item("new"))
+                    .with(Separator)
+                    .with(MenuItem::new("Close"))
+                    .with(item("quit")),
+            )),
+        );
+        let file = submenu_token(&backend, &attached, "file");
+        let new = token(&attached, "new");
+        let quit = token(&attached, "quit");
+        let close = backend.find(&["FILE", "Close"]).unwrap();
+
+        let model = Menu::new().with(submenu(
+            "file",
+            Menu::new()
+                .with(
+                    MenuItem::new("New Window")
+                        .with_id("new")
+                        .with_accelerator("CmdOrCtrl+N".parse().unwrap()),
+                )
+                .with(item("open"))
+                .with(MenuItem::new("Close").with_enabled(false))
+                .with(Separator),
+        ));
+        update(&mut attached, model).unwrap();
+        let open = token(&attached, "open");
+        let ops = take_batch(&backend);
+        assert_eq!(
+            ops[..2],
+            [
+                Op::UpdateItem {
+                    item: new,
+                    property: ItemProperty::Title("New Window".into()),
+                },
+                Op::UpdateItem {
+                    item: new,
+                    property: ItemProperty::Accelerator(Some("CmdOrCtrl+N".parse().unwrap())),
+                },
+            ]
+        );
+        assert!(matches!(
+            ops[2],
+            Op::InsertItem { menu, index: 1, item, .. } if menu == file && item == open
+        ));
+        assert_eq!(
+            ops[3..],
+            [
+                Op::MoveItem {
+                    item: close,
+                    menu: file,
+                    index: 2,
+                },
+                Op::UpdateItem {
+                    item: close,
+                    property: ItemProperty::Enabled(false),
+                },
+                Op::RemoveItem { item: quit },
+            ]
+        );
+        assert!(attached.item("quit").is_none());
+        assert_eq!(backend.items(file).len(), 4);
+    }
+
+    #[test]
+    fn test_kind_changed() {
+        let (backend, mut attached) = setup(Menu::new().with(item("a")).with(item("b")));
+        let old = attached.item("a").unwrap();
+
+        let model = Menu::new()
+            .with(submenu("a", Menu::new().with(item("b"))))
+            .with(item("c"));
+        update(&mut attached, model).unwrap();
+        let new = attached.item("a").unwrap();
+        assert_ne!(old, new);
+        assert!(!old.exists());
+        assert_eq!(new.items().unwrap(), [attached.item("b").unwrap()]);
+        assert!(backend.find(&["A", "B"]).is_some());
+        assert_eq!(attached.items().len(), 2);
+    }
+
+    #[test]
+    fn test_handlers() {
+        let count = Rc::new(Cell::new(0));
+        let handler_count = Rc::clone(&count);
+        let (backend, mut attached) = setup(Menu::new().with(item("a")));
+
+        let model = Menu::new()
+            .with(item("a").with_handler(move || handler_count.set(handler_count.get() + 1)));
+        update(&mut attached, model).unwrap();
+        assert_eq!(take_batch(&backend), []);
+        assert!(backend.click_path(&["A"]));
+        assert_eq!(count.get(), 1);
+
+        update(&mut attached, Menu::new().with(item("a"))).unwrap();
+        assert!(backend.click_path(&["A"]));
+        assert_eq!(count.get(), 1);
+    }
+
+    #[test]
+    fn test_update_in_handler() {
+        let (backend, attached) = setup(Menu::new());
+        let attached = Rc::new(RefCell::new(attached));
+        let calls = Rc::new(RefCell::new(Vec::new()));
+
+        // Each handler re-derives the menu, replacing itself with the next
+        // one, and the last one with none.
+        let first = {
+            let attached = Rc::clone(&attached);
+            let calls = Rc::clone(&calls);
+            move || {
+                calls.borrow_mut().push("first");
+                let second = {
+                    let attached = Rc::clone(&attached);
+                    let calls = Rc::clone(&calls);
+                    move || {
+                        calls.borrow_mut().push("second");
+                        let model = Menu::new().with(item("a"));
+                        update(&mut attached.borrow_mut(), model).unwrap();
+                    }
+                };
+                let model = Menu::new().with(item("a").with_handler(second));
+                update(&mut attached.borrow_mut(), model).unwrap();
+            }
+        };
+        let model = Menu::new().with(item("a").with_handler(first));
+        update(&mut attached.borrow_mut(), model).unwrap();
+
+        for _ in 0..3 {
+            assert!(backend.click_path(&["A"]));
+        }
+        assert_eq!(*calls.borrow(), ["first", "second"]);
+    }
+
+    #[test]
+    fn test_longest_increasing() {
+        let marked = |positions: &[Option<usize>]| -> Vec<usize> {
+            let stable = longest_increasing(positions);
+            (0..positions.len()).filter(|i| stable[*i]).collect()
+        };
+        assert_eq!(marked(&[]), [0; 0]);
+        assert_eq!(marked(&[None, None]), [0; 0]);
+        assert_eq!(marked(&[Some(0), Some(1), Some(2)]), [0, 1, 2]);
+        assert_eq!(marked(&[Some(2), Some(1), Some(0)]), [2]);
+        assert_eq!(
+            marked(&[Some(1), None, Some(2), Some(3), Some(0)]),
+            [0, 2, 3]
+        );
+        assert_eq!(marked(&[Some(3), Some(0), Some(1), Some(2)]), [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_duplicate_ids() {
+        let (backend, mut attached) = setup(Menu::new().with(item("a")));
+        let model = Menu::new()
+            .with(item("b"))
+            .with(submenu("c", Menu::new().with(item("b"))));
+        let err = update(&mut attached, model).unwrap_err();
+        assert_eq!(err.to_string(), "duplicate item id `b`");
+        assert_eq!(backend.take_ops(), []);
+    }
+}
@@ -0,0 +1,302 @@
+//! Changing many items at once.
+
+use core::mem;
+use std::rc::Rc;
+
+use super::{check_supported, AttachedMenu, ItemHandle, State};
+use crate::backend::{ItemProperty, ItemProps};
+use crate::{Accelerator, Error};
+
+impl<C> AttachedMenu<C> {
+    /// Change many items at once.
+    ///
+    /// The changes made through the [`Transaction`] are queued, and sent to
+    /// the backend together once `f` returns, so that the native menu is only
+    /// redrawn once. If `f` fails, none of the queued changes are made.
+    ///
+    /// Changes made directly through [`ItemHandle`]s while `f` runs are not
+    /// part of the transaction: they are made right away, and stay even if
+    /// `f` or the transaction fails.
+    ///
+    /// If the backend fails to apply a change, the changes that were already
+    /// applied are reverted, and the error reports the item that failed
+    /// through [`Error::is_item_failed`] and [`Error::failed_item`].
+    pub fn transaction<R>(
+        &self,
+        f: impl FnOnce(&mut Transaction<'_, C>) -> Result<R, Error>,
+    ) -> Result<R, Error> {
+        let mut tx = Transaction {
+            menu: self,
+            changes: Vec::new(),
+        };
+        let value = f(&mut tx)?;
+        let changes = tx.changes;
+        self.state.borrow_mut().apply(changes)?;
+        Ok(value)
+    }
+}
+
+/// Changes to an [`AttachedMenu`] that are applied together.
+///
+/// Created by [`AttachedMenu::transaction`]. When the same property of an
+/// item is set more than once, only the last value is sent to the backend.
+pub struct Transaction<'a, C = ()> {
+    menu: &'a AttachedMenu<C>,
+    /// The changes, in the order the properties were first set.
+    changes: Vec<(ItemHandle<C>, ItemProperty)>,
+}
+
+impl<C> Transaction<'_, C> {
+    pub fn set_title(
+        &mut self,
+        item: &ItemHandle<C>,
+        title: impl Into<String>,
+    ) -> Result<(), Error> {
+        self.update(item, ItemProperty::Title(title.into()))
+    }
+
+    pub fn set_accelerator(
+        &mut self,
+        item: &ItemHandle<C>,
+        accelerator: Option<Accelerator>,
+    ) -> Result<(), Error> {
+        self.update(item, ItemProperty::Accelerator(accelerator))
+    }
+
+    pub fn set_enabled(&mut self, item: &ItemHandle<C>, enabled: bool) -> Result<(), Error> {
+        self.update(item, ItemProperty::Enabled(enabled))
+    }
+
+    pub fn set_checked(
+        &mut self,
+        item: &ItemHandle<C>,
+        checked: Option<bool>,
+    ) -> Result<(), Error> {
+        self.update(item, ItemProperty::Checked(checked))
+    }
+
+    pub fn set_radio_group(
+        &mut self,
+        item: &ItemHandle<C>,
+        group: Option<String>,
+    ) -> Result<(), Error> {
+        self.update(item, ItemProperty::RadioGroup(group))
+    }
+
+    pub fn set_hidden(&mut self, item: &ItemHandle<C>, hidden: bool) -> Result<(), Error> {
+        self.update(item, ItemProperty::Hidden(hidden))
+    }
+
+    /// Queue a change to a property of the item.
+    ///
+    /// Fails right away if the item has been removed, if it's in a different
+    /// menu, or if the property can't be set on this kind of item.
+    pub fn update(&mut self, item: &ItemHandle<C>, property: ItemProperty) -> Result<(), Error> {
+        if item.state.as_ptr() != Rc::as_ptr(&self.menu.state) {
+            return Err(Error::different_menu());
+        }
+        item.with(|_, live| check_supported(&live.props.kind, &property))?;
+
+        let queued = self.changes.iter_mut().find(|(other, queued)| {
+            other.item == item.item && mem::discriminant(queued) == mem::discriminant(&property)
+        });
+        match queued {
+            Some((_, queued)) => *queued = property,
+            None => self.changes.push((item.clone(), property)),
+        }
+        Ok(())
+    }
+}
+
+impl<C> State<C> {
+    /// Apply queued changes in a single batch, reverting them all if one
+    /// fails.
+    fn apply(&mut self, changes: Vec<(ItemHandle<C>, ItemProperty)>) -> Result<(), Error> {
+        // Items may have been removed after their changes were queued.
+        for (handle, _) in &changes {
+            if !self.items.contains_key(&handle.item) {
+                return Err(Error::item_removed(handle.id.clone()));
+            }
+        }
+
+        self.backend.begin_batch();
+        let mut applied = Vec::new();
+        let mut result = Ok(());
+        for (handle, property) in changes {
+            let old = current(&self.items[&handle.item].props, &property);
+            match self.update(handle.item, property) {
+                Ok(()) => applied.push((handle.item, old)),
+                Err(err) => {
+                    result = Err(Error::item_failed(handle.item, handle.id, err));
+                    break;
+                }
+            }
+        }
+        if result.is_err() {
+            // The error of the change that failed is the one worth reporting,
+            // so reverting is best effort.
+            for (item, old) in applied.into_iter().rev() {
+                let _ = self.update(item, old);
+            }
+        }
+        let ended = self.backend.end_batch();
+        result.and(ended)
+    }
+}
+
+/// The current value of the property that `property` changes.
+fn current(props: &ItemProps, property: &ItemProperty) -> ItemProperty {
+    match property {
+        ItemProperty::Title(_) => ItemProperty::Title(props.title.clone()),
+        ItemProperty::Accelerator(_) => ItemProperty::Accelerator(props.accelerator),
+        ItemProperty::Enabled(_) => ItemProperty::Enabled(props.enabled),
+        ItemProperty::Checked(_) => ItemProperty::Checked(props.checked),
+        ItemProperty::RadioGroup(_) => ItemProperty::RadioGroup(props.radio_group.clone()),
+        ItemProperty::Hidden(_) => ItemProperty::Hidden(props.hidden),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::{HeadlessBackend, Op};
+    use crate::{Menu, MenuItem, Separator, Submenu};
+
+    fn attach() -> (HeadlessBackend, AttachedMenu) {
+        let backend = HeadlessBackend::new();
+        let menu = Menu::new().with(Submenu::new(
+            "File",
+            Menu::new()
+                .with(MenuItem::new("New").with_id("new"))
+                .with(MenuItem::new("Open").with_id("open"))
+                .with(Separator),
+        ));
+        let attached = backend.attach(menu).unwrap();
+        backend.take_ops();
+        (backend, attached)
+    }
+
+    #[test]
+    fn test_coalesce() {
+        let (backend, attached) = attach();
+        let new = attached.item("new").unwrap();
+        let open = attached.item("open").unwrap();
+
+        attached
+            .transaction(|tx| {
+                tx.set_title(&new, "New Window")?;
+                tx.set_enabled(&open, false)?;
+                tx.set_title(&new, "New Tab")?;
+                tx.set_enabled(&new, true)?;
+                tx.set_checked(&new, Some(true))?;
+                tx.set_checked(&new, None)
+            })
+            .unwrap();
+        assert_eq!(
+            backend.take_ops(),
+            [
+                Op::BeginBatch,
+                Op::UpdateItem {
+                    item: new.token(),
+                    property: ItemProperty::Title("New Tab".into()),
+                },
+                Op::UpdateItem {
+                    item: open.token(),
+                    property: ItemProperty::Enabled(false),
+                },
+                Op::EndBatch,
+            ]
+        );
+        assert_eq!(new.title().unwrap(), "New Tab");
+        assert!(!open.enabled().unwrap());
+    }
+
+    #[test]
+    fn test_rollback() {
+        let (backend, attached) = attach();
+        let new = attached.item("new").unwrap();
+        let open = attached.item("open").unwrap();
+        backend.set_failing(Some(open.token()));
+
+        let err = attached
+            .transaction(|tx| {
+                tx.set_title(&new, "New Tab")?;
+                tx.set_hidden(&new, true)?;
+                tx.set_title(&open, "Open Recent")
+            })
+            .unwrap_err();
+        assert!(err.is_item_failed());
+        assert_eq!(err.failed_item(), open.id());
+        assert_eq!(
+            err.to_string(),
+            "failed to change menu item `open`: the item is set to fail"
+        );
+
+        let update = |item, property| Op::UpdateItem { item, property };
+        assert_eq!(
+            backend.take_ops(),
+            [
+                Op::BeginBatch,
+                update(new.token(), ItemProperty::Title("New Tab".into())),
+                update(new.token(), ItemProperty::Hidden(true)),
+                update(new.token(), ItemProperty::Hidden(false)),
+                update(new.token(), ItemProperty::Title("New".into())),
+                Op::EndBatch,
+            ]
+        );
+        assert_eq!(new.title().unwrap(), "New");
+        assert!(!new.hidden().unwrap());
+        let props = backend.props(new.token()).unwrap();
+        assert_eq!(props.title, "New");
+        assert!(!props.hidden);
+    }
+
+    #[test]
+    fn test_closure_fails() {
+        let (backend, attached) = attach();
+        let new = attached.item("new").unwrap();
+        let separator = attached.items()[0].items().unwrap().remove(2);
+
+        let err = attached
+            .transaction(|tx| {
+                tx.set_title(&new, "New Tab")?;
+                tx.set_checked(&separator, Some(true))
+            })
+            .unwrap_err();
+        assert_eq!(err.to_string(), "checkmark is not supported on separators");
+        assert!(!err.is_item_failed());
+        assert_eq!(err.failed_item(), None);
+        assert_eq!(backend.take_ops(), []);
+        assert_eq!(new.title().unwrap(), "New");
+    }
+
+    #[test]
+    fn test_removed_while_queued() {
+        let (backend, attached) = attach();
+        let new = attached.item("new").unwrap();
+        let open = attached.item("open").unwrap();
+
+        let err = attached
+            .transaction(|tx| {
+                tx.set_title(&new, "New Tab")?;
+                tx.set_enabled(&open, false)?;
+                open.remove()
+            })
+            .unwrap_err();
+        assert!(err.is_item_removed());
+        assert_eq!(backend.take_ops(), [Op::RemoveItem { item: open.token() }]);
+        assert_eq!(new.title().unwrap(), "New");
+    }
+
+    #[test]
+    fn test_other_menu() {
+        let (_, attached) = attach();
+        let (_, other) = attach();
+        let new = other.item("new").unwrap();
+        let err = attached
+            .transaction(|tx| tx.set_enabled(&new, false))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "the item is in a different menu");
+        assert!(new.enabled().unwrap());
+    }
+}
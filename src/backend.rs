@@ -0,0 +1,388 @@
+//! Extension point for platform implementations.
+//!
+//! A [`Backend`] knows how to create native menus and items, and how to
+//! attach them to a window. The platform-agnostic [`Menu`][crate::Menu] is
+//! realized through these operations by [`attach`][crate::attach], which
+//! keeps track of handlers, and dispatches activations reported by the
+//! backend.
+//!
+//! Menus and items are identified by tokens that are allocated by the caller,
+//! and never reused within the same attached menu.
+
+use core::fmt;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use raw_window_handle::RawWindowHandle;
+
+use crate::{Accelerator, Error, MenuEntry, Modifiers};
+
+/// Identifies a menu created through a [`Backend`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MenuToken(pub u32);
+
+/// Identifies an item created through a [`Backend`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ItemToken(pub u32);
+
+/// The role a menu plays.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MenuKind {
+    /// The top-level menu, that is attached to a window.
+    Bar,
+
+    /// A menu opened by a submenu item.
+    Submenu,
+}
+
+/// The kind of an item.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ItemKind {
+    /// A regular, clickable item.
+    Item,
+
+    /// An item that opens the given menu.
+    ///
+    /// The menu has been created before the item is inserted.
+    Submenu(MenuToken),
+
+    /// A separator line.
+    Separator,
+}
+
+/// The properties of an item, as given to a backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemProps {
+    pub kind: ItemKind,
+    pub title: String,
+    pub accelerator: Option<Accelerator>,
+    pub enabled: bool,
+    /// `None` if the item is not checkable.
+    pub checked: Option<bool>,
+    /// The radio group of a checkable item.
+    pub radio_group: Option<String>,
+    pub hidden: bool,
+}
+
+impl ItemProps {
+    /// A separator, with default values for the other properties.
+    pub fn separator() -> Self {
+        Self {
+            kind: ItemKind::Separator,
+            title: String::new(),
+            accelerator: None,
+            enabled: true,
+            checked: None,
+            radio_group: None,
+            hidden: false,
+        }
+    }
+
+    /// The properties of an entry in the model.
+    ///
+    /// `submenu` is used as the menu of submenu entries.
+    pub(crate) fn from_entry<C>(entry: &MenuEntry<C>, submenu: Option<MenuToken>) -> Self {
+        match entry {
+            MenuEntry::Separator(_) => Self::separator(),
+            MenuEntry::Submenu(model) => Self {
+                kind: ItemKind::Submenu(submenu.expect("submenu token")),
+                title: model.title().to_owned(),
+                accelerator: None,
+                enabled: model.enabled(),
+                checked: None,
+                radio_group: None,
+                hidden: model.hidden(),
+            },
+            MenuEntry::Item(model) => Self {
+                kind: ItemKind::Item,
+                title: model.title().to_owned(),
+                accelerator: model.accelerator(),
+                enabled: model.enabled(),
+                checked: model.checked(),
+                radio_group: model.radio_group().map(str::to_owned),
+                hidden: model.hidden(),
+            },
+        }
+    }
+
+    /// Apply a changed property.
+    pub fn apply(&mut self, property: &ItemProperty) {
+        match property {
+            ItemProperty::Title(title) => self.title = title.clone(),
+            ItemProperty::Accelerator(accelerator) => self.accelerator = *accelerator,
+            ItemProperty::Enabled(enabled) => self.enabled = *enabled,
+            ItemProperty::Checked(checked) => self.checked = *checked,
+            ItemProperty::RadioGroup(group) => self.radio_group = group.clone(),
+            ItemProperty::Hidden(hidden) => self.hidden = *hidden,
+        }
+    }
+}
+
+/// A single property of an item that changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ItemProperty {
+    Title(String),
+    Accelerator(Option<Accelerator>),
+    Enabled(bool),
+    Checked(Option<bool>),
+    RadioGroup(Option<String>),
+    Hidden(bool),
+}
+
+/// How an item was activated.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ActivationSource {
+    /// The item was chosen in the menu, usually by clicking it.
+    Click,
+
+    /// The accelerator of the item was pressed.
+    Accelerator,
+
+    /// The application activated the item, with
+    /// [`ItemHandle::activate`][crate::ItemHandle::activate].
+    Programmatic,
+}
+
+/// What a backend knows about an activation.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Activation {
+    pub source: ActivationSource,
+    /// The modifier keys that were held.
+    pub modifiers: Modifiers,
+    /// The window the item was activated in, if the backend knows it better
+    /// than the window the menu was attached to.
+    pub window: Option<RawWindowHandle>,
+}
+
+impl Activation {
+    /// An activation without modifiers, in the window the menu was attached
+    /// to.
+    pub fn new(source: ActivationSource) -> Self {
+        Self {
+            source,
+            modifiers: Modifiers::empty(),
+            window: None,
+        }
+    }
+}
+
+/// Callback through which a backend reports that an item was activated.
+#[derive(Clone)]
+pub struct Activator(Rc<dyn Fn(ItemToken, Activation)>);
+
+impl Activator {
+    pub fn new(f: impl Fn(ItemToken, Activation) + 'static) -> Self {
+        Self(Rc::new(f))
+    }
+
+    /// Report that the given item was activated.
+    ///
+    /// The item's handler is called before this returns.
+    pub fn activate(&self, item: ItemToken, activation: Activation) {
+        (self.0)(item, activation)
+    }
+}
+
+impl fmt::Debug for Activator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Activator").finish_non_exhaustive()
+    }
+}
+
+/// A platform implementation of menus.
+///
+/// Calls happen in a well-defined order: the bar is created first, then the
+/// activator is set, other menus are created, everything is populated, and
+/// finally the bar is attached. After that, items can still be inserted,
+/// updated, moved and removed.
+pub trait Backend {
+    /// Whether this backend can attach menus to the given window.
+    fn supports(&self, window: RawWindowHandle) -> bool;
+
+    /// Set the callback to use when an item is activated.
+    fn set_activator(&mut self, activator: Activator);
+
+    /// Create a new, empty menu.
+    ///
+    /// Backends that can only attach one menu at a time should fail with
+    /// [`MenuKind::Bar`] while a menu is attached, before anything else
+    /// about the new menu reaches them.
+    fn create_menu(&mut self, menu: MenuToken, kind: MenuKind) -> Result<(), Error>;
+
+    /// Insert an item into a menu at the given index.
+    ///
+    /// The index counts all items, including hidden ones.
+    fn insert_item(
+        &mut self,
+        menu: MenuToken,
+        index: usize,
+        item: ItemToken,
+        props: &ItemProps,
+    ) -> Result<(), Error>;
+
+    /// Destroy a menu that no item refers to, because inserting the submenu
+    /// item for it failed.
+    ///
+    /// The default does nothing.
+    fn destroy_menu(&mut self, menu: MenuToken) {}
+
+    /// Remove an item from its menu.
+    ///
+    /// If the item is a submenu item, the submenu and everything in it is
+    /// destroyed as well.
+    fn remove_item(&mut self, item: ItemToken) -> Result<(), Error>;
+
+    /// Change a property of an item.
+    fn update_item(&mut self, item: ItemToken, property: &ItemProperty) -> Result<(), Error>;
+
+    /// Move an item to the given index in a menu.
+    ///
+    /// The menu may be the one the item is already in. The index is the
+    /// position the item ends up at, counting all items, including hidden
+    /// ones. If the item is a submenu item, it keeps its submenu.
+    fn move_item(&mut self, item: ItemToken, menu: MenuToken, index: usize) -> Result<(), Error>;
+
+    /// Start a batch of changes.
+    ///
+    /// Until the batch ends, the backend may defer work that only has to
+    /// happen once for all the changes, like redrawing the menu bar. The
+    /// default does nothing.
+    fn begin_batch(&mut self) {}
+
+    /// End a batch of changes, and do the work that was deferred.
+    fn end_batch(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Set the function to call when activations were queued, to be taken
+    /// with [`take_activations`][Self::take_activations].
+    ///
+    /// Backends that receive activations on another thread can't report
+    /// them through the activator, so they queue them and call this on that
+    /// thread instead. The default does nothing.
+    fn set_wakeup(&mut self, wakeup: Wakeup) {}
+
+    /// Take the activations that were queued, oldest first.
+    ///
+    /// The default returns none.
+    fn take_activations(&mut self) -> Vec<(ItemToken, Activation)> {
+        Vec::new()
+    }
+
+    /// Attach a menu created with [`MenuKind::Bar`] to a window.
+    fn attach(&mut self, menu: MenuToken, window: RawWindowHandle) -> Result<(), Error>;
+
+    /// Detach the attached menu from its window, because the
+    /// [`AttachedMenu`][crate::AttachedMenu] was dropped, or because
+    /// populating or attaching it failed.
+    ///
+    /// No more calls follow. The window should stop showing the menu, even
+    /// if the backend itself lives on. The default does nothing.
+    fn detach(&mut self, menu: MenuToken) {}
+}
+
+impl<B: Backend + ?Sized> Backend for Box<B> {
+    fn supports(&self, window: RawWindowHandle) -> bool {
+        (**self).supports(window)
+    }
+
+    fn set_activator(&mut self, activator: Activator) {
+        (**self).set_activator(activator)
+    }
+
+    fn create_menu(&mut self, menu: MenuToken, kind: MenuKind) -> Result<(), Error> {
+        (**self).create_menu(menu, kind)
+    }
+
+    fn insert_item(
+        &mut self,
+        menu: MenuToken,
+        index: usize,
+        item: ItemToken,
+        props: &ItemProps,
+    ) -> Result<(), Error> {
+        (**self).insert_item(menu, index, item, props)
+    }
+
+    fn destroy_menu(&mut self, menu: MenuToken) {
+        (**self).destroy_menu(menu)
+    }
+
+    fn remove_item(&mut self, item: ItemToken) -> Result<(), Error> {
+        (**self).remove_item(item)
+    }
+
+    fn update_item(&mut self, item: ItemToken, property: &ItemProperty) -> Result<(), Error> {
+        (**self).update_item(item, property)
+    }
+
+    fn move_item(&mut self, item: ItemToken, menu: MenuToken, index: usize) -> Result<(), Error> {
+        (**self).move_item(item, menu, index)
+    }
+
+    fn begin_batch(&mut self) {
+        (**self).begin_batch()
+    }
+
+    fn end_batch(&mut self) -> Result<(), Error> {
+        (**self).end_batch()
+    }
+
+    fn set_wakeup(&mut self, wakeup: Wakeup) {
+        (**self).set_wakeup(wakeup)
+    }
+
+    fn take_activations(&mut self) -> Vec<(ItemToken, Activation)> {
+        (**self).take_activations()
+    }
+
+    fn attach(&mut self, menu: MenuToken, window: RawWindowHandle) -> Result<(), Error> {
+        (**self).attach(menu, window)
+    }
+
+    fn detach(&mut self, menu: MenuToken) {
+        (**self).detach(menu)
+    }
+}
+
+/// Called from another thread when a backend queued activations.
+pub type Wakeup = Arc<dyn Fn() + Send + Sync>;
+
+/// Creates a backend, or returns `None` if it's unavailable.
+pub type BackendFactory = fn() -> Option<Box<dyn Backend>>;
+
+std::thread_local! {
+    static REGISTERED: RefCell<Vec<BackendFactory>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Register a backend to be considered by [`attach`][crate::attach] on the
+/// current thread.
+///
+/// Registered backends are tried before the built-in ones, in the order they
+/// were registered.
+pub fn register_backend(factory: BackendFactory) {
+    REGISTERED.with(|registered| registered.borrow_mut().push(factory));
+}
+
+/// The backends to consider when attaching a menu, in order of priority.
+pub(crate) fn candidates() -> Vec<Box<dyn Backend>> {
+    let builtin: &[BackendFactory] = &[
+        #[cfg(windows)]
+        || Some(Box::new(crate::win32::Win32Backend::new())),
+        #[cfg(target_os = "macos")]
+        || {
+            let mtm = crate::appkit::MainThreadMarker::new()?;
+            Some(Box::new(crate::appkit::AppKitBackend::new(mtm)))
+        },
+        #[cfg(all(unix, not(target_os = "macos")))]
+        || Some(Box::new(crate::dbusmenu::DbusMenuBackend::new().ok()?)),
+    ];
+
+    REGISTERED
+        .with(|registered| registered.borrow().clone())
+        .into_iter()
+        .chain(builtin.iter().copied())
+        .filter_map(|factory| factory())
+        .collect()
+}
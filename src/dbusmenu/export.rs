@@ -0,0 +1,116 @@
+//! The connection that the D-Bus backends export a menu tree on.
+//!
+//! The [`DbusMenuBackend`][super::DbusMenuBackend] and the
+//! [`GtkMenuBackend`][crate::gtkmenu::GtkMenuBackend] describe the same tree
+//! with different interfaces, but otherwise connect, queue activations and
+//! dispatch them alike.
+
+use std::cell::RefCell;
+use std::sync::Arc;
+
+use raw_window_handle::RawWindowHandle;
+use zbus::blocking::connection::Builder;
+use zbus::blocking::Connection;
+use zbus::object_server::SignalEmitter;
+
+use super::interface::{Shared, Wakeup};
+use crate::backend::{Activation, ActivationSource, Activator, ItemToken};
+use crate::Error;
+
+/// A connection serving a menu tree, and the state of the menu it's
+/// serving.
+pub(crate) struct Export {
+    pub(crate) connection: Connection,
+    pub(crate) shared: Arc<Shared>,
+    pub(crate) activator: Option<Activator>,
+    /// The window the menu is attached to.
+    pub(crate) window: Option<RawWindowHandle>,
+    path: &'static str,
+}
+
+impl Export {
+    /// Connect to the session bus, or the bus with the given address, and
+    /// serve the interfaces that `serve` adds at `path`.
+    pub(crate) fn connect<'a>(
+        address: Option<&'a str>,
+        path: &'static str,
+        serve: impl FnOnce(Builder<'a>, &Arc<Shared>) -> zbus::Result<Builder<'a>>,
+    ) -> Result<Self, Error> {
+        let builder = match address {
+            Some(address) => Builder::address(address),
+            None => Builder::session(),
+        };
+        let shared = Arc::new(Shared::default());
+        let connection = builder
+            .and_then(|builder| serve(builder, &shared))
+            .and_then(Builder::build)
+            .map_err(Error::dbus)?;
+        Ok(Self::new(connection, shared, path))
+    }
+
+    pub(crate) fn new(connection: Connection, shared: Arc<Shared>, path: &'static str) -> Self {
+        Self {
+            connection,
+            shared,
+            activator: None,
+            window: None,
+            path,
+        }
+    }
+
+    pub(crate) fn bus_name(&self) -> String {
+        let name = self.connection.unique_name();
+        name.map(ToString::to_string).unwrap_or_default()
+    }
+
+    pub(crate) fn set_wakeup(&self, wakeup: Wakeup) {
+        self.shared.set_wakeup(Some(wakeup));
+    }
+
+    /// Take the items that the desktop activated, as clicks in the window
+    /// that the menu is attached to.
+    pub(crate) fn take_activations(&self) -> Vec<(ItemToken, Activation)> {
+        let activation = Activation {
+            window: self.window,
+            ..Activation::new(ActivationSource::Click)
+        };
+        let activated = self.shared.take_activated();
+        activated
+            .into_iter()
+            .map(|item| (item, activation))
+            .collect()
+    }
+
+    pub(crate) fn emitter(&self) -> Result<SignalEmitter<'_>, Error> {
+        SignalEmitter::new(self.connection.inner(), self.path).map_err(Error::dbus)
+    }
+
+    /// Emit a signal about a detached menu.
+    ///
+    /// There's no one to report errors to, and the desktop may be gone.
+    pub(crate) fn emit_detached(&self, emit: impl FnOnce(&SignalEmitter<'_>) -> zbus::Result<()>) {
+        if let Ok(emitter) = self.emitter() {
+            let _ = emit(&emitter);
+        }
+    }
+}
+
+/// Call the handlers of the items that the desktop activated since the last
+/// call, without borrowing the backend while they run.
+///
+/// Returns the number of activations.
+pub(crate) fn dispatch<T: AsRef<Export>>(inner: &RefCell<T>) -> usize {
+    let (activations, activator) = {
+        let inner = inner.borrow();
+        let export = inner.as_ref();
+        (export.take_activations(), export.activator.clone())
+    };
+    let activator = match activator {
+        Some(activator) => activator,
+        None => return 0,
+    };
+    for (item, activation) in &activations {
+        activator.activate(*item, *activation);
+    }
+    activations.len()
+}
@@ -0,0 +1,442 @@
+//! The `com.canonical.dbusmenu` interface.
+//!
+//! Calls are answered on the connection's thread, from a copy of the menu
+//! tree that the backend keeps up to date. The tree is shared with the
+//! other D-Bus exporters.
+
+use core::convert::TryFrom;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use zbus::fdo;
+use zbus::zvariant::{OwnedValue, StructureBuilder, Value};
+
+use crate::backend::{ItemKind, ItemProperty, ItemProps, ItemToken, MenuToken};
+
+/// The name of the interface.
+pub(super) const INTERFACE: &str = "com.canonical.dbusmenu";
+
+/// The version of the protocol that is implemented.
+const VERSION: u32 = 3;
+
+pub(crate) use crate::backend::Wakeup;
+
+/// State shared between the backend and the connection's thread.
+#[derive(Default)]
+pub(crate) struct Shared {
+    tree: Mutex<Tree>,
+    /// Items activated by the desktop, waiting to be dispatched.
+    activated: Mutex<VecDeque<ItemToken>>,
+    wakeup: Mutex<Option<Wakeup>>,
+}
+
+impl Shared {
+    pub(crate) fn tree(&self) -> MutexGuard<'_, Tree> {
+        // The tree is only changed through methods that can't panic halfway.
+        self.tree.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    pub(crate) fn set_wakeup(&self, wakeup: Option<Wakeup>) {
+        *self.wakeup.lock().unwrap_or_else(|err| err.into_inner()) = wakeup;
+    }
+
+    /// Take the items that were activated, oldest first.
+    pub(crate) fn take_activated(&self) -> VecDeque<ItemToken> {
+        let mut activated = self.activated.lock().unwrap_or_else(|err| err.into_inner());
+        core::mem::take(&mut *activated)
+    }
+
+    /// Queue an activation, if the item can be activated.
+    ///
+    /// Takes the tree that the caller looked the item up in, so that it
+    /// can't be removed in between.
+    pub(crate) fn activate(&self, tree: MutexGuard<'_, Tree>, item: ItemToken) {
+        let props = match tree.items.get(&item) {
+            Some(node) => &node.props,
+            None => return,
+        };
+        if props.kind != ItemKind::Item || !props.enabled || props.hidden {
+            return;
+        }
+        self.activated
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push_back(item);
+        drop(tree);
+
+        let wakeup = self
+            .wakeup
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone();
+        if let Some(wakeup) = wakeup {
+            wakeup();
+        }
+    }
+
+    fn event(&self, id: i32, event_id: &str) -> fdo::Result<()> {
+        let tree = self.tree();
+        let item = tree.lookup(id)?;
+        // Other events, like "opened" and "hovered", don't need handling.
+        if let (Some(item), "clicked") = (item, event_id) {
+            self.activate(tree, item);
+        }
+        Ok(())
+    }
+}
+
+/// An item or the root, with its properties and children, as `(ia{sv}av)`.
+///
+/// The children are variants holding layouts.
+type Layout = (i32, HashMap<String, Value<'static>>, Vec<Value<'static>>);
+
+/// The realized menus, as seen by the desktop.
+#[derive(Default)]
+pub(crate) struct Tree {
+    /// Incremented whenever the layout changes.
+    pub(crate) revision: u32,
+    /// The menu that was attached.
+    pub(crate) root: Option<MenuToken>,
+    pub(crate) menus: HashMap<MenuToken, Vec<ItemToken>>,
+    pub(crate) items: HashMap<ItemToken, Node>,
+    /// The item that opens each submenu.
+    pub(crate) parents: HashMap<MenuToken, ItemToken>,
+}
+
+pub(crate) struct Node {
+    /// The menu the item is in.
+    pub(crate) menu: MenuToken,
+    pub(crate) props: ItemProps,
+}
+
+/// The DBusMenu id of an item.
+///
+/// Id 0 is the root, so item tokens are offset by one.
+pub(crate) fn dbus_id(item: ItemToken) -> i32 {
+    i32::try_from(item.0)
+        .ok()
+        .and_then(|id| id.checked_add(1))
+        .expect("too many items")
+}
+
+impl Tree {
+    /// The item with the given DBusMenu id, `Some(None)` for the root, or
+    /// `None` if there's no such item.
+    fn item(&self, id: i32) -> Option<Option<ItemToken>> {
+        if id == 0 {
+            return Some(None);
+        }
+        let item = ItemToken(u32::try_from(id.checked_sub(1)?).ok()?);
+        self.items.contains_key(&item).then_some(Some(item))
+    }
+
+    /// Look up the item with the given id, or `None` for the root.
+    fn lookup(&self, id: i32) -> fdo::Result<Option<ItemToken>> {
+        match self.item(id) {
+            Some(item) => Ok(item),
+            None => Err(fdo::Error::InvalidArgs(format!("unknown id {}", id))),
+        }
+    }
+
+    /// Insert an item into a menu.
+    pub(crate) fn insert(
+        &mut self,
+        menu: MenuToken,
+        index: usize,
+        item: ItemToken,
+        props: &ItemProps,
+    ) {
+        if let ItemKind::Submenu(submenu) = props.kind {
+            self.parents.insert(submenu, item);
+        }
+        let node = Node {
+            menu,
+            props: props.clone(),
+        };
+        self.items.insert(item, node);
+        self.menus
+            .get_mut(&menu)
+            .expect("unknown menu")
+            .insert(index, item);
+        self.revision += 1;
+    }
+
+    /// Remove an item, and everything in its submenu.
+    ///
+    /// Returns the menu the item was in.
+    pub(crate) fn remove(&mut self, item: ItemToken) -> MenuToken {
+        let menu = self.items[&item].menu;
+        self.menus.get_mut(&menu).unwrap().retain(|i| *i != item);
+        self.forget_item(item);
+        self.revision += 1;
+        menu
+    }
+
+    /// Move an item to the given position.
+    ///
+    /// Returns the menu the item was in.
+    pub(crate) fn move_item(
+        &mut self,
+        item: ItemToken,
+        menu: MenuToken,
+        index: usize,
+    ) -> MenuToken {
+        let old = core::mem::replace(&mut self.items.get_mut(&item).unwrap().menu, menu);
+        self.menus.get_mut(&old).unwrap().retain(|i| *i != item);
+        self.menus.get_mut(&menu).unwrap().insert(index, item);
+        self.revision += 1;
+        old
+    }
+
+    /// Forget an item, and everything in its submenu.
+    pub(crate) fn forget_item(&mut self, item: ItemToken) {
+        if let Some(node) = self.items.remove(&item) {
+            if let ItemKind::Submenu(submenu) = node.props.kind {
+                self.parents.remove(&submenu);
+                for child in self.menus.remove(&submenu).unwrap_or_default() {
+                    self.forget_item(child);
+                }
+            }
+        }
+    }
+
+    /// The DBusMenu id of the item that opens the menu, or 0 for the root.
+    pub(super) fn parent_id(&self, menu: MenuToken) -> i32 {
+        self.parents.get(&menu).map_or(0, |item| dbus_id(*item))
+    }
+
+    /// The menu that the item opens, or the root menu.
+    fn children(&self, item: Option<ItemToken>) -> &[ItemToken] {
+        let menu = match item {
+            None => self.root,
+            Some(item) => match self.items[&item].props.kind {
+                ItemKind::Submenu(menu) => Some(menu),
+                _ => None,
+            },
+        };
+        menu.and_then(|menu| self.menus.get(&menu))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// The properties of an item or the root that aren't at their default
+    /// values.
+    pub(super) fn properties(
+        &self,
+        item: Option<ItemToken>,
+    ) -> Vec<(&'static str, Value<'static>)> {
+        match item {
+            None => vec![("children-display", Value::from("submenu"))],
+            Some(item) => properties(&self.items[&item].props),
+        }
+    }
+
+    /// The properties, as sent over D-Bus, keeping only the given names
+    /// unless they're empty.
+    fn property_map(
+        &self,
+        item: Option<ItemToken>,
+        names: &[String],
+    ) -> HashMap<String, Value<'static>> {
+        self.properties(item)
+            .into_iter()
+            .filter(|(name, _)| names.is_empty() || names.iter().any(|n| n == name))
+            .map(|(name, value)| (name.to_owned(), value))
+            .collect()
+    }
+
+    /// The layout of an item or the root.
+    ///
+    /// A negative depth includes all descendants.
+    fn layout(&self, item: Option<ItemToken>, depth: i32, names: &[String]) -> Layout {
+        let children = if depth == 0 {
+            Vec::new()
+        } else {
+            self.children(item)
+                .iter()
+                .map(|child| {
+                    let depth = depth.saturating_sub(1);
+                    let (id, properties, children) = self.layout(Some(*child), depth, names);
+                    let layout = StructureBuilder::new()
+                        .add_field(id)
+                        .add_field(properties)
+                        .add_field(children)
+                        .build()
+                        .expect("layout has fields");
+                    Value::from(layout)
+                })
+                .collect()
+        };
+        let id = item.map_or(0, dbus_id);
+        (id, self.property_map(item, names), children)
+    }
+}
+
+/// The properties of an item that aren't at their default values.
+fn properties(props: &ItemProps) -> Vec<(&'static str, Value<'static>)> {
+    let mut properties = Vec::new();
+    if props.kind == ItemKind::Separator {
+        properties.push(("type", Value::from("separator")));
+    } else {
+        // Underscores mark mnemonics, which titles don't have.
+        properties.push(("label", Value::from(props.title.replace('_', "__"))));
+        if !props.enabled {
+            properties.push(("enabled", Value::from(false)));
+        }
+    }
+    if props.hidden {
+        properties.push(("visible", Value::from(false)));
+    }
+    if let Some(accelerator) = props.accelerator {
+        let shortcut = vec![accelerator.to_dbusmenu_shortcut()];
+        properties.push(("shortcut", Value::from(shortcut)));
+    }
+    if let Some(checked) = props.checked {
+        let toggle_type = match props.radio_group {
+            Some(_) => "radio",
+            None => "checkmark",
+        };
+        properties.push(("toggle-type", Value::from(toggle_type)));
+        properties.push(("toggle-state", Value::from(i32::from(checked))));
+    }
+    if let ItemKind::Submenu(_) = props.kind {
+        properties.push(("children-display", Value::from("submenu")));
+    }
+    properties
+}
+
+/// The names of the DBusMenu properties that a change affects.
+pub(super) fn property_names(property: &ItemProperty) -> &'static [&'static str] {
+    match property {
+        ItemProperty::Title(_) => &["label"],
+        ItemProperty::Accelerator(_) => &["shortcut"],
+        ItemProperty::Enabled(_) => &["enabled"],
+        ItemProperty::Checked(_) => &["toggle-type", "toggle-state"],
+        ItemProperty::RadioGroup(_) => &["toggle-type"],
+        ItemProperty::Hidden(_) => &["visible"],
+    }
+}
+
+/// The object that is served on the connection.
+pub(super) struct DbusMenu(pub(super) Arc<Shared>);
+
+#[zbus::interface(name = "com.canonical.dbusmenu")]
+impl DbusMenu {
+    fn get_layout(
+        &self,
+        parent_id: i32,
+        recursion_depth: i32,
+        property_names: Vec<String>,
+    ) -> fdo::Result<(u32, Layout)> {
+        let tree = self.0.tree();
+        let parent = tree.lookup(parent_id)?;
+        let layout = tree.layout(parent, recursion_depth, &property_names);
+        Ok((tree.revision, layout))
+    }
+
+    fn get_group_properties(
+        &self,
+        ids: Vec<i32>,
+        property_names: Vec<String>,
+    ) -> Vec<(i32, HashMap<String, Value<'static>>)> {
+        let tree = self.0.tree();
+        ids.into_iter()
+            .filter_map(|id| Some((id, tree.property_map(tree.item(id)?, &property_names))))
+            .collect()
+    }
+
+    fn get_property(&self, id: i32, name: &str) -> fdo::Result<OwnedValue> {
+        let tree = self.0.tree();
+        let value = tree
+            .properties(tree.lookup(id)?)
+            .into_iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| value);
+        match value {
+            Some(value) => {
+                OwnedValue::try_from(value).map_err(|err| fdo::Error::Failed(err.to_string()))
+            }
+            None => Err(fdo::Error::InvalidArgs(format!(
+                "unknown property {}",
+                name
+            ))),
+        }
+    }
+
+    fn event(&self, id: i32, event_id: &str, data: Value<'_>, timestamp: u32) -> fdo::Result<()> {
+        self.0.event(id, event_id)
+    }
+
+    /// Returns the ids that are unknown.
+    fn event_group(&self, events: Vec<(i32, String, OwnedValue, u32)>) -> fdo::Result<Vec<i32>> {
+        let mut errors = Vec::new();
+        for (id, event_id, _, _) in &events {
+            if self.0.event(*id, event_id).is_err() {
+                errors.push(*id);
+            }
+        }
+        if !events.is_empty() && errors.len() == events.len() {
+            return Err(fdo::Error::InvalidArgs("all ids are unknown".to_owned()));
+        }
+        Ok(errors)
+    }
+
+    /// Whether the menu should be fetched again before it's shown.
+    ///
+    /// The desktop always has the current layout, so it never has to.
+    fn about_to_show(&self, id: i32) -> fdo::Result<bool> {
+        self.0.tree().lookup(id)?;
+        Ok(false)
+    }
+
+    /// Returns the ids that need updating, which are none, and the ids that
+    /// are unknown.
+    fn about_to_show_group(&self, ids: Vec<i32>) -> (Vec<i32>, Vec<i32>) {
+        let tree = self.0.tree();
+        let errors = ids
+            .into_iter()
+            .filter(|id| tree.lookup(*id).is_err())
+            .collect();
+        (Vec::new(), errors)
+    }
+
+    #[zbus(property)]
+    fn version(&self) -> u32 {
+        VERSION
+    }
+
+    #[zbus(property)]
+    fn text_direction(&self) -> &str {
+        "ltr"
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> &str {
+        "normal"
+    }
+
+    #[zbus(property)]
+    fn icon_theme_path(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(signal)]
+    pub(super) async fn items_properties_updated(
+        emitter: &zbus::object_server::SignalEmitter<'_>,
+        updated_props: Vec<(i32, HashMap<String, Value<'_>>)>,
+        removed_props: Vec<(i32, Vec<String>)>,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub(super) async fn layout_updated(
+        emitter: &zbus::object_server::SignalEmitter<'_>,
+        revision: u32,
+        parent: i32,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn item_activation_requested(
+        emitter: &zbus::object_server::SignalEmitter<'_>,
+        id: i32,
+        timestamp: u32,
+    ) -> zbus::Result<()>;
+}
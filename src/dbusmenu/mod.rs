@@ -0,0 +1,841 @@
+//! Linux implementation of menubars, through `com.canonical.dbusmenu`.
+//!
+//! Desktops with a global menu, like KDE Plasma, show the menus that
+//! applications export over D-Bus with the DBusMenu protocol. The
+//! [`DbusMenuBackend`] exports the attached menu as a DBusMenu object, keeps
+//! it up to date as items change, and registers it as the menu of the window
+//! with the `com.canonical.AppMenu.Registrar` service.
+//!
+//! The desktop's calls are answered on the connection's own thread, but
+//! handlers must run on the thread that owns the menu. Activations are
+//! therefore queued until [`DbusMenuBackend::dispatch`] or
+//! [`AttachedMenu::dispatch`][crate::AttachedMenu::dispatch] is called, which
+//! applications should do whenever the wakeup set with
+//! [`DbusMenuBackend::set_wakeup`] is called.
+
+use core::fmt;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::rc::Rc;
+use std::sync::{Arc, MutexGuard};
+
+use raw_window_handle::RawWindowHandle;
+use zbus::blocking::Connection;
+
+use self::export::Export;
+use self::interface::{dbus_id, property_names, DbusMenu, Shared, Wakeup};
+use self::registrar::Registered;
+use crate::backend::{
+    Activation, Activator, Backend, ItemProperty, ItemProps, ItemToken, MenuKind, MenuToken,
+};
+use crate::Error;
+
+pub(crate) mod export;
+pub(crate) mod interface;
+pub(crate) mod registrar;
+
+/// The object path that menus are exported at.
+pub const OBJECT_PATH: &str = "/MenuBar";
+
+/// A [`Backend`] that exports menus over D-Bus.
+///
+/// This is a cheap handle; clones refer to the same connection. Keep a clone
+/// around after attaching to dispatch activations.
+///
+/// It supports X11 windows.
+#[derive(Clone)]
+pub struct DbusMenuBackend(Rc<RefCell<Inner>>);
+
+struct Inner {
+    export: Export,
+    /// The changes to signal when the current batch ends.
+    batch: Option<Changes>,
+    /// Whether menus belong to windows, and are registered with the
+    /// registrar.
+    windowed: bool,
+    registered: Registered,
+    /// Whether the registrar is being watched for restarts.
+    watching: bool,
+}
+
+/// Changes that the desktop hasn't been told about yet.
+#[derive(Default)]
+struct Changes {
+    /// The id of the item whose children changed, or 0 if several did.
+    layout: Option<i32>,
+    properties: BTreeMap<ItemToken, BTreeSet<&'static str>>,
+}
+
+impl DbusMenuBackend {
+    /// Export menus on the session bus.
+    pub fn new() -> Result<Self, Error> {
+        Self::connect(None)
+    }
+
+    /// Export menus on the bus with the given address, like
+    /// `unix:path=/run/user/1000/bus`.
+    pub fn with_address(address: &str) -> Result<Self, Error> {
+        Self::connect(Some(address))
+    }
+
+    fn connect(address: Option<&str>) -> Result<Self, Error> {
+        let export = Export::connect(address, OBJECT_PATH, |builder, shared| {
+            builder.serve_at(OBJECT_PATH, DbusMenu(Arc::clone(shared)))
+        })?;
+        Ok(Self::from_parts(export, true))
+    }
+
+    /// Export a menu that doesn't belong to a window, like the menu of a
+    /// tray icon, on an existing connection.
+    ///
+    /// It can be attached with any window handle.
+    pub(crate) fn windowless(connection: &Connection) -> Result<Self, Error> {
+        let shared = Arc::new(Shared::default());
+        connection
+            .object_server()
+            .at(OBJECT_PATH, DbusMenu(Arc::clone(&shared)))
+            .map_err(Error::dbus)?;
+        let export = Export::new(connection.clone(), shared, OBJECT_PATH);
+        Ok(Self::from_parts(export, false))
+    }
+
+    fn from_parts(export: Export, windowed: bool) -> Self {
+        Self(Rc::new(RefCell::new(Inner {
+            export,
+            batch: None,
+            windowed,
+            registered: Registered::default(),
+            watching: false,
+        })))
+    }
+
+    /// The unique name of the connection that menus are exported on.
+    pub fn bus_name(&self) -> String {
+        self.0.borrow().export.bus_name()
+    }
+
+    /// Set the function to call when the desktop activates an item.
+    ///
+    /// It's called on the connection's thread, and should wake up the event
+    /// loop, so that it calls [`dispatch`][Self::dispatch].
+    pub fn set_wakeup(&self, wakeup: impl Fn() + Send + Sync + 'static) {
+        self.0.borrow().export.set_wakeup(Arc::new(wakeup));
+    }
+
+    /// Call the handlers of the items that the desktop activated since the
+    /// last call.
+    ///
+    /// Returns the number of activations.
+    pub fn dispatch(&self) -> usize {
+        export::dispatch(&self.0)
+    }
+}
+
+impl AsRef<Export> for Inner {
+    fn as_ref(&self) -> &Export {
+        &self.export
+    }
+}
+
+impl Inner {
+    fn lock_registered(&self) -> MutexGuard<'_, Option<u32>> {
+        self.registered
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Signal that the children of the given item changed.
+    fn layout_changed(&mut self, parent: i32) -> Result<(), Error> {
+        match &mut self.batch {
+            Some(changes) => {
+                changes.layout = match changes.layout {
+                    Some(other) if other != parent => Some(0),
+                    _ => Some(parent),
+                };
+                Ok(())
+            }
+            None => self.emit(Changes {
+                layout: Some(parent),
+                ..Changes::default()
+            }),
+        }
+    }
+
+    /// Signal that properties of the item changed.
+    fn properties_changed(&mut self, item: ItemToken, names: &[&'static str]) -> Result<(), Error> {
+        match &mut self.batch {
+            Some(changes) => {
+                changes.properties.entry(item).or_default().extend(names);
+                Ok(())
+            }
+            None => {
+                let mut changes = Changes::default();
+                changes
+                    .properties
+                    .insert(item, names.iter().copied().collect());
+                self.emit(changes)
+            }
+        }
+    }
+
+    /// Tell the desktop about changes, if the menu is attached.
+    fn emit(&self, changes: Changes) -> Result<(), Error> {
+        let (revision, updated, removed) = {
+            let tree = self.export.shared.tree();
+            if tree.root.is_none() {
+                return Ok(());
+            }
+            let mut updated = Vec::new();
+            let mut removed = Vec::new();
+            for (item, names) in changes.properties {
+                // The item may have been removed later in the batch.
+                if !tree.items.contains_key(&item) {
+                    continue;
+                }
+                let mut properties = tree.properties(Some(item));
+                let mut values = HashMap::new();
+                let mut missing = Vec::new();
+                for name in names {
+                    match properties.iter().position(|(n, _)| *n == name) {
+                        Some(i) => {
+                            values.insert(name.to_owned(), properties.swap_remove(i).1);
+                        }
+                        None => missing.push(name.to_owned()),
+                    }
+                }
+                if !values.is_empty() {
+                    updated.push((dbus_id(item), values));
+                }
+                if !missing.is_empty() {
+                    removed.push((dbus_id(item), missing));
+                }
+            }
+            (tree.revision, updated, removed)
+        };
+
+        let emitter = self.export.emitter()?;
+        if let Some(parent) = changes.layout {
+            zbus::block_on(DbusMenu::layout_updated(&emitter, revision, parent))
+                .map_err(Error::dbus)?;
+        }
+        if !updated.is_empty() || !removed.is_empty() {
+            zbus::block_on(DbusMenu::items_properties_updated(
+                &emitter, updated, removed,
+            ))
+            .map_err(Error::dbus)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        if let Some(window) = self.lock_registered().take() {
+            // The registrar notices when the connection goes away anyway.
+            let _ = registrar::unregister(&self.export.connection, window);
+        }
+        // Stops the thread that watches the registrar.
+        let _ = self.export.connection.clone().close();
+    }
+}
+
+impl fmt::Debug for DbusMenuBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DbusMenuBackend")
+            .field("bus_name", &self.bus_name())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Backend for DbusMenuBackend {
+    fn supports(&self, window: RawWindowHandle) -> bool {
+        !self.0.borrow().windowed
+            || matches!(window, RawWindowHandle::Xlib(_) | RawWindowHandle::Xcb(_))
+    }
+
+    fn set_activator(&mut self, activator: Activator) {
+        self.0.borrow_mut().export.activator = Some(activator);
+    }
+
+    fn create_menu(&mut self, menu: MenuToken, kind: MenuKind) -> Result<(), Error> {
+        let inner = self.0.borrow();
+        inner.export.shared.tree().menus.insert(menu, Vec::new());
+        Ok(())
+    }
+
+    fn destroy_menu(&mut self, menu: MenuToken) {
+        self.0.borrow().export.shared.tree().menus.remove(&menu);
+    }
+
+    fn insert_item(
+        &mut self,
+        menu: MenuToken,
+        index: usize,
+        item: ItemToken,
+        props: &ItemProps,
+    ) -> Result<(), Error> {
+        let mut inner = self.0.borrow_mut();
+        let parent = {
+            let mut tree = inner.export.shared.tree();
+            tree.insert(menu, index, item, props);
+            tree.parent_id(menu)
+        };
+        inner.layout_changed(parent)
+    }
+
+    fn remove_item(&mut self, item: ItemToken) -> Result<(), Error> {
+        let mut inner = self.0.borrow_mut();
+        let parent = {
+            let mut tree = inner.export.shared.tree();
+            let menu = tree.remove(item);
+            tree.parent_id(menu)
+        };
+        inner.layout_changed(parent)
+    }
+
+    fn update_item(&mut self, item: ItemToken, property: &ItemProperty) -> Result<(), Error> {
+        let mut inner = self.0.borrow_mut();
+        {
+            let mut tree = inner.export.shared.tree();
+            let node = tree
+                .items
+                .get_mut(&item)
+                .ok_or_else(|| Error::item_removed(None))?;
+            node.props.apply(property);
+        }
+        inner.properties_changed(item, property_names(property))
+    }
+
+    fn move_item(&mut self, item: ItemToken, menu: MenuToken, index: usize) -> Result<(), Error> {
+        let mut inner = self.0.borrow_mut();
+        let parent = {
+            let mut tree = inner.export.shared.tree();
+            let old = tree.move_item(item, menu, index);
+            if old == menu {
+                tree.parent_id(menu)
+            } else {
+                0
+            }
+        };
+        inner.layout_changed(parent)
+    }
+
+    fn begin_batch(&mut self) {
+        self.0.borrow_mut().batch = Some(Changes::default());
+    }
+
+    fn end_batch(&mut self) -> Result<(), Error> {
+        let mut inner = self.0.borrow_mut();
+        match inner.batch.take() {
+            Some(changes) => inner.emit(changes),
+            None => Ok(()),
+        }
+    }
+
+    fn set_wakeup(&mut self, wakeup: Wakeup) {
+        self.0.borrow().export.set_wakeup(wakeup);
+    }
+
+    fn take_activations(&mut self) -> Vec<(ItemToken, Activation)> {
+        self.0.borrow().export.take_activations()
+    }
+
+    fn attach(&mut self, menu: MenuToken, window: RawWindowHandle) -> Result<(), Error> {
+        let mut inner = self.0.borrow_mut();
+        if !inner.windowed {
+            {
+                let mut tree = inner.export.shared.tree();
+                tree.revision += 1;
+                tree.root = Some(menu);
+            }
+            return inner.layout_changed(0);
+        }
+
+        let handle = window;
+        let window = registrar::window_id(window).ok_or_else(Error::unexpected_window_type)?;
+        if !inner.watching {
+            registrar::watch(&inner.export.connection, Arc::clone(&inner.registered))?;
+            inner.watching = true;
+        }
+
+        // The registrar may ask for the layout as soon as it's told about
+        // the window.
+        let previous = {
+            let mut tree = inner.export.shared.tree();
+            tree.revision += 1;
+            tree.root.replace(menu)
+        };
+        if let Err(err) = registrar::register(&inner.export.connection, window) {
+            inner.export.shared.tree().root = previous;
+            return Err(err);
+        }
+        inner.export.window = Some(handle);
+        let old = inner.lock_registered().replace(window);
+        if let Some(old) = old.filter(|old| *old != window) {
+            registrar::unregister(&inner.export.connection, old)?;
+        }
+        inner.layout_changed(0)
+    }
+
+    /// The window is unregistered, and the menu is emptied for anyone still
+    /// showing it.
+    fn detach(&mut self, menu: MenuToken) {
+        let mut inner = self.0.borrow_mut();
+        inner.export.window = None;
+        let revision = {
+            let mut tree = inner.export.shared.tree();
+            tree.root = None;
+            tree.menus.clear();
+            tree.items.clear();
+            tree.parents.clear();
+            tree.revision += 1;
+            tree.revision
+        };
+
+        // The registrar may be gone along with the desktop.
+        let window = inner.lock_registered().take();
+        if let Some(window) = window {
+            let _ = registrar::unregister(&inner.export.connection, window);
+        }
+        inner.export.emit_detached(|emitter| {
+            zbus::block_on(DbusMenu::layout_updated(emitter, revision, 0))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::TryFrom;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    use raw_window_handle::{HasRawWindowHandle, XlibWindowHandle};
+    use zbus::blocking::connection::Builder;
+    use zbus::message::Type;
+    use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+    use zbus::{MatchRule, Message};
+
+    use super::interface::INTERFACE;
+    use super::registrar::{REGISTRAR, REGISTRAR_PATH};
+    use super::*;
+    use crate::test_util::{call, describe, menu, next_signal, signals, TestBus, XlibWindow};
+    use crate::{AttachedMenu, MenuItem};
+
+    type RawLayout = (i32, HashMap<String, OwnedValue>, Vec<OwnedValue>);
+
+    /// A stand-in for the registrar, that reports the calls it gets.
+    struct StandInRegistrar(Mutex<mpsc::Sender<String>>);
+
+    #[zbus::interface(name = "com.canonical.AppMenu.Registrar")]
+    impl StandInRegistrar {
+        fn register_window(&self, window_id: u32, menu_object_path: ObjectPath<'_>) {
+            let call = format!("RegisterWindow({:#x}, {})", window_id, menu_object_path);
+            let _ = self.0.lock().unwrap().send(call);
+        }
+
+        fn unregister_window(&self, window_id: u32) {
+            let call = format!("UnregisterWindow({:#x})", window_id);
+            let _ = self.0.lock().unwrap().send(call);
+        }
+    }
+
+    /// Start a registrar, that stops when the connection is closed.
+    fn registrar(bus: &TestBus) -> (Connection, mpsc::Receiver<String>) {
+        let (sender, receiver) = mpsc::channel();
+        let connection = Builder::address(bus.address())
+            .unwrap()
+            .name(REGISTRAR)
+            .unwrap()
+            .serve_at(REGISTRAR_PATH, StandInRegistrar(Mutex::new(sender)))
+            .unwrap()
+            .build()
+            .unwrap();
+        (connection, receiver)
+    }
+
+    fn next_call(calls: &mpsc::Receiver<String>) -> String {
+        calls
+            .recv_timeout(Duration::from_secs(5))
+            .expect("the registrar wasn't called")
+    }
+
+    fn attach(bus: &TestBus) -> (DbusMenuBackend, AttachedMenu<&'static str>, Connection) {
+        let (registrar, _) = registrar(bus);
+        let backend = DbusMenuBackend::with_address(bus.address()).unwrap();
+        let attached = crate::attach_with(menu(), &XlibWindow, backend.clone()).unwrap();
+        (backend, attached, registrar)
+    }
+
+    /// The layout as lines of text, with the properties of each item.
+    fn outline(layout: RawLayout) -> Vec<String> {
+        fn walk(layout: RawLayout, indent: usize, lines: &mut Vec<String>) {
+            let (_, properties, children) = layout;
+            lines.push(format!("{}{}", "  ".repeat(indent), describe(&properties)));
+            for child in children {
+                walk(RawLayout::try_from(child).unwrap(), indent + 1, lines);
+            }
+        }
+        let mut lines = Vec::new();
+        walk(layout, 0, &mut lines);
+        lines
+    }
+
+    #[test]
+    fn test_layout() {
+        let bus = TestBus::start();
+        let (backend, attached, _registrar) = attach(&bus);
+        let client = bus.connect();
+        let bus_name = backend.bus_name();
+
+        let reply = call(
+            &client,
+            &bus_name,
+            INTERFACE,
+            "GetLayout",
+            &(0, -1, Vec::<&str>::new()),
+        )
+        .unwrap();
+        let (_, layout): (u32, RawLayout) = reply.body().deserialize().unwrap();
+        let all = outline(layout);
+        assert_eq!(
+            all,
+            [
+                r#"children-display="submenu""#,
+                r#"  children-display="submenu" label="File""#,
+                r#"    label="New" shortcut=[["Control", "n"]]"#,
+                r#"    enabled=false label="Save__As""#,
+                r#"    type="separator""#,
+                r#"    label="Autosave" toggle-state=1 toggle-type="checkmark""#,
+                r#"    label="Hidden" visible=false"#,
+                r#"  children-display="submenu" label="Help""#,
+            ]
+        );
+
+        // Any negative depth means all of them.
+        let body = (0, i32::MIN, Vec::<&str>::new());
+        let reply = call(&client, &bus_name, INTERFACE, "GetLayout", &body).unwrap();
+        let (_, layout): (u32, RawLayout) = reply.body().deserialize().unwrap();
+        assert_eq!(outline(layout), all);
+
+        // Only one level, with only the requested properties.
+        let reply = call(
+            &client,
+            &bus_name,
+            INTERFACE,
+            "GetLayout",
+            &(0, 1, vec!["label"]),
+        )
+        .unwrap();
+        let (_, layout): (u32, RawLayout) = reply.body().deserialize().unwrap();
+        assert_eq!(
+            outline(layout),
+            ["", r#"  label="File""#, r#"  label="Help""#]
+        );
+
+        let save_as = dbus_id(attached.item("save-as").unwrap().token());
+        let reply = call(
+            &client,
+            &bus_name,
+            INTERFACE,
+            "GetProperty",
+            &(save_as, "enabled"),
+        )
+        .unwrap();
+        let enabled: OwnedValue = reply.body().deserialize().unwrap();
+        assert!(!bool::try_from(enabled).unwrap());
+
+        let new = dbus_id(attached.item("new").unwrap().token());
+        let ids = vec![new, 99];
+        let reply = call(
+            &client,
+            &bus_name,
+            INTERFACE,
+            "GetGroupProperties",
+            &(ids, vec!["label"]),
+        )
+        .unwrap();
+        let properties: Vec<(i32, HashMap<String, OwnedValue>)> =
+            reply.body().deserialize().unwrap();
+        assert_eq!(properties.len(), 1);
+        assert_eq!(properties[0].0, new);
+        assert_eq!(properties[0].1["label"].to_string(), r#""New""#);
+
+        let layout = (99, -1, Vec::<&str>::new());
+        let err = call(&client, &bus_name, INTERFACE, "GetLayout", &layout).unwrap_err();
+        assert!(err.to_string().contains("unknown id 99"), "{}", err);
+
+        let proxy = zbus::blocking::fdo::PropertiesProxy::builder(&client)
+            .destination(bus_name.as_str())
+            .unwrap()
+            .path(OBJECT_PATH)
+            .unwrap()
+            .build()
+            .unwrap();
+        let interface = zbus::names::InterfaceName::from_static_str(INTERFACE).unwrap();
+        let version = proxy.get(interface, "Version").unwrap();
+        assert_eq!(u32::try_from(version).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_signals() {
+        let bus = TestBus::start();
+        let (backend, attached, _registrar) = attach(&bus);
+        let client = bus.connect();
+        let bus_name = backend.bus_name();
+        let rule = MatchRule::builder()
+            .msg_type(Type::Signal)
+            .interface(INTERFACE)
+            .unwrap()
+            .build();
+        let signals = signals(&client, rule);
+
+        let new = attached.item("new").unwrap();
+        new.set_title("New Window").unwrap();
+        let (signal, message) = next_signal(&signals);
+        assert_eq!(signal, "com.canonical.dbusmenu.ItemsPropertiesUpdated");
+        type Updated = (
+            Vec<(i32, HashMap<String, OwnedValue>)>,
+            Vec<(i32, Vec<String>)>,
+        );
+        let (updated, removed): Updated = message.body().deserialize().unwrap();
+        assert_eq!(updated.len(), 1);
+        assert_eq!(updated[0].0, dbus_id(new.token()));
+        assert_eq!(updated[0].1["label"].to_string(), r#""New Window""#);
+        assert!(removed.is_empty());
+
+        // Checkable items of radio groups have their own toggle type.
+        let autosave = attached.item("autosave").unwrap();
+        autosave.set_radio_group(Some("save".into())).unwrap();
+        let (_, message) = next_signal(&signals);
+        let (updated, _): Updated = message.body().deserialize().unwrap();
+        assert_eq!(updated[0].1["toggle-type"].to_string(), r#""radio""#);
+
+        // Properties back at their default are removed.
+        autosave.set_checked(None).unwrap();
+        let (_, message) = next_signal(&signals);
+        let (updated, removed): Updated = message.body().deserialize().unwrap();
+        assert!(updated.is_empty());
+        assert_eq!(
+            removed,
+            [(
+                dbus_id(autosave.token()),
+                vec!["toggle-state".to_owned(), "toggle-type".to_owned()]
+            )]
+        );
+
+        // Changing the layout bumps the revision.
+        let reply = call(
+            &client,
+            &bus_name,
+            INTERFACE,
+            "GetLayout",
+            &(0, 0, Vec::<&str>::new()),
+        )
+        .unwrap();
+        let (revision, _): (u32, RawLayout) = reply.body().deserialize().unwrap();
+        let help = attached.item("help").unwrap();
+        help.push(MenuItem::new("About")).unwrap();
+        let (signal, message) = next_signal(&signals);
+        assert_eq!(signal, "com.canonical.dbusmenu.LayoutUpdated");
+        let (new_revision, parent): (u32, i32) = message.body().deserialize().unwrap();
+        assert!(new_revision > revision);
+        assert_eq!(parent, dbus_id(help.token()));
+
+        // A transaction is signalled once.
+        attached
+            .transaction(|tx| {
+                tx.set_enabled(&new, false)?;
+                tx.set_title(&autosave, "Save Automatically")
+            })
+            .unwrap();
+        let (signal, message) = next_signal(&signals);
+        assert_eq!(signal, "com.canonical.dbusmenu.ItemsPropertiesUpdated");
+        let (updated, _): Updated = message.body().deserialize().unwrap();
+        let ids: Vec<_> = updated.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, [dbus_id(new.token()), dbus_id(autosave.token())]);
+        assert!(signals.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+
+    #[test]
+    fn test_events() {
+        let bus = TestBus::start();
+        let (backend, attached, _registrar) = attach(&bus);
+        let client = bus.connect();
+        let bus_name = backend.bus_name();
+        let wakeups = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&wakeups);
+        backend.set_wakeup(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let id = |id| dbus_id(attached.item(id).unwrap().token());
+        let (new, save_as) = (id("new"), id("save-as"));
+        let file = dbus_id(attached.items()[0].token());
+        let data = Value::from(0);
+        call(
+            &client,
+            &bus_name,
+            INTERFACE,
+            "Event",
+            &(new, "clicked", &data, 0u32),
+        )
+        .unwrap();
+        // Opening a menu isn't an activation.
+        call(
+            &client,
+            &bus_name,
+            INTERFACE,
+            "Event",
+            &(file, "opened", &data, 0u32),
+        )
+        .unwrap();
+        // Disabled items can't be activated.
+        call(
+            &client,
+            &bus_name,
+            INTERFACE,
+            "Event",
+            &(save_as, "clicked", &data, 0u32),
+        )
+        .unwrap();
+        assert_eq!(wakeups.load(Ordering::SeqCst), 1);
+        assert!(attached.take_commands().is_empty());
+        assert_eq!(backend.dispatch(), 1);
+        assert_eq!(attached.take_commands(), ["new"]);
+        assert_eq!(backend.dispatch(), 0);
+        // Activations report the registered window.
+        let window = XlibWindow.raw_window_handle();
+        assert_eq!(backend.0.borrow().export.window, Some(window));
+
+        let events = vec![(new, "clicked", &data, 0u32), (99, "clicked", &data, 0u32)];
+        let reply = call(&client, &bus_name, INTERFACE, "EventGroup", &(events,)).unwrap();
+        let errors: Vec<i32> = reply.body().deserialize().unwrap();
+        assert_eq!(errors, [99]);
+        assert_eq!(backend.dispatch(), 1);
+
+        // Activations can also be dispatched through the menu. Its wakeup
+        // counts by tens, to tell it apart from the one it replaces.
+        let counter = Arc::clone(&wakeups);
+        attached.set_wakeup(move || {
+            counter.fetch_add(10, Ordering::SeqCst);
+        });
+        let before = wakeups.load(Ordering::SeqCst);
+        call(
+            &client,
+            &bus_name,
+            INTERFACE,
+            "Event",
+            &(new, "clicked", &data, 0u32),
+        )
+        .unwrap();
+        assert_eq!(wakeups.load(Ordering::SeqCst), before + 10);
+        assert_eq!(attached.dispatch(), 1);
+        assert_eq!(attached.take_commands(), ["new", "new"]);
+
+        let reply = call(&client, &bus_name, INTERFACE, "AboutToShow", &(file,)).unwrap();
+        let need_update: bool = reply.body().deserialize().unwrap();
+        assert!(!need_update);
+        let reply = call(
+            &client,
+            &bus_name,
+            INTERFACE,
+            "AboutToShowGroup",
+            &(vec![0, file, 99],),
+        )
+        .unwrap();
+        let (updates, errors): (Vec<i32>, Vec<i32>) = reply.body().deserialize().unwrap();
+        assert!(updates.is_empty());
+        assert_eq!(errors, [99]);
+
+        // Items removed after the desktop looked them up are ignored.
+        let new = attached.item("new").unwrap();
+        let token = new.token();
+        new.remove().unwrap();
+        let shared = Arc::clone(&backend.0.borrow().export.shared);
+        shared.activate(shared.tree(), token);
+        assert_eq!(backend.dispatch(), 0);
+        let property = ItemProperty::Enabled(false);
+        let err = backend.clone().update_item(token, &property).unwrap_err();
+        assert!(err.is_item_removed(), "{:?}", err);
+    }
+
+    #[test]
+    fn test_registrar() {
+        let bus = TestBus::start();
+        let (registrar, calls) = registrar(&bus);
+        let backend = DbusMenuBackend::with_address(bus.address()).unwrap();
+        let attached = crate::attach_with(menu(), &XlibWindow, backend.clone()).unwrap();
+        assert_eq!(next_call(&calls), "RegisterWindow(0x4000001, /MenuBar)");
+
+        // A restarted registrar is told about the window again.
+        registrar.close().unwrap();
+        let (registrar, calls) = self::registrar(&bus);
+        assert_eq!(next_call(&calls), "RegisterWindow(0x4000001, /MenuBar)");
+
+        drop(attached);
+        assert_eq!(next_call(&calls), "UnregisterWindow(0x4000001)");
+        assert_eq!(backend.0.borrow().export.shared.tree().root, None);
+        // A restarted registrar isn't told about the window anymore.
+        registrar.close().unwrap();
+        let (registrar, calls) = self::registrar(&bus);
+        assert!(calls.recv_timeout(Duration::from_millis(100)).is_err());
+        drop(backend);
+        assert!(calls.recv_timeout(Duration::from_millis(100)).is_err());
+        drop(registrar);
+    }
+
+    #[test]
+    fn test_no_registrar() {
+        let bus = TestBus::start();
+        let backend = DbusMenuBackend::with_address(bus.address()).unwrap();
+        let err = crate::attach_with(menu(), &XlibWindow, backend.clone()).unwrap_err();
+        assert!(err.is_no_registrar(), "{:?}", err);
+        assert!(backend.0.borrow().export.shared.tree().items.is_empty());
+
+        // The menu can be attached once a registrar shows up.
+        let (_registrar, calls) = registrar(&bus);
+        crate::attach_with(menu(), &XlibWindow, backend).unwrap();
+        assert_eq!(next_call(&calls), "RegisterWindow(0x4000001, /MenuBar)");
+    }
+
+    #[test]
+    fn test_generic_attach() {
+        let bus = TestBus::start();
+        // The built-in backend connects to the session bus.
+        std::env::set_var("DBUS_SESSION_BUS_ADDRESS", bus.address());
+        let err = crate::attach(menu(), &XlibWindow).unwrap_err();
+        assert!(err.is_no_registrar(), "{:?}", err);
+
+        let (_registrar, calls) = registrar(&bus);
+        let _attached = crate::attach(menu(), &XlibWindow).unwrap();
+        assert_eq!(next_call(&calls), "RegisterWindow(0x4000001, /MenuBar)");
+    }
+
+    #[test]
+    fn test_window_id() {
+        let mut xlib = XlibWindowHandle::empty();
+        xlib.window = 0x0400_0001;
+        let mut xcb = raw_window_handle::XcbWindowHandle::empty();
+        xcb.window = 0x0200_0003;
+        let wayland = raw_window_handle::WaylandWindowHandle::empty();
+        assert_eq!(
+            registrar::window_id(RawWindowHandle::Xlib(xlib)),
+            Some(0x0400_0001)
+        );
+        assert_eq!(
+            registrar::window_id(RawWindowHandle::Xcb(xcb)),
+            Some(0x0200_0003)
+        );
+        assert_eq!(
+            registrar::window_id(RawWindowHandle::Xlib(XlibWindowHandle::empty())),
+            None
+        );
+        assert_eq!(
+            registrar::window_id(RawWindowHandle::Wayland(wayland)),
+            None
+        );
+    }
+}
@@ -0,0 +1,131 @@
+//! Registration with the `com.canonical.AppMenu.Registrar` service.
+//!
+//! The global menu learns which window a menu belongs to from the
+//! registrar. Registrations are lost when the registrar restarts, so the
+//! window is registered again whenever the service gets a new owner.
+//!
+//! Other services that applications register with, like the tray, are
+//! watched the same way.
+
+use core::convert::TryFrom;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use raw_window_handle::RawWindowHandle;
+use zbus::blocking::fdo::DBusProxy;
+use zbus::blocking::Connection;
+use zbus::proxy::CacheProperties;
+use zbus::zvariant::ObjectPath;
+
+use super::OBJECT_PATH;
+use crate::Error;
+
+/// The well-known name of the registrar.
+pub(super) const REGISTRAR: &str = "com.canonical.AppMenu.Registrar";
+
+/// The object path of the registrar.
+pub(super) const REGISTRAR_PATH: &str = "/com/canonical/AppMenu/Registrar";
+
+#[zbus::proxy(
+    interface = "com.canonical.AppMenu.Registrar",
+    default_service = "com.canonical.AppMenu.Registrar",
+    default_path = "/com/canonical/AppMenu/Registrar",
+    gen_async = false,
+    blocking_name = "RegistrarProxy"
+)]
+trait Registrar {
+    fn register_window(&self, window_id: u32, menu_object_path: ObjectPath<'_>)
+        -> zbus::Result<()>;
+
+    fn unregister_window(&self, window_id: u32) -> zbus::Result<()>;
+}
+
+/// The X11 window id of the window, if it's an X11 window.
+pub(crate) fn window_id(window: RawWindowHandle) -> Option<u32> {
+    let id = match window {
+        RawWindowHandle::Xlib(handle) => u32::try_from(handle.window).ok()?,
+        RawWindowHandle::Xcb(handle) => handle.window,
+        _ => return None,
+    };
+    Some(id).filter(|id| *id != 0)
+}
+
+/// The window that is registered, shared with the thread that watches the
+/// registrar.
+pub(super) type Registered = Arc<Mutex<Option<u32>>>;
+
+fn proxy(connection: &Connection) -> zbus::Result<RegistrarProxy<'static>> {
+    RegistrarProxy::builder(connection)
+        .cache_properties(CacheProperties::No)
+        .build()
+}
+
+/// Register the menu as the menu of the window.
+pub(super) fn register(connection: &Connection, window: u32) -> Result<(), Error> {
+    let path = ObjectPath::from_static_str_unchecked(OBJECT_PATH);
+    let result = proxy(connection).and_then(|proxy| proxy.register_window(window, path));
+    match result {
+        Ok(()) => Ok(()),
+        Err(err) if is_missing_service(&err) => Err(Error::no_registrar()),
+        Err(err) => Err(Error::dbus(err)),
+    }
+}
+
+/// Whether a call failed because nobody owns the name it was sent to.
+pub(crate) fn is_missing_service(err: &zbus::Error) -> bool {
+    match err {
+        zbus::Error::MethodError(name, _, _) => matches!(
+            name.as_str(),
+            "org.freedesktop.DBus.Error.ServiceUnknown"
+                | "org.freedesktop.DBus.Error.NameHasNoOwner"
+        ),
+        _ => false,
+    }
+}
+
+/// Remove the window's registration.
+pub(super) fn unregister(connection: &Connection, window: u32) -> Result<(), Error> {
+    proxy(connection)
+        .and_then(|proxy| proxy.unregister_window(window))
+        .map_err(Error::dbus)
+}
+
+/// Register the window again whenever the registrar gets a new owner.
+pub(super) fn watch(connection: &Connection, registered: Registered) -> Result<(), Error> {
+    watch_owner(connection, REGISTRAR, move |connection| {
+        let window = *registered.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(window) = window {
+            // There's nobody to report failures to; the next registrar will
+            // get another chance.
+            let _ = register(connection, window);
+        }
+    })
+}
+
+/// Call a function on another thread whenever the service with the given
+/// name gets a new owner.
+///
+/// The thread stops when the connection is closed.
+pub(crate) fn watch_owner(
+    connection: &Connection,
+    name: &'static str,
+    mut f: impl FnMut(&Connection) + Send + 'static,
+) -> Result<(), Error> {
+    let changes = DBusProxy::new(connection)
+        .and_then(|proxy| proxy.receive_name_owner_changed_with_args(&[(0, name)]))
+        .map_err(Error::dbus)?;
+    let connection = connection.clone();
+    thread::Builder::new()
+        .name(format!("menubar-watch-{}", name))
+        .spawn(move || {
+            for change in changes {
+                if let Ok(args) = change.args() {
+                    if args.new_owner().is_some() {
+                        f(&connection);
+                    }
+                }
+            }
+        })
+        .map_err(Error::io)?;
+    Ok(())
+}
@@ -0,0 +1,384 @@
+//! The `org.gtk.Menus` and `org.gtk.Actions` interfaces.
+//!
+//! Every menu is exported as a group of menus. Menu 0 of a group links to
+//! the sections of the menu, which are the runs of items between
+//! separators, and the sections are the following menus of the group. The
+//! attached menu is group 0.
+//!
+//! Items that can be activated have an action, that's enabled when the item
+//! is. Checkable items have a boolean state, except for those in a radio
+//! group: the items of a group share an action with a string state, which is
+//! the target of the checked item. Each item targets its own action name.
+
+use core::convert::TryFrom;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use zbus::fdo;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{OwnedValue, Signature, Value};
+
+use crate::backend::{ItemKind, ItemProps, ItemToken, MenuToken};
+use crate::dbusmenu::interface::{Shared, Tree};
+
+/// The prefix of action names in menu items, that tells the desktop to look
+/// for them in the window's actions.
+const ACTION_PREFIX: &str = "win.";
+
+/// The attributes and links of a menu item.
+pub(super) type Attributes = HashMap<String, Value<'static>>;
+
+/// A menu of a group, as `(uuaa{sv})`.
+type GroupMenu = (u32, u32, Vec<Attributes>);
+
+/// A change to a menu, as `(uuuuaa{sv})`.
+///
+/// That's the group and the menu, the position of the change, the number of
+/// items removed there, and the items added in their place.
+pub(super) type MenuChange = (u32, u32, u32, u32, Vec<Attributes>);
+
+/// An action, as `(bgav)`.
+///
+/// That's whether the action is enabled, the type of its parameter, and its
+/// state if it has one.
+pub(super) type Action = (bool, Signature, Vec<Value<'static>>);
+
+/// The group that a menu is exported as.
+pub(super) fn group(tree: &Tree, menu: MenuToken) -> u32 {
+    if tree.root == Some(menu) {
+        0
+    } else {
+        menu.0.checked_add(1).expect("too many menus")
+    }
+}
+
+/// The menu that is exported as the given group.
+fn group_menu(tree: &Tree, group: u32) -> Option<MenuToken> {
+    let menu = match group.checked_sub(1) {
+        None => tree.root?,
+        Some(menu) => MenuToken(menu),
+    };
+    // The root is only exported as group 0.
+    (tree.menus.contains_key(&menu) && group == self::group(tree, menu)).then_some(menu)
+}
+
+/// The name of the action of an item.
+pub(super) fn action_name(item: ItemToken) -> String {
+    format!("item-{}", item.0)
+}
+
+/// The item with the given action.
+fn action_item(tree: &Tree, name: &str) -> fdo::Result<ItemToken> {
+    name.strip_prefix("item-")
+        .and_then(|token| token.parse().ok())
+        .map(ItemToken)
+        .filter(|item| action(tree, *item).is_some())
+        .ok_or_else(|| fdo::Error::InvalidArgs(format!("unknown action {}", name)))
+}
+
+/// The description of the action of an item, if it has one of its own.
+pub(super) fn action(tree: &Tree, item: ItemToken) -> Option<Action> {
+    let props = &tree.items.get(&item)?.props;
+    if props.kind != ItemKind::Item || is_radio(props) {
+        return None;
+    }
+    let state = props.checked.map(Value::from).into_iter().collect();
+    Some((props.enabled, Signature::Unit, state))
+}
+
+/// Whether the item shares the action of its radio group.
+pub(super) fn is_radio(props: &ItemProps) -> bool {
+    props.kind == ItemKind::Item && props.checked.is_some() && props.radio_group.is_some()
+}
+
+/// The name of the action of a radio group of a menu.
+///
+/// The name of the group is hex-encoded, since action names are limited to
+/// alphanumerics, dashes and dots.
+fn group_action_name(menu: MenuToken, group: &str) -> String {
+    let group: String = group.bytes().map(|byte| format!("{:02x}", byte)).collect();
+    format!("radio-{}-{}", menu.0, group)
+}
+
+/// The items of each radio group, by the name of its action.
+fn radio_groups(tree: &Tree) -> BTreeMap<String, Vec<ItemToken>> {
+    let mut groups = BTreeMap::<_, Vec<_>>::new();
+    for (menu, items) in &tree.menus {
+        for item in items {
+            let props = &tree.items[item].props;
+            if let (true, Some(group)) = (is_radio(props), &props.radio_group) {
+                let name = group_action_name(*menu, group);
+                groups.entry(name).or_default().push(*item);
+            }
+        }
+    }
+    groups
+}
+
+/// The description of the action of a radio group.
+///
+/// It's enabled if any of its items is, and its state is the target of the
+/// first checked item, or empty if none is.
+fn group_action(tree: &Tree, items: &[ItemToken]) -> Action {
+    let enabled = items.iter().any(|item| tree.items[item].props.enabled);
+    let checked = items
+        .iter()
+        .find(|item| tree.items[item].props.checked == Some(true));
+    let state = checked.map(|item| action_name(*item)).unwrap_or_default();
+    (enabled, Signature::Str, vec![Value::from(state)])
+}
+
+/// The descriptions of the actions of all radio groups, by name.
+pub(super) fn group_actions(tree: &Tree) -> BTreeMap<String, Action> {
+    radio_groups(tree)
+        .into_iter()
+        .map(|(name, items)| (name, group_action(tree, &items)))
+        .collect()
+}
+
+/// The item of the radio group with the given action, that the target refers
+/// to.
+///
+/// Returns `Ok(None)` if there's no group with that action.
+fn group_item(tree: &Tree, name: &str, target: Option<&str>) -> fdo::Result<Option<ItemToken>> {
+    let items = match radio_groups(tree).remove(name) {
+        Some(items) => items,
+        None => return Ok(None),
+    };
+    items
+        .into_iter()
+        .find(|item| Some(&*action_name(*item)) == target)
+        .map(Some)
+        .ok_or_else(|| fdo::Error::InvalidArgs(format!("invalid target for action {}", name)))
+}
+
+/// The visible items of a menu, split into sections at separators.
+fn sections(tree: &Tree, menu: MenuToken) -> Vec<Vec<ItemToken>> {
+    let mut sections = vec![Vec::new()];
+    for item in tree.menus.get(&menu).map_or(&[][..], Vec::as_slice) {
+        let props = &tree.items[item].props;
+        if props.hidden {
+            continue;
+        }
+        match props.kind {
+            ItemKind::Separator => sections.push(Vec::new()),
+            _ => sections.last_mut().unwrap().push(*item),
+        }
+    }
+    sections
+}
+
+/// The number of items in each section of a menu.
+pub(super) fn shape(tree: &Tree, menu: MenuToken) -> Vec<usize> {
+    sections(tree, menu).iter().map(Vec::len).collect()
+}
+
+fn count(n: usize) -> u32 {
+    u32::try_from(n).expect("too many items")
+}
+
+fn link(group: u32, menu: u32) -> Value<'static> {
+    Value::from((group, menu))
+}
+
+fn attributes(tree: &Tree, item: ItemToken) -> Attributes {
+    let props = &tree.items[&item].props;
+    let mut attributes = HashMap::new();
+    // Underscores mark mnemonics, which titles don't have.
+    let label = Value::from(props.title.replace('_', "__"));
+    attributes.insert("label".to_owned(), label);
+    if let ItemKind::Submenu(submenu) = props.kind {
+        let submenu = link(group(tree, submenu), 0);
+        attributes.insert(":submenu".to_owned(), submenu);
+    } else if let (true, Some(group)) = (is_radio(props), &props.radio_group) {
+        let menu = tree.items[&item].menu;
+        let action = format!("{}{}", ACTION_PREFIX, group_action_name(menu, group));
+        attributes.insert("action".to_owned(), Value::from(action));
+        attributes.insert("target".to_owned(), Value::from(action_name(item)));
+    } else {
+        let action = format!("{}{}", ACTION_PREFIX, action_name(item));
+        attributes.insert("action".to_owned(), Value::from(action));
+    }
+    if let Some(accelerator) = props.accelerator {
+        let accel = Value::from(accelerator.to_gtk_accel());
+        attributes.insert("accel".to_owned(), accel);
+    }
+    attributes
+}
+
+/// The menus of the group that a menu is exported as.
+fn group_menus(tree: &Tree, menu: MenuToken) -> Vec<GroupMenu> {
+    let group = group(tree, menu);
+    let sections = sections(tree, menu);
+    let links = (1..=count(sections.len()))
+        .map(|section| {
+            let mut attributes = HashMap::new();
+            attributes.insert(":section".to_owned(), link(group, section));
+            attributes
+        })
+        .collect();
+    let sections = sections.iter().enumerate().map(|(i, items)| {
+        let items = items.iter().map(|item| attributes(tree, *item)).collect();
+        (group, count(i + 1), items)
+    });
+    core::iter::once((group, 0, links))
+        .chain(sections)
+        .collect()
+}
+
+/// The changes that replace the menus of a group, given the number of items
+/// in each section before.
+pub(super) fn menu_changes(tree: &Tree, menu: MenuToken, old: &[usize]) -> Vec<MenuChange> {
+    let menus = group_menus(tree, menu);
+    let group = group(tree, menu);
+    // Sections that no longer exist are emptied.
+    let emptied = (menus.len()..=old.len()).map(|section| (group, count(section), Vec::new()));
+    menus
+        .into_iter()
+        .chain(emptied)
+        .map(|(group, id, items)| {
+            let removed = match id {
+                0 => old.len(),
+                section => old.get(section as usize - 1).copied().unwrap_or(0),
+            };
+            (group, id, 0, count(removed), items)
+        })
+        .collect()
+}
+
+/// The changes that empty the attached menu, given the number of items in
+/// each of its sections.
+pub(super) fn emptied(old: &[usize]) -> Vec<MenuChange> {
+    let sections = old
+        .iter()
+        .enumerate()
+        .map(|(i, removed)| (count(i + 1), *removed));
+    core::iter::once((0, old.len()))
+        .chain(sections)
+        .map(|(menu, removed)| (0, menu, 0, count(removed), Vec::new()))
+        .collect()
+}
+
+/// The menus that are served on the connection.
+pub(super) struct GtkMenus(pub(super) Arc<Shared>);
+
+#[zbus::interface(name = "org.gtk.Menus")]
+impl GtkMenus {
+    /// The menus of the given groups.
+    ///
+    /// Changes are signalled for all groups, so subscriptions aren't
+    /// tracked.
+    fn start(&self, groups: Vec<u32>) -> Vec<GroupMenu> {
+        let tree = self.0.tree();
+        groups
+            .into_iter()
+            .filter_map(|group| group_menu(&tree, group))
+            .flat_map(|menu| group_menus(&tree, menu))
+            .collect()
+    }
+
+    fn end(&self, groups: Vec<u32>) {}
+
+    #[zbus(signal)]
+    pub(super) async fn changed(
+        emitter: &SignalEmitter<'_>,
+        changes: Vec<MenuChange>,
+    ) -> zbus::Result<()>;
+}
+
+/// The actions that are served on the connection.
+pub(super) struct GtkActions(pub(super) Arc<Shared>);
+
+#[zbus::interface(name = "org.gtk.Actions")]
+impl GtkActions {
+    fn list(&self) -> Vec<String> {
+        let tree = self.0.tree();
+        let mut items: Vec<_> = tree
+            .items
+            .keys()
+            .filter(|item| action(&tree, **item).is_some())
+            .collect();
+        items.sort();
+        let items = items.into_iter().map(|item| action_name(*item));
+        items.chain(radio_groups(&tree).into_keys()).collect()
+    }
+
+    fn describe(&self, action_name: &str) -> fdo::Result<Action> {
+        let tree = self.0.tree();
+        if let Some(items) = radio_groups(&tree).get(action_name) {
+            return Ok(group_action(&tree, items));
+        }
+        let item = action_item(&tree, action_name)?;
+        Ok(action(&tree, item).unwrap())
+    }
+
+    fn describe_all(&self) -> HashMap<String, Action> {
+        let tree = self.0.tree();
+        tree.items
+            .keys()
+            .filter_map(|item| Some((action_name(*item), action(&tree, *item)?)))
+            .chain(group_actions(&tree))
+            .collect()
+    }
+
+    fn activate(
+        &self,
+        action_name: &str,
+        parameter: Vec<OwnedValue>,
+        platform_data: HashMap<String, OwnedValue>,
+    ) -> fdo::Result<()> {
+        let tree = self.0.tree();
+        // The actions of radio groups take the target of an item.
+        let target = parameter
+            .first()
+            .and_then(|value| <&str>::try_from(&**value).ok());
+        let item = match group_item(&tree, action_name, target)? {
+            Some(item) => item,
+            None => action_item(&tree, action_name)?,
+        };
+        self.0.activate(tree, item);
+        Ok(())
+    }
+
+    /// Changing the state of a checkable item activates it, since its
+    /// handler decides the state. For radio groups, that's the item that the
+    /// new state targets.
+    fn set_state(
+        &self,
+        action_name: &str,
+        value: Value<'_>,
+        platform_data: HashMap<String, OwnedValue>,
+    ) -> fdo::Result<()> {
+        let tree = self.0.tree();
+        let target = <&str>::try_from(&value).ok();
+        if let Some(item) = group_item(&tree, action_name, target)? {
+            if tree.items[&item].props.checked != Some(true) {
+                self.0.activate(tree, item);
+            }
+            return Ok(());
+        }
+        let item = action_item(&tree, action_name)?;
+        let state = bool::try_from(&value).ok();
+        match tree.items[&item].props.checked {
+            Some(checked) if state.is_some() => {
+                if state != Some(checked) {
+                    self.0.activate(tree, item);
+                }
+                Ok(())
+            }
+            _ => Err(fdo::Error::InvalidArgs(format!(
+                "invalid state for action {}",
+                action_name
+            ))),
+        }
+    }
+
+    #[zbus(signal)]
+    pub(super) async fn changed(
+        emitter: &SignalEmitter<'_>,
+        removals: Vec<String>,
+        enable_changes: HashMap<String, bool>,
+        state_changes: HashMap<String, Value<'_>>,
+        additions: HashMap<String, Action>,
+    ) -> zbus::Result<()>;
+}
@@ -0,0 +1,929 @@
+//! Linux implementation of menubars, through `org.gtk.Menus`.
+//!
+//! GNOME-family shells and HUDs read menus the way GTK applications export
+//! them: as menu models with the `org.gtk.Menus` interface, whose items
+//! refer to actions with the `org.gtk.Actions` interface. The
+//! [`GtkMenuBackend`] exports the attached menu like that, keeps it up to
+//! date as items change, and points the desktop to it with the
+//! `_GTK_MENUBAR_OBJECT_PATH` family of properties on the window.
+//!
+//! Like with the [`DbusMenuBackend`][crate::dbusmenu::DbusMenuBackend],
+//! activations are queued until [`GtkMenuBackend::dispatch`] or
+//! [`AttachedMenu::dispatch`][crate::AttachedMenu::dispatch] is called.
+
+use core::fmt;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use raw_window_handle::RawWindowHandle;
+
+use self::interface::{
+    action, action_name, emptied, group_actions, is_radio, menu_changes, shape, Action, GtkActions,
+    GtkMenus,
+};
+use crate::backend::{
+    Activation, Activator, Backend, ItemKind, ItemProperty, ItemProps, ItemToken, MenuKind,
+    MenuToken, Wakeup,
+};
+use crate::dbusmenu::export::{self, Export};
+use crate::dbusmenu::interface::Tree;
+use crate::dbusmenu::registrar::window_id;
+use crate::Error;
+
+mod interface;
+mod x11;
+
+/// The object path that menus and actions are exported at.
+pub const OBJECT_PATH: &str = "/MenuBar";
+
+/// A [`Backend`] that exports menus over D-Bus, like GTK does.
+///
+/// Clones share the connection, like those of the
+/// [`DbusMenuBackend`][crate::dbusmenu::DbusMenuBackend], and it supports
+/// the same windows.
+#[derive(Clone)]
+pub struct GtkMenuBackend(Rc<RefCell<Inner>>);
+
+struct Inner {
+    export: Export,
+    /// The changes to signal when the current batch ends.
+    batch: Option<Changes>,
+    /// Whether to set the properties of the window when attaching.
+    window_properties: bool,
+    /// The X display that the window is on, or `None` for the default one.
+    display: Option<String>,
+}
+
+/// Changes that the desktop hasn't been told about yet.
+#[derive(Default)]
+struct Changes {
+    /// The menus whose items changed, with the number of items in each of
+    /// their sections before the change.
+    menus: BTreeMap<MenuToken, Vec<usize>>,
+    /// Items whose action was removed.
+    ///
+    /// Items whose action changed its type, like by becoming checkable, are
+    /// in both `removed` and `added`.
+    removed: BTreeSet<ItemToken>,
+    added: BTreeSet<ItemToken>,
+    /// Items whose action was enabled or disabled.
+    enabled: BTreeSet<ItemToken>,
+    /// Items whose action changed its state.
+    state: BTreeSet<ItemToken>,
+    /// The actions of radio groups before the changes.
+    ///
+    /// Any change can affect the groups, so they're compared as a whole.
+    groups: Option<BTreeMap<String, Action>>,
+}
+
+impl Changes {
+    /// Note that the items of the menu are about to change.
+    fn touch(&mut self, tree: &Tree, menu: MenuToken) {
+        self.menus.entry(menu).or_insert_with(|| shape(tree, menu));
+    }
+
+    /// Note that the item, and everything in its submenu, is about to be
+    /// removed.
+    fn remove(&mut self, tree: &Tree, item: ItemToken) {
+        let node = &tree.items[&item];
+        match node.props.kind {
+            ItemKind::Item => {
+                if action(tree, item).is_some() {
+                    self.removed.insert(item);
+                }
+            }
+            ItemKind::Submenu(submenu) => {
+                for child in &tree.menus[&submenu] {
+                    self.remove(tree, *child);
+                }
+            }
+            ItemKind::Separator => {}
+        }
+    }
+}
+
+impl GtkMenuBackend {
+    /// Export menus on the session bus.
+    pub fn new() -> Result<Self, Error> {
+        Self::connect(None)
+    }
+
+    /// Export menus on the bus with the given address, like
+    /// `unix:path=/run/user/1000/bus`.
+    pub fn with_address(address: &str) -> Result<Self, Error> {
+        Self::connect(Some(address))
+    }
+
+    fn connect(address: Option<&str>) -> Result<Self, Error> {
+        let export = Export::connect(address, OBJECT_PATH, |builder, shared| {
+            builder
+                .serve_at(OBJECT_PATH, GtkMenus(Arc::clone(shared)))?
+                .serve_at(OBJECT_PATH, GtkActions(Arc::clone(shared)))
+        })?;
+        Ok(Self(Rc::new(RefCell::new(Inner {
+            export,
+            batch: None,
+            window_properties: true,
+            display: None,
+        }))))
+    }
+
+    /// The unique name of the connection that menus are exported on.
+    pub fn bus_name(&self) -> String {
+        self.0.borrow().export.bus_name()
+    }
+
+    /// The properties that point the desktop to the menus, as pairs of
+    /// property names and UTF-8 string values.
+    pub fn window_properties(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("_GTK_UNIQUE_BUS_NAME", self.bus_name()),
+            ("_GTK_MENUBAR_OBJECT_PATH", OBJECT_PATH.to_owned()),
+            ("_GTK_WINDOW_OBJECT_PATH", OBJECT_PATH.to_owned()),
+        ]
+    }
+
+    /// Set whether attaching sets the [window
+    /// properties][Self::window_properties] on the window.
+    ///
+    /// This is the default, and opens a connection to the default X display.
+    /// Disable it to set the properties through the connection that created
+    /// the window instead.
+    pub fn set_window_properties(&self, enabled: bool) {
+        self.0.borrow_mut().window_properties = enabled;
+    }
+
+    /// Set the function to call when the desktop activates an item, like
+    /// [`DbusMenuBackend::set_wakeup`][crate::dbusmenu::DbusMenuBackend::set_wakeup].
+    pub fn set_wakeup(&self, wakeup: impl Fn() + Send + Sync + 'static) {
+        self.0.borrow().export.set_wakeup(Arc::new(wakeup));
+    }
+
+    /// Call the handlers of the items that the desktop activated since the
+    /// last call, returning how many there were.
+    pub fn dispatch(&self) -> usize {
+        export::dispatch(&self.0)
+    }
+}
+
+impl AsRef<Export> for Inner {
+    fn as_ref(&self) -> &Export {
+        &self.export
+    }
+}
+
+impl Inner {
+    /// Change the tree, and tell the desktop about it unless a batch is in
+    /// progress.
+    fn change(&mut self, f: impl FnOnce(&mut Tree, &mut Changes)) -> Result<(), Error> {
+        let batching = self.batch.is_some();
+        let mut changes = self.batch.take().unwrap_or_default();
+        let mut tree = self.export.shared.tree();
+        changes.groups.get_or_insert_with(|| group_actions(&tree));
+        f(&mut tree, &mut changes);
+        drop(tree);
+        if batching {
+            self.batch = Some(changes);
+            Ok(())
+        } else {
+            self.emit(changes)
+        }
+    }
+
+    /// Tell the desktop about changes, if the menu is attached.
+    fn emit(&self, changes: Changes) -> Result<(), Error> {
+        let (menus, removals, enable_changes, state_changes, additions) = {
+            let tree = self.export.shared.tree();
+            if tree.root.is_none() {
+                return Ok(());
+            }
+            let menus: Vec<_> = changes
+                .menus
+                .iter()
+                // The menu may have been removed later in the batch.
+                .filter(|(menu, _)| tree.menus.contains_key(menu))
+                .flat_map(|(menu, old)| menu_changes(&tree, *menu, old))
+                .collect();
+            let mut removals: Vec<_> = changes
+                .removed
+                .iter()
+                .map(|item| action_name(*item))
+                .collect();
+            let mut additions: HashMap<_, _> = changes
+                .added
+                .iter()
+                .filter_map(|item| Some((action_name(*item), action(&tree, *item)?)))
+                .collect();
+            // Added actions are described in full.
+            let changed = |items: &BTreeSet<ItemToken>| {
+                items
+                    .iter()
+                    .filter(|item| !changes.added.contains(item))
+                    .filter_map(|item| Some((action_name(*item), action(&tree, *item)?)))
+                    .collect::<Vec<_>>()
+            };
+            let mut enable_changes: HashMap<_, _> = changed(&changes.enabled)
+                .into_iter()
+                .map(|(name, (enabled, _, _))| (name, enabled))
+                .collect();
+            let mut state_changes: HashMap<_, _> = changed(&changes.state)
+                .into_iter()
+                .filter_map(|(name, (_, _, mut state))| Some((name, state.pop()?)))
+                .collect();
+
+            // Radio groups are compared as a whole.
+            let old = changes.groups.unwrap_or_default();
+            let new = group_actions(&tree);
+            removals.extend(old.keys().filter(|name| !new.contains_key(*name)).cloned());
+            for (name, (enabled, signature, mut state)) in new {
+                match old.get(&name) {
+                    None => {
+                        additions.insert(name, (enabled, signature, state));
+                    }
+                    Some((old_enabled, _, old_state)) => {
+                        if enabled != *old_enabled {
+                            enable_changes.insert(name.clone(), enabled);
+                        }
+                        if state != *old_state {
+                            state_changes.insert(name, state.pop().unwrap());
+                        }
+                    }
+                }
+            }
+            (menus, removals, enable_changes, state_changes, additions)
+        };
+
+        let emitter = self.export.emitter()?;
+        if !menus.is_empty() {
+            zbus::block_on(GtkMenus::changed(&emitter, menus)).map_err(Error::dbus)?;
+        }
+        if !removals.is_empty()
+            || !enable_changes.is_empty()
+            || !state_changes.is_empty()
+            || !additions.is_empty()
+        {
+            zbus::block_on(GtkActions::changed(
+                &emitter,
+                removals,
+                enable_changes,
+                state_changes,
+                additions,
+            ))
+            .map_err(Error::dbus)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for GtkMenuBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GtkMenuBackend")
+            .field("bus_name", &self.bus_name())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Backend for GtkMenuBackend {
+    fn supports(&self, window: RawWindowHandle) -> bool {
+        matches!(window, RawWindowHandle::Xlib(_) | RawWindowHandle::Xcb(_))
+    }
+
+    fn set_activator(&mut self, activator: Activator) {
+        self.0.borrow_mut().export.activator = Some(activator);
+    }
+
+    fn create_menu(&mut self, menu: MenuToken, kind: MenuKind) -> Result<(), Error> {
+        let inner = self.0.borrow();
+        inner.export.shared.tree().menus.insert(menu, Vec::new());
+        Ok(())
+    }
+
+    fn destroy_menu(&mut self, menu: MenuToken) {
+        self.0.borrow().export.shared.tree().menus.remove(&menu);
+    }
+
+    fn insert_item(
+        &mut self,
+        menu: MenuToken,
+        index: usize,
+        item: ItemToken,
+        props: &ItemProps,
+    ) -> Result<(), Error> {
+        self.0.borrow_mut().change(|tree, changes| {
+            changes.touch(tree, menu);
+            tree.insert(menu, index, item, props);
+            if action(tree, item).is_some() {
+                changes.added.insert(item);
+            }
+        })
+    }
+
+    fn remove_item(&mut self, item: ItemToken) -> Result<(), Error> {
+        self.0.borrow_mut().change(|tree, changes| {
+            changes.touch(tree, tree.items[&item].menu);
+            changes.remove(tree, item);
+            tree.remove(item);
+        })
+    }
+
+    fn update_item(&mut self, item: ItemToken, property: &ItemProperty) -> Result<(), Error> {
+        self.0.borrow_mut().change(|tree, changes| {
+            let node = tree.items.get(&item).expect("unknown item");
+            let mut props = node.props.clone();
+            props.apply(property);
+            // Joining or leaving a radio group changes the action of the item.
+            let attributes = match property {
+                ItemProperty::Title(_)
+                | ItemProperty::Accelerator(_)
+                | ItemProperty::RadioGroup(_)
+                | ItemProperty::Hidden(_) => true,
+                ItemProperty::Enabled(_) | ItemProperty::Checked(_) => {
+                    is_radio(&props) != is_radio(&node.props)
+                }
+            };
+            if attributes {
+                changes.touch(tree, node.menu);
+            }
+
+            let old = action(tree, item);
+            tree.items.get_mut(&item).unwrap().props = props;
+            match (old, action(tree, item)) {
+                (Some((old_enabled, _, old_state)), Some((enabled, _, state))) => {
+                    if enabled != old_enabled {
+                        changes.enabled.insert(item);
+                    }
+                    if state.len() != old_state.len() {
+                        // The action became checkable, or stopped being.
+                        changes.removed.insert(item);
+                        changes.added.insert(item);
+                    } else if state != old_state {
+                        changes.state.insert(item);
+                    }
+                }
+                (Some(_), None) => {
+                    changes.removed.insert(item);
+                }
+                (None, Some(_)) => {
+                    changes.added.insert(item);
+                }
+                (None, None) => {}
+            }
+        })
+    }
+
+    fn move_item(&mut self, item: ItemToken, menu: MenuToken, index: usize) -> Result<(), Error> {
+        self.0.borrow_mut().change(|tree, changes| {
+            changes.touch(tree, tree.items[&item].menu);
+            changes.touch(tree, menu);
+            tree.move_item(item, menu, index);
+        })
+    }
+
+    fn begin_batch(&mut self) {
+        self.0.borrow_mut().batch = Some(Changes::default());
+    }
+
+    fn end_batch(&mut self) -> Result<(), Error> {
+        let mut inner = self.0.borrow_mut();
+        match inner.batch.take() {
+            Some(changes) => inner.emit(changes),
+            None => Ok(()),
+        }
+    }
+
+    fn set_wakeup(&mut self, wakeup: Wakeup) {
+        self.0.borrow().export.set_wakeup(wakeup);
+    }
+
+    fn take_activations(&mut self) -> Vec<(ItemToken, Activation)> {
+        self.0.borrow().export.take_activations()
+    }
+
+    fn attach(&mut self, menu: MenuToken, window: RawWindowHandle) -> Result<(), Error> {
+        let handle = window;
+        let window = window_id(window).ok_or_else(Error::unexpected_window_type)?;
+        let properties = self.window_properties();
+        let mut inner = self.0.borrow_mut();
+        if inner.window_properties {
+            x11::set_properties(inner.display.as_deref(), window, &properties)?;
+        }
+        {
+            let mut tree = inner.export.shared.tree();
+            tree.root = Some(menu);
+            tree.revision += 1;
+        }
+        inner.export.window = Some(handle);
+        Ok(())
+    }
+
+    /// The menu is emptied, since the properties of the window keep pointing
+    /// to it.
+    fn detach(&mut self, menu: MenuToken) {
+        let mut inner = self.0.borrow_mut();
+        inner.export.window = None;
+        let changes = {
+            let mut tree = inner.export.shared.tree();
+            let old = shape(&tree, menu);
+            tree.root = None;
+            tree.menus.clear();
+            tree.items.clear();
+            tree.parents.clear();
+            tree.revision += 1;
+            emptied(&old)
+        };
+        inner
+            .export
+            .emit_detached(|emitter| zbus::block_on(GtkMenus::changed(emitter, changes)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::TryFrom;
+    use std::collections::HashMap;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    use raw_window_handle::{HasRawWindowHandle, XlibWindowHandle};
+    use x11rb::connection::Connection as _;
+    use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _, CreateWindowAux, WindowClass};
+    use zbus::message::Type;
+    use zbus::zvariant::{OwnedValue, Value};
+    use zbus::{MatchRule, Message};
+
+    use super::*;
+    use crate::test_util::{
+        call, describe, menu, next_signal, signals, TestBus, TestDisplay, XlibWindow,
+    };
+    use crate::{AttachedMenu, Menu, MenuItem, Submenu};
+
+    type RawMenus = Vec<(u32, u32, Vec<HashMap<String, OwnedValue>>)>;
+    type RawMenuChanges = Vec<(u32, u32, u32, u32, Vec<HashMap<String, OwnedValue>>)>;
+    type RawAction = (bool, zbus::zvariant::Signature, Vec<OwnedValue>);
+
+    fn attach(bus: &TestBus) -> (GtkMenuBackend, AttachedMenu<&'static str>) {
+        let backend = GtkMenuBackend::with_address(bus.address()).unwrap();
+        // There's no X server to set properties on.
+        backend.set_window_properties(false);
+        let attached = crate::attach_with(menu(), &XlibWindow, backend.clone()).unwrap();
+        (backend, attached)
+    }
+
+    /// The menus as lines of text, with the attributes of each item.
+    fn outline(menus: RawMenus) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (group, menu, items) in menus {
+            lines.push(format!("{}.{}:", group, menu));
+            for attributes in items {
+                lines.push(format!("  {}", describe(&attributes)));
+            }
+        }
+        lines
+    }
+
+    #[test]
+    fn test_menus() {
+        let bus = TestBus::start();
+        let (backend, _attached) = attach(&bus);
+        let client = bus.connect();
+        let bus_name = backend.bus_name();
+
+        let groups = vec![0u32, 1, 2, 3, 99];
+        let reply = call(&client, &bus_name, "org.gtk.Menus", "Start", &(groups,)).unwrap();
+        let menus: RawMenus = reply.body().deserialize().unwrap();
+        // The attached menu is only group 0, and unknown groups are skipped.
+        assert_eq!(
+            outline(menus),
+            [
+                "0.0:",
+                "  :section=(uint32 0, uint32 1)",
+                "0.1:",
+                r#"  :submenu=(uint32 2, uint32 0) label="File""#,
+                r#"  :submenu=(uint32 3, uint32 0) label="Help""#,
+                "2.0:",
+                "  :section=(uint32 2, uint32 1)",
+                "  :section=(uint32 2, uint32 2)",
+                "2.1:",
+                r#"  accel="<Control>n" action="win.item-1" label="New""#,
+                r#"  action="win.item-2" label="Save__As""#,
+                "2.2:",
+                r#"  action="win.item-4" label="Autosave""#,
+                "3.0:",
+                "  :section=(uint32 3, uint32 1)",
+                "3.1:",
+            ]
+        );
+        call(&client, &bus_name, "org.gtk.Menus", "End", &(vec![0u32],)).unwrap();
+
+        let properties = backend.window_properties();
+        assert_eq!(
+            properties,
+            [
+                ("_GTK_UNIQUE_BUS_NAME", backend.bus_name()),
+                ("_GTK_MENUBAR_OBJECT_PATH", "/MenuBar".to_owned()),
+                ("_GTK_WINDOW_OBJECT_PATH", "/MenuBar".to_owned()),
+            ]
+        );
+    }
+
+    /// A window on a test display.
+    struct Window(u32);
+
+    unsafe impl HasRawWindowHandle for Window {
+        fn raw_window_handle(&self) -> RawWindowHandle {
+            let mut handle = XlibWindowHandle::empty();
+            handle.window = self.0.into();
+            RawWindowHandle::Xlib(handle)
+        }
+    }
+
+    #[test]
+    fn test_window_properties() {
+        let bus = TestBus::start();
+        let display = TestDisplay::start();
+        let (connection, screen) = x11rb::connect(Some(display.name())).unwrap();
+        let root = connection.setup().roots[screen].root;
+        let window = connection.generate_id().unwrap();
+        connection
+            .create_window(
+                x11rb::COPY_DEPTH_FROM_PARENT,
+                window,
+                root,
+                0,
+                0,
+                1,
+                1,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                x11rb::COPY_FROM_PARENT,
+                &CreateWindowAux::new(),
+            )
+            .unwrap()
+            .check()
+            .unwrap();
+        let backend = GtkMenuBackend::with_address(bus.address()).unwrap();
+        backend.0.borrow_mut().display = Some(display.name().to_owned());
+
+        // The menu isn't exported if the properties can't be set.
+        assert!(crate::attach_with(menu(), &XlibWindow, backend.clone()).is_err());
+        assert_eq!(backend.0.borrow().export.shared.tree().root, None);
+
+        let _attached = crate::attach_with(menu(), &Window(window), backend.clone()).unwrap();
+        for (name, value) in backend.window_properties() {
+            let atom = connection.intern_atom(false, name.as_bytes()).unwrap();
+            let atom = atom.reply().unwrap().atom;
+            let property = connection
+                .get_property(false, window, atom, AtomEnum::ANY, 0, u32::MAX)
+                .unwrap()
+                .reply()
+                .unwrap();
+            assert_eq!(property.value, value.as_bytes(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_detach() {
+        let bus = TestBus::start();
+        let (backend, attached) = attach(&bus);
+        let client = bus.connect();
+        let bus_name = backend.bus_name();
+        let rule = MatchRule::builder()
+            .msg_type(Type::Signal)
+            .path(OBJECT_PATH)
+            .unwrap()
+            .build();
+        let signals = signals(&client, rule);
+
+        drop(attached);
+        let (signal, message) = next_signal(&signals);
+        assert_eq!(signal, "org.gtk.Menus.Changed");
+        let (changes,): (RawMenuChanges,) = message.body().deserialize().unwrap();
+        assert_eq!(
+            changes,
+            [(0, 0, 0, 1, Vec::new()), (0, 1, 0, 2, Vec::new())]
+        );
+
+        let reply = call(&client, &bus_name, "org.gtk.Menus", "Start", &(vec![0u32],)).unwrap();
+        let menus: RawMenus = reply.body().deserialize().unwrap();
+        assert!(menus.is_empty());
+        assert!(backend.0.borrow().export.shared.tree().parents.is_empty());
+    }
+
+    #[test]
+    fn test_failed_attach() {
+        let bus = TestBus::start();
+        let backend = GtkMenuBackend::with_address(bus.address()).unwrap();
+        let client = bus.connect();
+        let bus_name = backend.bus_name();
+
+        // Populating works, but there's no window to attach to.
+        let err = crate::attach_with(menu(), &Window(0), backend.clone()).unwrap_err();
+        assert_eq!(err.to_string(), "unexpected window type");
+        let reply = call(&client, &bus_name, "org.gtk.Actions", "List", &()).unwrap();
+        let names: Vec<String> = reply.body().deserialize().unwrap();
+        assert!(names.is_empty(), "{:?}", names);
+    }
+
+    #[test]
+    fn test_actions() {
+        let bus = TestBus::start();
+        let (backend, attached) = attach(&bus);
+        let client = bus.connect();
+        let bus_name = backend.bus_name();
+        let action = |id| action_name(attached.item(id).unwrap().token());
+
+        let reply = call(&client, &bus_name, "org.gtk.Actions", "List", &()).unwrap();
+        let names: Vec<String> = reply.body().deserialize().unwrap();
+        assert_eq!(
+            names,
+            [
+                action("new"),
+                action("save-as"),
+                action("autosave"),
+                "item-5".to_owned()
+            ]
+        );
+
+        let reply = call(&client, &bus_name, "org.gtk.Actions", "DescribeAll", &()).unwrap();
+        let actions: HashMap<String, RawAction> = reply.body().deserialize().unwrap();
+        assert_eq!(actions.len(), 4);
+        let (enabled, parameter, state) = &actions[&action("save-as")];
+        assert!(!enabled);
+        assert_eq!(parameter.to_string(), "");
+        assert!(state.is_empty());
+        let (enabled, _, state) = &actions[&action("autosave")];
+        assert!(enabled);
+        assert_eq!(state.len(), 1);
+        assert!(bool::try_from(&state[0]).unwrap());
+
+        let reply = call(
+            &client,
+            &bus_name,
+            "org.gtk.Actions",
+            "Describe",
+            &(action("new"),),
+        )
+        .unwrap();
+        let (enabled, _, state): RawAction = reply.body().deserialize().unwrap();
+        assert!(enabled);
+        assert!(state.is_empty());
+
+        let activate = |name: String| {
+            let parameter: Vec<Value<'_>> = Vec::new();
+            let platform_data: HashMap<&str, Value<'_>> = HashMap::new();
+            let body = (name, parameter, platform_data);
+            call(&client, &bus_name, "org.gtk.Actions", "Activate", &body)
+        };
+        activate(action("new")).unwrap();
+        // Disabled items can't be activated.
+        activate(action("save-as")).unwrap();
+        let err = activate("item-0".to_owned()).unwrap_err();
+        assert!(err.to_string().contains("unknown action item-0"), "{}", err);
+        assert_eq!(backend.dispatch(), 1);
+        assert_eq!(attached.take_commands(), ["new"]);
+        // Activations report the window the menu is attached to.
+        let window = XlibWindow.raw_window_handle();
+        assert_eq!(backend.0.borrow().export.window, Some(window));
+
+        let set_state = |name: String, state: Value<'_>| {
+            let platform_data: HashMap<&str, Value<'_>> = HashMap::new();
+            let body = (name, state, platform_data);
+            call(&client, &bus_name, "org.gtk.Actions", "SetState", &body)
+        };
+        // Only changes of the state activate the item.
+        set_state(action("autosave"), Value::from(true)).unwrap();
+        assert_eq!(backend.dispatch(), 0);
+        set_state(action("autosave"), Value::from(false)).unwrap();
+        assert_eq!(backend.dispatch(), 1);
+        assert_eq!(attached.take_commands(), ["autosave"]);
+        set_state(action("new"), Value::from(true)).unwrap_err();
+        set_state(action("autosave"), Value::from("on")).unwrap_err();
+    }
+
+    #[test]
+    fn test_signals() {
+        let bus = TestBus::start();
+        let (backend, attached) = attach(&bus);
+        let client = bus.connect();
+        let rule = MatchRule::builder()
+            .msg_type(Type::Signal)
+            .path(OBJECT_PATH)
+            .unwrap()
+            .build();
+        let signals = signals(&client, rule);
+        type ActionChanges = (
+            Vec<String>,
+            HashMap<String, bool>,
+            HashMap<String, OwnedValue>,
+            HashMap<String, RawAction>,
+        );
+
+        let new = attached.item("new").unwrap();
+        new.set_title("New Window").unwrap();
+        let (signal, message) = next_signal(&signals);
+        assert_eq!(signal, "org.gtk.Menus.Changed");
+        let (changes,): (RawMenuChanges,) = message.body().deserialize().unwrap();
+        let changes: Vec<_> = changes
+            .into_iter()
+            .map(|(group, menu, position, removed, added)| {
+                (group, menu, position, removed, added.len())
+            })
+            .collect();
+        assert_eq!(changes, [(2, 0, 0, 2, 2), (2, 1, 0, 2, 2), (2, 2, 0, 1, 1)]);
+
+        // Hiding the only item in a section empties it.
+        let autosave = attached.item("autosave").unwrap();
+        autosave.set_hidden(true).unwrap();
+        let (_, message) = next_signal(&signals);
+        let (changes,): (RawMenuChanges,) = message.body().deserialize().unwrap();
+        assert_eq!(changes[2].3, 1);
+        assert!(changes[2].4.is_empty());
+
+        new.set_enabled(false).unwrap();
+        let (signal, message) = next_signal(&signals);
+        assert_eq!(signal, "org.gtk.Actions.Changed");
+        let (removals, enabled, state, additions): ActionChanges =
+            message.body().deserialize().unwrap();
+        assert!(removals.is_empty() && state.is_empty() && additions.is_empty());
+        assert_eq!(enabled, HashMap::from([(action_name(new.token()), false)]));
+
+        autosave.set_checked(Some(false)).unwrap();
+        let (_, message) = next_signal(&signals);
+        let (_, _, state, _): ActionChanges = message.body().deserialize().unwrap();
+        assert!(!bool::try_from(&state[&action_name(autosave.token())]).unwrap());
+
+        // Becoming checkable changes the type of the action.
+        new.set_checked(Some(true)).unwrap();
+        let (_, message) = next_signal(&signals);
+        let (removals, _, state, additions): ActionChanges = message.body().deserialize().unwrap();
+        assert_eq!(removals, [action_name(new.token())]);
+        assert!(state.is_empty());
+        assert_eq!(additions[&action_name(new.token())].2.len(), 1);
+
+        let help = attached.item("help").unwrap();
+        let about = help.push(MenuItem::new("About")).unwrap();
+        let (signal, message) = next_signal(&signals);
+        assert_eq!(signal, "org.gtk.Menus.Changed");
+        let (changes,): (RawMenuChanges,) = message.body().deserialize().unwrap();
+        let menus: Vec<_> = changes
+            .iter()
+            .map(|(group, menu, ..)| (*group, *menu))
+            .collect();
+        assert_eq!(menus, [(3, 0), (3, 1)]);
+        let (signal, message) = next_signal(&signals);
+        assert_eq!(signal, "org.gtk.Actions.Changed");
+        let (_, _, _, additions): ActionChanges = message.body().deserialize().unwrap();
+        assert!(additions.contains_key(&action_name(about.token())));
+
+        // Removing a submenu removes the actions in it.
+        let file = attached.items().remove(0);
+        file.remove().unwrap();
+        let (_, message) = next_signal(&signals);
+        let (changes,): (RawMenuChanges,) = message.body().deserialize().unwrap();
+        assert_eq!(changes[1].3, 2);
+        let (_, message) = next_signal(&signals);
+        let (removals, _, _, _): ActionChanges = message.body().deserialize().unwrap();
+        assert_eq!(removals, ["item-1", "item-2", "item-4", "item-5"]);
+
+        // A transaction is signalled once per interface.
+        attached
+            .transaction(|tx| {
+                tx.set_title(&about, "About This App")?;
+                tx.set_enabled(&about, false)?;
+                tx.set_accelerator(&about, Some("F1".parse().unwrap()))
+            })
+            .unwrap();
+        let (signal, message) = next_signal(&signals);
+        assert_eq!(signal, "org.gtk.Menus.Changed");
+        let (changes,): (RawMenuChanges,) = message.body().deserialize().unwrap();
+        assert_eq!(changes.len(), 2);
+        let (signal, _) = next_signal(&signals);
+        assert_eq!(signal, "org.gtk.Actions.Changed");
+        assert!(signals.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+
+    #[test]
+    fn test_radio_groups() {
+        let bus = TestBus::start();
+        let backend = GtkMenuBackend::with_address(bus.address()).unwrap();
+        backend.set_window_properties(false);
+        let radio = |title: &'static str, checked| {
+            MenuItem::new(title)
+                .with_id(title)
+                .with_command(title)
+                .with_checked(checked)
+                .with_radio_group("size")
+        };
+        let menu = Menu::default().with(Submenu::new(
+            "View",
+            Menu::default()
+                .with(radio("small", true))
+                .with(radio("large", false))
+                .with(MenuItem::new("Zoom").with_checked(false)),
+        ));
+        let attached = crate::attach_with(menu, &XlibWindow, backend.clone()).unwrap();
+        let client = bus.connect();
+        let bus_name = backend.bus_name();
+        let rule = MatchRule::builder()
+            .msg_type(Type::Signal)
+            .path(OBJECT_PATH)
+            .unwrap()
+            .build();
+        let signals = signals(&client, rule);
+        // The group is named after its menu, and the hex-encoded name.
+        let group = "radio-1-73697a65".to_owned();
+
+        let reply = call(&client, &bus_name, "org.gtk.Menus", "Start", &(vec![2u32],)).unwrap();
+        let menus: RawMenus = reply.body().deserialize().unwrap();
+        assert_eq!(
+            outline(menus)[3..],
+            [
+                r#"  action="win.radio-1-73697a65" label="small" target="item-1""#,
+                r#"  action="win.radio-1-73697a65" label="large" target="item-2""#,
+                r#"  action="win.item-3" label="Zoom""#,
+            ]
+        );
+
+        let reply = call(&client, &bus_name, "org.gtk.Actions", "List", &()).unwrap();
+        let names: Vec<String> = reply.body().deserialize().unwrap();
+        assert_eq!(names, ["item-3", &group]);
+        let reply = call(
+            &client,
+            &bus_name,
+            "org.gtk.Actions",
+            "Describe",
+            &(&group,),
+        )
+        .unwrap();
+        let (enabled, parameter, state): RawAction = reply.body().deserialize().unwrap();
+        assert!(enabled);
+        assert_eq!(parameter.to_string(), "s");
+        assert_eq!(<&str>::try_from(&*state[0]).unwrap(), "item-1");
+
+        let activate = |target: &str| {
+            let parameter = vec![Value::from(target)];
+            let platform_data: HashMap<&str, Value<'_>> = HashMap::new();
+            let body = (&group, parameter, platform_data);
+            call(&client, &bus_name, "org.gtk.Actions", "Activate", &body)
+        };
+        activate("item-2").unwrap();
+        assert_eq!(backend.dispatch(), 1);
+        assert_eq!(attached.take_commands(), ["large"]);
+        // Items outside of the group can't be targeted.
+        let err = activate("item-3").unwrap_err();
+        assert!(err.to_string().contains("invalid target"), "{}", err);
+
+        let set_state = |state: &str| {
+            let platform_data: HashMap<&str, Value<'_>> = HashMap::new();
+            let body = (&group, Value::from(state), platform_data);
+            call(&client, &bus_name, "org.gtk.Actions", "SetState", &body)
+        };
+        // Only targeting an unchecked item activates it.
+        set_state("item-1").unwrap();
+        assert_eq!(backend.dispatch(), 0);
+        set_state("item-2").unwrap();
+        assert_eq!(backend.dispatch(), 1);
+        assert_eq!(attached.take_commands(), ["large"]);
+
+        let (small, large) = (
+            attached.item("small").unwrap(),
+            attached.item("large").unwrap(),
+        );
+        attached
+            .transaction(|tx| {
+                tx.set_checked(&small, Some(false))?;
+                tx.set_checked(&large, Some(true))
+            })
+            .unwrap();
+        let (signal, message) = next_signal(&signals);
+        assert_eq!(signal, "org.gtk.Actions.Changed");
+        type ActionChanges = (
+            Vec<String>,
+            HashMap<String, bool>,
+            HashMap<String, OwnedValue>,
+            HashMap<String, RawAction>,
+        );
+        let (removals, enabled, state, additions): ActionChanges =
+            message.body().deserialize().unwrap();
+        assert!(removals.is_empty() && enabled.is_empty() && additions.is_empty());
+        assert_eq!(<&str>::try_from(&*state[&group]).unwrap(), "item-2");
+
+        // Leaving the group gives the item an action of its own, and removing
+        // the last item of the group removes its action.
+        small.set_radio_group(None).unwrap();
+        let (signal, _) = next_signal(&signals);
+        assert_eq!(signal, "org.gtk.Menus.Changed");
+        let (_, message) = next_signal(&signals);
+        let (removals, _, _, additions): ActionChanges = message.body().deserialize().unwrap();
+        assert!(removals.is_empty());
+        assert_eq!(additions.keys().collect::<Vec<_>>(), ["item-1"]);
+        large.remove().unwrap();
+        let (_, _) = next_signal(&signals);
+        let (_, message) = next_signal(&signals);
+        let (removals, _, _, _): ActionChanges = message.body().deserialize().unwrap();
+        assert_eq!(removals, [group]);
+    }
+}
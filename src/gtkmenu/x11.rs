@@ -0,0 +1,38 @@
+//! Setting the properties that point the desktop to the menus of a window.
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ConnectionExt as _, PropMode};
+use x11rb::wrapper::ConnectionExt as _;
+
+use crate::Error;
+
+/// Set UTF-8 string properties on a window, on the given display, or the
+/// default one.
+pub(super) fn set_properties(
+    display: Option<&str>,
+    window: u32,
+    properties: &[(&str, String)],
+) -> Result<(), Error> {
+    let (connection, _) = x11rb::connect(display).map_err(Error::x11)?;
+    let intern = |name: &str| -> Result<u32, Error> {
+        let cookie = connection
+            .intern_atom(false, name.as_bytes())
+            .map_err(Error::x11)?;
+        Ok(cookie.reply().map_err(Error::x11)?.atom)
+    };
+    let utf8_string = intern("UTF8_STRING")?;
+    for (name, value) in properties {
+        connection
+            .change_property8(
+                PropMode::REPLACE,
+                window,
+                intern(name)?,
+                utf8_string,
+                value.as_bytes(),
+            )
+            .map_err(Error::x11)?
+            .check()
+            .map_err(Error::x11)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,970 @@
+//! In-memory implementation of menus, for testing.
+//!
+//! The [`HeadlessBackend`] keeps the realized menu tree in memory, and lets
+//! you activate items like a user would. It also records every operation it
+//! receives, so you can assert what would have been sent to a native API.
+
+use core::fmt;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use raw_window_handle::{HasRawWindowHandle, RawWindowHandle, WebWindowHandle};
+
+use crate::backend::{
+    Activation, ActivationSource, Activator, Backend, ItemKind, ItemProperty, ItemProps, ItemToken,
+    MenuKind, MenuToken,
+};
+use crate::{Accelerator, AttachedMenu, Error, Menu, Modifiers};
+
+/// An operation received by a [`HeadlessBackend`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    CreateMenu {
+        menu: MenuToken,
+        kind: MenuKind,
+    },
+    DestroyMenu {
+        menu: MenuToken,
+    },
+    InsertItem {
+        menu: MenuToken,
+        index: usize,
+        item: ItemToken,
+        props: ItemProps,
+    },
+    RemoveItem {
+        item: ItemToken,
+    },
+    UpdateItem {
+        item: ItemToken,
+        property: ItemProperty,
+    },
+    MoveItem {
+        item: ItemToken,
+        menu: MenuToken,
+        index: usize,
+    },
+    Attach {
+        menu: MenuToken,
+    },
+    Detach {
+        menu: MenuToken,
+    },
+    BeginBatch,
+    EndBatch,
+}
+
+/// A [`Backend`] that keeps menus in memory.
+///
+/// This is a cheap handle; clones refer to the same menus. Keep a clone
+/// around after attaching to inspect and interact with the menu.
+///
+/// It supports every kind of window.
+#[derive(Clone, Default)]
+pub struct HeadlessBackend(Rc<RefCell<Inner>>);
+
+#[derive(Default)]
+struct Inner {
+    activator: Option<Activator>,
+    menus: HashMap<MenuToken, Vec<ItemToken>>,
+    items: HashMap<ItemToken, Node>,
+    /// The menu that was attached.
+    root: Option<MenuToken>,
+    ops: Vec<Op>,
+    /// Changes to this item fail.
+    failing: Option<ItemToken>,
+}
+
+struct Node {
+    /// The menu the item is in.
+    menu: MenuToken,
+    props: ItemProps,
+}
+
+impl Inner {
+    /// Find an item by following titles from the root menu.
+    fn find(&self, path: &[&str]) -> Option<ItemToken> {
+        let mut menu = self.root?;
+        let (last, init) = path.split_last()?;
+        for title in init {
+            let item = self.find_in(menu, title)?;
+            match self.items[&item].props.kind {
+                ItemKind::Submenu(submenu) => menu = submenu,
+                _ => return None,
+            }
+        }
+        self.find_in(menu, last)
+    }
+
+    fn find_in(&self, menu: MenuToken, title: &str) -> Option<ItemToken> {
+        self.menus[&menu].iter().copied().find(|item| {
+            let props = &self.items[item].props;
+            props.kind != ItemKind::Separator && props.title == title
+        })
+    }
+
+    /// The item that opens the given menu.
+    fn parent(&self, menu: MenuToken) -> Option<ItemToken> {
+        self.items
+            .iter()
+            .find(|(_, node)| node.props.kind == ItemKind::Submenu(menu))
+            .map(|(item, _)| *item)
+    }
+
+    /// Whether a user would be able to activate the item.
+    ///
+    /// The item and all its ancestors must be enabled and visible, and the
+    /// menu must be attached.
+    fn reachable(&self, item: ItemToken) -> bool {
+        let mut current = item;
+        loop {
+            let node = match self.items.get(&current) {
+                Some(node) => node,
+                None => return false,
+            };
+            if !node.props.enabled || node.props.hidden {
+                return false;
+            }
+            if Some(node.menu) == self.root {
+                return true;
+            }
+            match self.parent(node.menu) {
+                Some(parent) => current = parent,
+                None => return false,
+            }
+        }
+    }
+
+    fn check_failing(&self, item: ItemToken) -> Result<(), Error> {
+        if self.failing == Some(item) {
+            let err = std::io::Error::other("the item is set to fail");
+            return Err(Error::io(err));
+        }
+        Ok(())
+    }
+
+    fn forget_item(&mut self, item: ItemToken) {
+        if let Some(node) = self.items.remove(&item) {
+            if let ItemKind::Submenu(submenu) = node.props.kind {
+                for child in self.menus.remove(&submenu).unwrap_or_default() {
+                    self.forget_item(child);
+                }
+            }
+        }
+    }
+}
+
+impl HeadlessBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attach a menu to this backend.
+    ///
+    /// Convenience for [`attach_with`][crate::attach_with], since there is
+    /// no window to attach to.
+    pub fn attach<C: Clone + 'static>(&self, menu: Menu<C>) -> Result<AttachedMenu<C>, Error> {
+        crate::attach_with(menu, &NoWindow, self.clone())
+    }
+
+    /// The attached menu, if any.
+    pub fn root(&self) -> Option<MenuToken> {
+        self.0.borrow().root
+    }
+
+    /// The items in the given menu, including hidden ones.
+    pub fn items(&self, menu: MenuToken) -> Vec<ItemToken> {
+        self.0
+            .borrow()
+            .menus
+            .get(&menu)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The current properties of an item.
+    pub fn props(&self, item: ItemToken) -> Option<ItemProps> {
+        let inner = self.0.borrow();
+        inner.items.get(&item).map(|node| node.props.clone())
+    }
+
+    /// Find an item by following a path of titles from the attached menu.
+    pub fn find(&self, path: &[&str]) -> Option<ItemToken> {
+        self.0.borrow().find(path)
+    }
+
+    /// The current properties of the item at the given path.
+    pub fn props_at(&self, path: &[&str]) -> Option<ItemProps> {
+        self.find(path).and_then(|item| self.props(item))
+    }
+
+    /// Click an item, as a user would.
+    ///
+    /// Returns `false` if the item doesn't exist, is not a regular item, or
+    /// if it or one of its parents is disabled or hidden.
+    #[doc(alias = "performActionForItemAtIndex")]
+    pub fn click(&self, item: ItemToken) -> bool {
+        self.click_with_modifiers(item, Modifiers::empty())
+    }
+
+    /// Click an item while holding the given modifier keys.
+    pub fn click_with_modifiers(&self, item: ItemToken, modifiers: Modifiers) -> bool {
+        let activation = Activation {
+            modifiers,
+            ..Activation::new(ActivationSource::Click)
+        };
+        self.activate(item, activation)
+    }
+
+    fn activate(&self, item: ItemToken, activation: Activation) -> bool {
+        let activator = {
+            let inner = self.0.borrow();
+            let clickable = matches!(
+                inner.items.get(&item),
+                Some(node) if node.props.kind == ItemKind::Item
+            );
+            if !clickable || !inner.reachable(item) {
+                return false;
+            }
+            inner.activator.clone()
+        };
+
+        // The handler may call back into the backend.
+        match activator {
+            Some(activator) => {
+                activator.activate(item, activation);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Click the item at the given path, as a user would.
+    pub fn click_path(&self, path: &[&str]) -> bool {
+        match self.find(path) {
+            Some(item) => self.click(item),
+            None => false,
+        }
+    }
+
+    /// Press a key combination, triggering the item with a matching
+    /// accelerator.
+    ///
+    /// Returns `false` if no clickable item has the accelerator.
+    pub fn send_key(&self, pressed: Accelerator) -> bool {
+        let item = {
+            let inner = self.0.borrow();
+            let mut items: Vec<_> = inner
+                .items
+                .iter()
+                .filter(|(item, node)| {
+                    node.props.kind == ItemKind::Item
+                        && matches!(node.props.accelerator, Some(a) if a.matches(&pressed))
+                        && inner.reachable(**item)
+                })
+                .map(|(item, _)| *item)
+                .collect();
+            // The first item wins, like in native menus.
+            items.sort();
+            items.first().copied()
+        };
+
+        let activation = Activation {
+            modifiers: pressed.modifiers(),
+            ..Activation::new(ActivationSource::Accelerator)
+        };
+        match item {
+            Some(item) => self.activate(item, activation),
+            None => false,
+        }
+    }
+
+    /// All operations received so far.
+    pub fn ops(&self) -> Vec<Op> {
+        self.0.borrow().ops.clone()
+    }
+
+    /// Take the operations received so far, clearing the log.
+    pub fn take_ops(&self) -> Vec<Op> {
+        std::mem::take(&mut self.0.borrow_mut().ops)
+    }
+
+    /// Make inserts, updates, moves and removals of the given item fail, or
+    /// stop failing with `None`.
+    ///
+    /// Failed operations are not recorded.
+    pub fn set_failing(&self, item: Option<ItemToken>) {
+        self.0.borrow_mut().failing = item;
+    }
+}
+
+impl fmt::Debug for HeadlessBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.0.borrow();
+        f.debug_struct("HeadlessBackend")
+            .field("root", &inner.root)
+            .field("menus", &inner.menus.len())
+            .field("items", &inner.items.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Backend for HeadlessBackend {
+    fn supports(&self, window: RawWindowHandle) -> bool {
+        true
+    }
+
+    fn set_activator(&mut self, activator: Activator) {
+        self.0.borrow_mut().activator = Some(activator);
+    }
+
+    fn create_menu(&mut self, menu: MenuToken, kind: MenuKind) -> Result<(), Error> {
+        let mut inner = self.0.borrow_mut();
+        // Tokens start over for every attached menu, so a second bar would
+        // overwrite the menus and items of the first.
+        if kind == MenuKind::Bar && inner.root.is_some() {
+            return Err(Error::menu_exists());
+        }
+        inner.ops.push(Op::CreateMenu { menu, kind });
+        inner.menus.insert(menu, Vec::new());
+        Ok(())
+    }
+
+    fn destroy_menu(&mut self, menu: MenuToken) {
+        let mut inner = self.0.borrow_mut();
+        inner.ops.push(Op::DestroyMenu { menu });
+        inner.menus.remove(&menu);
+    }
+
+    fn insert_item(
+        &mut self,
+        menu: MenuToken,
+        index: usize,
+        item: ItemToken,
+        props: &ItemProps,
+    ) -> Result<(), Error> {
+        let mut inner = self.0.borrow_mut();
+        inner.check_failing(item)?;
+        inner.ops.push(Op::InsertItem {
+            menu,
+            index,
+            item,
+            props: props.clone(),
+        });
+        inner
+            .menus
+            .get_mut(&menu)
+            .expect("unknown menu token")
+            .insert(index, item);
+        let props = props.clone();
+        inner.items.insert(item, Node { menu, props });
+        Ok(())
+    }
+
+    fn remove_item(&mut self, item: ItemToken) -> Result<(), Error> {
+        let mut inner = self.0.borrow_mut();
+        inner.check_failing(item)?;
+        inner.ops.push(Op::RemoveItem { item });
+        let menu = inner.items.get(&item).expect("unknown item token").menu;
+        inner.menus.get_mut(&menu).unwrap().retain(|i| *i != item);
+        inner.forget_item(item);
+        Ok(())
+    }
+
+    fn update_item(&mut self, item: ItemToken, property: &ItemProperty) -> Result<(), Error> {
+        let mut inner = self.0.borrow_mut();
+        inner.check_failing(item)?;
+        inner.ops.push(Op::UpdateItem {
+            item,
+            property: property.clone(),
+        });
+        let node = inner.items.get_mut(&item).expect("unknown item token");
+        node.props.apply(property);
+        Ok(())
+    }
+
+    fn move_item(&mut self, item: ItemToken, menu: MenuToken, index: usize) -> Result<(), Error> {
+        let mut inner = self.0.borrow_mut();
+        inner.check_failing(item)?;
+        inner.ops.push(Op::MoveItem { item, menu, index });
+        let node = inner.items.get_mut(&item).expect("unknown item token");
+        let old = core::mem::replace(&mut node.menu, menu);
+        inner.menus.get_mut(&old).unwrap().retain(|i| *i != item);
+        inner
+            .menus
+            .get_mut(&menu)
+            .expect("unknown menu token")
+            .insert(index, item);
+        Ok(())
+    }
+
+    fn begin_batch(&mut self) {
+        self.0.borrow_mut().ops.push(Op::BeginBatch);
+    }
+
+    fn end_batch(&mut self) -> Result<(), Error> {
+        self.0.borrow_mut().ops.push(Op::EndBatch);
+        Ok(())
+    }
+
+    fn attach(&mut self, menu: MenuToken, window: RawWindowHandle) -> Result<(), Error> {
+        let mut inner = self.0.borrow_mut();
+        if inner.root.is_some() {
+            return Err(Error::menu_exists());
+        }
+        inner.ops.push(Op::Attach { menu });
+        inner.root = Some(menu);
+        Ok(())
+    }
+
+    fn detach(&mut self, menu: MenuToken) {
+        let mut inner = self.0.borrow_mut();
+        inner.ops.push(Op::Detach { menu });
+        inner.root = None;
+        inner.menus.clear();
+        inner.items.clear();
+    }
+}
+
+/// Stand-in for a window when attaching to a backend that doesn't need
+/// one, like a [`HeadlessBackend`].
+pub(crate) struct NoWindow;
+
+impl NoWindow {
+    /// Whether the handle is the stand-in, rather than a real window.
+    pub(crate) fn is(handle: RawWindowHandle) -> bool {
+        handle == NoWindow.raw_window_handle()
+    }
+}
+
+unsafe impl HasRawWindowHandle for NoWindow {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        RawWindowHandle::Web(WebWindowHandle::empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::{MenuItem, Separator, Submenu};
+
+    fn counter() -> (Rc<Cell<u32>>, impl FnMut() + 'static) {
+        let count = Rc::new(Cell::new(0));
+        let handler_count = Rc::clone(&count);
+        (count, move || handler_count.set(handler_count.get() + 1))
+    }
+
+    #[test]
+    fn test_ops() {
+        let backend = HeadlessBackend::new();
+        let menu = Menu::new().with(Submenu::new(
+            "File",
+            Menu::new()
+                .with(MenuItem::new("Save").with_accelerator("Ctrl+S".parse().unwrap()))
+                .with(Separator),
+        ));
+        let attached = backend.attach(menu).unwrap();
+
+        let save = ItemProps {
+            accelerator: Some("Ctrl+S".parse().unwrap()),
+            ..ItemProps::from_entry::<()>(&MenuItem::new("Save").into(), None)
+        };
+        let file = ItemProps::from_entry(
+            &Submenu::new("File", Menu::new()).into(),
+            Some(MenuToken(1)),
+        );
+        assert_eq!(
+            backend.take_ops(),
+            [
+                Op::CreateMenu {
+                    menu: MenuToken(0),
+                    kind: MenuKind::Bar,
+                },
+                Op::CreateMenu {
+                    menu: MenuToken(1),
+                    kind: MenuKind::Submenu,
+                },
+                Op::InsertItem {
+                    menu: MenuToken(0),
+                    index: 0,
+                    item: ItemToken(0),
+                    props: file,
+                },
+                Op::InsertItem {
+                    menu: MenuToken(1),
+                    index: 0,
+                    item: ItemToken(1),
+                    props: save,
+                },
+                Op::InsertItem {
+                    menu: MenuToken(1),
+                    index: 1,
+                    item: ItemToken(2),
+                    props: ItemProps::separator(),
+                },
+                Op::Attach { menu: MenuToken(0) },
+            ]
+        );
+        assert!(backend.ops().is_empty());
+
+        drop(attached);
+        assert_eq!(backend.take_ops(), [Op::Detach { menu: MenuToken(0) }]);
+        assert_eq!(backend.root(), None);
+    }
+
+    #[test]
+    fn test_inspect() {
+        let backend = HeadlessBackend::new();
+        let menu = Menu::new().with(Submenu::new(
+            "View",
+            Menu::new()
+                .with(MenuItem::new("Word Wrap").with_checked(true))
+                .with(MenuItem::new("Zoom").with_enabled(false)),
+        ));
+        let attached = backend.attach(menu).unwrap();
+
+        assert_eq!(backend.root(), Some(attached.root()));
+        assert_eq!(backend.items(attached.root()).len(), 1);
+        let wrap = backend.props_at(&["View", "Word Wrap"]).unwrap();
+        assert_eq!(wrap.checked, Some(true));
+        let zoom = backend.props_at(&["View", "Zoom"]).unwrap();
+        assert!(!zoom.enabled);
+        assert!(backend.find(&["View", "Nothing"]).is_none());
+        assert!(backend.find(&["View", "Zoom", "Nothing"]).is_none());
+        assert!(backend.find(&[]).is_none());
+    }
+
+    #[test]
+    fn test_click() {
+        let backend = HeadlessBackend::new();
+        let (open, open_handler) = counter();
+        let (close, close_handler) = counter();
+        let (hidden, hidden_handler) = counter();
+        let menu = Menu::new()
+            .with(Submenu::new(
+                "File",
+                Menu::new()
+                    .with(MenuItem::new("Open").with_handler(open_handler))
+                    .with(
+                        MenuItem::new("Close")
+                            .with_enabled(false)
+                            .with_handler(close_handler),
+                    )
+                    .with(Separator),
+            ))
+            .with(
+                Submenu::new(
+                    "Hidden",
+                    Menu::new().with(MenuItem::new("Item").with_handler(hidden_handler)),
+                )
+                .with_hidden(true),
+            );
+        let _attached = backend.attach(menu).unwrap();
+
+        assert!(backend.click_path(&["File", "Open"]));
+        let item = backend.find(&["File", "Open"]).unwrap();
+        assert!(backend.click(item));
+        assert_eq!(open.get(), 2);
+
+        // Disabled items, items in hidden submenus, and submenus can't be clicked.
+        assert!(!backend.click_path(&["File", "Close"]));
+        assert_eq!(close.get(), 0);
+        assert!(!backend.click_path(&["Hidden", "Item"]));
+        assert_eq!(hidden.get(), 0);
+        assert!(!backend.click_path(&["File"]));
+        assert!(!backend.click_path(&["Edit"]));
+        assert!(!backend.click(ItemToken(42)));
+    }
+
+    #[test]
+    fn test_send_key() {
+        let backend = HeadlessBackend::new();
+        let (first, first_handler) = counter();
+        let (second, second_handler) = counter();
+        let menu = Menu::new().with(Submenu::new(
+            "File",
+            Menu::new()
+                .with(
+                    MenuItem::new("New")
+                        .with_accelerator("CmdOrCtrl+N".parse().unwrap())
+                        .with_handler(first_handler),
+                )
+                .with(
+                    MenuItem::new("New Window")
+                        .with_accelerator("CmdOrCtrl+N".parse().unwrap())
+                        .with_handler(second_handler),
+                ),
+        ));
+        let _attached = backend.attach(menu).unwrap();
+
+        let primary = if cfg!(target_os = "macos") {
+            "Cmd+N"
+        } else {
+            "Ctrl+N"
+        };
+        assert!(backend.send_key(primary.parse().unwrap()));
+        assert_eq!((first.get(), second.get()), (1, 0));
+        assert!(!backend.send_key("Ctrl+M".parse().unwrap()));
+        assert!(!backend.send_key("Ctrl+Shift+N".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_commands() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Command {
+            New,
+            Quit,
+        }
+
+        let backend = HeadlessBackend::new();
+        let menu = Menu::default().with(Submenu::new(
+            "File",
+            Menu::default()
+                .with(
+                    MenuItem::new("New")
+                        .with_accelerator("CmdOrCtrl+N".parse().unwrap())
+                        .with_command(Command::New),
+                )
+                .with(MenuItem::new("Quit").with_command(Command::Quit)),
+        ));
+        let attached = backend.attach(menu).unwrap();
+
+        assert!(backend.click_path(&["File", "Quit"]));
+        assert!(backend.send_key("CmdOrCtrl+N".parse::<Accelerator>().unwrap()));
+        assert_eq!(attached.take_commands(), [Command::Quit, Command::New]);
+    }
+
+    #[test]
+    fn test_remove_handle() {
+        let backend = HeadlessBackend::new();
+        let menu = Menu::new().with(Submenu::new(
+            "Edit",
+            Menu::new()
+                .with(MenuItem::new("Undo").with_id("undo"))
+                .with(MenuItem::new("Redo").with_id("redo")),
+        ));
+        let attached = backend.attach(menu).unwrap();
+        let undo = attached.item("undo").unwrap();
+        assert_eq!(backend.find(&["Edit", "Undo"]), Some(undo.token()));
+
+        backend.take_ops();
+        undo.remove().unwrap();
+        assert_eq!(backend.take_ops(), [Op::RemoveItem { item: undo.token() }]);
+        assert_eq!(backend.find(&["Edit", "Undo"]), None);
+        assert!(!backend.click(undo.token()));
+        assert!(undo.remove().unwrap_err().is_item_removed());
+
+        // Other handles are unaffected.
+        let redo = attached.item("redo").unwrap();
+        assert_eq!(redo.title().unwrap(), "Redo");
+        assert!(backend.click_path(&["Edit", "Redo"]));
+    }
+
+    #[test]
+    fn test_mutate_handle() {
+        let backend = HeadlessBackend::new();
+        let menu = Menu::new().with(
+            Submenu::new(
+                "Edit",
+                Menu::new()
+                    .with(MenuItem::new("Undo").with_id("undo"))
+                    .with(MenuItem::new("Word Wrap").with_id("wrap"))
+                    .with(Separator),
+            )
+            .with_id("edit"),
+        );
+        let attached = backend.attach(menu).unwrap();
+        let undo = attached.item("undo").unwrap();
+        let wrap = attached.item("wrap").unwrap();
+        let edit = attached.item("edit").unwrap();
+        backend.take_ops();
+
+        undo.set_enabled(false).unwrap();
+        assert!(!backend.click_path(&["Edit", "Undo"]));
+        undo.set_enabled(true).unwrap();
+        assert!(backend.click_path(&["Edit", "Undo"]));
+
+        undo.set_title("Undo Typing").unwrap();
+        assert_eq!(undo.title().unwrap(), "Undo Typing");
+        assert_eq!(backend.find(&["Edit", "Undo"]), None);
+        assert_eq!(backend.find(&["Edit", "Undo Typing"]), Some(undo.token()));
+
+        let accel: Accelerator = "CmdOrCtrl+Z".parse().unwrap();
+        undo.set_accelerator(Some(accel)).unwrap();
+        assert!(backend.send_key(accel));
+        undo.set_accelerator(None).unwrap();
+        assert!(!backend.send_key(accel));
+
+        wrap.set_checked(Some(true)).unwrap();
+        assert_eq!(backend.props(wrap.token()).unwrap().checked, Some(true));
+        assert_eq!(wrap.checked().unwrap(), Some(true));
+
+        edit.set_hidden(true).unwrap();
+        assert!(edit.hidden().unwrap());
+        assert!(!backend.click_path(&["Edit", "Word Wrap"]));
+        edit.set_title("Editing").unwrap();
+        assert!(backend.find(&["Editing", "Word Wrap"]).is_some());
+
+        assert_eq!(
+            backend.take_ops(),
+            [
+                (undo.token(), ItemProperty::Enabled(false)),
+                (undo.token(), ItemProperty::Enabled(true)),
+                (undo.token(), ItemProperty::Title("Undo Typing".into())),
+                (undo.token(), ItemProperty::Accelerator(Some(accel))),
+                (undo.token(), ItemProperty::Accelerator(None)),
+                (wrap.token(), ItemProperty::Checked(Some(true))),
+                (edit.token(), ItemProperty::Hidden(true)),
+                (edit.token(), ItemProperty::Title("Editing".into())),
+            ]
+            .map(|(item, property)| Op::UpdateItem { item, property })
+        );
+
+        // Unchanged values aren't sent to the backend.
+        wrap.set_checked(Some(true)).unwrap();
+        undo.set_title("Undo Typing").unwrap();
+        assert_eq!(backend.take_ops(), []);
+    }
+
+    #[test]
+    fn test_mutate_unsupported() {
+        let backend = HeadlessBackend::new();
+        let menu = Menu::new().with(Submenu::new("File", Menu::new()).with_id("file"));
+        let attached = backend.attach(menu).unwrap();
+        let file = attached.item("file").unwrap();
+        backend.take_ops();
+
+        let err = file.set_checked(Some(true)).unwrap_err();
+        assert_eq!(err.to_string(), "checkmark is not supported on submenus");
+        let err = file.set_accelerator(Some("Ctrl+F".parse().unwrap()));
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "accelerator is not supported on submenus"
+        );
+        assert_eq!(backend.take_ops(), []);
+        assert_eq!(file.checked().unwrap(), None);
+
+        file.remove().unwrap();
+        assert!(file.set_enabled(false).unwrap_err().is_item_removed());
+    }
+
+    fn titles(backend: &HeadlessBackend, menu: MenuToken) -> Vec<String> {
+        backend
+            .items(menu)
+            .into_iter()
+            .map(|item| backend.props(item).unwrap().title)
+            .collect()
+    }
+
+    #[test]
+    fn test_insert_remove() {
+        let backend = HeadlessBackend::new();
+        let menu = Menu::new().with(
+            Submenu::new(
+                "File",
+                Menu::new()
+                    .with(MenuItem::new("New").with_id("new"))
+                    .with(MenuItem::new("Open").with_id("open"))
+                    .with(MenuItem::new("Close")),
+            )
+            .with_id("file"),
+        );
+        let attached = backend.attach(menu).unwrap();
+        let root = backend.root().unwrap();
+        let file = attached.item("file").unwrap();
+        let submenu = match backend.props(file.token()).unwrap().kind {
+            ItemKind::Submenu(submenu) => submenu,
+            kind => panic!("unexpected kind {:?}", kind),
+        };
+
+        let clicked = Rc::new(Cell::new(false));
+        let handler_clicked = Rc::clone(&clicked);
+        let save = file
+            .insert(
+                1,
+                MenuItem::new("Save").with_handler(move || handler_clicked.set(true)),
+            )
+            .unwrap();
+        assert_eq!(save.index().unwrap(), 1);
+        assert!(backend.click_path(&["File", "Save"]));
+        assert!(clicked.get());
+        attached.push(Submenu::new("Edit", Menu::new())).unwrap();
+        assert_eq!(titles(&backend, root), ["File", "Edit"]);
+
+        // By id, by index and by handle.
+        attached.remove("open").unwrap();
+        file.remove_at(2).unwrap();
+        assert_eq!(titles(&backend, submenu), ["New", "Save"]);
+        save.remove().unwrap();
+        attached.remove_at(1).unwrap();
+        assert_eq!(titles(&backend, root), ["File"]);
+        assert_eq!(titles(&backend, submenu), ["New"]);
+
+        let err = attached.remove("open").unwrap_err();
+        assert_eq!(err.to_string(), "no menu item has the id `open`");
+        let new = attached.item("new").unwrap();
+        let err = new.push(MenuItem::new("Nested")).unwrap_err();
+        assert_eq!(err.to_string(), "the parent is not a submenu");
+    }
+
+    #[test]
+    fn test_move() {
+        let backend = HeadlessBackend::new();
+        let count = Rc::new(Cell::new(0));
+        let handler_count = Rc::clone(&count);
+        let menu = Menu::new()
+            .with(
+                Submenu::new(
+                    "File",
+                    Menu::new()
+                        .with(MenuItem::new("New"))
+                        .with(MenuItem::new("Recent").with_id("recent"))
+                        .with(
+                            MenuItem::new("Quit")
+                                .with_id("quit")
+                                .with_handler(move || handler_count.set(handler_count.get() + 1)),
+                        ),
+                )
+                .with_id("file"),
+            )
+            .with(Submenu::new("Edit", Menu::new()).with_id("edit"));
+        let attached = backend.attach(menu).unwrap();
+        let quit = attached.item("quit").unwrap();
+        let edit = attached.item("edit").unwrap();
+        backend.take_ops();
+
+        quit.move_to(0).unwrap();
+        assert_eq!(
+            backend.take_ops(),
+            [Op::MoveItem {
+                item: quit.token(),
+                menu: MenuToken(1),
+                index: 0,
+            }]
+        );
+        let file_items = backend.items(MenuToken(1));
+        assert_eq!(file_items[0], quit.token());
+
+        // Submenus keep their items, and items keep their handlers.
+        let recent = attached.item("recent").unwrap();
+        let recent_menu = recent.detach().unwrap();
+        let recent = attached
+            .item("file")
+            .unwrap()
+            .push(Submenu::new("Open Recent", Menu::new().with(recent_menu)))
+            .unwrap();
+        recent.move_into(Some(&edit), 0).unwrap();
+        quit.move_into(Some(&recent), 1).unwrap();
+        assert!(backend.click_path(&["Edit", "Open Recent", "Quit"]));
+        assert_eq!(count.get(), 1);
+        let recent_item = attached.item("recent").unwrap();
+        assert_eq!(
+            backend.find(&["Edit", "Open Recent", "Recent"]),
+            Some(recent_item.token())
+        );
+        edit.move_to(0).unwrap();
+        assert_eq!(titles(&backend, backend.root().unwrap()), ["Edit", "File"]);
+        assert_eq!(titles(&backend, MenuToken(1)), ["New"]);
+    }
+
+    #[test]
+    fn test_detach() {
+        let first = HeadlessBackend::new();
+        let second = HeadlessBackend::new();
+        let count = Rc::new(Cell::new(0));
+        let handler_count = Rc::clone(&count);
+        let menu = Menu::new().with(
+            Submenu::new(
+                "Window",
+                Menu::new()
+                    .with(
+                        MenuItem::new("Minimize")
+                            .with_id("minimize")
+                            .with_accelerator("CmdOrCtrl+M".parse().unwrap())
+                            .with_role(crate::Role::Minimize)
+                            .with_handler(move || handler_count.set(handler_count.get() + 1)),
+                    )
+                    .with(Separator)
+                    .with(
+                        MenuItem::new("Zoom")
+                            .with_checked(false)
+                            .with_radio_group("size")
+                            .with_enabled(false),
+                    ),
+            )
+            .with_id("window"),
+        );
+        let source = first.attach(menu).unwrap();
+        let target = second.attach(Menu::new()).unwrap();
+
+        let handle = source.item("window").unwrap();
+        let window = handle.detach().unwrap();
+        assert!(handle.title().unwrap_err().is_item_removed());
+        assert!(source.item("minimize").is_none());
+        assert!(first.find(&["Window"]).is_none());
+
+        let submenu = window.as_submenu().unwrap();
+        assert_eq!(submenu.id().unwrap(), "window");
+        let minimize = submenu.menu().find_id("minimize").unwrap();
+        let minimize = minimize.as_item().unwrap();
+        assert_eq!(minimize.role(), Some(crate::Role::Minimize));
+        assert!(minimize.has_handler());
+        let zoom = submenu.menu().find(&["Zoom"]).unwrap().as_item().unwrap();
+        assert_eq!(zoom.checked(), Some(false));
+        assert_eq!(zoom.radio_group(), Some("size"));
+        assert!(!zoom.enabled());
+
+        target.push(window).unwrap();
+        assert!(second.send_key("CmdOrCtrl+M".parse().unwrap()));
+        assert_eq!(count.get(), 1);
+        assert!(target.item("minimize").is_some());
+        assert_eq!(second.items(MenuToken(1)).len(), 3);
+        let zoom = second.props_at(&["Window", "Zoom"]).unwrap();
+        assert_eq!(zoom.radio_group.as_deref(), Some("size"));
+    }
+
+    #[test]
+    fn test_attach_twice() {
+        let backend = HeadlessBackend::new();
+        let (quit, quit_handler) = counter();
+        let (copy, copy_handler) = counter();
+        let file = Menu::new().with(MenuItem::new("Quit").with_handler(quit_handler));
+        let menu = Menu::new().with(Submenu::new("File", file));
+        let attached = backend.attach(menu).unwrap();
+
+        let edit = Menu::new().with(MenuItem::new("Copy").with_handler(copy_handler));
+        let menu = Menu::new().with(Submenu::new("Edit", edit));
+        assert!(backend.attach(menu).is_err());
+
+        // The first menu is untouched, and still dispatches to its handlers.
+        assert!(backend.find(&["Edit", "Copy"]).is_none());
+        assert!(backend.click_path(&["File", "Quit"]));
+        assert_eq!(quit.get(), 1);
+        assert_eq!(copy.get(), 0);
+
+        drop(attached);
+        assert!(backend.attach(Menu::new()).is_ok());
+    }
+
+    #[test]
+    fn test_failed_attach() {
+        let backend = HeadlessBackend::new();
+        let menu =
+            || Menu::new().with(Submenu::new("File", Menu::new().with(MenuItem::new("New"))));
+        backend.set_failing(Some(ItemToken(1)));
+        assert!(backend.attach(menu()).is_err());
+
+        // What was built before the failure is torn down again.
+        let ops = backend.take_ops();
+        assert_eq!(ops.last(), Some(&Op::Detach { menu: MenuToken(0) }));
+        assert!(backend.items(MenuToken(0)).is_empty());
+        assert!(backend.find(&["File"]).is_none());
+
+        backend.set_failing(None);
+        let _attached = backend.attach(menu()).unwrap();
+        assert!(backend.find(&["File", "New"]).is_some());
+    }
+}
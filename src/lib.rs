@@ -13,11 +13,46 @@ pub mod appkit;
 #[cfg(windows)]
 pub mod win32;
 
+pub mod accelerator;
+mod attached;
+pub mod backend;
+#[cfg(all(unix, not(target_os = "macos")))]
+pub mod dbusmenu;
+#[cfg(all(unix, not(target_os = "macos")))]
+pub mod gtkmenu;
+pub mod headless;
+mod model;
+mod panic;
+pub mod role;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(feature = "software")]
+pub mod software;
 #[cfg(test)]
 mod test_util;
+#[cfg(all(unix, not(target_os = "macos")))]
+pub mod tray;
 
 use core::fmt;
 
+use self::backend::ItemToken;
+
+pub use self::accelerator::{Accelerator, Key, Modifiers};
+pub use self::attached::{
+    attach, attach_with, update, ActivationContext, AttachedMenu, HandlerContext, ItemHandle,
+    Transaction,
+};
+#[cfg(feature = "events")]
+pub use self::attached::{Events, MenuEvent, Overflow};
+pub use self::backend::{register_backend, ActivationSource, Backend};
+pub use self::model::{ItemId, Menu, MenuCommand, MenuEntry, MenuItem, Separator, Submenu};
+pub use self::panic::{
+    panic_policy, set_error_hook, set_panic_hook, set_panic_policy, HandlerPanic, PanicPolicy,
+};
+pub use self::role::Role;
+#[cfg(feature = "macros")]
+pub use menubar_macros::{menu, Menu};
+
 /// Error that can occur during operation of `menubar`.
 pub struct Error(Box<Impl>);
 
@@ -30,6 +65,56 @@ enum Impl {
 
     /// This isn't the window type we expected.
     UnexpectedWindowType,
+
+    /// Two entries in the attached menu have the same id.
+    DuplicateId(ItemId),
+
+    /// The item referred to by a handle no longer exists.
+    ItemRemoved(Option<ItemId>),
+
+    /// No item has the given id.
+    UnknownId(ItemId),
+
+    /// An entry can't be inserted into or moved to the given parent.
+    InvalidParent(&'static str),
+
+    /// The handle belongs to a different attached menu.
+    DifferentMenu,
+
+    /// The index is larger than the number of entries in the menu.
+    IndexOutOfBounds { index: usize, len: usize },
+
+    /// The property can't be set on this kind of item.
+    Unsupported {
+        property: &'static str,
+        kind: &'static str,
+    },
+
+    /// The backend failed to change an item in a transaction.
+    ItemFailed {
+        item: ItemToken,
+        id: Option<ItemId>,
+        error: Error,
+    },
+
+    /// Communicating over D-Bus failed.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    Dbus(zbus::Error),
+
+    /// Communicating with the X server failed.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    X11(Box<dyn std::error::Error + Send + Sync>),
+
+    /// The data isn't a font that can be drawn with.
+    #[cfg(feature = "software")]
+    Font(ab_glyph::InvalidFont),
+
+    /// All command IDs of the thread are in use.
+    #[cfg(windows)]
+    TooManyItems,
+
+    /// No service is running that shows the menus of windows.
+    NoRegistrar,
 }
 
 impl fmt::Debug for Error {
@@ -38,6 +123,36 @@ impl fmt::Debug for Error {
             Impl::Io(io) => fmt::Debug::fmt(io, f),
             Impl::MenuExists => f.write_str("MenuExists"),
             Impl::UnexpectedWindowType => f.write_str("UnexpectedWindowType"),
+            Impl::DuplicateId(id) => f.debug_tuple("DuplicateId").field(id).finish(),
+            Impl::ItemRemoved(id) => f.debug_tuple("ItemRemoved").field(id).finish(),
+            Impl::UnknownId(id) => f.debug_tuple("UnknownId").field(id).finish(),
+            Impl::InvalidParent(reason) => f.debug_tuple("InvalidParent").field(reason).finish(),
+            Impl::DifferentMenu => f.write_str("DifferentMenu"),
+            Impl::IndexOutOfBounds { index, len } => f
+                .debug_struct("IndexOutOfBounds")
+                .field("index", index)
+                .field("len", len)
+                .finish(),
+            Impl::Unsupported { property, kind } => f
+                .debug_struct("Unsupported")
+                .field("property", property)
+                .field("kind", kind)
+                .finish(),
+            Impl::ItemFailed { item, id, error } => f
+                .debug_struct("ItemFailed")
+                .field("item", item)
+                .field("id", id)
+                .field("error", error)
+                .finish(),
+            #[cfg(all(unix, not(target_os = "macos")))]
+            Impl::Dbus(err) => f.debug_tuple("Dbus").field(err).finish(),
+            #[cfg(all(unix, not(target_os = "macos")))]
+            Impl::X11(err) => f.debug_tuple("X11").field(err).finish(),
+            #[cfg(feature = "software")]
+            Impl::Font(err) => f.debug_tuple("Font").field(err).finish(),
+            #[cfg(windows)]
+            Impl::TooManyItems => f.write_str("TooManyItems"),
+            Impl::NoRegistrar => f.write_str("NoRegistrar"),
         }
     }
 }
@@ -48,6 +163,37 @@ impl fmt::Display for Error {
             Impl::Io(io) => fmt::Display::fmt(io, f),
             Impl::MenuExists => f.write_str("a menu already exists for the given menu target"),
             Impl::UnexpectedWindowType => f.write_str("unexpected window type"),
+            Impl::DuplicateId(id) => write!(f, "duplicate item id `{}`", id),
+            Impl::ItemRemoved(Some(id)) => write!(f, "menu item `{}` has been removed", id),
+            Impl::ItemRemoved(None) => f.write_str("menu item has been removed"),
+            Impl::UnknownId(id) => write!(f, "no menu item has the id `{}`", id),
+            Impl::InvalidParent(reason) => f.write_str(reason),
+            Impl::DifferentMenu => f.write_str("the item is in a different menu"),
+            Impl::IndexOutOfBounds { index, len } => write!(
+                f,
+                "index {} is out of bounds for a menu with {} entries",
+                index, len
+            ),
+            Impl::Unsupported { property, kind } => {
+                write!(f, "{} is not supported on {}", property, kind)
+            }
+            Impl::ItemFailed {
+                id: Some(id),
+                error,
+                ..
+            } => write!(f, "failed to change menu item `{}`: {}", id, error),
+            Impl::ItemFailed { item, error, .. } => {
+                write!(f, "failed to change menu item {}: {}", item.0, error)
+            }
+            #[cfg(all(unix, not(target_os = "macos")))]
+            Impl::Dbus(err) => write!(f, "D-Bus error: {}", err),
+            #[cfg(all(unix, not(target_os = "macos")))]
+            Impl::X11(err) => write!(f, "X11 error: {}", err),
+            #[cfg(feature = "software")]
+            Impl::Font(err) => write!(f, "invalid font: {}", err),
+            #[cfg(windows)]
+            Impl::TooManyItems => f.write_str("too many menu items on this thread"),
+            Impl::NoRegistrar => f.write_str("no global menu registrar is running"),
         }
     }
 }
@@ -56,12 +202,55 @@ impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match &*self.0 {
             Impl::Io(io) => Some(io),
+            Impl::ItemFailed { error, .. } => Some(error),
+            #[cfg(all(unix, not(target_os = "macos")))]
+            Impl::Dbus(err) => Some(err),
+            #[cfg(all(unix, not(target_os = "macos")))]
+            Impl::X11(err) => Some(&**err),
+            #[cfg(feature = "software")]
+            Impl::Font(err) => Some(err),
             _ => None,
         }
     }
 }
 
 impl Error {
+    /// Whether the error occurred because an [`ItemHandle`] was used after
+    /// its item was removed, or after the menu was dropped.
+    pub fn is_item_removed(&self) -> bool {
+        matches!(&*self.0, Impl::ItemRemoved(_))
+    }
+
+    /// Whether the backend failed to change an item in
+    /// [`AttachedMenu::transaction`].
+    pub fn is_item_failed(&self) -> bool {
+        matches!(&*self.0, Impl::ItemFailed { .. })
+    }
+
+    /// The id of the item that the backend failed to change in
+    /// [`AttachedMenu::transaction`].
+    ///
+    /// Returns `None` for other errors, and for items without an id.
+    pub fn failed_item(&self) -> Option<&ItemId> {
+        match &*self.0 {
+            Impl::ItemFailed { id, .. } => id.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Whether the error occurred because there's no global menu to show
+    /// the menu in.
+    ///
+    /// This only happens on Linux, with desktops that don't have a global
+    /// menu.
+    pub fn is_no_registrar(&self) -> bool {
+        matches!(&*self.0, Impl::NoRegistrar)
+    }
+
+    fn io(err: std::io::Error) -> Self {
+        Impl::Io(err).into()
+    }
+
     fn last_io_error() -> Self {
         Impl::Io(std::io::Error::last_os_error()).into()
     }
@@ -73,6 +262,62 @@ impl Error {
     fn unexpected_window_type() -> Self {
         Impl::UnexpectedWindowType.into()
     }
+
+    fn duplicate_id(id: ItemId) -> Self {
+        Impl::DuplicateId(id).into()
+    }
+
+    fn item_removed(id: Option<ItemId>) -> Self {
+        Impl::ItemRemoved(id).into()
+    }
+
+    fn unknown_id(id: &str) -> Self {
+        Impl::UnknownId(ItemId::from(id.to_owned())).into()
+    }
+
+    fn invalid_parent(reason: &'static str) -> Self {
+        Impl::InvalidParent(reason).into()
+    }
+
+    fn different_menu() -> Self {
+        Impl::DifferentMenu.into()
+    }
+
+    fn index_out_of_bounds(index: usize, len: usize) -> Self {
+        Impl::IndexOutOfBounds { index, len }.into()
+    }
+
+    fn unsupported(property: &'static str, kind: &'static str) -> Self {
+        Impl::Unsupported { property, kind }.into()
+    }
+
+    fn item_failed(item: ItemToken, id: Option<ItemId>, error: Error) -> Self {
+        Impl::ItemFailed { item, id, error }.into()
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn dbus(err: zbus::Error) -> Self {
+        Impl::Dbus(err).into()
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn x11(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Impl::X11(err.into()).into()
+    }
+
+    #[cfg(feature = "software")]
+    fn font(err: ab_glyph::InvalidFont) -> Self {
+        Impl::Font(err).into()
+    }
+
+    #[cfg(windows)]
+    fn too_many_items() -> Self {
+        Impl::TooManyItems.into()
+    }
+
+    fn no_registrar() -> Self {
+        Impl::NoRegistrar.into()
+    }
 }
 
 impl From<Impl> for Error {
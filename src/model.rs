@@ -0,0 +1,910 @@
+//! Platform-agnostic description of menus.
+//!
+//! These types only describe what a menu should look like, and can be
+//! created and inspected on any platform. The platform modules consume them
+//! to create the native menus.
+
+use core::fmt;
+use std::borrow::{Borrow, Cow};
+
+use crate::backend::ActivationSource;
+use crate::{Accelerator, ActivationContext, HandlerContext, Modifiers, Role};
+
+/// Handler that is invoked when a menu item is activated.
+pub(crate) enum Handler<C> {
+    Plain(Box<dyn FnMut()>),
+    Context(ContextHandler<C>),
+}
+
+pub(crate) type ContextHandler<C> = Box<dyn FnMut(&mut HandlerContext<C>)>;
+
+impl<C> Handler<C> {
+    pub(crate) fn call(&mut self, context: &mut HandlerContext<C>) {
+        match self {
+            Self::Plain(handler) => handler(),
+            Self::Context(handler) => handler(context),
+        }
+    }
+}
+
+/// An identifier chosen by the user for an item or submenu.
+///
+/// Ids make it possible to find entries without depending on their title or
+/// position. They should be unique within a menubar.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ItemId(Cow<'static, str>);
+
+impl ItemId {
+    pub const fn new(id: &'static str) -> Self {
+        Self(Cow::Borrowed(id))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&'static str> for ItemId {
+    fn from(id: &'static str) -> Self {
+        Self(Cow::Borrowed(id))
+    }
+}
+
+impl From<String> for ItemId {
+    fn from(id: String) -> Self {
+        Self(Cow::Owned(id))
+    }
+}
+
+impl Borrow<str> for ItemId {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq<str> for ItemId {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for ItemId {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Debug for ItemId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for ItemId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A menu, consisting of a list of entries.
+///
+/// This can be used both as a menubar, where each entry is usually a
+/// [`Submenu`], and as a submenu or popup menu.
+///
+/// Items can carry a command of type `C`, which is delivered to the
+/// application when the item is activated; see
+/// [`AttachedMenu`][crate::AttachedMenu]. Menus that only use handlers use
+/// the default, `()`.
+///
+/// ```
+/// use menubar::{Menu, MenuItem, Submenu};
+///
+/// #[derive(Clone)]
+/// enum Command {
+///     Open,
+///     Quit,
+/// }
+///
+/// let menu = Menu::default().with(Submenu::new(
+///     "File",
+///     Menu::default()
+///         .with(MenuItem::new("Open").with_command(Command::Open))
+///         .with(MenuItem::new("Quit").with_command(Command::Quit)),
+/// ));
+/// # let _: Menu<Command> = menu;
+/// ```
+///
+/// With the `serde` feature, menus can be loaded from and saved to files.
+/// Handlers and commands aren't stored; bind handlers by id with
+/// [`bind`][Self::bind].
+pub struct Menu<C = ()> {
+    entries: Vec<MenuEntry<C>>,
+}
+
+impl Menu {
+    /// Create a new, empty menu without commands.
+    ///
+    /// Menus with commands are created with [`Menu::default`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<C> Menu<C> {
+    /// Add an entry to the end of the menu, and return the menu.
+    ///
+    /// Useful for constructing menus in a single expression.
+    pub fn with(mut self, entry: impl Into<MenuEntry<C>>) -> Self {
+        self.push(entry);
+        self
+    }
+
+    /// Add an entry to the end of the menu.
+    pub fn push(&mut self, entry: impl Into<MenuEntry<C>>) {
+        self.entries.push(entry.into());
+    }
+
+    /// Insert an entry at the specified index.
+    ///
+    /// Panics if `index > menu.len()`.
+    pub fn insert(&mut self, index: usize, entry: impl Into<MenuEntry<C>>) {
+        self.entries.insert(index, entry.into());
+    }
+
+    /// Remove and return the entry at the specified index.
+    ///
+    /// Panics if `index >= menu.len()`.
+    pub fn remove(&mut self, index: usize) -> MenuEntry<C> {
+        self.entries.remove(index)
+    }
+
+    /// The entries in this menu, including separators.
+    pub fn entries(&self) -> &[MenuEntry<C>] {
+        &self.entries
+    }
+
+    /// Mutable access to the entries in this menu.
+    pub fn entries_mut(&mut self) -> &mut [MenuEntry<C>] {
+        &mut self.entries
+    }
+
+    pub fn into_entries(self) -> Vec<MenuEntry<C>> {
+        self.entries
+    }
+
+    /// Number of entries in this menu, including separators.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Find an entry by following a path of titles through the submenus.
+    ///
+    /// Separators are never matched.
+    pub fn find(&self, path: &[&str]) -> Option<&MenuEntry<C>> {
+        let (first, rest) = path.split_first()?;
+        let entry = self
+            .entries
+            .iter()
+            .find(|entry| entry.title() == Some(*first))?;
+        if rest.is_empty() {
+            Some(entry)
+        } else {
+            entry.as_submenu()?.menu().find(rest)
+        }
+    }
+
+    /// Mutable version of [`find`][Self::find].
+    pub fn find_mut(&mut self, path: &[&str]) -> Option<&mut MenuEntry<C>> {
+        let (first, rest) = path.split_first()?;
+        let entry = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.title() == Some(*first))?;
+        if rest.is_empty() {
+            Some(entry)
+        } else {
+            entry.as_submenu_mut()?.menu_mut().find_mut(rest)
+        }
+    }
+
+    /// Find an entry with the given id in this menu or any submenu.
+    pub fn find_id(&self, id: &str) -> Option<&MenuEntry<C>> {
+        self.entries.iter().find_map(|entry| {
+            if entry.id().is_some_and(|entry_id| entry_id == id) {
+                return Some(entry);
+            }
+            entry.as_submenu()?.menu().find_id(id)
+        })
+    }
+
+    /// Mutable version of [`find_id`][Self::find_id].
+    pub fn find_id_mut(&mut self, id: &str) -> Option<&mut MenuEntry<C>> {
+        for entry in &mut self.entries {
+            if entry.id().is_some_and(|entry_id| entry_id == id) {
+                return Some(entry);
+            }
+            if let Some(found) = entry
+                .as_submenu_mut()
+                .and_then(|submenu| submenu.menu_mut().find_id_mut(id))
+            {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    /// Set the handler of the item with the given id.
+    ///
+    /// This is useful for menus that are loaded from a file, where handlers
+    /// are bound afterwards. Returns `false` if there is no item with the id.
+    pub fn bind(&mut self, id: &str, handler: impl FnMut() + 'static) -> bool {
+        match self.find_id_mut(id).and_then(MenuEntry::as_item_mut) {
+            Some(item) => {
+                item.set_handler(handler);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl<C> Default for Menu<C> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl<C: fmt::Debug> fmt::Debug for Menu<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(&self.entries).finish()
+    }
+}
+
+/// A command type that knows the menu it's delivered from.
+///
+/// This is usually implemented with `#[derive(Menu)]`, which requires the
+/// `macros` feature.
+pub trait MenuCommand: Sized {
+    /// Create the menu, with one item for each command.
+    fn menu() -> Menu<Self>;
+
+    /// Find the command of the item with the given id.
+    fn from_item_id(id: &str) -> Option<Self>;
+}
+
+/// An entry in a [`Menu`].
+pub enum MenuEntry<C = ()> {
+    /// A regular, clickable item.
+    Item(MenuItem<C>),
+
+    /// An item that opens a submenu.
+    Submenu(Submenu<C>),
+
+    /// A separator line.
+    Separator(Separator),
+}
+
+impl<C> MenuEntry<C> {
+    /// The title of the entry, or `None` if this is a separator.
+    pub fn title(&self) -> Option<&str> {
+        match self {
+            Self::Item(item) => Some(item.title()),
+            Self::Submenu(submenu) => Some(submenu.title()),
+            Self::Separator(_) => None,
+        }
+    }
+
+    /// The id of the entry, if it has one.
+    pub fn id(&self) -> Option<&ItemId> {
+        match self {
+            Self::Item(item) => item.id(),
+            Self::Submenu(submenu) => submenu.id(),
+            Self::Separator(_) => None,
+        }
+    }
+
+    pub fn as_item(&self) -> Option<&MenuItem<C>> {
+        match self {
+            Self::Item(item) => Some(item),
+            _ => None,
+        }
+    }
+
+    pub fn as_item_mut(&mut self) -> Option<&mut MenuItem<C>> {
+        match self {
+            Self::Item(item) => Some(item),
+            _ => None,
+        }
+    }
+
+    pub fn as_submenu(&self) -> Option<&Submenu<C>> {
+        match self {
+            Self::Submenu(submenu) => Some(submenu),
+            _ => None,
+        }
+    }
+
+    pub fn as_submenu_mut(&mut self) -> Option<&mut Submenu<C>> {
+        match self {
+            Self::Submenu(submenu) => Some(submenu),
+            _ => None,
+        }
+    }
+
+    pub fn is_separator(&self) -> bool {
+        matches!(self, Self::Separator(_))
+    }
+}
+
+impl<C: fmt::Debug> fmt::Debug for MenuEntry<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Item(item) => fmt::Debug::fmt(item, f),
+            Self::Submenu(submenu) => fmt::Debug::fmt(submenu, f),
+            Self::Separator(separator) => fmt::Debug::fmt(separator, f),
+        }
+    }
+}
+
+impl<C> From<MenuItem<C>> for MenuEntry<C> {
+    fn from(item: MenuItem<C>) -> Self {
+        Self::Item(item)
+    }
+}
+
+impl<C> From<Submenu<C>> for MenuEntry<C> {
+    fn from(submenu: Submenu<C>) -> Self {
+        Self::Submenu(submenu)
+    }
+}
+
+impl<C> From<Separator> for MenuEntry<C> {
+    fn from(separator: Separator) -> Self {
+        Self::Separator(separator)
+    }
+}
+
+/// A regular menu item.
+///
+/// Items are enabled, visible and not checkable by default.
+pub struct MenuItem<C = ()> {
+    id: Option<ItemId>,
+    title: String,
+    role: Option<Role>,
+    accelerator: Option<Accelerator>,
+    enabled: bool,
+    /// `None` if the item is not checkable.
+    checked: Option<bool>,
+    radio_group: Option<String>,
+    hidden: bool,
+    handler: Option<Handler<C>>,
+    command: Option<C>,
+}
+
+impl<C> MenuItem<C> {
+    /// Create a new menu item with the given title.
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            id: None,
+            title: title.into(),
+            role: None,
+            accelerator: None,
+            enabled: true,
+            checked: None,
+            radio_group: None,
+            hidden: false,
+            handler: None,
+            command: None,
+        }
+    }
+
+    pub fn with_id(mut self, id: impl Into<ItemId>) -> Self {
+        self.set_id(Some(id.into()));
+        self
+    }
+
+    pub fn with_role(mut self, role: Role) -> Self {
+        self.set_role(Some(role));
+        self
+    }
+
+    pub fn with_accelerator(mut self, accelerator: Accelerator) -> Self {
+        self.set_accelerator(Some(accelerator));
+        self
+    }
+
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.set_enabled(enabled);
+        self
+    }
+
+    /// Make the item checkable, with the given initial state.
+    pub fn with_checked(mut self, checked: bool) -> Self {
+        self.set_checked(Some(checked));
+        self
+    }
+
+    /// Put the item in a radio group, see
+    /// [`set_radio_group`][Self::set_radio_group].
+    pub fn with_radio_group(mut self, group: impl Into<String>) -> Self {
+        self.set_radio_group(Some(group.into()));
+        self
+    }
+
+    pub fn with_hidden(mut self, hidden: bool) -> Self {
+        self.set_hidden(hidden);
+        self
+    }
+
+    /// Set the handler that is called when the item is activated.
+    pub fn with_handler(mut self, handler: impl FnMut() + 'static) -> Self {
+        self.set_handler(handler);
+        self
+    }
+
+    /// Set a handler that can change the menu, through the context it's
+    /// given, once it returns.
+    pub fn with_context_handler(
+        mut self,
+        handler: impl FnMut(&mut HandlerContext<C>) + 'static,
+    ) -> Self {
+        self.set_context_handler(handler);
+        self
+    }
+
+    /// Set the command that is delivered when the item is activated.
+    pub fn with_command(mut self, command: C) -> Self {
+        self.set_command(Some(command));
+        self
+    }
+
+    // Id
+
+    pub fn id(&self) -> Option<&ItemId> {
+        self.id.as_ref()
+    }
+
+    pub fn set_id(&mut self, id: Option<ItemId>) {
+        self.id = id;
+    }
+
+    // Title
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn set_title(&mut self, title: impl Into<String>) {
+        self.title = title.into();
+    }
+
+    // Role
+
+    /// The standard action that this item performs, if any.
+    pub fn role(&self) -> Option<Role> {
+        self.role
+    }
+
+    pub fn set_role(&mut self, role: Option<Role>) {
+        self.role = role;
+    }
+
+    // Accelerator
+
+    /// The keyboard shortcut used to activate the item.
+    pub fn accelerator(&self) -> Option<Accelerator> {
+        self.accelerator
+    }
+
+    pub fn set_accelerator(&mut self, accelerator: Option<Accelerator>) {
+        self.accelerator = accelerator;
+    }
+
+    // Enabling
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    // Checkmark
+
+    /// The checked state of the item, or `None` if the item is not checkable.
+    pub fn checked(&self) -> Option<bool> {
+        self.checked
+    }
+
+    /// Set the checked state of the item.
+    ///
+    /// `None` makes the item non-checkable.
+    pub fn set_checked(&mut self, checked: Option<bool>) {
+        self.checked = checked;
+    }
+
+    // Radio group
+
+    /// The radio group of the item, if any.
+    pub fn radio_group(&self) -> Option<&str> {
+        self.radio_group.as_deref()
+    }
+
+    /// Set the radio group of the item.
+    ///
+    /// Checkable items of the same menu with the same group are shown as
+    /// mutually exclusive choices, where the platform has a way to. Checking
+    /// one doesn't uncheck the others, that's up to the handlers.
+    pub fn set_radio_group(&mut self, group: Option<String>) {
+        self.radio_group = group;
+    }
+
+    // Hiding
+
+    /// Whether the item is hidden or not.
+    pub fn hidden(&self) -> bool {
+        self.hidden
+    }
+
+    pub fn set_hidden(&mut self, hidden: bool) {
+        self.hidden = hidden;
+    }
+
+    // Handler
+
+    pub fn has_handler(&self) -> bool {
+        self.handler.is_some()
+    }
+
+    pub fn set_handler(&mut self, handler: impl FnMut() + 'static) {
+        self.handler = Some(Handler::Plain(Box::new(handler)));
+    }
+
+    pub fn set_context_handler(&mut self, handler: impl FnMut(&mut HandlerContext<C>) + 'static) {
+        self.handler = Some(Handler::Context(Box::new(handler)));
+    }
+
+    /// Remove the handler from the item, and return it.
+    pub(crate) fn take_handler(&mut self) -> Option<Handler<C>> {
+        self.handler.take()
+    }
+
+    /// Put back a handler that was taken with [`take_handler`][Self::take_handler].
+    pub(crate) fn restore_handler(&mut self, handler: Handler<C>) {
+        self.handler = Some(handler);
+    }
+
+    // Command
+
+    /// The command that is delivered when the item is activated.
+    pub fn command(&self) -> Option<&C> {
+        self.command.as_ref()
+    }
+
+    pub fn set_command(&mut self, command: Option<C>) {
+        self.command = command;
+    }
+
+    /// Remove the command from the item, and return it.
+    pub(crate) fn take_command(&mut self) -> Option<C> {
+        self.command.take()
+    }
+
+    /// Invoke the handler, as if the item had been activated.
+    ///
+    /// Since the item isn't part of an attached menu, changes queued through
+    /// the [`HandlerContext`] are discarded.
+    ///
+    /// Returns `false` if the item has no handler.
+    pub fn activate(&mut self) -> bool {
+        match &mut self.handler {
+            Some(handler) => {
+                let activation = ActivationContext::new(
+                    self.id.clone(),
+                    ActivationSource::Programmatic,
+                    Modifiers::empty(),
+                    None,
+                );
+                handler.call(&mut HandlerContext::detached(activation));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl<C: fmt::Debug> fmt::Debug for MenuItem<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MenuItem")
+            .field("id", &self.id)
+            .field("title", &self.title)
+            .field("role", &self.role)
+            .field("accelerator", &self.accelerator)
+            .field("enabled", &self.enabled)
+            .field("checked", &self.checked)
+            .field("radio_group", &self.radio_group)
+            .field("hidden", &self.hidden)
+            .field("handler", &self.handler.is_some())
+            .field("command", &self.command)
+            .finish()
+    }
+}
+
+/// An item that opens a submenu.
+pub struct Submenu<C = ()> {
+    id: Option<ItemId>,
+    title: String,
+    enabled: bool,
+    hidden: bool,
+    menu: Menu<C>,
+}
+
+impl<C> Submenu<C> {
+    /// Create a new submenu item with the given title and contents.
+    pub fn new(title: impl Into<String>, menu: Menu<C>) -> Self {
+        Self {
+            id: None,
+            title: title.into(),
+            enabled: true,
+            hidden: false,
+            menu,
+        }
+    }
+
+    pub fn with_id(mut self, id: impl Into<ItemId>) -> Self {
+        self.set_id(Some(id.into()));
+        self
+    }
+
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.set_enabled(enabled);
+        self
+    }
+
+    pub fn with_hidden(mut self, hidden: bool) -> Self {
+        self.set_hidden(hidden);
+        self
+    }
+
+    pub fn id(&self) -> Option<&ItemId> {
+        self.id.as_ref()
+    }
+
+    pub fn set_id(&mut self, id: Option<ItemId>) {
+        self.id = id;
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn set_title(&mut self, title: impl Into<String>) {
+        self.title = title.into();
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn hidden(&self) -> bool {
+        self.hidden
+    }
+
+    pub fn set_hidden(&mut self, hidden: bool) {
+        self.hidden = hidden;
+    }
+
+    /// The contents of the submenu.
+    pub fn menu(&self) -> &Menu<C> {
+        &self.menu
+    }
+
+    pub fn menu_mut(&mut self) -> &mut Menu<C> {
+        &mut self.menu
+    }
+
+    pub fn into_menu(self) -> Menu<C> {
+        self.menu
+    }
+}
+
+impl<C: fmt::Debug> fmt::Debug for Submenu<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Submenu")
+            .field("id", &self.id)
+            .field("title", &self.title)
+            .field("enabled", &self.enabled)
+            .field("hidden", &self.hidden)
+            .field("menu", &self.menu)
+            .finish()
+    }
+}
+
+/// A separator line between items.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Separator;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::test_util::STRINGS;
+
+    fn file_menu() -> Menu {
+        Menu::new().with(Submenu::new(
+            "File",
+            Menu::new()
+                .with(MenuItem::new("New").with_accelerator("CmdOrCtrl+N".parse().unwrap()))
+                .with(Separator)
+                .with(Submenu::new(
+                    "Open Recent",
+                    Menu::new().with(MenuItem::new("a.txt")),
+                ))
+                .with(MenuItem::new("Word Wrap").with_checked(true))
+                .with(MenuItem::new("Close").with_enabled(false)),
+        ))
+    }
+
+    #[test]
+    fn test_defaults() {
+        let item: MenuItem = MenuItem::new("");
+        assert_eq!(item.role(), None);
+        assert_eq!(item.accelerator(), None);
+        assert!(item.enabled());
+        assert_eq!(item.checked(), None);
+        assert!(!item.hidden());
+        assert!(!item.has_handler());
+
+        let submenu = Submenu::new("", Menu::new());
+        assert!(submenu.enabled());
+        assert!(!submenu.hidden());
+        assert!(submenu.menu().is_empty());
+    }
+
+    #[test]
+    fn test_title() {
+        let mut item: MenuItem = MenuItem::new("");
+        let mut submenu = Submenu::new("", Menu::new());
+        STRINGS.iter().for_each(|&title| {
+            item.set_title(title);
+            assert_eq!(item.title(), title);
+            submenu.set_title(title);
+            assert_eq!(submenu.title(), title);
+        });
+    }
+
+    #[test]
+    fn test_length() {
+        let mut menu = Menu::new();
+        assert_eq!(menu.len(), 0);
+        menu.push(MenuItem::new("test"));
+        assert_eq!(menu.len(), 1);
+        menu.push(Separator);
+        assert_eq!(menu.len(), 2);
+        menu.insert(1, Submenu::new("test", Menu::new()));
+        assert_eq!(menu.len(), 3);
+        assert!(menu.remove(2).is_separator());
+        assert_eq!(menu.len(), 2);
+        assert!(menu.entries()[1].as_submenu().is_some());
+    }
+
+    #[test]
+    fn test_find() {
+        let mut menu = file_menu();
+
+        let new = menu.find(&["File", "New"]).unwrap().as_item().unwrap();
+        assert_eq!(new.accelerator(), Some("CmdOrCtrl+N".parse().unwrap()));
+        let recent = menu.find(&["File", "Open Recent", "a.txt"]).unwrap();
+        assert_eq!(recent.title(), Some("a.txt"));
+        assert_eq!(
+            menu.find(&["File", "Word Wrap"])
+                .unwrap()
+                .as_item()
+                .unwrap()
+                .checked(),
+            Some(true)
+        );
+
+        assert!(menu.find(&[]).is_none());
+        assert!(menu.find(&["Edit"]).is_none());
+        assert!(menu.find(&["File", "New", "Something"]).is_none());
+
+        let close = menu.find_mut(&["File", "Close"]).unwrap();
+        let close = close.as_item_mut().unwrap();
+        assert!(!close.enabled());
+        close.set_enabled(true);
+        assert!(menu
+            .find(&["File", "Close"])
+            .unwrap()
+            .as_item()
+            .unwrap()
+            .enabled());
+    }
+
+    #[test]
+    fn test_find_id() {
+        let mut menu = Menu::new().with(
+            Submenu::new(
+                "File",
+                Menu::new()
+                    .with(MenuItem::new("New").with_id("new"))
+                    .with(Submenu::new(
+                        "Open Recent",
+                        Menu::new().with(MenuItem::new("a.txt").with_id(String::from("recent-0"))),
+                    )),
+            )
+            .with_id("file"),
+        );
+
+        assert_eq!(menu.find_id("file").unwrap().title(), Some("File"));
+        assert_eq!(menu.find_id("new").unwrap().title(), Some("New"));
+        assert_eq!(menu.find_id("recent-0").unwrap().title(), Some("a.txt"));
+        assert!(menu.find_id("Open Recent").is_none());
+
+        let item = menu.find_id_mut("new").unwrap().as_item_mut().unwrap();
+        assert_eq!(item.id(), Some(&ItemId::new("new")));
+        item.set_id(Some("renamed".into()));
+        assert!(menu.find_id("new").is_none());
+        assert_eq!(menu.find_id("renamed").unwrap().title(), Some("New"));
+    }
+
+    #[test]
+    fn test_bind() {
+        let count = Rc::new(Cell::new(0));
+        let mut menu = Menu::new().with(
+            Submenu::new(
+                "File",
+                Menu::new().with(MenuItem::new("New").with_id("new")),
+            )
+            .with_id("file"),
+        );
+
+        let handler_count = Rc::clone(&count);
+        assert!(menu.bind("new", move || handler_count.set(handler_count.get() + 1)));
+        assert!(!menu.bind("file", || {}));
+        assert!(!menu.bind("missing", || {}));
+
+        let new = menu.find_id_mut("new").unwrap().as_item_mut().unwrap();
+        assert!(new.activate());
+        assert_eq!(count.get(), 1);
+    }
+
+    #[test]
+    fn test_handler() {
+        let count = Rc::new(Cell::new(0));
+        let mut item: MenuItem = MenuItem::new("test");
+        assert!(!item.activate());
+
+        let handler_count = Rc::clone(&count);
+        item.set_handler(move || handler_count.set(handler_count.get() + 1));
+        assert!(item.has_handler());
+        assert!(item.activate());
+        assert!(item.activate());
+        assert_eq!(count.get(), 2);
+
+        let mut handler = item.take_handler().unwrap();
+        assert!(!item.has_handler());
+        let activation = ActivationContext::new(
+            None,
+            ActivationSource::Programmatic,
+            Modifiers::empty(),
+            None,
+        );
+        handler.call(&mut HandlerContext::detached(activation));
+        assert_eq!(count.get(), 3);
+    }
+}
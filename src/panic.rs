@@ -0,0 +1,264 @@
+//! What happens when a handler panics.
+//!
+//! Handlers are called from native event loops, which panics can't unwind
+//! through. By default the process is aborted, but the panic can also be
+//! caught and reported to a hook instead, optionally disabling the item so
+//! that it can't panic again.
+//!
+//! Changes that handlers queue through their
+//! [`HandlerContext`][crate::HandlerContext] are applied after the handler
+//! returns, so when one fails, there's no one to return the error to. These
+//! errors are reported to a hook of their own.
+//!
+//! The policy and the hooks apply to every menu on the current thread,
+//! whatever its backend.
+
+use core::any::Any;
+use core::fmt;
+use std::cell::{Cell, RefCell};
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+
+use crate::{Error, ItemId};
+
+/// What happens when a handler or a dispatcher panics.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PanicPolicy {
+    /// Abort the process. This is the default.
+    #[default]
+    Abort,
+
+    /// Catch the panic, and report it to the hook set with
+    /// [`set_panic_hook`].
+    Report,
+
+    /// Catch the panic, report it, and disable the item.
+    Disable,
+}
+
+/// A panic that was caught while handling an activated item.
+pub struct HandlerPanic {
+    id: Option<ItemId>,
+    payload: Box<dyn Any + Send>,
+}
+
+impl HandlerPanic {
+    /// The id of the item that was activated.
+    pub fn id(&self) -> Option<&ItemId> {
+        self.id.as_ref()
+    }
+
+    /// The value the handler panicked with.
+    pub fn payload(&self) -> &(dyn Any + Send) {
+        &*self.payload
+    }
+
+    /// The panic message, if the handler panicked with a string.
+    pub fn message(&self) -> Option<&str> {
+        match self.payload.downcast_ref::<&'static str>() {
+            Some(message) => Some(message),
+            None => self.payload.downcast_ref::<String>().map(String::as_str),
+        }
+    }
+}
+
+impl fmt::Debug for HandlerPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HandlerPanic")
+            .field("id", &self.id)
+            .field("message", &self.message())
+            .finish_non_exhaustive()
+    }
+}
+
+type Hook = Rc<dyn Fn(&HandlerPanic)>;
+type ErrorHook = Rc<dyn Fn(&Error)>;
+
+std::thread_local! {
+    static POLICY: Cell<PanicPolicy> = const { Cell::new(PanicPolicy::Abort) };
+    static HOOK: RefCell<Option<Hook>> = const { RefCell::new(None) };
+    static ERROR_HOOK: RefCell<Option<ErrorHook>> = const { RefCell::new(None) };
+}
+
+/// Set what happens when a handler panics on the current thread.
+pub fn set_panic_policy(policy: PanicPolicy) {
+    POLICY.with(|current| current.set(policy));
+}
+
+/// What happens when a handler panics on the current thread.
+pub fn panic_policy() -> PanicPolicy {
+    POLICY.with(Cell::get)
+}
+
+/// Set the function that caught panics are reported to on the current
+/// thread, replacing the previous one.
+///
+/// The process is aborted if the hook panics itself.
+pub fn set_panic_hook(hook: impl Fn(&HandlerPanic) + 'static) {
+    HOOK.with(|current| *current.borrow_mut() = Some(Rc::new(hook)));
+}
+
+/// Set the function that changes queued by handlers are reported to on the
+/// current thread when they fail, replacing the previous one.
+///
+/// Without a hook, these errors are ignored. The process is aborted if the
+/// hook panics.
+pub fn set_error_hook(hook: impl Fn(&Error) + 'static) {
+    ERROR_HOOK.with(|current| *current.borrow_mut() = Some(Rc::new(hook)));
+}
+
+/// Report an error of a queued change to the hook.
+pub(crate) fn report_error(error: &Error) {
+    let hook = ERROR_HOOK.with(|hook| hook.borrow().clone());
+    if let Some(hook) = hook {
+        if panic::catch_unwind(AssertUnwindSafe(|| hook(error))).is_err() {
+            std::process::abort();
+        }
+    }
+}
+
+/// Call a handler of the item with the given id, applying the policy if it
+/// panics.
+///
+/// Returns the policy if a panic was caught.
+pub(crate) fn guard(id: Option<&ItemId>, f: impl FnOnce()) -> Result<(), PanicPolicy> {
+    let payload = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(()) => return Ok(()),
+        Err(payload) => payload,
+    };
+
+    let policy = panic_policy();
+    if policy == PanicPolicy::Abort {
+        std::process::abort();
+    }
+
+    let hook = HOOK.with(|hook| hook.borrow().clone());
+    if let Some(hook) = hook {
+        let report = HandlerPanic {
+            id: id.cloned(),
+            payload,
+        };
+        if panic::catch_unwind(AssertUnwindSafe(|| hook(&report))).is_err() {
+            std::process::abort();
+        }
+    }
+    Err(policy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::HeadlessBackend;
+    use crate::{Menu, MenuItem, Submenu};
+
+    type Reports = Rc<RefCell<Vec<(Option<ItemId>, String)>>>;
+
+    /// Catch panics for the duration of the test, collecting them.
+    fn collect(policy: PanicPolicy) -> Reports {
+        set_panic_policy(policy);
+        let reports = Rc::new(RefCell::new(Vec::new()));
+        let hook_reports = Rc::clone(&reports);
+        set_panic_hook(move |report| {
+            let message = report.message().unwrap_or_default().to_owned();
+            hook_reports
+                .borrow_mut()
+                .push((report.id().cloned(), message));
+        });
+        reports
+    }
+
+    #[test]
+    fn test_report() {
+        let reports = collect(PanicPolicy::Report);
+        let backend = HeadlessBackend::new();
+        let menu = Menu::default().with(Submenu::new(
+            "File",
+            Menu::default()
+                .with(
+                    MenuItem::new("Broken")
+                        .with_id("broken")
+                        .with_command("broken")
+                        .with_handler(|| panic!("oops")),
+                )
+                .with(
+                    MenuItem::new("Formatted")
+                        .with_command("formatted")
+                        .with_handler(|| panic!("{} failed", "formatting")),
+                ),
+        ));
+        let attached = backend.attach(menu).unwrap();
+
+        assert!(backend.click_path(&["File", "Broken"]));
+        assert!(backend.click_path(&["File", "Broken"]));
+        assert!(backend.click_path(&["File", "Formatted"]));
+        assert_eq!(
+            *reports.borrow(),
+            [
+                (Some(ItemId::from("broken")), "oops".to_owned()),
+                (Some(ItemId::from("broken")), "oops".to_owned()),
+                (None, "formatting failed".to_owned()),
+            ]
+        );
+        // The item stays enabled, but its command isn't delivered.
+        assert!(attached.item("broken").unwrap().enabled().unwrap());
+        assert!(attached.take_commands().is_empty());
+    }
+
+    #[test]
+    fn test_disable() {
+        let reports = collect(PanicPolicy::Disable);
+        let backend = HeadlessBackend::new();
+        let menu = Menu::new().with(Submenu::new(
+            "File",
+            Menu::new()
+                .with(
+                    MenuItem::new("Broken")
+                        .with_id("broken")
+                        .with_handler(|| panic!("oops")),
+                )
+                .with(MenuItem::new("Fine").with_id("fine")),
+        ));
+        let attached = backend.attach(menu).unwrap();
+
+        assert!(backend.click_path(&["File", "Broken"]));
+        assert!(!backend.click_path(&["File", "Broken"]));
+        assert_eq!(reports.borrow().len(), 1);
+        assert!(!attached.item("broken").unwrap().enabled().unwrap());
+        assert!(attached.item("fine").unwrap().enabled().unwrap());
+    }
+
+    #[test]
+    fn test_dispatcher_panics() {
+        let reports = collect(PanicPolicy::Disable);
+        let backend = HeadlessBackend::new();
+        let menu = Menu::default().with(Submenu::new(
+            "File",
+            Menu::default().with(MenuItem::new("Save").with_id("save").with_command(1)),
+        ));
+        let attached = backend.attach(menu).unwrap();
+        attached.set_dispatcher(|command: i32| panic!("command {}", command));
+
+        assert!(backend.click_path(&["File", "Save"]));
+        assert_eq!(
+            *reports.borrow(),
+            [(Some(ItemId::from("save")), "command 1".to_owned())]
+        );
+        assert!(!attached.item("save").unwrap().enabled().unwrap());
+    }
+
+    #[test]
+    fn test_payload() {
+        let caught = Rc::new(Cell::new(None));
+        let hook_caught = Rc::clone(&caught);
+        set_panic_policy(PanicPolicy::Report);
+        set_panic_hook(move |report| {
+            assert_eq!(report.message(), None);
+            hook_caught.set(report.payload().downcast_ref::<i32>().copied());
+        });
+
+        let res = guard(None, || std::panic::panic_any(42));
+        assert_eq!(res, Err(PanicPolicy::Report));
+        assert_eq!(caught.get(), Some(42));
+        assert_eq!(guard(None, || {}), Ok(()));
+    }
+}
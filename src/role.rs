@@ -0,0 +1,132 @@
+//! Standard actions that menu items can perform.
+//!
+//! `menubar-macros` has a copy of the names, to check roles at compile time.
+//! Its tests check that the two agree.
+
+use core::fmt;
+use core::str::FromStr;
+
+/// A standard action, like copying or quitting the application.
+///
+/// Roles identify items that every application has, so that backends and
+/// tools can treat them specially. They are written in `snake_case`, like
+/// `"select_all"`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Role {
+    About,
+    Preferences,
+    Services,
+    Hide,
+    HideOthers,
+    ShowAll,
+    Quit,
+    New,
+    Open,
+    Save,
+    SaveAs,
+    Print,
+    Close,
+    Undo,
+    Redo,
+    Cut,
+    Copy,
+    Paste,
+    Delete,
+    SelectAll,
+    Find,
+    Minimize,
+    Zoom,
+    ToggleFullScreen,
+    BringAllToFront,
+    Help,
+}
+
+const NAMES: &[(Role, &str)] = &[
+    (Role::About, "about"),
+    (Role::Preferences, "preferences"),
+    (Role::Services, "services"),
+    (Role::Hide, "hide"),
+    (Role::HideOthers, "hide_others"),
+    (Role::ShowAll, "show_all"),
+    (Role::Quit, "quit"),
+    (Role::New, "new"),
+    (Role::Open, "open"),
+    (Role::Save, "save"),
+    (Role::SaveAs, "save_as"),
+    (Role::Print, "print"),
+    (Role::Close, "close"),
+    (Role::Undo, "undo"),
+    (Role::Redo, "redo"),
+    (Role::Cut, "cut"),
+    (Role::Copy, "copy"),
+    (Role::Paste, "paste"),
+    (Role::Delete, "delete"),
+    (Role::SelectAll, "select_all"),
+    (Role::Find, "find"),
+    (Role::Minimize, "minimize"),
+    (Role::Zoom, "zoom"),
+    (Role::ToggleFullScreen, "toggle_full_screen"),
+    (Role::BringAllToFront, "bring_all_to_front"),
+    (Role::Help, "help"),
+];
+
+impl Role {
+    /// The `snake_case` name of the role.
+    pub fn name(self) -> &'static str {
+        NAMES
+            .iter()
+            .find(|(role, _)| *role == self)
+            .map(|(_, name)| *name)
+            .expect("all roles have a name")
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Role {
+    type Err = ParseRoleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NAMES
+            .iter()
+            .find(|(_, name)| *name == s)
+            .map(|(role, _)| *role)
+            .ok_or_else(|| ParseRoleError { name: s.to_owned() })
+    }
+}
+
+/// An error that can be returned when parsing a [`Role`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseRoleError {
+    name: String,
+}
+
+impl fmt::Display for ParseRoleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown role `{}`", self.name)
+    }
+}
+
+impl std::error::Error for ParseRoleError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        for (role, name) in NAMES {
+            assert_eq!(role.to_string(), *name);
+            assert_eq!(name.parse::<Role>(), Ok(*role));
+        }
+        assert_eq!(
+            "SelectAll".parse::<Role>().unwrap_err().to_string(),
+            "unknown role `SelectAll`"
+        );
+    }
+}
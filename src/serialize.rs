@@ -0,0 +1,718 @@
+//! `serde` support for the menu model.
+//!
+//! A menu is stored as a struct with an `items` list. Each entry is a struct
+//! with the fields `id`, `title`, `role`, `accel`, `enabled`, `checked`,
+//! `group` (the radio group) and `hidden` for items, `id`, `title`,
+//! `enabled`, `hidden` and `items` for submenus, and `separator = true` for
+//! separators. In TOML:
+//!
+//! ```toml
+//! [[items]]
+//! title = "File"
+//!
+//! [[items.items]]
+//! id = "new"
+//! title = "New"
+//! accel = "CmdOrCtrl+N"
+//!
+//! [[items.items]]
+//! separator = true
+//! ```
+//!
+//! Handlers can't be serialized; bind them after loading with
+//! [`Menu::bind`].
+//!
+//! Errors in an entry are prefixed with the path to the entry, like
+//! `File > Open Recent > [3]`. The path is tracked in a thread-local while
+//! deserializing, since `serde` has no way of passing context down.
+
+use core::cell::RefCell;
+use core::fmt;
+use core::marker::PhantomData;
+use std::collections::HashSet;
+
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeSeq, SerializeStruct, Serializer};
+use serde::{Deserialize, Serialize};
+
+use crate::{Accelerator, ItemId, Menu, MenuEntry, MenuItem, Role, Separator, Submenu};
+
+impl Serialize for ItemId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ItemId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::from)
+    }
+}
+
+/// Deserialize a value using its `FromStr` implementation.
+struct FromStrVisitor<T>(&'static str, PhantomData<T>);
+
+impl<'de, T> Visitor<'de> for FromStrVisitor<T>
+where
+    T: core::str::FromStr,
+    T::Err: fmt::Display,
+{
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<T, E> {
+        s.parse()
+            .map_err(|err| E::custom(format_args!("invalid {} `{}`: {}", self.0, s, err)))
+    }
+}
+
+impl Serialize for Accelerator {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Accelerator {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(FromStrVisitor("accelerator", PhantomData))
+    }
+}
+
+impl Serialize for Role {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Role {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(FromStrVisitor("role", PhantomData))
+    }
+}
+
+// Serialization
+
+impl<C> Serialize for Menu<C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Menu", 1)?;
+        state.serialize_field("items", &Entries(self.entries()))?;
+        state.end()
+    }
+}
+
+struct Entries<'a, C>(&'a [MenuEntry<C>]);
+
+impl<C> Serialize for Entries<'_, C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+        for entry in self.0 {
+            seq.serialize_element(entry)?;
+        }
+        seq.end()
+    }
+}
+
+impl<C> Serialize for MenuEntry<C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("MenuEntry", FIELDS.len())?;
+        // Fields with default values are skipped, to keep files readable.
+        fn field<S: SerializeStruct, T: Serialize>(
+            state: &mut S,
+            key: &'static str,
+            value: Option<T>,
+        ) -> Result<(), S::Error> {
+            match value {
+                Some(value) => state.serialize_field(key, &value),
+                None => state.skip_field(key),
+            }
+        }
+
+        match self {
+            Self::Item(item) => {
+                field(&mut state, "id", item.id())?;
+                state.serialize_field("title", item.title())?;
+                field(&mut state, "role", item.role())?;
+                field(&mut state, "accel", item.accelerator())?;
+                field(
+                    &mut state,
+                    "enabled",
+                    Some(false).filter(|_| !item.enabled()),
+                )?;
+                field(&mut state, "checked", item.checked())?;
+                field(&mut state, "group", item.radio_group())?;
+                field(&mut state, "hidden", Some(true).filter(|_| item.hidden()))?;
+            }
+            Self::Submenu(submenu) => {
+                field(&mut state, "id", submenu.id())?;
+                state.serialize_field("title", submenu.title())?;
+                field(
+                    &mut state,
+                    "enabled",
+                    Some(false).filter(|_| !submenu.enabled()),
+                )?;
+                field(
+                    &mut state,
+                    "hidden",
+                    Some(true).filter(|_| submenu.hidden()),
+                )?;
+                // Last, since TOML requires tables to come after values.
+                state.serialize_field("items", &Entries(submenu.menu().entries()))?;
+            }
+            Self::Separator(_) => state.serialize_field("separator", &true)?,
+        }
+        state.end()
+    }
+}
+
+// Deserialization
+//
+// Entries are first deserialized into `RawEntry`, and converted to the model
+// once the whole menu has been read. Formats may give the fields in any
+// order (TOML puts nested tables first), so the titles of the parents are
+// only known at that point.
+
+thread_local! {
+    static STATE: RefCell<State> = RefCell::default();
+}
+
+/// Context while deserializing the raw entries.
+#[derive(Default)]
+struct State {
+    /// The entries that are currently being deserialized.
+    path: Vec<Segment>,
+    /// Whether the current error already includes the path.
+    located: bool,
+}
+
+#[derive(Clone)]
+struct Segment {
+    index: usize,
+    title: Option<String>,
+}
+
+fn format_path(path: &[Segment]) -> String {
+    let (last, parents) = path.split_last().expect("no entry");
+    let mut s = String::new();
+    for segment in parents {
+        match &segment.title {
+            Some(title) => s.push_str(title),
+            None => s.push_str(&format!("[{}]", segment.index)),
+        }
+        s.push_str(" > ");
+    }
+    s.push_str(&format!("[{}]", last.index));
+    s
+}
+
+impl State {
+    fn with<R>(f: impl FnOnce(&mut State) -> R) -> R {
+        STATE.with(|state| f(&mut state.borrow_mut()))
+    }
+
+    /// Add the path to the current entry to an error, unless it's already
+    /// there.
+    fn locate<E: de::Error>(err: E) -> E {
+        Self::with(|state| {
+            if state.located {
+                return err;
+            }
+            state.located = true;
+            E::custom(format_args!("{}: {}", format_path(&state.path), err))
+        })
+    }
+}
+
+/// Resets the state when a menu has been deserialized, even on errors.
+struct StateGuard(State);
+
+impl StateGuard {
+    fn enter() -> Self {
+        Self(State::with(core::mem::take))
+    }
+}
+
+impl Drop for StateGuard {
+    fn drop(&mut self) {
+        State::with(|state| *state = core::mem::take(&mut self.0));
+    }
+}
+
+impl<'de, C> Deserialize<'de> for Menu<C> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MenuVisitor<C>(PhantomData<C>);
+
+        impl<'de, C> Visitor<'de> for MenuVisitor<C> {
+            type Value = Menu<C>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a menu")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Menu<C>, A::Error> {
+                let mut entries = None;
+                while let Some(key) = map.next_key::<MenuField>()? {
+                    match key {
+                        MenuField::Items if entries.is_some() => {
+                            return Err(de::Error::duplicate_field("items"));
+                        }
+                        MenuField::Items => entries = Some(map.next_value_seed(EntriesSeed)?),
+                    }
+                }
+                let entries = entries.ok_or_else(|| de::Error::missing_field("items"))?;
+
+                let mut converter = Converter::default();
+                converter.menu(entries).map_err(de::Error::custom)
+            }
+        }
+
+        let _guard = StateGuard::enter();
+        deserializer.deserialize_struct("Menu", &["items"], MenuVisitor(PhantomData))
+    }
+}
+
+enum MenuField {
+    Items,
+}
+
+impl<'de> Deserialize<'de> for MenuField {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FieldVisitor;
+
+        impl<'de> Visitor<'de> for FieldVisitor {
+            type Value = MenuField;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("`items`")
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<MenuField, E> {
+                match s {
+                    "items" => Ok(MenuField::Items),
+                    _ => Err(E::unknown_field(s, &["items"])),
+                }
+            }
+        }
+
+        deserializer.deserialize_identifier(FieldVisitor)
+    }
+}
+
+/// Deserializes a list of entries, keeping track of the path.
+struct EntriesSeed;
+
+impl<'de> DeserializeSeed<'de> for EntriesSeed {
+    type Value = Vec<RawEntry>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for EntriesSeed {
+    type Value = Vec<RawEntry>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a list of menu entries")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut entries = Vec::new();
+        loop {
+            let index = entries.len();
+            State::with(|state| state.path.push(Segment { index, title: None }));
+            let entry = seq.next_element_seed(EntrySeed).map_err(State::locate);
+            State::with(|state| state.path.pop());
+            match entry? {
+                Some(entry) => entries.push(entry),
+                None => return Ok(entries),
+            }
+        }
+    }
+}
+
+const FIELDS: &[&str] = &[
+    "id",
+    "title",
+    "role",
+    "accel",
+    "enabled",
+    "checked",
+    "group",
+    "hidden",
+    "items",
+    "separator",
+];
+
+#[derive(Copy, Clone, PartialEq)]
+enum Field {
+    Id,
+    Title,
+    Role,
+    Accel,
+    Enabled,
+    Checked,
+    Group,
+    Hidden,
+    Items,
+    Separator,
+}
+
+impl Field {
+    fn name(self) -> &'static str {
+        FIELDS[self as usize]
+    }
+}
+
+impl<'de> Deserialize<'de> for Field {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FieldVisitor;
+
+        impl<'de> Visitor<'de> for FieldVisitor {
+            type Value = Field;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a menu entry field")
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<Field, E> {
+                Ok(match s {
+                    "id" => Field::Id,
+                    "title" => Field::Title,
+                    "role" => Field::Role,
+                    "accel" => Field::Accel,
+                    "enabled" => Field::Enabled,
+                    "checked" => Field::Checked,
+                    "group" => Field::Group,
+                    "hidden" => Field::Hidden,
+                    "items" => Field::Items,
+                    "separator" => Field::Separator,
+                    _ => return Err(E::unknown_field(s, FIELDS)),
+                })
+            }
+        }
+
+        deserializer.deserialize_identifier(FieldVisitor)
+    }
+}
+
+/// An entry as written in the file, before it's been validated.
+#[derive(Default)]
+struct RawEntry {
+    /// The fields that were given, in order.
+    fields: Vec<Field>,
+    id: Option<ItemId>,
+    title: Option<String>,
+    role: Option<String>,
+    accel: Option<String>,
+    enabled: Option<bool>,
+    checked: Option<bool>,
+    group: Option<String>,
+    hidden: Option<bool>,
+    items: Option<Vec<RawEntry>>,
+    separator: bool,
+}
+
+/// Deserializes a single entry.
+struct EntrySeed;
+
+impl<'de> DeserializeSeed<'de> for EntrySeed {
+    type Value = RawEntry;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_struct("MenuEntry", FIELDS, self)
+    }
+}
+
+impl<'de> Visitor<'de> for EntrySeed {
+    type Value = RawEntry;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a menu entry")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<RawEntry, A::Error> {
+        let mut raw = RawEntry::default();
+        while let Some(field) = map.next_key::<Field>()? {
+            if raw.fields.contains(&field) {
+                return Err(de::Error::duplicate_field(field.name()));
+            }
+            raw.fields.push(field);
+            match field {
+                Field::Id => raw.id = Some(map.next_value()?),
+                Field::Title => {
+                    let title: String = map.next_value()?;
+                    State::with(|state| {
+                        let segment = state.path.last_mut().expect("no entry");
+                        segment.title = Some(title.clone());
+                    });
+                    raw.title = Some(title);
+                }
+                Field::Role => raw.role = Some(map.next_value()?),
+                Field::Accel => raw.accel = Some(map.next_value()?),
+                Field::Enabled => raw.enabled = Some(map.next_value()?),
+                Field::Checked => raw.checked = Some(map.next_value()?),
+                Field::Group => raw.group = Some(map.next_value()?),
+                Field::Hidden => raw.hidden = Some(map.next_value()?),
+                Field::Items => raw.items = Some(map.next_value_seed(EntriesSeed)?),
+                Field::Separator => raw.separator = map.next_value()?,
+            }
+        }
+        Ok(raw)
+    }
+}
+
+/// Validates raw entries, and converts them to the model.
+#[derive(Default)]
+struct Converter {
+    path: Vec<Segment>,
+    ids: HashSet<ItemId>,
+}
+
+impl Converter {
+    fn menu<C>(&mut self, entries: Vec<RawEntry>) -> Result<Menu<C>, String> {
+        let mut menu = Menu::default();
+        for (index, raw) in entries.into_iter().enumerate() {
+            let title = raw.title.clone();
+            self.path.push(Segment { index, title });
+            menu.push(self.entry(raw)?);
+            self.path.pop();
+        }
+        Ok(menu)
+    }
+
+    /// An error in the current entry.
+    fn error(&self, msg: impl fmt::Display) -> String {
+        format!("{}: {}", format_path(&self.path), msg)
+    }
+
+    fn entry<C>(&mut self, raw: RawEntry) -> Result<MenuEntry<C>, String> {
+        if raw.separator {
+            if let Some(field) = raw.fields.iter().find(|f| **f != Field::Separator) {
+                let msg = format!("`{}` is not supported on separators", field.name());
+                return Err(self.error(msg));
+            }
+            return Ok(Separator.into());
+        }
+
+        let title = match raw.title {
+            Some(title) => title,
+            None => return Err(self.error("missing field `title`")),
+        };
+        if let Some(id) = &raw.id {
+            if !self.ids.insert(id.clone()) {
+                return Err(self.error(format_args!("duplicate id `{}`", id)));
+            }
+        }
+
+        if let Some(entries) = raw.items {
+            for field in [Field::Role, Field::Accel, Field::Checked, Field::Group] {
+                if raw.fields.contains(&field) {
+                    let msg = format!("`{}` is not supported on submenus", field.name());
+                    return Err(self.error(msg));
+                }
+            }
+            let menu = self.menu(entries)?;
+            let mut submenu = Submenu::new(title, menu);
+            submenu.set_id(raw.id);
+            submenu.set_enabled(raw.enabled.unwrap_or(true));
+            submenu.set_hidden(raw.hidden.unwrap_or(false));
+            return Ok(submenu.into());
+        }
+
+        let mut item = MenuItem::new(title);
+        item.set_id(raw.id);
+        if let Some(role) = raw.role {
+            item.set_role(Some(self.parse("role", &role)?));
+        }
+        if let Some(accel) = raw.accel {
+            item.set_accelerator(Some(self.parse("accelerator", &accel)?));
+        }
+        item.set_enabled(raw.enabled.unwrap_or(true));
+        item.set_checked(raw.checked);
+        item.set_radio_group(raw.group);
+        item.set_hidden(raw.hidden.unwrap_or(false));
+        Ok(item.into())
+    }
+
+    fn parse<T>(&self, what: &str, s: &str) -> Result<T, String>
+    where
+        T: core::str::FromStr,
+        T::Err: fmt::Display,
+    {
+        s.parse()
+            .map_err(|err| self.error(format_args!("invalid {} `{}`: {}", what, s, err)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_menu() -> Menu {
+        Menu::new().with(
+            Submenu::new(
+                "File",
+                Menu::new()
+                    .with(
+                        MenuItem::new("New")
+                            .with_id("new")
+                            .with_role(Role::New)
+                            .with_accelerator("CmdOrCtrl+N".parse().unwrap()),
+                    )
+                    .with(Separator)
+                    .with(Submenu::new(
+                        "Open Recent",
+                        Menu::new().with(MenuItem::new("a.txt").with_hidden(true)),
+                    ))
+                    .with(MenuItem::new("Word Wrap").with_checked(true))
+                    .with(
+                        MenuItem::new("Spaces")
+                            .with_checked(false)
+                            .with_radio_group("indent"),
+                    )
+                    .with(MenuItem::new("Close").with_enabled(false)),
+            )
+            .with_id("file"),
+        )
+    }
+
+    const TOML: &str = r#"[[items]]
+id = "file"
+title = "File"
+
+[[items.items]]
+id = "new"
+title = "New"
+role = "new"
+accel = "CmdOrCtrl+N"
+
+[[items.items]]
+separator = true
+
+[[items.items]]
+title = "Open Recent"
+
+[[items.items.items]]
+title = "a.txt"
+hidden = true
+
+[[items.items]]
+title = "Word Wrap"
+checked = true
+
+[[items.items]]
+title = "Spaces"
+checked = false
+group = "indent"
+
+[[items.items]]
+title = "Close"
+enabled = false
+"#;
+
+    #[test]
+    fn test_toml() {
+        assert_eq!(toml::to_string(&file_menu()).unwrap(), TOML);
+        let menu: Menu = toml::from_str(TOML).unwrap();
+        assert_eq!(format!("{:?}", menu), format!("{:?}", file_menu()));
+    }
+
+    #[test]
+    fn test_json() {
+        let json = serde_json::to_string(&file_menu()).unwrap();
+        assert!(json.starts_with(
+            r#"{"items":[{"id":"file","title":"File","items":[{"id":"new","title":"New","role":"new","accel":"CmdOrCtrl+N"},{"separator":true},"#
+        ));
+        let menu: Menu = serde_json::from_str(&json).unwrap();
+        assert_eq!(format!("{:?}", menu), format!("{:?}", file_menu()));
+    }
+
+    #[test]
+    fn test_ron() {
+        let ron = ron::to_string(&file_menu()).unwrap();
+        let menu: Menu = ron::from_str(&ron).unwrap();
+        assert_eq!(format!("{:?}", menu), format!("{:?}", file_menu()));
+
+        let menu: Menu = ron::from_str(
+            r#"(items: [(title: "Edit", items: [(title: "Copy", role: "copy", accel: "CmdOrCtrl+C")])])"#,
+        )
+        .unwrap();
+        let copy = menu.find(&["Edit", "Copy"]).unwrap().as_item().unwrap();
+        assert_eq!(copy.role(), Some(Role::Copy));
+    }
+
+    fn json_error(json: &str) -> String {
+        let err = serde_json::from_str::<Menu>(json).unwrap_err().to_string();
+        // Strip the position, which only makes the expected errors noisy.
+        match err.rfind(" at line ") {
+            Some(index) => err[..index].to_owned(),
+            None => err,
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let cases = [
+            (
+                r#"{"items": [{"title": "File", "items": [{"title": "Open Recent", "items": [{"title": "a"}, {"title": "b"}, {"title": "c"}, {"title": "d", "accel": "Ctrl+Foo"}]}]}]}"#,
+                "File > Open Recent > [3]: invalid accelerator `Ctrl+Foo`: unknown key `Foo` at position 5",
+            ),
+            (
+                r#"{"items": [{"title": "File", "items": [{"title": "Save", "role": "Save"}]}]}"#,
+                "File > [0]: invalid role `Save`: unknown role `Save`",
+            ),
+            (
+                r#"{"items": [{"title": "File", "items": [{"separator": true}, {"accel": "Ctrl+N"}]}]}"#,
+                "File > [1]: missing field `title`",
+            ),
+            (
+                r#"{"items": [{"title": "File", "items": [{"titel": "New"}]}]}"#,
+                "File > [0]: unknown field `titel`, expected one of `id`, `title`, `role`, `accel`, `enabled`, `checked`, `group`, `hidden`, `items`, `separator`",
+            ),
+            (
+                r#"{"items": [{"title": "File", "items": [], "accel": "Ctrl+F"}]}"#,
+                "[0]: `accel` is not supported on submenus",
+            ),
+            (
+                r#"{"items": [{"separator": true, "title": "File"}]}"#,
+                "[0]: `title` is not supported on separators",
+            ),
+            (
+                r#"{"items": [{"title": "File", "id": "file", "items": [{"title": "File", "id": "file"}]}]}"#,
+                "File > [0]: duplicate id `file`",
+            ),
+            (
+                r#"{"items": [{"title": "File", "items": [{"title": "New", "enabled": "yes"}]}]}"#,
+                "File > [0]: invalid type: string \"yes\", expected a boolean",
+            ),
+        ];
+        for (json, expected) in cases {
+            assert_eq!(json_error(json), expected);
+        }
+    }
+
+    #[test]
+    fn test_toml_error() {
+        let toml =
+            "[[items]]\ntitle = \"File\"\n\n[[items.items]]\ntitle = \"New\"\naccel = \"Ctrl+\"\n";
+        let err = toml::from_str::<Menu>(toml).unwrap_err();
+        assert!(
+            err.message()
+                .starts_with("File > [0]: invalid accelerator `Ctrl+`"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_state_is_reset() {
+        let json = r#"{"items": [{"title": "File", "items": [{"title": "New", "id": "new", "accel": "Ctrl+Foo"}]}]}"#;
+        assert!(serde_json::from_str::<Menu>(json).is_err());
+        // Ids and paths from the failed attempt are forgotten.
+        let json = r#"{"items": [{"title": "New", "id": "new", "enabled": 1}]}"#;
+        assert!(json_error(json).starts_with("[0]: invalid type"));
+    }
+}
@@ -0,0 +1,271 @@
+//! Drawing the bar and the open menus.
+
+use ab_glyph::{Font as _, OutlineCurve, PxScale, ScaleFont as _};
+use tiny_skia::{Color, FillRule, Paint, PathBuilder, Pixmap, Rect as SkiaRect, Stroke, Transform};
+
+use super::layout::{Line, Metrics, Rect};
+use super::{mnemonic, Frame, Inner};
+use crate::backend::{ItemKind, ItemToken};
+
+/// Something to draw on, with what to draw with.
+struct Canvas<'a> {
+    pixmap: Pixmap,
+    inner: &'a Inner,
+    metrics: Metrics,
+}
+
+fn paint(color: [u8; 4]) -> Paint<'static> {
+    let [r, g, b, a] = color;
+    let mut paint = Paint::default();
+    paint.set_color(Color::from_rgba8(r, g, b, a));
+    paint.anti_alias = true;
+    paint
+}
+
+impl Canvas<'_> {
+    fn fill_rect(&mut self, rect: Rect, color: [u8; 4]) {
+        if let Some(rect) = SkiaRect::from_xywh(rect.x, rect.y, rect.width, rect.height) {
+            let paint = paint(color);
+            self.pixmap
+                .fill_rect(rect, &paint, Transform::identity(), None);
+        }
+    }
+
+    /// Draw a line of text, starting at `x` on the line whose top is at
+    /// `y`, and underline the character at the given index.
+    fn text(&mut self, text: &str, x: f32, y: f32, color: [u8; 4], underline: Option<usize>) {
+        let font = &self.inner.font.0;
+        let scaled = font.as_scaled(PxScale::from(self.metrics.font_size));
+        let (h_scale, v_scale) = (scaled.h_scale_factor(), scaled.v_scale_factor());
+        let (x, baseline) = (x.round(), (y + self.metrics.ascent).round());
+        let line = Line::new(&self.inner.font, self.metrics.font_size, text);
+        let paint = paint(color);
+        for (glyph, offset) in &line.glyphs {
+            let outline = match font.outline(*glyph) {
+                Some(outline) => outline,
+                None => continue,
+            };
+            let mut path = PathBuilder::new();
+            let mut last = None;
+            for curve in &outline.curves {
+                let (start, end) = match curve {
+                    OutlineCurve::Line(start, end) => (start, end),
+                    OutlineCurve::Quad(start, _, end) => (start, end),
+                    OutlineCurve::Cubic(start, _, _, end) => (start, end),
+                };
+                // A curve that doesn't continue the last one starts a new
+                // contour.
+                if last != Some(*start) {
+                    if last.is_some() {
+                        path.close();
+                    }
+                    path.move_to(start.x, start.y);
+                }
+                match curve {
+                    OutlineCurve::Line(_, p) => path.line_to(p.x, p.y),
+                    OutlineCurve::Quad(_, p1, p) => path.quad_to(p1.x, p1.y, p.x, p.y),
+                    OutlineCurve::Cubic(_, p1, p2, p) => {
+                        path.cubic_to(p1.x, p1.y, p2.x, p2.y, p.x, p.y)
+                    }
+                }
+                last = Some(*end);
+            }
+            path.close();
+            if let Some(path) = path.finish() {
+                // Outlines are in font units, with y pointing up.
+                let transform =
+                    Transform::from_row(h_scale, 0.0, 0.0, -v_scale, x + offset, baseline);
+                self.pixmap
+                    .fill_path(&path, &paint, FillRule::Winding, transform, None);
+            }
+        }
+
+        if let Some((start, end)) = underline.and_then(|index| line.span(index)) {
+            let y = baseline + self.metrics.unit.round().max(1.0);
+            let thickness = self.metrics.unit.round().max(1.0);
+            let rect = Rect {
+                x: x + start,
+                y,
+                width: end - start,
+                height: thickness,
+            };
+            self.fill_rect(rect, color);
+        }
+    }
+
+    /// The colors of the text of an item, and of its background if it has
+    /// one.
+    fn colors(&self, item: ItemToken, highlighted: bool) -> ([u8; 4], Option<[u8; 4]>) {
+        let style = &self.inner.style;
+        if !self.inner.selectable(item) {
+            (style.disabled_text, None)
+        } else if highlighted {
+            (style.highlight_text, Some(style.highlight))
+        } else {
+            (style.text, None)
+        }
+    }
+
+    /// The index of the character to underline in the title of an item.
+    fn underline(&self, item: ItemToken) -> Option<usize> {
+        if !self.inner.view.mnemonics || !self.inner.selectable(item) {
+            return None;
+        }
+        mnemonic(&self.inner.items[&item].props.title).map(|(index, _)| index)
+    }
+
+    fn check_mark(&mut self, x: f32, y: f32, color: [u8; 4]) {
+        let unit = self.metrics.unit;
+        let mut path = PathBuilder::new();
+        path.move_to(x - 4.0 * unit, y);
+        path.line_to(x - 1.0 * unit, y + 3.0 * unit);
+        path.line_to(x + 4.0 * unit, y - 3.5 * unit);
+        if let Some(path) = path.finish() {
+            let stroke = Stroke {
+                width: 1.5 * unit,
+                ..Stroke::default()
+            };
+            self.pixmap
+                .stroke_path(&path, &paint(color), &stroke, Transform::identity(), None);
+        }
+    }
+
+    fn bullet(&mut self, x: f32, y: f32, color: [u8; 4]) {
+        let unit = self.metrics.unit;
+        if let Some(path) = PathBuilder::from_circle(x, y, 3.0 * unit) {
+            self.pixmap.fill_path(
+                &path,
+                &paint(color),
+                FillRule::Winding,
+                Transform::identity(),
+                None,
+            );
+        }
+    }
+
+    fn arrow(&mut self, x: f32, y: f32, color: [u8; 4]) {
+        let unit = self.metrics.unit;
+        let mut path = PathBuilder::new();
+        path.move_to(x - 2.0 * unit, y - 4.0 * unit);
+        path.line_to(x + 2.0 * unit, y);
+        path.line_to(x - 2.0 * unit, y + 4.0 * unit);
+        path.close();
+        if let Some(path) = path.finish() {
+            self.pixmap.fill_path(
+                &path,
+                &paint(color),
+                FillRule::Winding,
+                Transform::identity(),
+                None,
+            );
+        }
+    }
+
+    fn bar(&mut self) {
+        let inner = self.inner;
+        let layout = inner.layout();
+        let style = &inner.style;
+        self.fill_rect(layout.bar, style.background);
+        let border = Rect {
+            y: layout.bar.bottom() - self.metrics.border,
+            height: self.metrics.border,
+            ..layout.bar
+        };
+        self.fill_rect(border, style.border);
+
+        let active = match inner.view.open.first() {
+            Some(level) => Some(level.item),
+            None => inner.view.focus.or(inner.view.hover),
+        };
+        for slot in &layout.items {
+            let (text, background) = self.colors(slot.item, active == Some(slot.item));
+            if let Some(background) = background {
+                self.fill_rect(slot.rect, background);
+            }
+            let title = &inner.items[&slot.item].props.title;
+            let x = slot.rect.x + self.metrics.bar_padding_x;
+            let y = slot.rect.y + (slot.rect.height - self.metrics.line_height) / 2.0;
+            self.text(title, x, y, text, self.underline(slot.item));
+        }
+
+        for (popup, level) in layout.popups.iter().zip(&inner.view.open) {
+            self.fill_rect(popup.rect, style.border);
+            let border = self.metrics.border;
+            let background = Rect {
+                x: popup.rect.x + border,
+                y: popup.rect.y + border,
+                width: popup.rect.width - 2.0 * border,
+                height: popup.rect.height - 2.0 * border,
+            };
+            self.fill_rect(background, style.background);
+
+            for row in &popup.rows {
+                let props = &inner.items[&row.item].props;
+                let rect = row.rect;
+                let middle = rect.y + rect.height / 2.0;
+                if props.kind == ItemKind::Separator {
+                    let line = Rect {
+                        x: rect.x + self.metrics.unit.round(),
+                        y: middle.floor(),
+                        width: rect.width - 2.0 * self.metrics.unit.round(),
+                        height: border,
+                    };
+                    self.fill_rect(line, style.separator);
+                    continue;
+                }
+
+                let (text, background) = self.colors(row.item, level.selected == Some(row.item));
+                if let Some(background) = background {
+                    self.fill_rect(rect, background);
+                }
+                let y = rect.y + (rect.height - self.metrics.line_height) / 2.0;
+                let x = rect.x + self.metrics.check_width;
+                self.text(&props.title, x, y, text, self.underline(row.item));
+                if props.checked == Some(true) {
+                    let x = rect.x + self.metrics.check_width / 2.0;
+                    match props.radio_group {
+                        Some(_) => self.bullet(x, middle, text),
+                        None => self.check_mark(x, middle, text),
+                    }
+                }
+                let end = rect.right() - self.metrics.arrow_width;
+                if let Some(accelerator) = inner.accelerator_text(row.item) {
+                    let width = Line::new(&inner.font, self.metrics.font_size, &accelerator).width;
+                    self.text(&accelerator, end - width.ceil(), y, text, None);
+                }
+                if let ItemKind::Submenu(_) = props.kind {
+                    self.arrow(end + self.metrics.arrow_width / 2.0, middle, text);
+                }
+            }
+        }
+    }
+}
+
+impl Inner {
+    pub(super) fn draw(&self) -> Frame {
+        let (width, height) = (self.width, self.height);
+        let pixmap = match Pixmap::new(width, height) {
+            Some(pixmap) => pixmap,
+            None => {
+                return Frame {
+                    width,
+                    height,
+                    pixels: Vec::new(),
+                }
+            }
+        };
+        let mut canvas = Canvas {
+            pixmap,
+            inner: self,
+            metrics: self.metrics(),
+        };
+        if self.root.is_some() {
+            canvas.bar();
+        }
+        Frame {
+            width,
+            height,
+            pixels: canvas.pixmap.take(),
+        }
+    }
+}
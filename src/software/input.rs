@@ -0,0 +1,415 @@
+//! Opening menus and choosing items in response to input.
+
+use super::layout::Hit;
+use super::{mnemonic, Inner, InputEvent, Level};
+use crate::backend::{Activation, ActivationSource, ItemKind, ItemToken};
+use crate::{Accelerator, Key, Modifiers};
+
+/// A direction to move the highlight in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Step {
+    First,
+    Last,
+    Next,
+    Previous,
+}
+
+/// Whether a character is the mnemonic of a title.
+fn is_mnemonic(title: &str, c: char) -> bool {
+    match mnemonic(title) {
+        Some((_, m)) => m.to_lowercase().eq(c.to_lowercase()),
+        None => false,
+    }
+}
+
+/// The item after the current one, in the given direction, wrapping
+/// around.
+fn step(items: &[ItemToken], current: Option<ItemToken>, step: Step) -> Option<ItemToken> {
+    let position = current.and_then(|current| items.iter().position(|i| *i == current));
+    let index = match (step, position) {
+        (Step::First, _) | (Step::Next, None) => 0,
+        (Step::Last, _) | (Step::Previous, None) => items.len().checked_sub(1)?,
+        (Step::Next, Some(i)) => (i + 1) % items.len(),
+        (Step::Previous, Some(i)) => (i + items.len() - 1) % items.len(),
+    };
+    items.get(index).copied()
+}
+
+impl Inner {
+    /// Handle an event, and return whether the bar used it.
+    ///
+    /// The item to activate is left in `activated`.
+    pub(super) fn handle(&mut self, event: InputEvent) -> bool {
+        match event {
+            InputEvent::PointerMoved { x, y } => {
+                self.pointer = Some((x, y));
+                self.pointer_moved(x, y)
+            }
+            InputEvent::PointerLeft => {
+                self.pointer = None;
+                self.view.hover = None;
+                false
+            }
+            InputEvent::PointerPressed => {
+                self.alt_tap = false;
+                self.pointer_pressed()
+            }
+            InputEvent::PointerReleased => self.pointer_released(),
+            InputEvent::ModifiersChanged(modifiers) => self.modifiers_changed(modifiers),
+            InputEvent::KeyPressed(key) => {
+                self.alt_tap = false;
+                self.key_pressed(key)
+            }
+        }
+    }
+
+    /// Close all menus, and take the focus from the bar.
+    fn close(&mut self) {
+        self.view.open.clear();
+        self.view.focus = None;
+        self.view.mnemonics = false;
+        self.pressed = None;
+    }
+
+    fn activate(&mut self, item: ItemToken, source: ActivationSource) {
+        self.close();
+        let activation = Activation {
+            modifiers: self.modifiers,
+            ..Activation::new(source)
+        };
+        self.activated = Some((item, activation));
+    }
+
+    /// The items of a menu that can be highlighted.
+    fn selectable_items(&self, level: &Level) -> Vec<ItemToken> {
+        self.visible(level.menu)
+            .filter(|item| self.selectable(*item))
+            .collect()
+    }
+
+    /// Open the menu of an item in the bar, or activate the item if it
+    /// isn't a submenu.
+    ///
+    /// From the keyboard, the first item of the menu is highlighted.
+    fn open_bar_item(&mut self, item: ItemToken, keyboard: bool) {
+        if !self.selectable(item) {
+            return;
+        }
+        match self.submenu(item) {
+            Some(menu) => {
+                let mut level = Level {
+                    item,
+                    menu,
+                    selected: None,
+                };
+                if keyboard {
+                    level.selected = step(&self.selectable_items(&level), None, Step::First);
+                }
+                self.view.focus = None;
+                self.view.open = vec![level];
+                self.view.mnemonics = keyboard;
+            }
+            None => self.activate(item, ActivationSource::Click),
+        }
+    }
+
+    /// Open the submenu of an item in the deepest open menu, or activate
+    /// the item if it isn't a submenu.
+    fn choose(&mut self, item: ItemToken) {
+        match self.submenu(item) {
+            Some(menu) => {
+                let mut level = Level {
+                    item,
+                    menu,
+                    selected: None,
+                };
+                level.selected = step(&self.selectable_items(&level), None, Step::First);
+                self.view.open.push(level);
+            }
+            None if self.selectable(item) => self.activate(item, ActivationSource::Click),
+            None => {}
+        }
+    }
+
+    /// Move to another item in the bar.
+    ///
+    /// While a menu is open, only items with menus are visited, and their
+    /// menus are opened.
+    fn step_bar(&mut self, direction: Step) {
+        let (current, open) = match self.view.open.first() {
+            Some(level) => (Some(level.item), true),
+            None => (self.view.focus, false),
+        };
+        let items: Vec<_> = self
+            .bar_items()
+            .into_iter()
+            .filter(|item| self.selectable(*item) && (!open || self.submenu(*item).is_some()))
+            .collect();
+        match step(&items, current, direction) {
+            Some(next) if open => self.open_bar_item(next, true),
+            Some(next) => self.view.focus = Some(next),
+            None => {}
+        }
+    }
+
+    /// The item in the bar whose title starts with the character.
+    fn bar_mnemonic(&self, c: char) -> Option<ItemToken> {
+        self.bar_items()
+            .into_iter()
+            .find(|item| self.selectable(*item) && is_mnemonic(&self.items[item].props.title, c))
+    }
+
+    fn pointer_moved(&mut self, x: f32, y: f32) -> bool {
+        match self.layout().hit(x, y) {
+            Hit::Menu(depth, item) => {
+                let item = item.filter(|item| self.selectable(*item));
+                // Keep the submenu of the item open.
+                let keep = match self.view.open.get(depth + 1) {
+                    Some(level) => Some(level.item) == item,
+                    None => false,
+                };
+                if !keep {
+                    self.view.open.truncate(depth + 1);
+                }
+                self.view.open[depth].selected = item;
+                if let Some(item) = item {
+                    if let (Some(menu), false) = (self.submenu(item), keep) {
+                        self.view.open.push(Level {
+                            item,
+                            menu,
+                            selected: None,
+                        });
+                    }
+                }
+                true
+            }
+            Hit::Bar(item) => {
+                self.view.hover = item;
+                let current = self.view.open.first().map(|level| level.item);
+                if let Some(item) = item {
+                    if current.is_some() && current != Some(item) && self.submenu(item).is_some() {
+                        let keyboard = self.view.mnemonics;
+                        self.open_bar_item(item, false);
+                        self.view.mnemonics = keyboard;
+                    }
+                }
+                true
+            }
+            Hit::Outside => {
+                self.view.hover = None;
+                if let Some(level) = self.view.open.last_mut() {
+                    level.selected = None;
+                }
+                !self.view.open.is_empty()
+            }
+        }
+    }
+
+    fn pointer_pressed(&mut self) -> bool {
+        let hit = match self.pointer {
+            Some((x, y)) => self.layout().hit(x, y),
+            None => Hit::Outside,
+        };
+        match hit {
+            Hit::Bar(Some(item)) => {
+                let current = self.view.open.first().map(|level| level.item);
+                if current == Some(item) {
+                    self.close();
+                } else if self.submenu(item).is_some() {
+                    self.open_bar_item(item, false);
+                } else {
+                    self.close();
+                    self.pressed = Some(item).filter(|item| self.selectable(*item));
+                }
+                true
+            }
+            Hit::Bar(None) => {
+                self.close();
+                true
+            }
+            Hit::Menu(..) => true,
+            Hit::Outside => {
+                // The click that closes menus doesn't go to the window.
+                let active = !self.view.open.is_empty() || self.view.focus.is_some();
+                self.close();
+                active
+            }
+        }
+    }
+
+    fn pointer_released(&mut self) -> bool {
+        let pressed = self.pressed.take();
+        let hit = match self.pointer {
+            Some((x, y)) => self.layout().hit(x, y),
+            None => Hit::Outside,
+        };
+        match hit {
+            Hit::Menu(_, Some(item)) => {
+                let clickable =
+                    matches!(self.props(item), Some(props) if props.kind == ItemKind::Item);
+                if clickable && self.selectable(item) {
+                    self.activate(item, ActivationSource::Click);
+                }
+                true
+            }
+            Hit::Menu(_, None) => true,
+            Hit::Bar(item) => {
+                if let Some(item) = item.filter(|item| Some(*item) == pressed) {
+                    self.activate(item, ActivationSource::Click);
+                }
+                true
+            }
+            Hit::Outside => !self.view.open.is_empty(),
+        }
+    }
+
+    /// Tapping Alt on its own focuses the bar, or closes it.
+    fn modifiers_changed(&mut self, modifiers: Modifiers) -> bool {
+        let old = core::mem::replace(&mut self.modifiers, modifiers);
+        if old.is_empty() && modifiers == Modifiers::ALT {
+            self.alt_tap = true;
+            return false;
+        }
+        let tapped = core::mem::take(&mut self.alt_tap);
+        if !(tapped && old == Modifiers::ALT && modifiers.is_empty()) {
+            return false;
+        }
+        if !self.view.open.is_empty() || self.view.focus.is_some() {
+            self.close();
+        } else {
+            self.step_bar(Step::First);
+            self.view.mnemonics = self.view.focus.is_some();
+        }
+        true
+    }
+
+    fn key_pressed(&mut self, key: Key) -> bool {
+        if !self.view.open.is_empty() {
+            return self.menu_key(key);
+        }
+        if let Some(focus) = self.view.focus {
+            return self.bar_key(focus, key);
+        }
+        let modifiers = self.modifiers.difference(Modifiers::SHIFT);
+        match key {
+            Key::F(10) if modifiers.is_empty() => {
+                self.step_bar(Step::First);
+                self.view.mnemonics = self.view.focus.is_some();
+                self.view.focus.is_some()
+            }
+            Key::Char(c) if modifiers == Modifiers::ALT => match self.bar_mnemonic(c) {
+                Some(item) => {
+                    self.open_bar_item(item, true);
+                    true
+                }
+                None => self.accelerator(key),
+            },
+            _ => self.accelerator(key),
+        }
+    }
+
+    /// A key pressed while the bar has focus, but no menu is open.
+    fn bar_key(&mut self, focus: ItemToken, key: Key) -> bool {
+        match key {
+            Key::Escape | Key::F(10) => self.close(),
+            Key::Left => self.step_bar(Step::Previous),
+            Key::Right => self.step_bar(Step::Next),
+            Key::Up | Key::Down | Key::Enter | Key::Space => self.open_bar_item(focus, true),
+            Key::Char(c) => {
+                if let Some(item) = self.bar_mnemonic(c) {
+                    self.open_bar_item(item, true);
+                }
+            }
+            _ => {
+                self.close();
+                return self.accelerator(key);
+            }
+        }
+        true
+    }
+
+    /// A key pressed while a menu is open.
+    ///
+    /// Open menus take all keys.
+    fn menu_key(&mut self, key: Key) -> bool {
+        let level = *self.view.open.last().unwrap();
+        let items = self.selectable_items(&level);
+        let select = |inner: &mut Self, direction| {
+            let selected = step(&items, level.selected, direction);
+            inner.view.open.last_mut().unwrap().selected = selected;
+        };
+        match key {
+            Key::Escape => {
+                let closed = self.view.open.pop().unwrap();
+                if self.view.open.is_empty() && self.view.mnemonics {
+                    self.view.focus = Some(closed.item);
+                }
+            }
+            Key::F(10) => self.close(),
+            Key::Up => select(self, Step::Previous),
+            Key::Down => select(self, Step::Next),
+            Key::Home => select(self, Step::First),
+            Key::End => select(self, Step::Last),
+            Key::Right => match level.selected.filter(|item| self.submenu(*item).is_some()) {
+                Some(item) => self.choose(item),
+                None => self.step_bar(Step::Next),
+            },
+            Key::Left if self.view.open.len() > 1 => {
+                self.view.open.pop();
+            }
+            Key::Left => self.step_bar(Step::Previous),
+            Key::Enter | Key::Space => {
+                if let Some(item) = level.selected {
+                    self.choose(item);
+                }
+            }
+            Key::Char(c) if self.modifiers.difference(Modifiers::SHIFT).is_empty() => {
+                let matching: Vec<_> = items
+                    .iter()
+                    .copied()
+                    .filter(|item| is_mnemonic(&self.items[item].props.title, c))
+                    .collect();
+                // With several matches, each press highlights the next one.
+                match matching[..] {
+                    [] => {}
+                    [item] => self.choose(item),
+                    _ => {
+                        let selected = step(&matching, level.selected, Step::Next);
+                        self.view.open.last_mut().unwrap().selected = selected;
+                    }
+                }
+            }
+            _ => {
+                self.accelerator(key);
+            }
+        }
+        true
+    }
+
+    /// Activate the item whose accelerator was pressed, if there is one.
+    fn accelerator(&mut self, key: Key) -> bool {
+        if !key.is_valid() {
+            return false;
+        }
+        let pressed = Accelerator::new(self.modifiers, key);
+        let mut items: Vec<_> = self
+            .items
+            .iter()
+            .filter(|(item, node)| {
+                node.props.kind == ItemKind::Item
+                    && matches!(node.props.accelerator, Some(a) if a.matches(&pressed))
+                    && self.reachable(**item)
+            })
+            .map(|(item, _)| *item)
+            .collect();
+        // The first item wins, like in native menus.
+        items.sort();
+        match items.first() {
+            Some(item) => {
+                self.activate(*item, ActivationSource::Accelerator);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,294 @@
+//! Where the bar, the open menus and their items are drawn.
+//!
+//! Everything is measured in physical pixels, and rounded to whole pixels
+//! so that edges are sharp.
+
+use ab_glyph::{Font as _, GlyphId, PxScale, ScaleFont as _};
+
+use super::{Font, Inner};
+use crate::backend::{ItemKind, ItemToken, MenuToken};
+
+/// Space left and right of titles in the bar.
+const BAR_PADDING_X: f32 = 8.0;
+/// Space above and below titles in the bar.
+const BAR_PADDING_Y: f32 = 4.0;
+/// Space above and below titles in menus.
+const ROW_PADDING_Y: f32 = 4.0;
+/// Space above the first and below the last item of a menu.
+const MENU_PADDING: f32 = 4.0;
+/// The space left of titles in menus, where check marks go.
+const CHECK_WIDTH: f32 = 24.0;
+/// The space right of titles and accelerators, where submenu arrows go.
+const ARROW_WIDTH: f32 = 20.0;
+/// The space between the titles and the accelerators of a menu.
+const ACCELERATOR_GAP: f32 = 24.0;
+const SEPARATOR_HEIGHT: f32 = 9.0;
+const BORDER: f32 = 1.0;
+const MIN_MENU_WIDTH: f32 = 120.0;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(super) struct Rect {
+    pub(super) x: f32,
+    pub(super) y: f32,
+    pub(super) width: f32,
+    pub(super) height: f32,
+}
+
+impl Rect {
+    fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub(super) fn right(&self) -> f32 {
+        self.x + self.width
+    }
+
+    pub(super) fn bottom(&self) -> f32 {
+        self.y + self.height
+    }
+
+    fn contains(&self, x: f32, y: f32) -> bool {
+        self.x <= x && x < self.right() && self.y <= y && y < self.bottom()
+    }
+}
+
+/// Sizes that depend on the font and the scale factor.
+pub(super) struct Metrics {
+    /// One logical pixel.
+    pub(super) unit: f32,
+    pub(super) font_size: f32,
+    /// The distance from the top of a line of text to its baseline.
+    pub(super) ascent: f32,
+    pub(super) line_height: f32,
+    pub(super) bar_height: f32,
+    row_height: f32,
+    separator_height: f32,
+    pub(super) border: f32,
+    pub(super) check_width: f32,
+    pub(super) arrow_width: f32,
+    accelerator_gap: f32,
+    menu_padding: f32,
+    min_menu_width: f32,
+    pub(super) bar_padding_x: f32,
+}
+
+/// The glyphs of a line of text, and where they go relative to its start.
+pub(super) struct Line {
+    pub(super) glyphs: Vec<(GlyphId, f32)>,
+    pub(super) width: f32,
+}
+
+impl Line {
+    pub(super) fn new(font: &Font, font_size: f32, text: &str) -> Self {
+        let font = font.0.as_scaled(PxScale::from(font_size));
+        let mut glyphs = Vec::new();
+        let mut x = 0.0;
+        let mut previous = None;
+        for c in text.chars() {
+            let glyph = font.glyph_id(c);
+            if let Some(previous) = previous {
+                x += font.kern(previous, glyph);
+            }
+            glyphs.push((glyph, x));
+            x += font.h_advance(glyph);
+            previous = Some(glyph);
+        }
+        Self { glyphs, width: x }
+    }
+
+    /// The horizontal extent of the character at the given index.
+    pub(super) fn span(&self, index: usize) -> Option<(f32, f32)> {
+        let start = self.glyphs.get(index)?.1;
+        let end = self
+            .glyphs
+            .get(index + 1)
+            .map_or(self.width, |glyph| glyph.1);
+        Some((start, end))
+    }
+}
+
+/// The bar item or menu item at a position.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) enum Hit {
+    /// An item in the bar, or empty space in it.
+    Bar(Option<ItemToken>),
+    /// An item in the open menu at the given depth, or a separator or the
+    /// border of the menu.
+    Menu(usize, Option<ItemToken>),
+    Outside,
+}
+
+pub(super) struct Slot {
+    pub(super) item: ItemToken,
+    pub(super) rect: Rect,
+}
+
+pub(super) struct Popup {
+    pub(super) menu: MenuToken,
+    pub(super) rect: Rect,
+    /// The visible items, including separators.
+    pub(super) rows: Vec<Slot>,
+}
+
+pub(super) struct Layout {
+    pub(super) bar: Rect,
+    pub(super) items: Vec<Slot>,
+    /// The open menus, starting with the one opened from the bar.
+    pub(super) popups: Vec<Popup>,
+}
+
+impl Layout {
+    pub(super) fn hit(&self, x: f32, y: f32) -> Hit {
+        for (depth, popup) in self.popups.iter().enumerate().rev() {
+            if popup.rect.contains(x, y) {
+                let row = popup.rows.iter().find(|row| row.rect.contains(x, y));
+                return Hit::Menu(depth, row.map(|row| row.item));
+            }
+        }
+        if self.bar.contains(x, y) {
+            let slot = self.items.iter().find(|slot| slot.rect.contains(x, y));
+            return Hit::Bar(slot.map(|slot| slot.item));
+        }
+        Hit::Outside
+    }
+}
+
+impl Inner {
+    pub(super) fn metrics(&self) -> Metrics {
+        let unit = self.scale;
+        let font_size = self.style.font_size * unit;
+        let font = self.font.0.as_scaled(PxScale::from(font_size));
+        let ascent = font.ascent().ceil();
+        let line_height = ascent + (-font.descent()).ceil();
+        Metrics {
+            unit,
+            font_size,
+            ascent,
+            line_height,
+            bar_height: line_height + (2.0 * BAR_PADDING_Y * unit).round(),
+            row_height: line_height + (2.0 * ROW_PADDING_Y * unit).round(),
+            separator_height: (SEPARATOR_HEIGHT * unit).round(),
+            border: (BORDER * unit).round().max(1.0),
+            check_width: (CHECK_WIDTH * unit).round(),
+            arrow_width: (ARROW_WIDTH * unit).round(),
+            accelerator_gap: (ACCELERATOR_GAP * unit).round(),
+            menu_padding: (MENU_PADDING * unit).round(),
+            min_menu_width: (MIN_MENU_WIDTH * unit).round(),
+            bar_padding_x: (BAR_PADDING_X * unit).round(),
+        }
+    }
+
+    /// The text that shows the accelerator of an item.
+    pub(super) fn accelerator_text(&self, item: ItemToken) -> Option<String> {
+        let accelerator = self.items[&item].props.accelerator?;
+        Some(accelerator.format_native(Default::default()))
+    }
+
+    pub(super) fn layout(&self) -> Layout {
+        let metrics = self.metrics();
+        let bar = Rect::new(0.0, 0.0, self.width as f32, metrics.bar_height);
+        let mut x = 0.0;
+        let items = self
+            .bar_items()
+            .into_iter()
+            .map(|item| {
+                let title = &self.items[&item].props.title;
+                let width = Line::new(&self.font, metrics.font_size, title).width;
+                let width = width.ceil() + 2.0 * metrics.bar_padding_x;
+                let rect = Rect::new(x, 0.0, width, metrics.bar_height);
+                x += width;
+                Slot { item, rect }
+            })
+            .collect::<Vec<_>>();
+
+        let mut popups: Vec<Popup> = Vec::new();
+        for level in &self.view.open {
+            // The item that opened the menu.
+            let parent = match popups.last() {
+                Some(popup) => popup.rows.iter().find(|row| row.item == level.item),
+                None => items.iter().find(|slot| slot.item == level.item),
+            };
+            let parent = match parent {
+                Some(parent) => parent.rect,
+                None => break,
+            };
+            let popup = self.popup(&metrics, level.menu, parent, !popups.is_empty());
+            popups.push(popup);
+        }
+
+        Layout { bar, items, popups }
+    }
+
+    /// Lay out a menu, next to the item that opened it.
+    fn popup(&self, metrics: &Metrics, menu: MenuToken, parent: Rect, nested: bool) -> Popup {
+        let mut titles: f32 = 0.0;
+        let mut accelerators: f32 = 0.0;
+        let mut height = 2.0 * (metrics.border + metrics.menu_padding);
+        for item in self.visible(menu) {
+            let props = &self.items[&item].props;
+            if props.kind == ItemKind::Separator {
+                height += metrics.separator_height;
+                continue;
+            }
+            height += metrics.row_height;
+            titles = titles.max(Line::new(&self.font, metrics.font_size, &props.title).width);
+            if let Some(text) = self.accelerator_text(item) {
+                let width = Line::new(&self.font, metrics.font_size, &text).width;
+                accelerators = accelerators.max(width);
+            }
+        }
+        let mut width = metrics.check_width + titles.ceil() + metrics.arrow_width;
+        if accelerators > 0.0 {
+            width += metrics.accelerator_gap + accelerators.ceil();
+        }
+        let width = (width + 2.0 * metrics.border).max(metrics.min_menu_width);
+
+        // Menus open below the bar, and submenus to the right of their item,
+        // unless they don't fit.
+        let (mut x, mut y) = if nested {
+            (
+                parent.right(),
+                parent.y - metrics.menu_padding - metrics.border,
+            )
+        } else {
+            (parent.x, parent.bottom())
+        };
+        let (frame_width, frame_height) = (self.width as f32, self.height as f32);
+        if x + width > frame_width {
+            x = if nested {
+                parent.x - width
+            } else {
+                frame_width - width
+            };
+        }
+        if y + height > frame_height {
+            y = frame_height - height;
+        }
+        let rect = Rect::new(x.max(0.0), y.max(0.0), width, height);
+
+        let mut y = rect.y + metrics.border + metrics.menu_padding;
+        let rows = self
+            .visible(menu)
+            .map(|item| {
+                let height = match self.items[&item].props.kind {
+                    ItemKind::Separator => metrics.separator_height,
+                    _ => metrics.row_height,
+                };
+                let width = rect.width - 2.0 * metrics.border;
+                let slot = Slot {
+                    item,
+                    rect: Rect::new(rect.x + metrics.border, y, width, height),
+                };
+                y += height;
+                slot
+            })
+            .collect();
+
+        Popup { menu, rect, rows }
+    }
+}
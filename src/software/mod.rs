@@ -0,0 +1,985 @@
+//! A menu bar that is drawn in software, for windows without native menus.
+//!
+//! Some windows have nowhere native to put a menu bar, like on Wayland
+//! compositors without a global menu, or in kiosks. The [`SoftwareBackend`]
+//! draws the attached menu into a [`Frame`] of RGBA pixels, that the
+//! application copies into its window, and opens menus in response to the
+//! [`InputEvent`]s that the application forwards to it.
+//!
+//! The bar is drawn at the top of the frame, and open menus are drawn over
+//! the rest of it, so the frame has the size of the window and is
+//! transparent where nothing is drawn.
+//!
+//! Menus open when their title in the bar is clicked, and follow the
+//! pointer from there. From the keyboard, F10 or tapping Alt focuses the
+//! bar, and Alt with a letter opens the menu whose title starts with it.
+//! The arrow keys move through the bar and the open menus, Enter chooses an
+//! item and Escape closes a menu. While a menu is open, a letter chooses the
+//! item whose title starts with it. Accelerators of items work whenever the
+//! application forwards key presses.
+//!
+//! Activations are sent to the menu's handlers from
+//! [`SoftwareBackend::handle_event`].
+
+use core::fmt;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use ab_glyph::FontArc;
+use raw_window_handle::RawWindowHandle;
+
+use crate::backend::{
+    Activation, Activator, Backend, ItemKind, ItemProperty, ItemProps, ItemToken, MenuKind,
+    MenuToken,
+};
+use crate::{Error, Key, Modifiers};
+
+mod draw;
+mod input;
+mod layout;
+
+/// A font to draw menus with, usually loaded from a TrueType or OpenType
+/// file.
+#[derive(Clone)]
+pub struct Font(FontArc);
+
+impl Font {
+    /// Load a font from the contents of a font file.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, Error> {
+        FontArc::try_from_vec(data).map(Self).map_err(Error::font)
+    }
+
+    /// Load a font from static data, like a file included with
+    /// [`include_bytes!`].
+    pub fn from_static(data: &'static [u8]) -> Result<Self, Error> {
+        FontArc::try_from_slice(data).map(Self).map_err(Error::font)
+    }
+}
+
+impl fmt::Debug for Font {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Font").finish_non_exhaustive()
+    }
+}
+
+/// The colors and text size that menus are drawn with.
+///
+/// Colors are RGBA, without premultiplied alpha. Sizes are in logical
+/// pixels, that are multiplied by the
+/// [scale factor][SoftwareBackend::set_scale_factor].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Style {
+    pub font_size: f32,
+    pub text: [u8; 4],
+    pub disabled_text: [u8; 4],
+    /// The background of the bar and the menus.
+    pub background: [u8; 4],
+    /// The background of the highlighted item.
+    pub highlight: [u8; 4],
+    /// The text of the highlighted item.
+    pub highlight_text: [u8; 4],
+    /// The line under the bar and around menus.
+    pub border: [u8; 4],
+    pub separator: [u8; 4],
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            font_size: 14.0,
+            text: [0x20, 0x20, 0x20, 0xff],
+            disabled_text: [0xa0, 0xa0, 0xa0, 0xff],
+            background: [0xf6, 0xf5, 0xf4, 0xff],
+            highlight: [0x35, 0x84, 0xe4, 0xff],
+            highlight_text: [0xff, 0xff, 0xff, 0xff],
+            border: [0xc0, 0xbf, 0xbc, 0xff],
+            separator: [0xde, 0xdd, 0xda, 0xff],
+        }
+    }
+}
+
+/// Input that the application forwards from its window.
+///
+/// Positions are in physical pixels from the top left corner of the window,
+/// like the pixels of a [`Frame`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum InputEvent {
+    /// The pointer moved to a position in the window.
+    PointerMoved { x: f32, y: f32 },
+    /// The pointer left the window.
+    PointerLeft,
+    /// The primary button was pressed where the pointer is.
+    PointerPressed,
+    /// The primary button was released where the pointer is.
+    PointerReleased,
+    /// The modifier keys that are held changed.
+    ModifiersChanged(Modifiers),
+    /// A key was pressed, while holding the modifiers from the last
+    /// [`ModifiersChanged`][Self::ModifiersChanged].
+    KeyPressed(Key),
+}
+
+/// A drawn menu bar, with the open menus.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Frame {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Frame {
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The pixels, row by row from the top, as RGBA with premultiplied
+    /// alpha.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn into_pixels(self) -> Vec<u8> {
+        self.pixels
+    }
+}
+
+impl fmt::Debug for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Frame")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A [`Backend`] that draws menus into a pixel buffer.
+///
+/// This is a cheap handle; clones refer to the same menus. Keep a clone
+/// around after attaching, to forward input to it and draw it.
+///
+/// It supports every kind of window, since it doesn't touch the window.
+#[derive(Clone)]
+pub struct SoftwareBackend(Rc<RefCell<Inner>>);
+
+struct Inner {
+    activator: Option<Activator>,
+    font: Font,
+    style: Style,
+    scale: f32,
+    width: u32,
+    height: u32,
+    menus: HashMap<MenuToken, Vec<ItemToken>>,
+    items: HashMap<ItemToken, Node>,
+    /// The menu that was attached.
+    root: Option<MenuToken>,
+    view: View,
+    /// Where the pointer is, if it's in the window.
+    pointer: Option<(f32, f32)>,
+    /// The item in the bar that the button was pressed on, if it isn't a
+    /// submenu.
+    pressed: Option<ItemToken>,
+    modifiers: Modifiers,
+    /// Whether Alt was pressed without anything else so far, so that
+    /// releasing it focuses the bar.
+    alt_tap: bool,
+    /// The item to activate once the backend is no longer borrowed.
+    activated: Option<(ItemToken, Activation)>,
+    /// Whether something changed since the last frame was drawn.
+    dirty: bool,
+}
+
+struct Node {
+    /// The menu the item is in.
+    menu: MenuToken,
+    props: ItemProps,
+}
+
+/// What is highlighted and open.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct View {
+    /// The item in the bar under the pointer.
+    hover: Option<ItemToken>,
+    /// The item in the bar that has keyboard focus, while no menu is open.
+    focus: Option<ItemToken>,
+    /// The open menus, starting with the one opened from the bar.
+    open: Vec<Level>,
+    /// Whether to underline the letters that choose items.
+    mnemonics: bool,
+}
+
+/// An open menu.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Level {
+    /// The submenu item that opened the menu.
+    item: ItemToken,
+    menu: MenuToken,
+    /// The highlighted item in the menu.
+    selected: Option<ItemToken>,
+}
+
+/// The first letter or digit of a title, that chooses its item from the
+/// keyboard, and its index among the characters.
+fn mnemonic(title: &str) -> Option<(usize, char)> {
+    title.chars().enumerate().find(|(_, c)| c.is_alphanumeric())
+}
+
+impl Inner {
+    fn props(&self, item: ItemToken) -> Option<&ItemProps> {
+        self.items.get(&item).map(|node| &node.props)
+    }
+
+    /// Whether the item can be highlighted and chosen.
+    fn selectable(&self, item: ItemToken) -> bool {
+        matches!(
+            self.props(item),
+            Some(props) if props.enabled && !props.hidden && props.kind != ItemKind::Separator
+        )
+    }
+
+    /// The menu that the item opens, if it's a submenu item that can be
+    /// chosen.
+    fn submenu(&self, item: ItemToken) -> Option<MenuToken> {
+        match self.props(item)?.kind {
+            ItemKind::Submenu(menu) if self.selectable(item) => Some(menu),
+            _ => None,
+        }
+    }
+
+    /// The items of a menu that are shown.
+    fn visible(&self, menu: MenuToken) -> impl Iterator<Item = ItemToken> + '_ {
+        self.menus
+            .get(&menu)
+            .map_or(&[][..], Vec::as_slice)
+            .iter()
+            .copied()
+            .filter(move |item| !self.items[item].props.hidden)
+    }
+
+    /// The items shown in the bar.
+    fn bar_items(&self) -> Vec<ItemToken> {
+        match self.root {
+            Some(root) => self
+                .visible(root)
+                .filter(|item| self.items[item].props.kind != ItemKind::Separator)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Whether a user would be able to activate the item.
+    ///
+    /// The item and all its ancestors must be enabled and visible, and the
+    /// menu must be attached.
+    fn reachable(&self, item: ItemToken) -> bool {
+        let mut current = item;
+        loop {
+            let node = match self.items.get(&current) {
+                Some(node) => node,
+                None => return false,
+            };
+            if !node.props.enabled || node.props.hidden {
+                return false;
+            }
+            if Some(node.menu) == self.root {
+                return true;
+            }
+            let parent = self
+                .items
+                .iter()
+                .find(|(_, parent)| parent.props.kind == ItemKind::Submenu(node.menu));
+            match parent {
+                Some((parent, _)) => current = *parent,
+                None => return false,
+            }
+        }
+    }
+
+    /// Close menus and clear highlights that no longer match the menu,
+    /// after it changed.
+    fn prune(&mut self) {
+        let mut view = core::mem::take(&mut self.view);
+        let mut parent = self.root;
+        let valid = view.open.iter().position(|level| {
+            let ok = parent.is_some()
+                && self.items.get(&level.item).map(|node| node.menu) == parent
+                && self.submenu(level.item) == Some(level.menu);
+            parent = Some(level.menu);
+            !ok
+        });
+        if let Some(valid) = valid {
+            view.open.truncate(valid);
+        }
+        for level in &mut view.open {
+            if let Some(selected) = level.selected {
+                let node = self.items.get(&selected);
+                if node.map(|node| node.menu) != Some(level.menu) || !self.selectable(selected) {
+                    level.selected = None;
+                }
+            }
+        }
+        let bar = self.bar_items();
+        view.hover = view.hover.filter(|item| bar.contains(item));
+        view.focus = view.focus.filter(|item| bar.contains(item));
+        self.view = view;
+        self.dirty = true;
+    }
+
+    fn forget_item(&mut self, item: ItemToken) {
+        if let Some(node) = self.items.remove(&item) {
+            if let ItemKind::Submenu(submenu) = node.props.kind {
+                for child in self.menus.remove(&submenu).unwrap_or_default() {
+                    self.forget_item(child);
+                }
+            }
+        }
+    }
+}
+
+impl SoftwareBackend {
+    pub fn new(font: Font) -> Self {
+        Self(Rc::new(RefCell::new(Inner {
+            activator: None,
+            font,
+            style: Style::default(),
+            scale: 1.0,
+            width: 0,
+            height: 0,
+            menus: HashMap::new(),
+            items: HashMap::new(),
+            root: None,
+            view: View::default(),
+            pointer: None,
+            pressed: None,
+            modifiers: Modifiers::empty(),
+            alt_tap: false,
+            activated: None,
+            dirty: true,
+        })))
+    }
+
+    pub fn set_style(&self, style: Style) {
+        let mut inner = self.0.borrow_mut();
+        inner.style = style;
+        inner.dirty = true;
+    }
+
+    /// Set the number of physical pixels per logical pixel of the window.
+    pub fn set_scale_factor(&self, scale: f64) {
+        let mut inner = self.0.borrow_mut();
+        inner.scale = scale as f32;
+        inner.dirty = true;
+    }
+
+    /// Set the size of the frame, usually the size of the window's
+    /// contents, in physical pixels.
+    pub fn resize(&self, width: u32, height: u32) {
+        let mut inner = self.0.borrow_mut();
+        inner.width = width;
+        inner.height = height;
+        inner.dirty = true;
+    }
+
+    /// The height of the bar, in physical pixels.
+    ///
+    /// The application can lay out its contents below it.
+    pub fn bar_height(&self) -> u32 {
+        self.0.borrow().metrics().bar_height as u32
+    }
+
+    /// Whether a menu is open or the bar has keyboard focus.
+    ///
+    /// While it is, the bar takes all input that is forwarded to it.
+    pub fn is_active(&self) -> bool {
+        let inner = self.0.borrow();
+        !inner.view.open.is_empty() || inner.view.focus.is_some()
+    }
+
+    /// Whether the bar looks different than in the last frame that was
+    /// drawn, because of input or changes to the menu.
+    pub fn needs_redraw(&self) -> bool {
+        self.0.borrow().dirty
+    }
+
+    /// Draw the bar and the open menus.
+    pub fn render(&self) -> Frame {
+        let mut inner = self.0.borrow_mut();
+        inner.dirty = false;
+        inner.draw()
+    }
+
+    /// Handle input from the window, and activate the item that it chose.
+    ///
+    /// Returns whether the bar used the input. If it didn't, the
+    /// application should handle it itself.
+    pub fn handle_event(&self, event: InputEvent) -> bool {
+        let (handled, activated) = {
+            let mut inner = self.0.borrow_mut();
+            let before = inner.view.clone();
+            let handled = inner.handle(event);
+            if inner.view != before {
+                inner.dirty = true;
+            }
+            let activated = inner.activated.take();
+            (handled, activated.zip(inner.activator.clone()))
+        };
+
+        // The handler may call back into the backend.
+        if let Some(((item, activation), activator)) = activated {
+            activator.activate(item, activation);
+        }
+        handled
+    }
+}
+
+impl fmt::Debug for SoftwareBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.0.borrow();
+        f.debug_struct("SoftwareBackend")
+            .field("root", &inner.root)
+            .field("width", &inner.width)
+            .field("height", &inner.height)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Backend for SoftwareBackend {
+    fn supports(&self, window: RawWindowHandle) -> bool {
+        true
+    }
+
+    fn set_activator(&mut self, activator: Activator) {
+        self.0.borrow_mut().activator = Some(activator);
+    }
+
+    fn create_menu(&mut self, menu: MenuToken, kind: MenuKind) -> Result<(), Error> {
+        let mut inner = self.0.borrow_mut();
+        // Only one menu can be drawn at a time, and the tokens of a second
+        // one would overwrite those of the first.
+        if kind == MenuKind::Bar && inner.root.is_some() {
+            return Err(Error::menu_exists());
+        }
+        inner.menus.insert(menu, Vec::new());
+        Ok(())
+    }
+
+    fn destroy_menu(&mut self, menu: MenuToken) {
+        self.0.borrow_mut().menus.remove(&menu);
+    }
+
+    fn insert_item(
+        &mut self,
+        menu: MenuToken,
+        index: usize,
+        item: ItemToken,
+        props: &ItemProps,
+    ) -> Result<(), Error> {
+        let mut inner = self.0.borrow_mut();
+        inner
+            .menus
+            .get_mut(&menu)
+            .expect("unknown menu token")
+            .insert(index, item);
+        let props = props.clone();
+        inner.items.insert(item, Node { menu, props });
+        inner.prune();
+        Ok(())
+    }
+
+    fn remove_item(&mut self, item: ItemToken) -> Result<(), Error> {
+        let mut inner = self.0.borrow_mut();
+        let menu = inner.items.get(&item).expect("unknown item token").menu;
+        inner.menus.get_mut(&menu).unwrap().retain(|i| *i != item);
+        inner.forget_item(item);
+        inner.prune();
+        Ok(())
+    }
+
+    fn update_item(&mut self, item: ItemToken, property: &ItemProperty) -> Result<(), Error> {
+        let mut inner = self.0.borrow_mut();
+        let node = inner.items.get_mut(&item).expect("unknown item token");
+        node.props.apply(property);
+        inner.prune();
+        Ok(())
+    }
+
+    fn move_item(&mut self, item: ItemToken, menu: MenuToken, index: usize) -> Result<(), Error> {
+        let mut inner = self.0.borrow_mut();
+        let node = inner.items.get_mut(&item).expect("unknown item token");
+        let old = core::mem::replace(&mut node.menu, menu);
+        inner.menus.get_mut(&old).unwrap().retain(|i| *i != item);
+        inner
+            .menus
+            .get_mut(&menu)
+            .expect("unknown menu token")
+            .insert(index, item);
+        inner.prune();
+        Ok(())
+    }
+
+    fn attach(&mut self, menu: MenuToken, window: RawWindowHandle) -> Result<(), Error> {
+        let mut inner = self.0.borrow_mut();
+        if inner.root.is_some() {
+            return Err(Error::menu_exists());
+        }
+        inner.root = Some(menu);
+        inner.prune();
+        Ok(())
+    }
+
+    fn detach(&mut self, menu: MenuToken) {
+        let mut inner = self.0.borrow_mut();
+        inner.root = None;
+        inner.menus.clear();
+        inner.items.clear();
+        inner.prune();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tiny_skia::{IntSize, Pixmap};
+
+    use super::layout::Hit;
+    use super::*;
+    use crate::headless::NoWindow;
+    use crate::{AttachedMenu, Menu, MenuItem, Separator, Submenu};
+
+    type Log = Rc<RefCell<Vec<&'static str>>>;
+
+    fn item(log: &Log, title: &'static str) -> MenuItem<()> {
+        let log = Rc::clone(log);
+        MenuItem::new(title)
+            .with_id(title)
+            .with_handler(move || log.borrow_mut().push(title))
+    }
+
+    /// A backend with a menu attached, and the titles of the items that
+    /// were activated.
+    fn attached() -> (SoftwareBackend, AttachedMenu<()>, Log) {
+        // Vendored, so that the snapshots don't depend on installed fonts.
+        let font = Font::from_static(include_bytes!("fonts/DejaVuSans.ttf")).unwrap();
+        let backend = SoftwareBackend::new(font);
+        backend.resize(400, 240);
+        let log = Log::default();
+        let menu = Menu::new()
+            .with(
+                Submenu::new(
+                    "File",
+                    Menu::new()
+                        .with(item(&log, "New").with_accelerator("CmdOrCtrl+N".parse().unwrap()))
+                        .with(item(&log, "Open…").with_accelerator("CmdOrCtrl+O".parse().unwrap()))
+                        .with(Separator)
+                        .with(
+                            Submenu::new(
+                                "Recent",
+                                Menu::new()
+                                    .with(item(&log, "notes.txt"))
+                                    .with(item(&log, "todo.txt")),
+                            )
+                            .with_id("Recent"),
+                        )
+                        .with(Separator)
+                        .with(item(&log, "Quit").with_accelerator("CmdOrCtrl+Q".parse().unwrap())),
+                )
+                .with_id("File"),
+            )
+            .with(
+                Submenu::new(
+                    "Edit",
+                    Menu::new()
+                        .with(
+                            item(&log, "Undo")
+                                .with_accelerator("CmdOrCtrl+Z".parse().unwrap())
+                                .with_enabled(false),
+                        )
+                        .with(item(&log, "Redo"))
+                        .with(Separator)
+                        .with(item(&log, "Word Wrap").with_checked(true)),
+                )
+                .with_id("Edit"),
+            )
+            .with(item(&log, "Help"));
+        let attached = crate::attach_with(menu, &NoWindow, backend.clone()).unwrap();
+        (backend, attached, log)
+    }
+
+    /// The middle of an item in the bar or in an open menu.
+    fn position(backend: &SoftwareBackend, item: ItemToken) -> (f32, f32) {
+        let layout = backend.0.borrow().layout();
+        let rows = layout.popups.iter().flat_map(|popup| &popup.rows);
+        let slot = layout
+            .items
+            .iter()
+            .chain(rows)
+            .find(|slot| slot.item == item);
+        let rect = slot.expect("item is not shown").rect;
+        (rect.x + rect.width / 2.0, rect.y + rect.height / 2.0)
+    }
+
+    fn token(attached: &AttachedMenu<()>, id: &str) -> ItemToken {
+        attached.item(id).unwrap().token()
+    }
+
+    fn move_to(backend: &SoftwareBackend, attached: &AttachedMenu<()>, id: &str) -> bool {
+        let (x, y) = position(backend, token(attached, id));
+        backend.handle_event(InputEvent::PointerMoved { x, y })
+    }
+
+    fn click(backend: &SoftwareBackend, attached: &AttachedMenu<()>, id: &str) {
+        move_to(backend, attached, id);
+        assert!(backend.handle_event(InputEvent::PointerPressed));
+        assert!(backend.handle_event(InputEvent::PointerReleased));
+    }
+
+    fn press(backend: &SoftwareBackend, modifiers: Modifiers, key: Key) -> bool {
+        backend.handle_event(InputEvent::ModifiersChanged(modifiers));
+        let handled = backend.handle_event(InputEvent::KeyPressed(key));
+        backend.handle_event(InputEvent::ModifiersChanged(Modifiers::empty()));
+        handled
+    }
+
+    /// The titles of the items that open the open menus, and of the
+    /// highlighted item in the deepest one.
+    fn open(backend: &SoftwareBackend) -> (Vec<String>, Option<String>) {
+        let inner = backend.0.borrow();
+        let title = |item: ItemToken| inner.items[&item].props.title.clone();
+        let open = inner.view.open.iter().map(|level| title(level.item));
+        let selected = inner.view.open.last().and_then(|level| level.selected);
+        (open.collect(), selected.map(title))
+    }
+
+    /// Compare a frame with the PNG of the same name in `snapshots`.
+    ///
+    /// Set `MENUBAR_UPDATE_SNAPSHOTS` to write the frame there instead.
+    fn assert_snapshot(name: &str, frame: Frame) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/software/snapshots")
+            .join(format!("{}.png", name));
+        let size = IntSize::from_wh(frame.width(), frame.height()).unwrap();
+        let actual = Pixmap::from_vec(frame.into_pixels(), size).unwrap();
+        if std::env::var_os("MENUBAR_UPDATE_SNAPSHOTS").is_some() {
+            actual.save_png(&path).unwrap();
+            return;
+        }
+
+        let expected = Pixmap::load_png(&path).unwrap_or_else(|err| {
+            panic!(
+                "failed to load {}: {}; set MENUBAR_UPDATE_SNAPSHOTS to create it",
+                path.display(),
+                err
+            )
+        });
+        assert_eq!(
+            (expected.width(), expected.height()),
+            (actual.width(), actual.height()),
+            "size of snapshot {}",
+            name
+        );
+        // Allow for rounding differences in anti-aliasing.
+        let different = expected
+            .data()
+            .iter()
+            .zip(actual.data())
+            .filter(|(expected, actual)| expected.abs_diff(**actual) > 2)
+            .count();
+        if different > 0 {
+            let output = std::env::temp_dir().join(format!("{}.png", name));
+            actual.save_png(&output).unwrap();
+            panic!(
+                "{} bytes differ from snapshot {}, see {}",
+                different,
+                name,
+                output.display()
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_font() {
+        let err = Font::from_bytes(vec![1, 2, 3]).unwrap_err();
+        assert!(err.to_string().starts_with("invalid font"));
+    }
+
+    #[test]
+    fn test_snapshot_bar() {
+        let (backend, attached, _) = attached();
+        assert!(move_to(&backend, &attached, "Edit"));
+        assert_eq!(backend.bar_height(), 23);
+        assert_snapshot("bar", backend.render());
+    }
+
+    #[test]
+    fn test_snapshot_menu() {
+        let (backend, attached, _) = attached();
+        click(&backend, &attached, "File");
+        move_to(&backend, &attached, "Open…");
+        assert_snapshot("menu", backend.render());
+    }
+
+    #[test]
+    fn test_snapshot_submenu() {
+        let (backend, attached, _) = attached();
+        click(&backend, &attached, "File");
+        move_to(&backend, &attached, "Recent");
+        move_to(&backend, &attached, "todo.txt");
+        assert_snapshot("submenu", backend.render());
+    }
+
+    #[test]
+    fn test_snapshot_mnemonics() {
+        let (backend, attached, _) = attached();
+        backend.set_scale_factor(2.0);
+        backend.resize(600, 320);
+        assert!(press(&backend, Modifiers::ALT, Key::Char('E')));
+        assert_snapshot("mnemonics", backend.render());
+    }
+
+    #[test]
+    fn test_redraw() {
+        let (backend, attached, _) = attached();
+        assert!(backend.needs_redraw());
+        backend.render();
+        assert!(!backend.needs_redraw());
+
+        // Moving over the same item again changes nothing.
+        move_to(&backend, &attached, "File");
+        assert!(backend.needs_redraw());
+        backend.render();
+        move_to(&backend, &attached, "File");
+        assert!(!backend.needs_redraw());
+
+        attached.item("Redo").unwrap().set_title("Again").unwrap();
+        assert!(backend.needs_redraw());
+    }
+
+    #[test]
+    fn test_pointer() {
+        let (backend, attached, log) = attached();
+        // Clicks outside go to the window while no menu is open.
+        backend.handle_event(InputEvent::PointerMoved { x: 200.0, y: 200.0 });
+        assert!(!backend.handle_event(InputEvent::PointerPressed));
+
+        click(&backend, &attached, "File");
+        assert_eq!(open(&backend), (vec!["File".to_owned()], None));
+        assert!(backend.is_active());
+
+        // Moving along the bar switches menus.
+        move_to(&backend, &attached, "Edit");
+        assert_eq!(open(&backend).0, ["Edit"]);
+        move_to(&backend, &attached, "Undo");
+        assert_eq!(open(&backend), (vec!["Edit".to_owned()], None));
+        click(&backend, &attached, "Undo");
+        assert!(log.borrow().is_empty());
+        click(&backend, &attached, "Redo");
+        assert_eq!(*log.borrow(), ["Redo"]);
+        assert!(!backend.is_active());
+
+        // Submenus open when hovered.
+        click(&backend, &attached, "File");
+        move_to(&backend, &attached, "Recent");
+        assert_eq!(open(&backend).0, ["File", "Recent"]);
+        click(&backend, &attached, "notes.txt");
+        assert_eq!(*log.borrow(), ["Redo", "notes.txt"]);
+
+        // Clicking the open menu's title, or outside, closes it.
+        click(&backend, &attached, "File");
+        click(&backend, &attached, "File");
+        assert!(!backend.is_active());
+        click(&backend, &attached, "File");
+        backend.handle_event(InputEvent::PointerMoved { x: 399.0, y: 239.0 });
+        assert!(backend.handle_event(InputEvent::PointerPressed));
+        assert!(!backend.is_active());
+
+        // Items in the bar are activated directly.
+        click(&backend, &attached, "Help");
+        assert_eq!(*log.borrow(), ["Redo", "notes.txt", "Help"]);
+    }
+
+    #[test]
+    fn test_keyboard() {
+        let (backend, attached, log) = attached();
+        let none = Modifiers::empty();
+        assert!(!press(&backend, none, Key::Down));
+
+        assert!(press(&backend, none, Key::F(10)));
+        assert!(backend.is_active());
+        assert!(press(&backend, none, Key::Right));
+        assert!(press(&backend, none, Key::Down));
+        // Disabled items are skipped.
+        assert_eq!(
+            open(&backend),
+            (vec!["Edit".to_owned()], Some("Redo".to_owned()))
+        );
+        press(&backend, none, Key::Down);
+        assert_eq!(open(&backend).1.as_deref(), Some("Word Wrap"));
+        press(&backend, none, Key::Down);
+        assert_eq!(open(&backend).1.as_deref(), Some("Redo"));
+
+        // Left and Right move through the bar, skipping plain items with
+        // menus open.
+        press(&backend, none, Key::Right);
+        assert_eq!(
+            open(&backend),
+            (vec!["File".to_owned()], Some("New".to_owned()))
+        );
+        press(&backend, none, Key::End);
+        press(&backend, none, Key::Up);
+        assert_eq!(open(&backend).1.as_deref(), Some("Recent"));
+        press(&backend, none, Key::Right);
+        assert_eq!(
+            open(&backend),
+            (
+                vec!["File".to_owned(), "Recent".to_owned()],
+                Some("notes.txt".to_owned())
+            )
+        );
+        press(&backend, none, Key::Left);
+        assert_eq!(open(&backend).0, ["File"]);
+        press(&backend, none, Key::Enter);
+        press(&backend, none, Key::Down);
+        press(&backend, none, Key::Enter);
+        assert_eq!(*log.borrow(), ["todo.txt"]);
+        assert!(!backend.is_active());
+
+        // Escape closes one menu at a time, and then the bar.
+        press(&backend, none, Key::F(10));
+        press(&backend, none, Key::Down);
+        assert_eq!(open(&backend).0, ["File"]);
+        press(&backend, none, Key::Escape);
+        assert_eq!(open(&backend).0, Vec::<String>::new());
+        assert!(backend.is_active());
+        press(&backend, none, Key::Escape);
+        assert!(!backend.is_active());
+    }
+
+    #[test]
+    fn test_mnemonics() {
+        let (backend, attached, log) = attached();
+        let none = Modifiers::empty();
+        assert!(press(&backend, Modifiers::ALT, Key::Char('F')));
+        assert_eq!(
+            open(&backend),
+            (vec!["File".to_owned()], Some("New".to_owned()))
+        );
+        press(&backend, none, Key::Char('r'));
+        assert_eq!(open(&backend).0, ["File", "Recent"]);
+        press(&backend, none, Key::Char('T'));
+        assert_eq!(*log.borrow(), ["todo.txt"]);
+
+        // Letters that start several items highlight each in turn.
+        attached
+            .item("File")
+            .unwrap()
+            .push(item(&log, "Quit All"))
+            .unwrap();
+        press(&backend, Modifiers::ALT, Key::Char('F'));
+        press(&backend, none, Key::Char('Q'));
+        assert_eq!(open(&backend).1.as_deref(), Some("Quit"));
+        press(&backend, none, Key::Char('Q'));
+        assert_eq!(open(&backend).1.as_deref(), Some("Quit All"));
+        press(&backend, none, Key::Char('Q'));
+        assert_eq!(open(&backend).1.as_deref(), Some("Quit"));
+
+        // Letters in the bar open its menus, and activate plain items.
+        press(&backend, none, Key::Escape);
+        press(&backend, none, Key::Char('H'));
+        assert_eq!(*log.borrow(), ["todo.txt", "Help"]);
+        assert!(!backend.is_active());
+
+        // Alt with a letter that doesn't start a menu does nothing.
+        assert!(!press(&backend, Modifiers::ALT, Key::Char('X')));
+    }
+
+    #[test]
+    fn test_alt_tap() {
+        let (backend, attached, _) = attached();
+        assert!(!backend.handle_event(InputEvent::ModifiersChanged(Modifiers::ALT)));
+        assert!(backend.handle_event(InputEvent::ModifiersChanged(Modifiers::empty())));
+        assert!(backend.is_active());
+        assert!(backend.0.borrow().view.mnemonics);
+        backend.handle_event(InputEvent::ModifiersChanged(Modifiers::ALT));
+        backend.handle_event(InputEvent::ModifiersChanged(Modifiers::empty()));
+        assert!(!backend.is_active());
+
+        // Alt that's used with a key doesn't focus the bar.
+        press(&backend, Modifiers::ALT, Key::F(4));
+        assert!(!backend.is_active());
+        backend.handle_event(InputEvent::ModifiersChanged(Modifiers::ALT));
+        backend.handle_event(InputEvent::ModifiersChanged(
+            Modifiers::ALT | Modifiers::SHIFT,
+        ));
+        backend.handle_event(InputEvent::ModifiersChanged(Modifiers::empty()));
+        assert!(!backend.is_active());
+    }
+
+    #[test]
+    fn test_accelerators() {
+        let (backend, attached, log) = attached();
+        let ctrl = Modifiers::CMD_OR_CTRL.resolve();
+        assert!(press(&backend, ctrl, Key::Char('n')));
+        assert_eq!(*log.borrow(), ["New"]);
+        // Disabled items don't react.
+        assert!(!press(&backend, ctrl, Key::Char('Z')));
+        assert!(!press(&backend, ctrl, Key::Char('X')));
+
+        // Accelerators work while a menu is open, and close it.
+        click(&backend, &attached, "Edit");
+        assert!(press(&backend, ctrl, Key::Char('O')));
+        assert_eq!(*log.borrow(), ["New", "Open…"]);
+        assert!(!backend.is_active());
+
+        attached.item("File").unwrap().set_enabled(false).unwrap();
+        assert!(!press(&backend, ctrl, Key::Char('N')));
+    }
+
+    #[test]
+    fn test_changes() {
+        let (backend, attached, _) = attached();
+        click(&backend, &attached, "File");
+        move_to(&backend, &attached, "Recent");
+        move_to(&backend, &attached, "notes.txt");
+        assert_eq!(open(&backend).0, ["File", "Recent"]);
+
+        // Removing the highlighted item clears the highlight.
+        attached.item("notes.txt").unwrap().remove().unwrap();
+        assert_eq!(
+            open(&backend),
+            (vec!["File".to_owned(), "Recent".to_owned()], None)
+        );
+
+        // Menus close when the item that opened them is hidden.
+        attached.item("Recent").unwrap().set_hidden(true).unwrap();
+        assert_eq!(open(&backend), (vec!["File".to_owned()], None));
+        attached.remove("File").unwrap();
+        assert_eq!(open(&backend).0, Vec::<String>::new());
+
+        // The bar moves over the removed menu.
+        let layout = backend.0.borrow().layout();
+        assert_eq!(
+            layout.hit(1.0, 1.0),
+            Hit::Bar(Some(token(&attached, "Edit")))
+        );
+    }
+
+    #[test]
+    fn test_attach_twice() {
+        let (backend, attached, log) = attached();
+        let copy = MenuItem::new("Copy").with_id("Copy");
+        let menu = Menu::new().with(Submenu::new("Other", Menu::new().with(copy)));
+        assert!(crate::attach_with(menu, &NoWindow, backend.clone()).is_err());
+
+        // The first menu is still drawn, and still dispatches to its handlers.
+        assert_eq!(backend.0.borrow().layout().items.len(), 3);
+        click(&backend, &attached, "File");
+        click(&backend, &attached, "New");
+        assert_eq!(*log.borrow(), ["New"]);
+    }
+}
@@ -8,3 +8,226 @@ pub static STRINGS: [&str; 5] = [
     // "test\0",
     // "test\0test",
 ];
+
+/// An X11 window that doesn't exist, for backends that only need its id.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub struct XlibWindow;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+unsafe impl raw_window_handle::HasRawWindowHandle for XlibWindow {
+    fn raw_window_handle(&self) -> raw_window_handle::RawWindowHandle {
+        let mut handle = raw_window_handle::XlibWindowHandle::empty();
+        handle.window = 0x0400_0001;
+        raw_window_handle::RawWindowHandle::Xlib(handle)
+    }
+}
+
+/// A private D-Bus session bus, that is stopped when dropped.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub struct TestBus {
+    daemon: std::process::Child,
+    address: String,
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl TestBus {
+    /// Start a bus with `dbus-daemon`.
+    ///
+    /// Panics if `dbus-daemon` isn't installed; the D-Bus tests need it.
+    pub fn start() -> Self {
+        use std::io::{BufRead, BufReader, ErrorKind};
+        use std::process::{Command, Stdio};
+
+        let daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut daemon = match daemon {
+            Ok(daemon) => daemon,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                panic!("dbus-daemon is not installed, install it to run the D-Bus tests")
+            }
+            Err(err) => panic!("failed to start dbus-daemon: {}", err),
+        };
+        let mut address = String::new();
+        let stdout = daemon.stdout.take().unwrap();
+        BufReader::new(stdout).read_line(&mut address).unwrap();
+        let address = address.trim().to_owned();
+        assert!(!address.is_empty(), "dbus-daemon didn't print its address");
+        Self { daemon, address }
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Connect a client to the bus.
+    pub fn connect(&self) -> zbus::blocking::Connection {
+        zbus::blocking::connection::Builder::address(self.address())
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl Drop for TestBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+/// A private X server, that is stopped when dropped.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub struct TestDisplay {
+    server: std::process::Child,
+    name: String,
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl TestDisplay {
+    /// Start a server with `Xvfb`, on the first free display.
+    ///
+    /// Panics if `Xvfb` isn't installed; the X11 tests need it.
+    pub fn start() -> Self {
+        use std::io::{BufRead, BufReader, ErrorKind};
+        use std::process::{Command, Stdio};
+
+        // The display number is written to stdout once the server is ready.
+        let server = Command::new("Xvfb")
+            .args(["-displayfd", "1", "-nolisten", "tcp"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut server = match server {
+            Ok(server) => server,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                panic!("Xvfb is not installed, install it to run the X11 tests")
+            }
+            Err(err) => panic!("failed to start Xvfb: {}", err),
+        };
+        let mut number = String::new();
+        let stdout = server.stdout.take().unwrap();
+        BufReader::new(stdout).read_line(&mut number).unwrap();
+        let number = number.trim();
+        assert!(!number.is_empty(), "Xvfb didn't print its display");
+        let name = format!(":{}", number);
+        Self { server, name }
+    }
+
+    /// The name of the display, like `:1`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl Drop for TestDisplay {
+    fn drop(&mut self) {
+        let _ = self.server.kill();
+        let _ = self.server.wait();
+    }
+}
+
+/// A menu with a bit of everything, for the D-Bus backends to export.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn menu() -> crate::Menu<&'static str> {
+    use crate::{Menu, MenuItem, Separator, Submenu};
+
+    Menu::default()
+        .with(Submenu::new(
+            "File",
+            Menu::default()
+                .with(
+                    MenuItem::new("New")
+                        .with_id("new")
+                        .with_command("new")
+                        .with_accelerator("CmdOrCtrl+N".parse().unwrap()),
+                )
+                .with(
+                    MenuItem::new("Save_As")
+                        .with_id("save-as")
+                        .with_command("save-as")
+                        .with_enabled(false),
+                )
+                .with(Separator)
+                .with(
+                    MenuItem::new("Autosave")
+                        .with_id("autosave")
+                        .with_command("autosave")
+                        .with_checked(true),
+                )
+                .with(MenuItem::new("Hidden").with_hidden(true)),
+        ))
+        .with(Submenu::new("Help", Menu::default()).with_id("help"))
+}
+
+/// Call a method of the menu exported on the bus with the given name.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn call<B>(
+    client: &zbus::blocking::Connection,
+    name: &str,
+    interface: &str,
+    method: &str,
+    body: &B,
+) -> zbus::Result<zbus::Message>
+where
+    B: serde::Serialize + zbus::zvariant::DynamicType,
+{
+    let path = crate::dbusmenu::OBJECT_PATH;
+    client.call_method(Some(name), path, Some(interface), method, body)
+}
+
+/// Properties or attributes as `name=value` pairs, sorted by name.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn describe(
+    properties: &std::collections::HashMap<String, zbus::zvariant::OwnedValue>,
+) -> String {
+    let mut properties: Vec<_> = properties.iter().collect();
+    properties.sort_by_key(|(name, _)| *name);
+    let properties: Vec<_> = properties
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, ***value))
+        .collect();
+    properties.join(" ")
+}
+
+/// Receive the signals that match the rule on another thread.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn signals(
+    client: &zbus::blocking::Connection,
+    rule: zbus::MatchRule<'static>,
+) -> std::sync::mpsc::Receiver<zbus::Message> {
+    let messages = zbus::blocking::MessageIterator::for_match_rule(rule, client, None).unwrap();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for message in messages {
+            if sender.send(message.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// The name of the next signal, like `org.gtk.Menus.Changed`, and its
+/// message.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn next_signal(signals: &std::sync::mpsc::Receiver<zbus::Message>) -> (String, zbus::Message) {
+    let message = signals
+        .recv_timeout(std::time::Duration::from_secs(5))
+        .expect("no signal was received");
+    let name = {
+        let header = message.header();
+        format!(
+            "{}.{}",
+            header.interface().unwrap(),
+            header.member().unwrap()
+        )
+    };
+    (name, message)
+}
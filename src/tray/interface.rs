@@ -0,0 +1,246 @@
+//! The `org.kde.StatusNotifierItem` interface, and the watcher that items
+//! register with.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use zbus::blocking::Connection;
+use zbus::object_server::SignalEmitter;
+use zbus::proxy::CacheProperties;
+use zbus::zvariant::ObjectPath;
+
+use super::{Icon, IconData, TrayEvent, TrayStatus};
+use crate::dbusmenu::interface::Wakeup;
+use crate::dbusmenu::registrar::is_missing_service;
+use crate::dbusmenu::OBJECT_PATH as MENU_PATH;
+use crate::Error;
+
+/// The object path that the item is exported at.
+pub(super) const ITEM_PATH: &str = "/StatusNotifierItem";
+
+/// The well-known name of the watcher.
+pub(super) const WATCHER: &str = "org.kde.StatusNotifierWatcher";
+
+/// The object path of the watcher.
+pub(super) const WATCHER_PATH: &str = "/StatusNotifierWatcher";
+
+#[zbus::proxy(
+    interface = "org.kde.StatusNotifierWatcher",
+    default_service = "org.kde.StatusNotifierWatcher",
+    default_path = "/StatusNotifierWatcher",
+    gen_async = false,
+    blocking_name = "WatcherProxy"
+)]
+trait Watcher {
+    fn register_status_notifier_item(&self, service: &str) -> zbus::Result<()>;
+}
+
+/// Register the item that is exported on the connection with the watcher,
+/// if there is one.
+pub(super) fn register(connection: &Connection) -> Result<(), Error> {
+    let name = match connection.unique_name() {
+        Some(name) => name.to_string(),
+        None => return Ok(()),
+    };
+    let result = WatcherProxy::builder(connection)
+        .cache_properties(CacheProperties::No)
+        .build()
+        .and_then(|proxy| proxy.register_status_notifier_item(&name));
+    match result {
+        Ok(()) => Ok(()),
+        Err(err) if is_missing_service(&err) => Ok(()),
+        Err(err) => Err(Error::dbus(err)),
+    }
+}
+
+/// An icon as `(iiay)`: its width, its height, and its ARGB32 pixels in
+/// network byte order.
+type Pixmap = (i32, i32, Vec<u8>);
+
+/// A tooltip as `(sa(iiay)ss)`: its icon name, its icon pixmaps, its title,
+/// and its description.
+type ToolTip = (String, Vec<Pixmap>, String, String);
+
+/// The properties of the item.
+pub(super) struct Props {
+    pub(super) id: String,
+    pub(super) title: String,
+    pub(super) tooltip: String,
+    pub(super) icon: Option<Icon>,
+    pub(super) status: TrayStatus,
+}
+
+/// State shared between the tray icon and the connection's thread.
+pub(super) struct Shared {
+    props: Mutex<Props>,
+    /// Events waiting to be dispatched.
+    events: Mutex<VecDeque<TrayEvent>>,
+    wakeup: Mutex<Option<Wakeup>>,
+}
+
+impl Shared {
+    pub(super) fn new(props: Props) -> Self {
+        Self {
+            props: Mutex::new(props),
+            events: Mutex::default(),
+            wakeup: Mutex::default(),
+        }
+    }
+
+    pub(super) fn props(&self) -> MutexGuard<'_, Props> {
+        self.props.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    pub(super) fn set_wakeup(&self, wakeup: Option<Wakeup>) {
+        *self.wakeup.lock().unwrap_or_else(|err| err.into_inner()) = wakeup;
+    }
+
+    /// Take the events that happened, oldest first.
+    pub(super) fn take_events(&self) -> VecDeque<TrayEvent> {
+        let mut events = self.events.lock().unwrap_or_else(|err| err.into_inner());
+        core::mem::take(&mut *events)
+    }
+
+    fn push_event(&self, event: TrayEvent) {
+        self.events
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push_back(event);
+
+        let wakeup = self
+            .wakeup
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone();
+        if let Some(wakeup) = wakeup {
+            wakeup();
+        }
+    }
+
+    fn icon_name(&self) -> String {
+        match &self.props().icon {
+            Some(Icon(IconData::Named(name))) => name.clone(),
+            _ => String::new(),
+        }
+    }
+
+    fn icon_pixmap(&self) -> Vec<Pixmap> {
+        match &self.props().icon {
+            Some(Icon(IconData::Pixels {
+                width,
+                height,
+                argb,
+            })) => vec![(*width, *height, argb.clone())],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// The object that is served on the connection.
+pub(super) struct StatusNotifierItem(pub(super) Arc<Shared>);
+
+#[zbus::interface(name = "org.kde.StatusNotifierItem")]
+impl StatusNotifierItem {
+    /// The menu is opened by the desktop, through the `Menu` property.
+    fn context_menu(&self, x: i32, y: i32) {}
+
+    fn activate(&self, x: i32, y: i32) {
+        self.0.push_event(TrayEvent::Activate { x, y });
+    }
+
+    fn secondary_activate(&self, x: i32, y: i32) {
+        self.0.push_event(TrayEvent::SecondaryActivate { x, y });
+    }
+
+    fn scroll(&self, delta: i32, orientation: &str) {}
+
+    #[zbus(property)]
+    fn category(&self) -> &str {
+        "ApplicationStatus"
+    }
+
+    #[zbus(property)]
+    fn id(&self) -> String {
+        self.0.props().id.clone()
+    }
+
+    #[zbus(property)]
+    fn title(&self) -> String {
+        self.0.props().title.clone()
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> &str {
+        self.0.props().status.as_str()
+    }
+
+    #[zbus(property)]
+    fn window_id(&self) -> i32 {
+        0
+    }
+
+    #[zbus(property)]
+    fn icon_name(&self) -> String {
+        self.0.icon_name()
+    }
+
+    #[zbus(property)]
+    fn icon_pixmap(&self) -> Vec<Pixmap> {
+        self.0.icon_pixmap()
+    }
+
+    #[zbus(property)]
+    fn overlay_icon_name(&self) -> &str {
+        ""
+    }
+
+    #[zbus(property)]
+    fn overlay_icon_pixmap(&self) -> Vec<Pixmap> {
+        Vec::new()
+    }
+
+    #[zbus(property)]
+    fn attention_icon_name(&self) -> &str {
+        ""
+    }
+
+    #[zbus(property)]
+    fn attention_icon_pixmap(&self) -> Vec<Pixmap> {
+        Vec::new()
+    }
+
+    #[zbus(property)]
+    fn attention_movie_name(&self) -> &str {
+        ""
+    }
+
+    #[zbus(property)]
+    fn tool_tip(&self) -> ToolTip {
+        let title = self.0.props().tooltip.clone();
+        (String::new(), Vec::new(), title, String::new())
+    }
+
+    /// Whether the item only has a menu, and activating it should open the
+    /// menu.
+    #[zbus(property)]
+    fn item_is_menu(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn menu(&self) -> ObjectPath<'static> {
+        ObjectPath::from_static_str_unchecked(MENU_PATH)
+    }
+
+    #[zbus(signal)]
+    pub(super) async fn new_title(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub(super) async fn new_icon(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub(super) async fn new_tool_tip(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub(super) async fn new_status(emitter: &SignalEmitter<'_>, status: &str) -> zbus::Result<()>;
+}
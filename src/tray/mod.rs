@@ -0,0 +1,544 @@
+//! Tray icons, also known as status icons.
+//!
+//! A [`TrayIcon`] shows an icon in the desktop's tray, with a menu that
+//! opens when it's clicked. It's meant for applications that run in the
+//! background, and may not have a window.
+//!
+//! Tray icons are only implemented on Linux so far, through
+//! `org.kde.StatusNotifierItem`. The icon registers with the
+//! `org.kde.StatusNotifierWatcher` service, whenever one is running, and its
+//! menu is exported with DBusMenu. Like with the
+//! [`DbusMenuBackend`][crate::dbusmenu::DbusMenuBackend], the desktop's calls
+//! are queued until [`TrayIcon::dispatch`] is called.
+
+use core::convert::TryFrom;
+use core::fmt;
+use std::cell::RefCell;
+use std::sync::Arc;
+
+use zbus::blocking::connection::Builder;
+use zbus::blocking::Connection;
+use zbus::object_server::SignalEmitter;
+
+use self::interface::{Props, Shared, StatusNotifierItem, ITEM_PATH, WATCHER};
+use crate::dbusmenu::registrar::watch_owner;
+use crate::dbusmenu::DbusMenuBackend;
+use crate::headless::NoWindow;
+use crate::{AttachedMenu, Error, Menu};
+
+mod interface;
+
+/// The image of a tray icon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Icon(IconData);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum IconData {
+    /// The name of an icon in the desktop's icon theme.
+    Named(String),
+    Pixels {
+        width: i32,
+        height: i32,
+        /// ARGB32 pixels in network byte order.
+        argb: Vec<u8>,
+    },
+}
+
+impl Icon {
+    /// An icon from the desktop's icon theme, like `mail-unread`.
+    pub fn from_name(name: impl Into<String>) -> Self {
+        Self(IconData::Named(name.into()))
+    }
+
+    /// An icon from RGBA pixels, row by row.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there aren't `width * height` pixels.
+    pub fn from_rgba(rgba: &[u8], width: u32, height: u32) -> Self {
+        let len = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|pixels| pixels.checked_mul(4));
+        assert_eq!(Some(rgba.len()), len, "icon has the wrong number of pixels");
+        let argb = rgba
+            .chunks_exact(4)
+            .flat_map(|pixel| [pixel[3], pixel[0], pixel[1], pixel[2]])
+            .collect();
+        Self(IconData::Pixels {
+            width: i32::try_from(width).expect("icon is too wide"),
+            height: i32::try_from(height).expect("icon is too tall"),
+            argb,
+        })
+    }
+}
+
+/// What a tray icon tells the user about the application.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TrayStatus {
+    /// Nothing interesting is happening; the desktop may hide the icon.
+    Passive,
+
+    /// The application is doing something.
+    #[default]
+    Active,
+
+    /// The application needs the user's attention.
+    NeedsAttention,
+}
+
+impl TrayStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Passive => "Passive",
+            Self::Active => "Active",
+            Self::NeedsAttention => "NeedsAttention",
+        }
+    }
+}
+
+/// Something the user did with a tray icon, other than using its menu.
+///
+/// The position is where it happened, in screen coordinates, if the desktop
+/// knows it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TrayEvent {
+    /// The icon was clicked.
+    Activate { x: i32, y: i32 },
+
+    /// The icon was clicked with the middle mouse button.
+    SecondaryActivate { x: i32, y: i32 },
+}
+
+type EventHandler = Box<dyn FnMut(TrayEvent)>;
+
+/// Describes a [`TrayIcon`] before it's shown.
+#[derive(Debug, Clone)]
+pub struct TrayIconBuilder {
+    id: String,
+    title: String,
+    tooltip: String,
+    icon: Option<Icon>,
+    status: TrayStatus,
+    address: Option<String>,
+}
+
+impl TrayIconBuilder {
+    /// Set the title, that the desktop may show next to the icon.
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Set the text shown when hovering over the icon.
+    pub fn with_tooltip(mut self, tooltip: impl Into<String>) -> Self {
+        self.tooltip = tooltip.into();
+        self
+    }
+
+    pub fn with_icon(mut self, icon: Icon) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+
+    pub fn with_status(mut self, status: TrayStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Show the icon on the bus with the given address, like
+    /// `unix:path=/run/user/1000/bus`, instead of the session bus.
+    pub fn with_bus_address(mut self, address: impl Into<String>) -> Self {
+        self.address = Some(address.into());
+        self
+    }
+
+    /// Show the icon, with the given menu.
+    pub fn build<C: Clone + 'static>(self, menu: Menu<C>) -> Result<TrayIcon<C>, Error> {
+        let builder = match &self.address {
+            Some(address) => Builder::address(address.as_str()),
+            None => Builder::session(),
+        }
+        .map_err(Error::dbus)?;
+        let shared = Arc::new(Shared::new(Props {
+            id: self.id,
+            title: self.title,
+            tooltip: self.tooltip,
+            icon: self.icon,
+            status: self.status,
+        }));
+        let connection = builder
+            .serve_at(ITEM_PATH, StatusNotifierItem(Arc::clone(&shared)))
+            .and_then(Builder::build)
+            .map_err(Error::dbus)?;
+
+        let backend = DbusMenuBackend::windowless(&connection)?;
+        let menu = crate::attach_with(menu, &NoWindow, backend.clone())?;
+
+        // Without a watcher, the icon is registered once one starts.
+        watch_owner(&connection, WATCHER, |connection| {
+            let _ = interface::register(connection);
+        })?;
+        interface::register(&connection)?;
+
+        Ok(TrayIcon {
+            menu,
+            backend,
+            connection,
+            shared,
+            handler: RefCell::new(None),
+        })
+    }
+}
+
+/// An icon in the desktop's tray, with a menu.
+///
+/// The icon is removed when this is dropped.
+pub struct TrayIcon<C = ()> {
+    menu: AttachedMenu<C>,
+    backend: DbusMenuBackend,
+    connection: Connection,
+    shared: Arc<Shared>,
+    handler: RefCell<Option<EventHandler>>,
+}
+
+impl TrayIcon<()> {
+    /// Describe a tray icon, with an id that is unique to the application,
+    /// like `sync-status`.
+    pub fn builder(id: impl Into<String>) -> TrayIconBuilder {
+        TrayIconBuilder {
+            id: id.into(),
+            title: String::new(),
+            tooltip: String::new(),
+            icon: None,
+            status: TrayStatus::default(),
+            address: None,
+        }
+    }
+}
+
+impl<C: Clone + 'static> TrayIcon<C> {
+    /// The menu of the icon.
+    pub fn menu(&self) -> &AttachedMenu<C> {
+        &self.menu
+    }
+
+    /// The unique name of the connection that the icon is exported on.
+    pub fn bus_name(&self) -> String {
+        let name = self.connection.unique_name();
+        name.map(ToString::to_string).unwrap_or_default()
+    }
+
+    pub fn set_title(&self, title: impl Into<String>) -> Result<(), Error> {
+        self.shared.props().title = title.into();
+        let emitter = self.emitter()?;
+        zbus::block_on(StatusNotifierItem::new_title(&emitter)).map_err(Error::dbus)
+    }
+
+    pub fn set_tooltip(&self, tooltip: impl Into<String>) -> Result<(), Error> {
+        self.shared.props().tooltip = tooltip.into();
+        let emitter = self.emitter()?;
+        zbus::block_on(StatusNotifierItem::new_tool_tip(&emitter)).map_err(Error::dbus)
+    }
+
+    pub fn set_icon(&self, icon: Option<Icon>) -> Result<(), Error> {
+        self.shared.props().icon = icon;
+        let emitter = self.emitter()?;
+        zbus::block_on(StatusNotifierItem::new_icon(&emitter)).map_err(Error::dbus)
+    }
+
+    pub fn set_status(&self, status: TrayStatus) -> Result<(), Error> {
+        self.shared.props().status = status;
+        let emitter = self.emitter()?;
+        zbus::block_on(StatusNotifierItem::new_status(&emitter, status.as_str()))
+            .map_err(Error::dbus)
+    }
+
+    /// Set the function that is called with the icon's events when they're
+    /// dispatched.
+    pub fn set_event_handler(&self, handler: impl FnMut(TrayEvent) + 'static) {
+        *self.handler.borrow_mut() = Some(Box::new(handler));
+    }
+
+    /// Set the function to call when the desktop activates the icon or an
+    /// item of its menu.
+    ///
+    /// It's called on the connection's thread, and should wake up the event
+    /// loop, so that it calls [`dispatch`][Self::dispatch].
+    pub fn set_wakeup(&self, wakeup: impl Fn() + Send + Sync + 'static) {
+        let wakeup = Arc::new(wakeup);
+        self.backend.set_wakeup({
+            let wakeup = Arc::clone(&wakeup);
+            move || wakeup()
+        });
+        self.shared.set_wakeup(Some(wakeup));
+    }
+
+    /// Call the handlers of the menu items that the desktop activated, and
+    /// the event handler with the icon's events, since the last call.
+    ///
+    /// Returns the number of activations and events.
+    pub fn dispatch(&self) -> usize {
+        let activations = self.backend.dispatch();
+        let events = self.shared.take_events();
+        // Don't hold the borrow while calling the handler, which may set
+        // another one.
+        let handler = self.handler.borrow_mut().take();
+        if let Some(mut handler) = handler {
+            for event in &events {
+                handler(*event);
+            }
+            self.handler.borrow_mut().get_or_insert(handler);
+        }
+        activations + events.len()
+    }
+
+    fn emitter(&self) -> Result<SignalEmitter<'_>, Error> {
+        SignalEmitter::new(self.connection.inner(), ITEM_PATH).map_err(Error::dbus)
+    }
+}
+
+impl<C> fmt::Debug for TrayIcon<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TrayIcon")
+            .field("id", &self.shared.props().id)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::TryFrom;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Mutex};
+    use std::time::Duration;
+
+    use zbus::blocking::fdo::PropertiesProxy;
+    use zbus::blocking::MessageIterator;
+    use zbus::message::Type;
+    use zbus::names::InterfaceName;
+    use zbus::zvariant::{OwnedValue, Value};
+    use zbus::{MatchRule, Message};
+
+    use super::interface::WATCHER_PATH;
+    use super::*;
+    use crate::dbusmenu::interface::dbus_id;
+    use crate::test_util::TestBus;
+    use crate::{MenuItem, Separator};
+
+    /// A stand-in for the watcher, that reports the items registered with it.
+    struct StandInWatcher(Mutex<mpsc::Sender<String>>);
+
+    #[zbus::interface(name = "org.kde.StatusNotifierWatcher")]
+    impl StandInWatcher {
+        fn register_status_notifier_item(&self, service: &str) {
+            let _ = self.0.lock().unwrap().send(service.to_owned());
+        }
+    }
+
+    /// Start a watcher, that stops when the connection is closed.
+    fn watcher(bus: &TestBus) -> (Connection, mpsc::Receiver<String>) {
+        let (sender, receiver) = mpsc::channel();
+        let connection = Builder::address(bus.address())
+            .unwrap()
+            .name(WATCHER)
+            .unwrap()
+            .serve_at(WATCHER_PATH, StandInWatcher(Mutex::new(sender)))
+            .unwrap()
+            .build()
+            .unwrap();
+        (connection, receiver)
+    }
+
+    fn next_registration(registrations: &mpsc::Receiver<String>) -> String {
+        registrations
+            .recv_timeout(Duration::from_secs(5))
+            .expect("no item was registered")
+    }
+
+    fn tray(bus: &TestBus) -> TrayIcon<&'static str> {
+        let menu = Menu::default()
+            .with(
+                MenuItem::new("Sync Now")
+                    .with_id("sync")
+                    .with_command("sync"),
+            )
+            .with(Separator)
+            .with(MenuItem::new("Quit").with_command("quit"));
+        TrayIcon::builder("sync-status")
+            .with_title("Sync")
+            .with_tooltip("Up to date")
+            .with_icon(Icon::from_name("emblem-synchronizing"))
+            .with_bus_address(bus.address())
+            .build(menu)
+            .unwrap()
+    }
+
+    fn properties(client: &Connection, tray: &TrayIcon<&'static str>) -> PropertiesProxy<'static> {
+        PropertiesProxy::builder(client)
+            .destination(tray.bus_name())
+            .unwrap()
+            .path(ITEM_PATH)
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    fn property(properties: &PropertiesProxy<'_>, name: &str) -> OwnedValue {
+        let interface = InterfaceName::from_static_str("org.kde.StatusNotifierItem").unwrap();
+        properties.get(interface, name).unwrap()
+    }
+
+    fn call(
+        client: &Connection,
+        tray: &TrayIcon<&'static str>,
+        path: &str,
+        interface: &str,
+        method: &str,
+        body: &(impl serde::Serialize + zbus::zvariant::DynamicType),
+    ) -> Message {
+        let name = tray.bus_name();
+        client
+            .call_method(Some(name.as_str()), path, Some(interface), method, body)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_icon() {
+        let icon = Icon::from_rgba(&[1, 2, 3, 4, 5, 6, 7, 8], 2, 1);
+        let expected = IconData::Pixels {
+            width: 2,
+            height: 1,
+            argb: vec![4, 1, 2, 3, 8, 5, 6, 7],
+        };
+        assert_eq!(icon.0, expected);
+    }
+
+    #[test]
+    #[should_panic = "icon has the wrong number of pixels"]
+    fn test_icon_size() {
+        let _ = Icon::from_rgba(&[0; 12], 2, 2);
+    }
+
+    #[test]
+    fn test_register() {
+        let bus = TestBus::start();
+        let (watcher, registrations) = self::watcher(&bus);
+        let tray = tray(&bus);
+        assert_eq!(next_registration(&registrations), tray.bus_name());
+
+        // A restarted watcher is told about the icon again.
+        watcher.close().unwrap();
+        let (_watcher, registrations) = self::watcher(&bus);
+        assert_eq!(next_registration(&registrations), tray.bus_name());
+    }
+
+    #[test]
+    fn test_no_watcher() {
+        let bus = TestBus::start();
+        let tray = tray(&bus);
+        let (_watcher, registrations) = watcher(&bus);
+        assert_eq!(next_registration(&registrations), tray.bus_name());
+    }
+
+    #[test]
+    fn test_properties() {
+        let bus = TestBus::start();
+        let tray = tray(&bus);
+        let client = bus.connect();
+        let properties = properties(&client, &tray);
+        let string = |name| String::try_from(property(&properties, name)).unwrap();
+
+        assert_eq!(string("Id"), "sync-status");
+        assert_eq!(string("Category"), "ApplicationStatus");
+        assert_eq!(string("Title"), "Sync");
+        assert_eq!(string("Status"), "Active");
+        assert_eq!(string("IconName"), "emblem-synchronizing");
+        let menu = property(&properties, "Menu");
+        assert_eq!(menu.to_string(), r#"objectpath "/MenuBar""#);
+        assert!(!bool::try_from(property(&properties, "ItemIsMenu")).unwrap());
+        type ToolTip = (String, Vec<(i32, i32, Vec<u8>)>, String, String);
+        let tooltip = ToolTip::try_from(property(&properties, "ToolTip")).unwrap();
+        assert_eq!(tooltip.2, "Up to date");
+
+        let rule = MatchRule::builder()
+            .msg_type(Type::Signal)
+            .path(ITEM_PATH)
+            .unwrap()
+            .build();
+        let mut signals = MessageIterator::for_match_rule(rule, &client, None).unwrap();
+        let mut next_signal = || {
+            let message = signals.next().unwrap().unwrap();
+            let member = message.header().member().unwrap().to_string();
+            (member, message)
+        };
+
+        tray.set_title("Syncing").unwrap();
+        assert_eq!(next_signal().0, "NewTitle");
+        assert_eq!(string("Title"), "Syncing");
+
+        tray.set_status(TrayStatus::NeedsAttention).unwrap();
+        let (member, message) = next_signal();
+        assert_eq!(member, "NewStatus");
+        let status: String = message.body().deserialize().unwrap();
+        assert_eq!(status, "NeedsAttention");
+        assert_eq!(string("Status"), "NeedsAttention");
+
+        tray.set_icon(Some(Icon::from_rgba(&[255, 0, 0, 255], 1, 1)))
+            .unwrap();
+        assert_eq!(next_signal().0, "NewIcon");
+        assert_eq!(string("IconName"), "");
+        let pixmaps = <Vec<(i32, i32, Vec<u8>)>>::try_from(property(&properties, "IconPixmap"));
+        assert_eq!(pixmaps.unwrap(), [(1, 1, vec![255, 255, 0, 0])]);
+
+        tray.set_tooltip("Syncing 3 files").unwrap();
+        assert_eq!(next_signal().0, "NewToolTip");
+    }
+
+    #[test]
+    fn test_events() {
+        let bus = TestBus::start();
+        let tray = tray(&bus);
+        let client = bus.connect();
+        let wakeups = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&wakeups);
+        tray.set_wakeup(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let handler_events = Rc::clone(&events);
+        tray.set_event_handler(move |event| handler_events.borrow_mut().push(event));
+
+        let item = "org.kde.StatusNotifierItem";
+        call(&client, &tray, ITEM_PATH, item, "Activate", &(10, 20));
+        call(
+            &client,
+            &tray,
+            ITEM_PATH,
+            item,
+            "SecondaryActivate",
+            &(0, 0),
+        );
+        // Opening the menu is left to the desktop.
+        call(&client, &tray, ITEM_PATH, item, "ContextMenu", &(0, 0));
+
+        // The menu is exported with DBusMenu.
+        let sync = dbus_id(tray.menu().item("sync").unwrap().token());
+        let data = Value::from(0);
+        let menu = "com.canonical.dbusmenu";
+        let event = (sync, "clicked", &data, 0u32);
+        call(&client, &tray, "/MenuBar", menu, "Event", &event);
+
+        assert_eq!(wakeups.load(Ordering::SeqCst), 3);
+        assert_eq!(tray.dispatch(), 3);
+        assert_eq!(
+            *events.borrow(),
+            [
+                TrayEvent::Activate { x: 10, y: 20 },
+                TrayEvent::SecondaryActivate { x: 0, y: 0 },
+            ]
+        );
+        assert_eq!(tray.menu().take_commands(), ["sync"]);
+        assert_eq!(tray.dispatch(), 0);
+    }
+}
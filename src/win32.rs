@@ -1,6 +1,14 @@
 //! Win32 implementation of menubars.
 
-use crate::Error;
+use crate::accelerator::win32::accel_table;
+use crate::accelerator::{Locale, Platform};
+use crate::backend::{
+    Activation, ActivationSource, Activator, Backend, ItemKind, ItemProperty, ItemProps, ItemToken,
+    MenuKind, MenuToken,
+};
+use crate::{
+    Accelerator, ActivationContext, Error, HandlerContext, MenuEntry, Modifiers, PanicPolicy,
+};
 
 use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
@@ -12,19 +20,31 @@ use std::num::NonZeroIsize;
 use std::ptr;
 use std::rc::Rc;
 
+use raw_window_handle::{RawWindowHandle, Win32WindowHandle};
+
 use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
 
-use windows_sys::Win32::UI::Shell::{DefSubclassProc, SetWindowSubclass};
+use windows_sys::Win32::UI::Input::KeyboardAndMouse::{
+    GetKeyState, VIRTUAL_KEY, VK_CONTROL, VK_LWIN, VK_MENU, VK_RWIN, VK_SHIFT,
+};
+
+use windows_sys::Win32::UI::Shell::{DefSubclassProc, RemoveWindowSubclass, SetWindowSubclass};
 
 use windows_sys::Win32::UI::WindowsAndMessaging::{
-    AppendMenuA, AppendMenuW, CreateMenu, CreatePopupMenu, DestroyMenu, GetMenu, InsertMenuItemA,
-    SetMenu, SetMenuInfo, MIIM_ID,
+    AppendMenuA, AppendMenuW, CreateMenu, CreatePopupMenu, DeleteMenu, DestroyMenu, DrawMenuBar,
+    EnableMenuItem, GetMenu, InsertMenuItemA, InsertMenuItemW, RemoveMenu, SetMenu, SetMenuInfo,
+    SetMenuItemInfoW, MIIM_ID,
 };
-use windows_sys::Win32::UI::WindowsAndMessaging::{HMENU, MENUINFO, MENUITEMINFOA};
 use windows_sys::Win32::UI::WindowsAndMessaging::{
-    MFT_SEPARATOR, MFT_STRING, MF_POPUP, MF_SEPARATOR, MF_STRING, MIIM_DATA, MIIM_FTYPE,
-    MIIM_STRING, MIIM_SUBMENU, MIIM_TYPE, MIM_STYLE, MNS_NOTIFYBYPOS, WM_COMMAND, WM_MENUCOMMAND,
-    WM_NCDESTROY,
+    CreateAcceleratorTableW, DestroyAcceleratorTable, GetAncestor, TranslateAcceleratorW, GA_ROOT,
+};
+use windows_sys::Win32::UI::WindowsAndMessaging::{ACCEL, HACCEL, MSG};
+use windows_sys::Win32::UI::WindowsAndMessaging::{HMENU, MENUINFO, MENUITEMINFOA, MENUITEMINFOW};
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    MFS_CHECKED, MFS_DISABLED, MFT_RADIOCHECK, MFT_SEPARATOR, MFT_STRING, MF_BYCOMMAND,
+    MF_BYPOSITION, MF_CHECKED, MF_GRAYED, MF_POPUP, MF_SEPARATOR, MF_STRING, MIIM_DATA, MIIM_FTYPE,
+    MIIM_STATE, MIIM_STRING, MIIM_SUBMENU, MIIM_TYPE, MIM_STYLE, MNS_NOTIFYBYPOS, WM_COMMAND,
+    WM_MENUCOMMAND, WM_NCDESTROY,
 };
 
 macro_rules! syscall {
@@ -42,124 +62,115 @@ macro_rules! syscall {
 // No one else should use this very unique ID.
 const SUBCLASS_ID: usize = 4 * 8 * 15 * 16 * 23 * 42;
 
-/// A handle that manages a menu key.
-struct MenuKeyHandle {
-    key: MenuKey,
-    unsend: PhantomData<*mut ()>,
-}
-
-impl MenuKeyHandle {
-    /// Create a new menu key handle.
-    fn new() -> Self {
-        std::thread_local! {
-            static SLOT_LIST: RefCell<SlotList> = RefCell::new(SlotList {
-                menu_keys: Vec::new(),
-                next_key: 0,
-                len: 0,
-            });
-        }
+// Used by `Win32Backend`, so that it can't collide with `Menu`.
+const BACKEND_SUBCLASS_ID: usize = SUBCLASS_ID + 1;
 
-        struct SlotList {
-            /// A list of menu keys indicating whether they have been freed or not.
-            menu_keys: Vec<Slot>,
-
-            /// The next menu key to use.
-            next_key: u16,
+std::thread_local! {
+    /// The command IDs in use on this thread.
+    static COMMAND_IDS: RefCell<SlotList> = const {
+        RefCell::new(SlotList {
+            ids: Vec::new(),
+            next_id: 0,
+            len: 0,
+        })
+    };
+}
 
-            /// The current number of occupied slots in the menu key list.
-            len: u16,
-        }
+struct SlotList {
+    /// A list of command IDs indicating whether they have been freed or not.
+    ids: Vec<Slot>,
 
-        enum Slot {
-            /// This slot is occupied.
-            Occupied,
+    /// The next command ID to use.
+    next_id: u16,
 
-            /// This slot is free.
-            ///
-            /// The value inside is the value that should be set to `NEXT_KEY`
-            /// when this slot is occupied.
-            Vacant(u16),
-        }
+    /// The current number of occupied slots in the command ID list.
+    len: u16,
+}
 
-        impl Drop for MenuKeyHandle {
-            fn drop(&mut self) {
-                // Free a slot in the key list.
-                let _ = SLOT_LIST.try_with(|slot_list| {
-                    let mut slot_list = slot_list.borrow_mut();
+enum Slot {
+    /// This slot is occupied.
+    Occupied,
 
-                    // Decrement length by one.
-                    let new_len = slot_list
-                        .len
-                        .checked_sub(1)
-                        .expect("menu key list is corrupt");
-                    slot_list.len = new_len;
+    /// This slot is free.
+    ///
+    /// The value inside is the value that should be set to `next_id`
+    /// when this slot is occupied.
+    Vacant(u16),
+}
 
-                    // Mark the slot at vacant.
-                    let our_key = self.key.0;
-                    slot_list.menu_keys[our_key as usize] = Slot::Vacant(slot_list.next_key);
-                    slot_list.next_key = our_key;
-                });
-            }
-        }
+/// A command ID, which identifies an item in `WM_COMMAND` and in
+/// accelerator tables.
+///
+/// `WM_COMMAND` only has room for 16 bits, so IDs are handed out from one
+/// list per thread. This keeps the items of menus that are built separately
+/// and then nested from colliding, in any window.
+struct CommandId {
+    id: u16,
+    unsend: PhantomData<*mut ()>,
+}
 
-        SLOT_LIST.with(|slot_list| {
+impl CommandId {
+    /// Take a free command ID.
+    ///
+    /// Fails if all IDs are in use.
+    fn new() -> Result<Self, Error> {
+        COMMAND_IDS.with(|slot_list| {
             let mut slot_list = slot_list.borrow_mut();
-            let our_key = slot_list.next_key;
+            let our_id = slot_list.next_id;
 
             // Increment length by one.
             {
-                let new_len = slot_list.len.checked_add(1).expect("too many menu keys");
+                let new_len = match slot_list.len.checked_add(1) {
+                    Some(new_len) => new_len,
+                    None => return Err(Error::too_many_items()),
+                };
                 slot_list.len = new_len;
             }
 
-            if slot_list.next_key == slot_list.menu_keys.len() as _ {
+            if slot_list.next_id == slot_list.ids.len() as u16 {
                 // Allocate a new slot at the end of the list.
-                slot_list.menu_keys.push(Slot::Occupied);
-                slot_list.next_key += 1;
+                slot_list.ids.push(Slot::Occupied);
+                slot_list.next_id += 1;
             } else {
                 // Take the vacant slot.
-                slot_list.next_key = match slot_list.menu_keys.get(our_key as usize) {
-                    Some(Slot::Vacant(next_key)) => *next_key,
-                    _ => panic!("menu key list is corrupt"),
+                slot_list.next_id = match slot_list.ids.get(our_id as usize) {
+                    Some(Slot::Vacant(next_id)) => *next_id,
+                    _ => panic!("command ID list is corrupt"),
                 };
-                slot_list.menu_keys[our_key as usize] = Slot::Occupied;
+                slot_list.ids[our_id as usize] = Slot::Occupied;
             }
 
-            MenuKeyHandle {
-                key: MenuKey(our_key),
+            Ok(CommandId {
+                id: our_id,
                 unsend: PhantomData,
-            }
+            })
         })
     }
 
-    /// Get the underlying menu key.
-    fn key(&self) -> MenuKey {
-        self.key
+    /// Get the underlying ID.
+    fn get(&self) -> u16 {
+        self.id
     }
 }
 
-/// Key given to a menu.
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-struct MenuKey(u16);
-
-/// Key given to an item in a menu.
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-struct ItemKey(u32);
-
-impl ItemKey {
-    /// Create a new `ItemKey` from a menu handle and an item ID.
-    fn new(menu: MenuKey, id: u16) -> Self {
-        ItemKey(((menu.0 as u32) << 16) | (id as u32))
-    }
-
-    /// Get the menu key.
-    fn menu(&self) -> MenuKey {
-        MenuKey((self.0 >> 16) as u16)
-    }
+impl Drop for CommandId {
+    fn drop(&mut self) {
+        // Free a slot in the ID list.
+        let _ = COMMAND_IDS.try_with(|slot_list| {
+            let mut slot_list = slot_list.borrow_mut();
 
-    /// Get the item ID.
-    fn id(&self) -> u16 {
-        self.0 as u16
+            // Decrement length by one.
+            let new_len = slot_list
+                .len
+                .checked_sub(1)
+                .expect("command ID list is corrupt");
+            slot_list.len = new_len;
+
+            // Mark the slot at vacant.
+            let our_id = self.id;
+            slot_list.ids[our_id as usize] = Slot::Vacant(slot_list.next_id);
+            slot_list.next_id = our_id;
+        });
     }
 }
 
@@ -190,45 +201,766 @@ unsafe extern "system" fn menu_subclass_proc(
 
         // If we are being destroyed, free our refdata.
         if msg == WM_NCDESTROY {
-            drop(Box::from_raw(refdata as *mut WindowData));
+            let data = Box::from_raw(refdata as *mut WindowData);
+            set_accelerator_table(hwnd, 0);
+            if data.accelerators != 0 {
+                DestroyAcceleratorTable(data.accelerators);
+            }
             early_out!();
         } else if msg == WM_COMMAND {
+            if !is_menu_command(wparam, lparam) {
+                early_out!();
+            }
+
             // Get a reference to the hash map containing our menu item data.
             let map_cell = &*(refdata as *const WindowData);
 
-            // Shouldn't be called reentrantly.
-            let mut map = map_cell.data.borrow_mut();
-
-            // Get the item key.
-            let key = ItemKey(wparam as u32);
+            // Get the command ID. The high word is 1 for accelerators.
+            let id = wparam as u16;
 
-            // Get the item data.
-            let data = leap!(map.get_mut(&key));
+            // Take the handler out of the map, so that no borrow is held while
+            // it runs. Otherwise a message sent to this window from inside the
+            // handler would panic on the `RefCell`, and abort.
+            let mut handler = {
+                let mut map = map_cell.data.borrow_mut();
+                let data = leap!(map.get_mut(&id));
+                mem::replace(&mut data.handler, Box::new(|_| {}))
+            };
 
             // Call the handler.
-            (data.handler)();
+            let activation = activation(hwnd, wparam);
+            let activation = ActivationContext::new(
+                None,
+                activation.source,
+                activation.modifiers,
+                activation.window,
+            );
+            let res = crate::panic::guard(None, || handler(&activation));
+
+            if let Some(data) = map_cell.data.borrow_mut().get_mut(&id) {
+                data.handler = handler;
+            }
+            if res == Err(PanicPolicy::Disable) {
+                EnableMenuItem(GetMenu(hwnd), u32::from(id), MF_BYCOMMAND | MF_GRAYED);
+                DrawMenuBar(hwnd);
+            }
         }
 
         early_out!();
     })
 }
 
-#[doc(hidden)]
-pub enum Empty {}
+unsafe extern "system" fn backend_subclass_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+    uidsubclass: usize,
+    refdata: usize,
+) -> LRESULT {
+    abort_on_panic(move || {
+        if msg == WM_NCDESTROY {
+            // The window is going away, release our reference to the shared data.
+            let shared = Rc::from_raw(refdata as *const BackendShared);
+            shared.subclassed.set(false);
+            set_accelerator_table(hwnd, 0);
+            RemoveWindowSubclass(hwnd, Some(backend_subclass_proc), BACKEND_SUBCLASS_ID);
+        } else if msg == WM_COMMAND && is_menu_command(wparam, lparam) {
+            let shared = &*(refdata as *const BackendShared);
+
+            let item = shared.commands.borrow().get(&(wparam as u16)).copied();
+
+            // Don't hold any borrows while calling the handler.
+            let activator = shared.activator.borrow().clone();
+            if let (Some(item), Some(activator)) = (item, activator) {
+                activator.activate(item, activation(hwnd, wparam));
+                return 0;
+            }
+        }
+
+        DefSubclassProc(hwnd, msg, wparam, lparam)
+    })
+}
+
+/// Whether a `WM_COMMAND` comes from a menu or an accelerator.
+///
+/// Controls send their notifications as commands too, with their own handle in
+/// `lparam` and a notification code in the high word. Their ids aren't ours,
+/// even if they happen to match.
+fn is_menu_command(wparam: WPARAM, lparam: LPARAM) -> bool {
+    lparam == 0 && (wparam >> 16) as u16 <= 1
+}
+
+/// Describe the activation that a `WM_COMMAND` sent to the window reports.
+fn activation(hwnd: HWND, wparam: WPARAM) -> Activation {
+    // The high word is 0 for menus, and 1 for accelerators.
+    let source = if (wparam >> 16) as u16 == 1 {
+        ActivationSource::Accelerator
+    } else {
+        ActivationSource::Click
+    };
+    let mut handle = Win32WindowHandle::empty();
+    handle.hwnd = hwnd as _;
+    Activation {
+        source,
+        modifiers: current_modifiers(),
+        window: Some(RawWindowHandle::Win32(handle)),
+    }
+}
+
+/// The modifier keys that are held, as of the message being processed.
+fn current_modifiers() -> Modifiers {
+    let keys: [(Modifiers, &[VIRTUAL_KEY]); 4] = [
+        (Modifiers::SHIFT, &[VK_SHIFT]),
+        (Modifiers::CONTROL, &[VK_CONTROL]),
+        (Modifiers::ALT, &[VK_MENU]),
+        (Modifiers::SUPER, &[VK_LWIN, VK_RWIN]),
+    ];
+    let mut modifiers = Modifiers::empty();
+    for (modifier, keys) in keys {
+        // The high bit is set while the key is down.
+        if keys
+            .iter()
+            .any(|key| unsafe { GetKeyState(i32::from(*key)) } < 0)
+        {
+            modifiers |= modifier;
+        }
+    }
+    modifiers
+}
+
+std::thread_local! {
+    /// The accelerator table of each window with a menu.
+    static ACCELERATOR_TABLES: RefCell<HashMap<HWND, HACCEL, ahash::RandomState>> =
+        RefCell::new(HashMap::with_hasher(ahash::RandomState::new()));
+}
+
+/// Set the accelerator table used for a window, or remove it if `table` is 0.
+fn set_accelerator_table(hwnd: HWND, table: HACCEL) {
+    ACCELERATOR_TABLES.with(|tables| {
+        let mut tables = tables.borrow_mut();
+        if table == 0 {
+            tables.remove(&hwnd);
+        } else {
+            tables.insert(hwnd, table);
+        }
+    });
+}
+
+/// Create an accelerator table from command IDs and their accelerators.
+///
+/// Returns 0 if there are no usable accelerators.
+fn create_accelerator_table(
+    commands: impl IntoIterator<Item = (u16, Accelerator)>,
+) -> Result<HACCEL, Error> {
+    let table: Vec<ACCEL> = accel_table(commands)
+        .into_iter()
+        .map(|accel| ACCEL {
+            fVirt: accel.flags,
+            key: accel.key,
+            cmd: accel.cmd,
+        })
+        .collect();
+    if table.is_empty() {
+        return Ok(0);
+    }
+    Ok(syscall!(nul CreateAcceleratorTableW(table.as_ptr(), table.len() as i32)))
+}
+
+/// Translate a key press into the command of the menu item with that
+/// accelerator.
+///
+/// Call this in the message loop for every message, before
+/// `TranslateMessage` and `DispatchMessage`; if it returns `true`, the
+/// message has been handled and must not be dispatched. With `winit`, use
+/// `EventLoopBuilderExtWindows::with_msg_hook`.
+///
+/// # Safety
+///
+/// `msg` must point to a valid `MSG`.
+pub unsafe fn translate_accelerator(msg: *const MSG) -> bool {
+    let hwnd = (*msg).hwnd;
+    if hwnd == 0 {
+        return false;
+    }
+
+    // Key presses are sent to the focused control, but the menu belongs to
+    // the top-level window.
+    let root = GetAncestor(hwnd, GA_ROOT);
+    let table = ACCELERATOR_TABLES.with(|tables| tables.borrow().get(&root).copied());
+    match table {
+        Some(table) => TranslateAcceleratorW(root, table, msg) != 0,
+        None => false,
+    }
+}
+
+/// Data shared between a `Win32Backend` and its window's subclass.
+struct BackendShared {
+    /// The item each command ID belongs to.
+    commands: RefCell<HashMap<u16, ItemToken, ahash::RandomState>>,
+
+    /// Callback for activated items.
+    activator: RefCell<Option<Activator>>,
+
+    /// Whether the window subclass holds a reference to this.
+    subclassed: Cell<bool>,
+}
+
+/// A native menu owned by a `Win32Backend`.
+struct NativeMenu {
+    hmenu: HMENU,
+
+    kind: MenuKind,
+
+    /// All items in the menu, including hidden ones.
+    items: Vec<ItemToken>,
+}
+
+/// A native item owned by a `Win32Backend`.
+struct NativeItem {
+    /// The menu the item is in.
+    menu: MenuToken,
+
+    /// The command ID of the item.
+    id: CommandId,
+
+    props: ItemProps,
+}
+
+/// [`Backend`] for Win32 windows.
+///
+/// Win32 has no concept of hidden items, so hidden items are removed from the
+/// native menu, and inserted again when shown.
+pub struct Win32Backend {
+    menus: HashMap<MenuToken, NativeMenu, ahash::RandomState>,
+    items: HashMap<ItemToken, NativeItem, ahash::RandomState>,
+
+    shared: Rc<BackendShared>,
+
+    /// The window and menu, once attached.
+    attached: Option<(HWND, MenuToken)>,
+
+    /// The language to show accelerators in.
+    locale: Locale,
+
+    /// The accelerator table of the attached window, or 0.
+    accelerators: HACCEL,
+
+    /// Whether a batch of changes is in progress, and whether the menubar
+    /// and the accelerators have to be updated once it ends.
+    batching: bool,
+    stale: Cell<bool>,
+}
+
+impl Win32Backend {
+    /// Create a new backend.
+    pub fn new() -> Self {
+        let shared = Rc::new(BackendShared {
+            commands: RefCell::new(HashMap::with_hasher(ahash::RandomState::new())),
+            activator: RefCell::new(None),
+            subclassed: Cell::new(false),
+        });
+
+        Win32Backend {
+            menus: HashMap::with_hasher(ahash::RandomState::new()),
+            items: HashMap::with_hasher(ahash::RandomState::new()),
+            shared,
+            attached: None,
+            locale: Locale::default(),
+            accelerators: 0,
+            batching: false,
+            stale: Cell::new(false),
+        }
+    }
+
+    /// Set the language to show accelerators in.
+    ///
+    /// This applies to items inserted or updated afterwards.
+    pub fn set_locale(&mut self, locale: Locale) {
+        self.locale = locale;
+    }
+
+    fn menu(&self, menu: MenuToken) -> &NativeMenu {
+        self.menus.get(&menu).expect("unknown menu token")
+    }
+
+    /// The native position of an item, i.e. the number of visible items
+    /// before it.
+    fn position(&self, menu: MenuToken, item: ItemToken) -> u32 {
+        self.menu(menu)
+            .items
+            .iter()
+            .take_while(|other| **other != item)
+            .filter(|other| !self.items[other].props.hidden)
+            .count() as u32
+    }
+
+    /// Insert the item into the native menu.
+    fn insert_native(&self, item: ItemToken) -> Result<(), Error> {
+        let native = &self.items[&item];
+        let position = self.position(native.menu, item);
+
+        let mut text = to_wide(&item_text(
+            &native.props.title,
+            native.props.accelerator,
+            self.locale,
+        ));
+        let mut info = self.item_info(&native.props, &mut text);
+        info.fMask |= MIIM_ID;
+        info.wID = u32::from(native.id.get());
+        if let ItemKind::Submenu(submenu) = native.props.kind {
+            info.fMask |= MIIM_SUBMENU;
+            info.hSubMenu = self.menu(submenu).hmenu;
+        }
+
+        let hmenu = self.menu(native.menu).hmenu;
+        syscall!(nul InsertMenuItemW(hmenu, position, 1, &info));
+        Ok(())
+    }
+
+    /// Create the item info describing the type, text and state of an item.
+    ///
+    /// `text` must be the wide version of the title, and outlive the info.
+    fn item_info(&self, props: &ItemProps, text: &mut [u16]) -> MENUITEMINFOW {
+        let mut info: MENUITEMINFOW = unsafe { mem::zeroed() };
+        info.cbSize = mem::size_of::<MENUITEMINFOW>() as u32;
+        info.fMask = MIIM_FTYPE | MIIM_STATE;
+        if props.kind == ItemKind::Separator {
+            info.fType = MFT_SEPARATOR;
+        } else {
+            info.fType = MFT_STRING;
+            info.fMask |= MIIM_STRING;
+            info.dwTypeData = text.as_mut_ptr();
+        }
+        // Items of radio groups are checked with a bullet.
+        if props.checked.is_some() && props.radio_group.is_some() {
+            info.fType |= MFT_RADIOCHECK;
+        }
+        if !props.enabled {
+            info.fState |= MFS_DISABLED;
+        }
+        if props.checked == Some(true) {
+            info.fState |= MFS_CHECKED;
+        }
+        info
+    }
+
+    /// Recreate the accelerator table of the attached window.
+    ///
+    /// Hidden and disabled items don't take part.
+    fn rebuild_accelerators(&mut self) -> Result<(), Error> {
+        if self.batching {
+            self.stale.set(true);
+            return Ok(());
+        }
+        let (hwnd, menu) = match self.attached {
+            Some((hwnd, menu)) if self.shared.subclassed.get() => (hwnd, menu),
+            _ => return Ok(()),
+        };
+
+        // Items that come earlier in the menu win when accelerators conflict.
+        let mut commands = Vec::new();
+        self.reachable_commands(menu, &mut commands);
+        let table = create_accelerator_table(commands)?;
+        if self.accelerators != 0 {
+            unsafe { DestroyAcceleratorTable(self.accelerators) };
+        }
+        self.accelerators = table;
+        set_accelerator_table(hwnd, table);
+        Ok(())
+    }
+
+    /// Collect the accelerators of the items that can be reached from the
+    /// menu, in menu order.
+    ///
+    /// Hidden and disabled items can't be reached, and neither can anything in
+    /// hidden or disabled submenus.
+    fn reachable_commands(&self, menu: MenuToken, commands: &mut Vec<(u16, Accelerator)>) {
+        for item in &self.menu(menu).items {
+            let native = &self.items[item];
+            let props = &native.props;
+            if !props.enabled || props.hidden {
+                continue;
+            }
+            match props.kind {
+                ItemKind::Item => {
+                    if let Some(accelerator) = props.accelerator {
+                        commands.push((native.id.get(), accelerator));
+                    }
+                }
+                ItemKind::Submenu(submenu) => self.reachable_commands(submenu, commands),
+                ItemKind::Separator => {}
+            }
+        }
+    }
+
+    /// Destroy the submenus of hidden items in the menu, at any depth.
+    ///
+    /// They aren't part of the native menu, so destroying it doesn't destroy
+    /// them.
+    fn destroy_hidden_submenus(&self, menu: MenuToken) {
+        for item in &self.menu(menu).items {
+            let native = &self.items[item];
+            if let ItemKind::Submenu(submenu) = native.props.kind {
+                self.destroy_hidden_submenus(submenu);
+                if native.props.hidden {
+                    unsafe { DestroyMenu(self.menu(submenu).hmenu) };
+                }
+            }
+        }
+    }
+
+    /// Move an item to the given index of a menu, without touching the
+    /// native menus.
+    fn relocate(&mut self, item: ItemToken, menu: MenuToken, index: usize) {
+        let native = self.items.get_mut(&item).expect("unknown item token");
+        let old = mem::replace(&mut native.menu, menu);
+        self.menus
+            .get_mut(&old)
+            .unwrap()
+            .items
+            .retain(|i| *i != item);
+        self.menus
+            .get_mut(&menu)
+            .expect("unknown menu token")
+            .items
+            .insert(index, item);
+    }
+
+    /// Forget about a submenu and everything in it.
+    ///
+    /// The native menu must be destroyed separately.
+    fn forget_menu(&mut self, menu: MenuToken) {
+        if let Some(native) = self.menus.remove(&menu) {
+            for item in native.items {
+                self.forget_item(item);
+            }
+        }
+    }
+
+    fn forget_item(&mut self, item: ItemToken) {
+        if let Some(native) = self.items.remove(&item) {
+            self.shared.commands.borrow_mut().remove(&native.id.get());
+            if let ItemKind::Submenu(submenu) = native.props.kind {
+                self.forget_menu(submenu);
+            }
+        }
+    }
+
+    /// Redraw the menubar if we're attached.
+    fn redraw(&self) {
+        if self.batching {
+            self.stale.set(true);
+            return;
+        }
+        if let Some((hwnd, _)) = self.attached {
+            unsafe { DrawMenuBar(hwnd) };
+        }
+    }
+}
 
-pub struct Hotkey<'a> {
-    // TODO
-    key: &'a str,
+impl Default for Win32Backend {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-type DataTable = HashMap<ItemKey, MenuItemData, ahash::RandomState>;
+impl Backend for Win32Backend {
+    fn supports(&self, window: RawWindowHandle) -> bool {
+        matches!(window, RawWindowHandle::Win32(handle) if !handle.hwnd.is_null())
+    }
+
+    fn set_activator(&mut self, activator: Activator) {
+        *self.shared.activator.borrow_mut() = Some(activator);
+    }
+
+    fn create_menu(&mut self, menu: MenuToken, kind: MenuKind) -> Result<(), Error> {
+        let hmenu = match kind {
+            MenuKind::Bar => syscall!(nul CreateMenu()),
+            MenuKind::Submenu => syscall!(nul CreatePopupMenu()),
+        };
+        self.menus.insert(
+            menu,
+            NativeMenu {
+                hmenu,
+                kind,
+                items: Vec::new(),
+            },
+        );
+        Ok(())
+    }
+
+    fn destroy_menu(&mut self, menu: MenuToken) {
+        if let Some(native) = self.menus.remove(&menu) {
+            unsafe { DestroyMenu(native.hmenu) };
+        }
+    }
+
+    fn insert_item(
+        &mut self,
+        menu: MenuToken,
+        index: usize,
+        item: ItemToken,
+        props: &ItemProps,
+    ) -> Result<(), Error> {
+        let id = CommandId::new()?;
+        let command = id.get();
+
+        self.menus
+            .get_mut(&menu)
+            .expect("unknown menu token")
+            .items
+            .insert(index, item);
+        self.items.insert(
+            item,
+            NativeItem {
+                menu,
+                id,
+                props: props.clone(),
+            },
+        );
+        if props.kind == ItemKind::Item {
+            self.shared.commands.borrow_mut().insert(command, item);
+        }
+
+        if !props.hidden {
+            if let Err(err) = self.insert_native(item) {
+                self.menus
+                    .get_mut(&menu)
+                    .unwrap()
+                    .items
+                    .retain(|i| *i != item);
+                self.forget_item(item);
+                return Err(err);
+            }
+            self.redraw();
+            if props.accelerator.is_some() {
+                self.rebuild_accelerators()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_item(&mut self, item: ItemToken) -> Result<(), Error> {
+        let native = self.items.get(&item).expect("unknown item token");
+        let menu = native.menu;
+
+        if let ItemKind::Submenu(submenu) = native.props.kind {
+            self.destroy_hidden_submenus(submenu);
+        }
+        if native.props.hidden {
+            // Not in the native menu, but the submenu still needs to be destroyed.
+            if let ItemKind::Submenu(submenu) = native.props.kind {
+                unsafe { DestroyMenu(self.menu(submenu).hmenu) };
+            }
+        } else {
+            // Also destroys the submenu, if any.
+            let position = self.position(menu, item);
+            syscall!(nul DeleteMenu(self.menu(menu).hmenu, position, MF_BYPOSITION));
+        }
+
+        self.menus
+            .get_mut(&menu)
+            .unwrap()
+            .items
+            .retain(|i| *i != item);
+        self.forget_item(item);
+        self.redraw();
+        self.rebuild_accelerators()
+    }
+
+    fn update_item(&mut self, item: ItemToken, property: &ItemProperty) -> Result<(), Error> {
+        let native = self.items.get(&item).expect("unknown item token");
+        let old = native.props.clone();
+        let mut props = old.clone();
+        props.apply(property);
+        let menu = native.menu;
+
+        // The cached properties decide the native positions of the other
+        // items, so they only change once the native menu has.
+        match (old.hidden, props.hidden) {
+            (true, true) => {
+                self.items.get_mut(&item).unwrap().props = props;
+                return Ok(());
+            }
+            (true, false) => {
+                // Inserting reads the new properties from the cache.
+                self.items.get_mut(&item).unwrap().props = props;
+                if let Err(err) = self.insert_native(item) {
+                    self.items.get_mut(&item).unwrap().props = old;
+                    return Err(err);
+                }
+            }
+            (false, true) => {
+                // Unlike `DeleteMenu`, this doesn't destroy the submenu.
+                let position = self.position(menu, item);
+                syscall!(nul RemoveMenu(self.menu(menu).hmenu, position, MF_BYPOSITION));
+                self.items.get_mut(&item).unwrap().props = props;
+            }
+            (false, false) => {
+                let mut text = to_wide(&item_text(&props.title, props.accelerator, self.locale));
+                let info = self.item_info(&props, &mut text);
+                let position = self.position(menu, item);
+                syscall!(nul SetMenuItemInfoW(self.menu(menu).hmenu, position, 1, &info));
+                self.items.get_mut(&item).unwrap().props = props;
+            }
+        }
+
+        self.redraw();
+        match property {
+            ItemProperty::Accelerator(_) | ItemProperty::Enabled(_) | ItemProperty::Hidden(_) => {
+                self.rebuild_accelerators()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn move_item(&mut self, item: ItemToken, menu: MenuToken, index: usize) -> Result<(), Error> {
+        let native = self.items.get(&item).expect("unknown item token");
+        let old = native.menu;
+        let old_index = self
+            .menu(old)
+            .items
+            .iter()
+            .position(|i| *i == item)
+            .expect("item is in its menu");
+        let hidden = native.props.hidden;
+
+        if !hidden {
+            // Unlike `DeleteMenu`, this doesn't destroy the submenu.
+            let position = self.position(old, item);
+            syscall!(nul RemoveMenu(self.menu(old).hmenu, position, MF_BYPOSITION));
+        }
+
+        self.relocate(item, menu, index);
+        if !hidden {
+            if let Err(err) = self.insert_native(item) {
+                // Put the item back where it was, rather than losing it. If
+                // that fails too, there's nothing left to try.
+                self.relocate(item, old, old_index);
+                let _ = self.insert_native(item);
+                return Err(err);
+            }
+            self.redraw();
+            self.rebuild_accelerators()?;
+        }
+        Ok(())
+    }
+
+    fn begin_batch(&mut self) {
+        self.batching = true;
+    }
+
+    fn end_batch(&mut self) -> Result<(), Error> {
+        self.batching = false;
+        if !self.stale.replace(false) {
+            return Ok(());
+        }
+        self.redraw();
+        self.rebuild_accelerators()
+    }
+
+    fn attach(&mut self, menu: MenuToken, window: RawWindowHandle) -> Result<(), Error> {
+        let hwnd = match window {
+            RawWindowHandle::Win32(handle) if !handle.hwnd.is_null() => handle.hwnd as HWND,
+            _ => return Err(Error::unexpected_window_type()),
+        };
+
+        // If the window already has a menu, error out. We don't want to step on any toes.
+        if unsafe { GetMenu(hwnd) } != 0 {
+            return Err(Error::menu_exists());
+        }
+
+        syscall!(nul SetMenu(hwnd, self.menu(menu).hmenu));
+
+        // The subclass holds a reference to the shared data until the window is destroyed.
+        let refdata = Rc::into_raw(Rc::clone(&self.shared));
+        unsafe {
+            SetWindowSubclass(
+                hwnd,
+                Some(backend_subclass_proc),
+                BACKEND_SUBCLASS_ID,
+                refdata as _,
+            )
+        };
+        self.shared.subclassed.set(true);
+
+        self.attached = Some((hwnd, menu));
+        self.rebuild_accelerators()
+    }
+}
+
+impl Drop for Win32Backend {
+    fn drop(&mut self) {
+        unsafe {
+            // The submenus of hidden items are not part of any other menu.
+            for native in self.items.values() {
+                if let (true, ItemKind::Submenu(submenu)) = (native.props.hidden, native.props.kind)
+                {
+                    DestroyMenu(self.menus[&submenu].hmenu);
+                }
+            }
+
+            // Whether the window destroyed the attached menu along with itself.
+            let mut destroyed = None;
+            if let Some((hwnd, menu)) = self.attached {
+                if self.shared.subclassed.get() {
+                    // The window is still alive; detach the menu, and remove our subclass.
+                    SetMenu(hwnd, 0);
+                    RemoveWindowSubclass(hwnd, Some(backend_subclass_proc), BACKEND_SUBCLASS_ID);
+                    drop(Rc::from_raw(Rc::as_ptr(&self.shared)));
+                    self.shared.subclassed.set(false);
+                    set_accelerator_table(hwnd, 0);
+                } else {
+                    destroyed = Some(menu);
+                }
+            }
+
+            if self.accelerators != 0 {
+                DestroyAcceleratorTable(self.accelerators);
+            }
+
+            // Destroying the bars also destroys all submenus in them.
+            for (menu, native) in &self.menus {
+                if native.kind == MenuKind::Bar && destroyed != Some(*menu) {
+                    DestroyMenu(native.hmenu);
+                }
+            }
+        }
+    }
+}
+
+/// The text of an item, with the accelerator right-aligned after a tab.
+fn item_text(title: &str, accelerator: Option<Accelerator>, locale: Locale) -> String {
+    match accelerator {
+        Some(accelerator) => format!(
+            "{}\t{}",
+            title,
+            accelerator.format(Platform::Windows, locale)
+        ),
+        None => title.to_owned(),
+    }
+}
+
+/// Convert a string to a nul-terminated wide string.
+fn to_wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(Some(0)).collect()
+}
+
+#[doc(hidden)]
+pub enum Empty {}
+
+type DataTable = HashMap<u16, MenuItemData, ahash::RandomState>;
 
 struct WindowData {
     /// The table of menu item data.
     data: RefCell<DataTable>,
 
-    /// The menu IDs we are currently holding.
-    _ids: Vec<MenuKeyHandle>,
+    /// The accelerator table of the window, or 0.
+    accelerators: HACCEL,
+
+    /// The command IDs we are currently holding.
+    _ids: Vec<CommandId>,
 }
 
 /// A menu to be attached to a window.
@@ -244,11 +976,11 @@ pub struct Menu {
     /// Data associated with the menu.
     data: DataTable,
 
-    /// The menu IDs we are currently holding.
-    menu_id: Vec<MenuKeyHandle>,
+    /// The command IDs of the items, including those of submenus.
+    ids: Vec<CommandId>,
 
-    /// The next item ID to use.
-    next_id: u16,
+    /// The accelerators of the items, by command ID.
+    accelerators: Vec<(u16, Accelerator)>,
 
     /// Menus are not thread-safe.
     _marker: PhantomData<*mut ()>,
@@ -275,16 +1007,69 @@ impl Menu {
         Menu {
             menu: Some(NonZeroIsize::new_unchecked(menu)),
             data: DataTable::with_hasher(ahash::RandomState::new()),
-            menu_id: vec![MenuKeyHandle::new()],
-            next_id: 0,
+            ids: Vec::new(),
+            accelerators: Vec::new(),
             _marker: PhantomData,
         }
     }
 
+    /// Create a menu from a platform-agnostic [`crate::Menu`].
+    ///
+    /// Win32 has no concept of hidden items, so hidden entries are skipped.
+    pub fn from_model(menu: crate::Menu) -> Result<Self, Error> {
+        let mut this = Menu::new()?;
+        this.extend_from_model(menu)?;
+        Ok(this)
+    }
+
+    fn extend_from_model(&mut self, menu: crate::Menu) -> Result<(), Error> {
+        for entry in menu.into_entries() {
+            match entry {
+                MenuEntry::Separator(_) => self.push(MenuItem::separator())?,
+                MenuEntry::Submenu(submenu) => {
+                    if submenu.hidden() {
+                        continue;
+                    }
+
+                    let flags = if submenu.enabled() { 0 } else { MF_GRAYED };
+                    let text = submenu.title().to_owned();
+                    let mut popup = Menu::new_popup()?;
+                    popup.extend_from_model(submenu.into_menu())?;
+                    self.append_submenu(&text, popup, flags)?;
+                }
+                MenuEntry::Item(mut item) => {
+                    if item.hidden() {
+                        continue;
+                    }
+
+                    let mut flags = 0;
+                    if !item.enabled() {
+                        flags |= MF_GRAYED;
+                    }
+                    if item.checked() == Some(true) {
+                        flags |= MF_CHECKED;
+                    }
+                    let mut handler = item.take_handler();
+                    let handler = Box::new(move |activation: &ActivationContext| {
+                        if let Some(handler) = &mut handler {
+                            handler.call(&mut HandlerContext::detached(activation.clone()));
+                        }
+                    });
+                    let text = item_text(item.title(), item.accelerator(), Locale::default());
+                    // Disabled items can't be activated with their accelerator either.
+                    let accelerator = item.accelerator().filter(|_| item.enabled());
+                    self.append_item(&text, flags, accelerator, handler)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Add a new menu item to the menu.
-    pub fn push<'t, 'h, H: MenuItemHandler>(
+    pub fn push<'t, H: MenuItemHandler>(
         &mut self,
-        item: impl Into<MenuItem<'t, 'h, H>>,
+        item: impl Into<MenuItem<'t, H>>,
     ) -> Result<(), Error> {
         // Create the menu item.
         let item = item.into();
@@ -295,48 +1080,69 @@ impl Menu {
                 syscall!(nul AppendMenuA(hmenu, MF_SEPARATOR, 0, ptr::null_mut()));
             }
 
-            Inner::Submenu { text, mut submenu } => {
-                // Menu item is a submenu.
-                let handle = submenu.menu.take().unwrap().get();
-                let items = mem::replace(
-                    &mut submenu.data,
-                    DataTable::with_hasher(ahash::RandomState::new()),
-                );
-
-                // Append items to our items.
-                self.data.extend(items.into_iter());
-
-                let text = CString::new(text).unwrap();
-                syscall!(nul AppendMenuA(hmenu, MF_POPUP, handle as _, text.as_ptr().cast()));
+            Inner::Submenu { text, submenu } => {
+                self.append_submenu(text, submenu, 0)?;
             }
 
             Inner::Item {
                 text,
-                hotkey,
+                accelerator,
                 mut handler,
             } => {
-                // Create a new item key.
-                let key = {
-                    let new_key = ItemKey::new(self.menu_id[0].key, self.next_id);
+                let text = item_text(text, accelerator, Locale::default());
+                let handler = Box::new(move |_: &ActivationContext| handler.invoke());
+                self.append_item(&text, 0, accelerator, handler)?;
+            }
+        };
 
-                    let next_id = self.next_id.checked_add(1).expect("menu item ID overflow");
-                    self.next_id = next_id;
+        Ok(())
+    }
 
-                    new_key
-                };
+    /// Append a submenu with the given extra flags.
+    fn append_submenu(&mut self, text: &str, mut submenu: Menu, flags: u32) -> Result<(), Error> {
+        let hmenu = self.menu.unwrap().get();
 
-                // Add this key to our map.
-                self.data.insert(
-                    key,
-                    MenuItemData {
-                        handler: Box::new(move || handler.invoke()),
-                    },
-                );
+        // Menu item is a submenu.
+        let handle = submenu.menu.take().unwrap().get();
+        let items = mem::replace(
+            &mut submenu.data,
+            DataTable::with_hasher(ahash::RandomState::new()),
+        );
 
-                let text = CString::new(text).unwrap();
-                syscall!(nul AppendMenuA(hmenu, MF_STRING, key.0 as _, text.as_ptr().cast()));
-            }
-        };
+        // Append items to our items, and keep the IDs they're using alive.
+        self.data.extend(items);
+        self.ids.append(&mut submenu.ids);
+        self.accelerators.append(&mut submenu.accelerators);
+
+        let text = to_wide(text);
+        syscall!(nul AppendMenuW(hmenu, MF_POPUP | flags, handle as _, text.as_ptr()));
+
+        Ok(())
+    }
+
+    /// Append a regular item with the given extra flags.
+    fn append_item(
+        &mut self,
+        text: &str,
+        flags: u32,
+        accelerator: Option<Accelerator>,
+        handler: LegacyHandler,
+    ) -> Result<(), Error> {
+        let hmenu = self.menu.unwrap().get();
+
+        // Create a new command ID.
+        let id = CommandId::new()?;
+        let key = id.get();
+        self.ids.push(id);
+
+        // Add this ID to our map.
+        self.data.insert(key, MenuItemData { handler });
+        if let Some(accelerator) = accelerator {
+            self.accelerators.push((key, accelerator));
+        }
+
+        let text = to_wide(text);
+        syscall!(nul AppendMenuW(hmenu, MF_STRING | flags, key as _, text.as_ptr()));
 
         Ok(())
     }
@@ -363,6 +1169,9 @@ impl Menu {
             return Err(Error::menu_exists());
         }
 
+        let accelerators = create_accelerator_table(mem::take(&mut self.accelerators))?;
+        set_accelerator_table(hwnd, accelerators);
+
         // Set the menu.
         SetMenu(hwnd, self.menu.take().unwrap().get());
 
@@ -371,7 +1180,11 @@ impl Menu {
             &mut self.data,
             HashMap::with_hasher(ahash::RandomState::new()),
         );
-        let data = Box::into_raw(Box::new(data));
+        let data = Box::into_raw(Box::new(WindowData {
+            data: RefCell::new(data),
+            accelerators,
+            _ids: mem::take(&mut self.ids),
+        }));
         SetWindowSubclass(hwnd, Some(menu_subclass_proc), SUBCLASS_ID, data as _);
 
         Ok(())
@@ -391,22 +1204,24 @@ impl Drop for Menu {
 /// Data associated with each menu item.
 struct MenuItemData {
     /// The handler for the menu item.
-    handler: Box<dyn FnMut()>,
+    handler: LegacyHandler,
 }
 
+type LegacyHandler = Box<dyn FnMut(&ActivationContext)>;
+
 /// A menu item.
-pub struct MenuItem<'txt, 'hotkey, Handler = Empty> {
-    inner: Inner<'txt, 'hotkey, Handler>,
+pub struct MenuItem<'txt, Handler = Empty> {
+    inner: Inner<'txt, Handler>,
 }
 
-enum Inner<'txt, 'hotkey, Handler> {
+enum Inner<'txt, Handler> {
     /// This is a regular menu item.
     Item {
         /// The text of the menu item.
         text: &'txt str,
 
-        /// Handler for the menu item.
-        hotkey: Option<Hotkey<'hotkey>>,
+        /// The keyboard shortcut of the menu item.
+        accelerator: Option<Accelerator>,
 
         /// Handler for the menu item.
         handler: Handler,
@@ -425,7 +1240,7 @@ enum Inner<'txt, 'hotkey, Handler> {
     },
 }
 
-impl MenuItem<'static, 'static> {
+impl MenuItem<'static> {
     /// Create a new separator.
     pub fn separator() -> Self {
         MenuItem {
@@ -434,7 +1249,7 @@ impl MenuItem<'static, 'static> {
     }
 }
 
-impl<'txt> MenuItem<'txt, 'static> {
+impl<'txt> MenuItem<'txt> {
     /// Create a drop-down menu item.
     pub fn submenu(text: &'txt str, submenu: Menu) -> Self {
         MenuItem {
@@ -443,13 +1258,13 @@ impl<'txt> MenuItem<'txt, 'static> {
     }
 }
 
-impl<'txt, 'hotkey, Handler: MenuItemHandler> MenuItem<'txt, 'hotkey, Handler> {
+impl<'txt, Handler: MenuItemHandler> MenuItem<'txt, Handler> {
     /// Create a new menu item.
-    pub fn new(text: &'txt str, hotkey: Option<Hotkey<'hotkey>>, handler: Handler) -> Self {
+    pub fn new(text: &'txt str, accelerator: Option<Accelerator>, handler: Handler) -> Self {
         MenuItem {
             inner: Inner::Item {
                 text,
-                hotkey,
+                accelerator,
                 handler,
             },
         }
@@ -492,3 +1307,202 @@ fn abort_on_panic<R>(f: impl FnOnce() -> R) -> R {
     mem::forget(bomb);
     r
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        GetMenuItemCount, GetMenuItemID, GetSubMenu, IsMenu,
+    };
+
+    fn parse(s: &str) -> Option<Accelerator> {
+        Some(s.parse().unwrap())
+    }
+
+    #[test]
+    fn test_accelerators_in_submenus() {
+        let mut file = Menu::new_popup().unwrap();
+        file.push(MenuItem::new("New", parse("Ctrl+N"), || {}))
+            .unwrap();
+        let mut edit = Menu::new_popup().unwrap();
+        edit.push(MenuItem::new("Copy", parse("Ctrl+C"), || {}))
+            .unwrap();
+        let mut bar = Menu::new().unwrap();
+        bar.push(MenuItem::submenu("File", file)).unwrap();
+        bar.push(MenuItem::submenu("Edit", edit)).unwrap();
+
+        // Both submenus were built on their own, but their IDs don't collide.
+        let ids: Vec<u16> = bar.accelerators.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids.len(), 2);
+        assert_ne!(ids[0], ids[1]);
+
+        // The accelerator table, the native menu and the `WM_COMMAND` lookup
+        // all use the same IDs.
+        let cmds: Vec<u16> = accel_table(bar.accelerators.clone())
+            .iter()
+            .map(|accel| accel.cmd)
+            .collect();
+        assert_eq!(cmds, ids);
+        let hmenu = bar.menu.unwrap().get();
+        for (position, id) in ids.iter().enumerate() {
+            let submenu = unsafe { GetSubMenu(hmenu, position as i32) };
+            assert_eq!(unsafe { GetMenuItemID(submenu, 0) }, u32::from(*id));
+            assert!(bar.data.contains_key(id));
+        }
+    }
+
+    fn submenu(title: &str, submenu: u32, hidden: bool) -> ItemProps {
+        ItemProps {
+            kind: ItemKind::Submenu(MenuToken(submenu)),
+            title: title.to_owned(),
+            hidden,
+            ..ItemProps::separator()
+        }
+    }
+
+    #[test]
+    fn test_remove_hidden_submenus() {
+        let mut backend = Win32Backend::new();
+        backend.create_menu(MenuToken(0), MenuKind::Bar).unwrap();
+        for menu in 1..=3 {
+            backend
+                .create_menu(MenuToken(menu), MenuKind::Submenu)
+                .unwrap();
+        }
+        let props = submenu("File", 1, false);
+        backend
+            .insert_item(MenuToken(0), 0, ItemToken(0), &props)
+            .unwrap();
+        let props = submenu("Recent", 2, true);
+        backend
+            .insert_item(MenuToken(1), 0, ItemToken(1), &props)
+            .unwrap();
+        let props = submenu("Older", 3, true);
+        backend
+            .insert_item(MenuToken(2), 0, ItemToken(2), &props)
+            .unwrap();
+
+        // Hidden submenus aren't destroyed along with their parent.
+        let hmenus: Vec<_> = (1..=3).map(|m| backend.menu(MenuToken(m)).hmenu).collect();
+        backend.remove_item(ItemToken(0)).unwrap();
+        for hmenu in hmenus {
+            assert_eq!(unsafe { IsMenu(hmenu) }, 0);
+        }
+        assert!(backend.items.is_empty());
+    }
+
+    #[test]
+    fn test_move_between_menus() {
+        let mut backend = Win32Backend::new();
+        backend.create_menu(MenuToken(0), MenuKind::Bar).unwrap();
+        backend
+            .create_menu(MenuToken(1), MenuKind::Submenu)
+            .unwrap();
+        let props = submenu("File", 1, false);
+        backend
+            .insert_item(MenuToken(0), 0, ItemToken(0), &props)
+            .unwrap();
+        let props = ItemProps {
+            kind: ItemKind::Item,
+            title: "New".to_owned(),
+            ..ItemProps::separator()
+        };
+        backend
+            .insert_item(MenuToken(0), 1, ItemToken(1), &props)
+            .unwrap();
+
+        backend.move_item(ItemToken(1), MenuToken(1), 0).unwrap();
+        let count = |menu| unsafe { GetMenuItemCount(backend.menu(MenuToken(menu)).hmenu) };
+        assert_eq!((count(0), count(1)), (1, 1));
+        assert_eq!(backend.menu(MenuToken(1)).items, [ItemToken(1)]);
+        assert_eq!(backend.items[&ItemToken(1)].menu, MenuToken(1));
+    }
+
+    #[test]
+    fn test_reachable_commands() {
+        let mut backend = Win32Backend::new();
+        backend.create_menu(MenuToken(0), MenuKind::Bar).unwrap();
+        backend
+            .create_menu(MenuToken(1), MenuKind::Submenu)
+            .unwrap();
+        let item = |title: &str, accelerator| ItemProps {
+            kind: ItemKind::Item,
+            title: title.to_owned(),
+            accelerator: parse(accelerator),
+            ..ItemProps::separator()
+        };
+        let props = submenu("File", 1, false);
+        backend
+            .insert_item(MenuToken(0), 0, ItemToken(0), &props)
+            .unwrap();
+        let props = item("New", "Ctrl+N");
+        backend
+            .insert_item(MenuToken(1), 0, ItemToken(1), &props)
+            .unwrap();
+        let props = item("Open", "Ctrl+O");
+        backend
+            .insert_item(MenuToken(0), 0, ItemToken(2), &props)
+            .unwrap();
+        let commands = |backend: &Win32Backend| {
+            let mut commands = Vec::new();
+            backend.reachable_commands(MenuToken(0), &mut commands);
+            let id = |item| backend.items[&ItemToken(item)].id.get();
+            let ids: Vec<_> = commands.iter().map(|(id, _)| *id).collect();
+            (ids, id(1), id(2))
+        };
+
+        // In menu order, rather than in the order the items were created.
+        let (ids, new, open) = commands(&backend);
+        assert_eq!(ids, [open, new]);
+        backend.move_item(ItemToken(2), MenuToken(0), 1).unwrap();
+        assert_eq!(commands(&backend).0, [new, open]);
+
+        // Nothing in a disabled or hidden submenu can be reached.
+        let disabled = ItemProperty::Enabled(false);
+        backend.update_item(ItemToken(0), &disabled).unwrap();
+        assert_eq!(commands(&backend).0, [open]);
+        let hidden = ItemProperty::Hidden(true);
+        backend
+            .update_item(ItemToken(0), &ItemProperty::Enabled(true))
+            .unwrap();
+        backend.update_item(ItemToken(0), &hidden).unwrap();
+        assert_eq!(commands(&backend).0, [open]);
+    }
+
+    #[test]
+    fn test_menu_commands() {
+        // Menus, accelerators, and notifications from controls with the same id.
+        assert!(is_menu_command(42, 0));
+        assert!(is_menu_command(1 << 16 | 42, 0));
+        assert!(!is_menu_command(42, 0x1234));
+        assert!(!is_menu_command(5 << 16 | 42, 0));
+    }
+
+    #[test]
+    fn test_command_ids_are_reused() {
+        let first = CommandId::new().unwrap();
+        let id = first.get();
+        let second = CommandId::new().unwrap();
+        assert_ne!(second.get(), id);
+        drop(first);
+        assert_eq!(CommandId::new().unwrap().get(), id);
+    }
+
+    #[test]
+    fn test_too_many_command_ids() {
+        let mut ids = Vec::new();
+        let err = loop {
+            match CommandId::new() {
+                Ok(id) => ids.push(id),
+                Err(err) => break err,
+            }
+        };
+        assert_eq!(err.to_string(), "too many menu items on this thread");
+        assert_eq!(ids.len(), usize::from(u16::MAX));
+
+        // Released IDs can be used again.
+        let id = ids.swap_remove(42).get();
+        assert_eq!(CommandId::new().unwrap().get(), id);
+    }
+}